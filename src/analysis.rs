@@ -0,0 +1,435 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::expr::{field_accepts, Expr};
+use crate::scanner::{Span, Token, TokenType};
+use crate::stmt::{Pattern, Stmt};
+use crate::suggest::did_you_mean;
+use crate::typecheck::{Type, TypeEnv};
+
+/// Probable type errors in `stmts`, found before running them with the types `typecheck` infers:
+/// operators applied to values they don't support, fns called with the wrong number of
+/// arguments, fields a struct doesn't have, and struct fields given a value their default's type
+/// doesn't accept. Only what the inferred types show for certain is reported; anything involving
+/// a parameter or another value of unknown type is left for the run to find.
+pub fn analyze(stmts: &[Stmt], environment: &Rc<RefCell<Environment>>) -> Vec<String> {
+    let mut analysis = Analysis {
+        types: TypeEnv::from_environment(&environment.borrow()),
+        environment: environment.clone(),
+        reassigned: HashSet::new(),
+        problems: vec![],
+        collecting: true,
+    };
+
+    // A variable assigned anywhere may hold another type by the time it is used, so the first
+    // pass finds those, and the second checks with their types as unknown
+    analysis.stmts(stmts);
+    analysis.collecting = false;
+    analysis.types = TypeEnv::from_environment(&environment.borrow());
+    analysis.stmts(stmts);
+    analysis.problems
+}
+
+struct Analysis {
+    types: TypeEnv,
+    environment: Rc<RefCell<Environment>>, // For the structs declared by earlier sources
+    reassigned: HashSet<String>,
+    problems: Vec<String>,
+    collecting: bool, // Whether this is the pass that only collects assigned names
+}
+
+impl Analysis {
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    // Checks the statement's expressions, then declares the names it introduces
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression { expression, .. } | Stmt::Err { expression, .. } => self.expr(expression),
+            Stmt::Log { expressions, .. } | Stmt::Print { expressions, .. } => expressions.iter().for_each(|expression| self.expr(expression)),
+            Stmt::Var { initializer, .. } | Stmt::Unpack { initializer, .. } | Stmt::Const { initializer, .. } => {
+                self.expr(initializer);
+                self.declare(stmt);
+            }
+            Stmt::Block { statements, .. } => self.scoped(|analysis| analysis.stmts(statements)),
+            Stmt::Module { body, .. } => self.scoped(|analysis| analysis.stmts(body)),
+            Stmt::IfStmt { predicate, then, elifs, els, .. } => {
+                self.expr(predicate);
+                self.scoped(|analysis| analysis.stmt(then));
+                for (condition, body) in elifs {
+                    self.expr(condition);
+                    self.scoped(|analysis| analysis.stmt(body));
+                }
+                if let Some(els) = els {
+                    self.scoped(|analysis| analysis.stmt(els));
+                }
+            }
+            Stmt::Match { subject, arms, default, .. } => {
+                self.expr(subject);
+                for (patterns, body) in arms {
+                    self.scoped(|analysis| {
+                        let mut names = vec![];
+                        patterns.iter().for_each(|pattern| bound_names(pattern, &mut names));
+                        for name in names {
+                            analysis.types.bind(&name, Type::Unknown);
+                        }
+                        analysis.stmt(body);
+                    });
+                }
+                if let Some(default) = default {
+                    self.scoped(|analysis| analysis.stmt(default));
+                }
+            }
+            Stmt::Import { fallback, .. } => {
+                if let Some(fallback) = fallback {
+                    self.expr(fallback);
+                }
+                self.declare(stmt);
+            }
+            Stmt::WhileStmt { condition, body, increment, .. } => {
+                self.expr(condition);
+                self.scoped(|analysis| analysis.stmt(body));
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+            }
+            Stmt::ReturnStmt { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Yield { value, .. } | Stmt::Throw { value, .. } => self.expr(value),
+            Stmt::With { name, initializer, body, .. } => {
+                self.expr(initializer);
+                self.scoped(|analysis| {
+                    analysis.types.bind(&name.lexeme, Type::Unknown);
+                    analysis.stmt(body);
+                });
+            }
+            Stmt::Try { body, name, handler, .. } => {
+                self.scoped(|analysis| analysis.stmt(body));
+                self.scoped(|analysis| {
+                    if let Some(name) = name {
+                        analysis.types.bind(&name.lexeme, Type::Unknown);
+                    }
+                    analysis.stmt(handler);
+                });
+            }
+            Stmt::ForIn { name, iterable, body, .. } => {
+                self.expr(iterable);
+                let element = match self.types.infer(iterable) {
+                    Type::Array(element) => *element,
+                    _ => Type::Unknown,
+                };
+                self.scoped(|analysis| {
+                    analysis.bind(&name.lexeme, element);
+                    analysis.stmt(body);
+                });
+            }
+            Stmt::LoopStmt { body, count, .. } => {
+                if let Some(count) = count {
+                    self.expr(count);
+                }
+                self.scoped(|analysis| analysis.stmt(body));
+            }
+            Stmt::FuncStmt { .. } => {
+                // Declared first, so calls inside the body know its arity
+                self.declare(stmt);
+                self.function_body(stmt);
+            }
+            Stmt::StructStmt { params, functions, .. } => {
+                params.values().for_each(|default| self.expr(default));
+                self.declare(stmt);
+                for function in functions {
+                    self.function_body(function);
+                }
+            }
+            Stmt::InterfaceStmt { .. } => self.declare(stmt),
+            Stmt::Break { .. } | Stmt::Continue { .. } => (),
+        }
+    }
+
+    fn function_body(&mut self, function: &Stmt) {
+        if let Stmt::FuncStmt { parameters, body, .. } = function {
+            self.scoped(|analysis| {
+                for parameter in parameters {
+                    analysis.types.bind(&parameter.lexeme, Type::Unknown);
+                }
+                analysis.stmts(body);
+            });
+        }
+    }
+
+    fn declare(&mut self, stmt: &Stmt) {
+        self.types.declare(stmt);
+        let names: Vec<&str> = match stmt {
+            Stmt::Var { name, .. } => vec![&name.lexeme],
+            Stmt::Unpack { names, .. } => names.iter().map(|name| name.lexeme.as_str()).collect(),
+            _ => vec![],
+        };
+        for name in names {
+            if self.reassigned.contains(name) {
+                self.types.bind(name, Type::Unknown);
+            }
+        }
+    }
+
+    fn bind(&mut self, name: &str, binding: Type) {
+        let binding = if self.reassigned.contains(name) { Type::Unknown } else { binding };
+        self.types.bind(name, binding);
+    }
+
+    // Runs `check` with declarations made inside it forgotten afterwards
+    fn scoped(&mut self, check: impl FnOnce(&mut Analysis)) {
+        let outer = self.types.clone();
+        check(self);
+        self.types = outer;
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal { .. } | Expr::Variable { .. } => (),
+            Expr::Array { elements, .. } | Expr::Tuple { elements, .. } | Expr::Interpolate { parts: elements, .. } => {
+                elements.iter().for_each(|element| self.expr(element));
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            Expr::Assign { name, value, .. } => {
+                self.expr(value);
+                if self.collecting {
+                    self.reassigned.insert(name.lexeme.clone());
+                }
+            }
+            Expr::Const { value, .. } | Expr::Grouping { expression: value, .. } | Expr::Propagate { result: value, .. } => self.expr(value),
+            Expr::FieldAssign { object, value, .. } => {
+                self.expr(object);
+                self.expr(value);
+            }
+            Expr::IndexAssign { object, index, value, .. } => {
+                self.expr(object);
+                self.expr(index);
+                self.expr(value);
+            }
+            Expr::Index { array: left, index: right, .. } | Expr::Logical { left, right, .. } | Expr::Range { start: left, end: right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Binary { left, operator, right, span } => {
+                self.expr(left);
+                self.expr(right);
+                if let Some(message) = binary_problem(&self.types.infer(left), operator, &self.types.infer(right)) {
+                    self.problem(*span, message);
+                }
+            }
+            Expr::Unary { operator, right, span } => {
+                self.expr(right);
+                if operator.token_type == TokenType::Minus {
+                    if let Some(message) = negation_problem(&self.types.infer(right)) {
+                        self.problem(*span, message);
+                    }
+                }
+            }
+            Expr::Call { callee, arguments, span, .. } => {
+                self.expr(callee);
+                arguments.iter().for_each(|argument| self.expr(argument));
+                if let (Expr::Variable { name, .. }, Type::Function { arity: Some(arity), .. }) = (&**callee, self.types.infer(callee)) {
+                    if arity != arguments.len() {
+                        self.problem(*span, format!("Callable {} expected {} arguments but got {}", name.lexeme, arity, arguments.len()));
+                    }
+                }
+            }
+            Expr::MethodCall { object, arguments, .. } => {
+                self.expr(object);
+                arguments.iter().for_each(|argument| self.expr(argument));
+            }
+            Expr::Module { name, function: Ok(function), .. } if !self.types.binds(name) => self.expr(function),
+            Expr::Module { member, .. } => self.expr(member),
+            Expr::PreFunction { args, .. } => args.iter().for_each(|argument| self.expr(argument)),
+            Expr::FieldAccess { object, field, span } => {
+                self.expr(object);
+                if let Type::Struct(name) = self.types.infer(object) {
+                    if let Some(fields) = self.types.fields(&name).filter(|fields| !fields.contains_key(&field.lexeme)) {
+                        let suggestion = did_you_mean(&field.lexeme, fields.keys().map(String::as_str));
+                        self.problem(*span, format!("Field '{}' not found in struct '{}'.{}", field.lexeme, name, suggestion));
+                    }
+                }
+            }
+            Expr::StructInst { name, fields, span } => {
+                fields.values().for_each(|value| self.expr(value));
+                self.struct_fields(name, fields, *span);
+            }
+        }
+    }
+
+    // Field values whose type the field's default doesn't accept
+    fn struct_fields(&mut self, name: &str, fields: &HashMap<String, Expr>, span: Span) {
+        let Some(expected_types) = self.types.fields(name).cloned() else {
+            return;
+        };
+        let mut names: Vec<&String> = fields.keys().collect();
+        names.sort();
+
+        for field in names {
+            let (Some(expected), actual) = (expected_types.get(field), self.types.infer(&fields[field])) else {
+                continue;
+            };
+            // Structs declared in this source aren't defined yet to tell which extends which
+            let known = |field_type: &Type| !matches!(field_type, Type::Unknown | Type::Struct(_));
+            if known(expected) && known(&actual) && !field_accepts(expected, &actual, &self.environment) {
+                self.problem(span, format!("Type mismatch for field '{}': expected {}, got {}", field, expected, actual));
+            }
+        }
+    }
+
+    fn problem(&mut self, span: Span, message: String) {
+        if !self.collecting {
+            self.problems.push(span.locate(message));
+        }
+    }
+}
+
+// The name runtime errors give values of the type, for the types whose operators work the same
+// for every value
+fn runtime_name(value_type: &Type) -> Option<&'static str> {
+    Some(match value_type {
+        Type::Int => "Int",
+        Type::BigInt => "BigInt",
+        Type::Float => "Float",
+        Type::Decimal => "Decimal",
+        Type::String => "String",
+        Type::Bool => "Bool",
+        Type::Nil => "nil",
+        Type::Array(_) => "Array",
+        Type::Map => "Map",
+        _ => return None,
+    })
+}
+
+fn is_number(value_type: &Type) -> bool {
+    matches!(value_type, Type::Int | Type::BigInt | Type::Float | Type::Decimal)
+}
+
+// The error `Expr::Binary` raises for operands of these types, when it raises one for any values
+fn binary_problem(left: &Type, operator: &Token, right: &Type) -> Option<String> {
+    let (left_name, right_name) = (runtime_name(left)?, runtime_name(right)?);
+    let numbers = is_number(left) && is_number(right);
+
+    let allowed = match operator.token_type {
+        TokenType::EqualEqual | TokenType::BangEqual => true,
+        TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash if numbers => match (left, right) {
+            // Decimals stay exact, so they only mix with Ints
+            (Type::Decimal, Type::Float) | (Type::Float, Type::Decimal) => {
+                return Some(format!("Cannot apply '{}' to {} and {}. Use decimal() to convert the Float.", operator.lexeme, left_name, right_name));
+            }
+            (Type::Decimal, Type::BigInt) | (Type::BigInt, Type::Decimal) => false,
+            _ => true,
+        },
+        TokenType::Plus => matches!((left, right), (Type::String, Type::String) | (Type::Array(_), Type::Array(_)))
+            || (*left == Type::String && is_number(right))
+            || (is_number(left) && *right == Type::String),
+        TokenType::Star => matches!((left, right), (Type::String, Type::Int | Type::Float) | (Type::Int | Type::Float, Type::String)),
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => numbers || (*left == Type::String && *right == Type::String),
+        TokenType::In => match right {
+            Type::Array(_) | Type::Map => true,
+            Type::String if *left == Type::String => true,
+            _ => return Some(format!("Cannot check whether {} is in {}.", left_name, right_name)),
+        },
+        _ => false,
+    };
+    if allowed {
+        return None;
+    }
+
+    let hint = if (*left == Type::String && is_number(right)) || (is_number(left) && *right == Type::String) {
+        " Use num() or str() to convert one side."
+    } else {
+        ""
+    };
+    Some(format!("Cannot apply '{}' to {} and {}.{}", operator.lexeme, left_name, right_name, hint))
+}
+
+// The error `-value` raises for a value of the type, when it raises one for any value
+fn negation_problem(value_type: &Type) -> Option<String> {
+    let name = runtime_name(value_type)?;
+    (!is_number(value_type)).then(|| format!("Cannot use - for {:?}", name))
+}
+
+// The names a match pattern binds
+fn bound_names(pattern: &Pattern, names: &mut Vec<String>) {
+    match pattern {
+        Pattern::Literal(_) => (),
+        Pattern::Bind(name) => names.push(name.clone()),
+        Pattern::Array { elements, rest } => {
+            elements.iter().for_each(|element| bound_names(element, names));
+            names.extend(rest.clone());
+        }
+        Pattern::Tuple(elements) => elements.iter().for_each(|element| bound_names(element, names)),
+        Pattern::Struct { fields, .. } => fields.iter().for_each(|(_, field)| bound_names(field, names)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn analyze_source(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        analyze(&stmts, &Rc::new(RefCell::new(Environment::new())))
+    }
+
+    #[test]
+    fn reports_probable_type_errors_before_running() {
+        let problems = analyze_source(
+            "struct Point { x: 0, label: \"\" }\n\
+             fn area(w, h) { return w * h; }\n\
+             var name = \"box\";\n\
+             var size = name - 1;\n\
+             if (size > 0) { print(area(2)); }\n\
+             var p = Point { x: \"left\", label: \"a\" };\n\
+             print(p.lable);\n\
+             fn scale(n) { return n * 2; }\n\
+             print(-name);"
+        );
+
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert!(problems[0].starts_with("Line 4:"), "{}", problems[0]);
+        assert!(problems[0].contains("Cannot apply '-' to String and Int"), "{}", problems[0]);
+        assert_eq!(problems[1], "Line 5:23: Callable area expected 2 arguments but got 1");
+        assert_eq!(problems[2], "Line 6:9: Type mismatch for field 'x': expected Int, got String");
+        assert_eq!(problems[3], "Line 7:9: Field 'lable' not found in struct 'Point'. Did you mean 'label'?");
+        assert!(problems[4].starts_with("Line 9:"), "{}", problems[4]);
+
+        let problems = analyze_source("var total = 1.5d + 0.5;\nvar found = 1 in \"abc\";\nvar repeated = \"ab\" * 2 + 1.0;");
+        assert_eq!(problems, [
+            "Line 1:18: Cannot apply '+' to Decimal and Float. Use decimal() to convert the Float.",
+            "Line 2:15: Cannot check whether Int is in String.",
+        ]);
+    }
+
+    #[test]
+    fn values_of_unknown_or_changing_type_are_not_reported() {
+        let problems = analyze_source(
+            "var total = 0;\n\
+             for (line in io.lines_stdin()) { total = total + line; }\n\
+             var label = 1;\n\
+             while (label != nil) { print(label - 1); label = \"done\"; label = nil; }\n\
+             fn f(a, b) { return a - b; }\n\
+             match (f(1, 2)) { x => print(x - 1); }\n\
+             var m = {1: \"a\"};\n\
+             print(m.keys()[0] + 1);"
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+}
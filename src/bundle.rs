@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::interpreter::find_module;
+use crate::scanner::{Scanner, TokenType};
+
+/// Inlines the modules a script imports, and the ones those import, as `module "name" { ... }`
+/// blocks ahead of the script. The imports themselves stay as they are and pick up the bundled
+/// copies, so aliases and namespaces work as before. Data files and optional modules that
+/// can't be found are left to be loaded at runtime.
+pub fn bundle(entry: &Path, roots: &[PathBuf]) -> Result<String, String> {
+    let source = read(entry)?;
+    let mut modules = vec![];
+    collect_modules(&source, roots, &mut HashSet::new(), &mut modules)?;
+
+    let mut bundled = String::new();
+    for (name, module_source) in modules {
+        bundled.push_str(&format!("module \"{}\" {{\n{}\n}}\n\n", name, module_source.trim_end()));
+    }
+    bundled.push_str(&source);
+    Ok(bundled)
+}
+
+// Adds the modules `source` imports to `modules`, each after the modules it imports itself
+fn collect_modules(source: &str, roots: &[PathBuf], seen: &mut HashSet<String>, modules: &mut Vec<(String, String)>) -> Result<(), String> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+
+    for pair in tokens.windows(2) {
+        if pair[0].token_type != TokenType::Import || pair[1].token_type != TokenType::String {
+            continue;
+        }
+
+        let name = pair[1].lexeme.trim_matches('"');
+        if !seen.insert(name.to_string()) {
+            continue;
+        }
+        let Some(path) = find_module(name, roots) else { continue };
+        if path.extension().is_none_or(|extension| extension != "rcn") {
+            continue;
+        }
+
+        let module_source = read(&path)?;
+        collect_modules(&module_source, roots, seen, modules)?;
+        modules.push((name.to_string(), module_source));
+    }
+
+    Ok(())
+}
+
+// Ends an executable made by `recolon build`, after the script and its length
+const EMBED_MARKER: &[u8] = b"RECOLON-EMBED";
+
+/// Writes a copy of the running interpreter to `output` with the bundled script appended,
+/// which the copy runs in place of its arguments. End users then only need that one file.
+pub fn build(entry: &Path, roots: &[PathBuf], output: &Path) -> Result<(), String> {
+    let script = bundle(entry, roots)?;
+    let interpreter = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut binary = fs::read(&interpreter).map_err(|e| format!("Failed to read '{}': {}", interpreter.display(), e))?;
+
+    // Building with an executable that carries a script replaces that script
+    if let Some(start) = embedded_start(&binary) {
+        binary.truncate(start);
+    }
+    binary.extend_from_slice(script.as_bytes());
+    binary.extend_from_slice(&(script.len() as u64).to_le_bytes());
+    binary.extend_from_slice(EMBED_MARKER);
+
+    fs::write(output, binary).map_err(|e| format!("Could not write '{}': {}", output.display(), e))?;
+    let permissions = fs::metadata(&interpreter).map_err(|e| e.to_string())?.permissions();
+    fs::set_permissions(output, permissions).map_err(|e| e.to_string())
+}
+
+/// The script appended by `recolon build` to the running executable, if there is one.
+pub fn embedded_script() -> Option<String> {
+    let mut file = fs::File::open(std::env::current_exe().ok()?).ok()?;
+    let mut trailer = [0; 8 + EMBED_MARKER.len()];
+    file.seek(SeekFrom::End(-(trailer.len() as i64))).ok()?;
+    file.read_exact(&mut trailer).ok()?;
+    if &trailer[8..] != EMBED_MARKER {
+        return None;
+    }
+
+    let length = u64::from_le_bytes(trailer[..8].try_into().ok()?);
+    file.seek(SeekFrom::End(-(trailer.len() as i64) - length as i64)).ok()?;
+    let mut script = String::new();
+    file.take(length).read_to_string(&mut script).ok()?;
+    Some(script)
+}
+
+// Where the script appended to `binary` begins
+fn embedded_start(binary: &[u8]) -> Option<usize> {
+    let body = binary.strip_suffix(EMBED_MARKER)?;
+    let (rest, length) = body.split_at_checked(body.len().checked_sub(8)?)?;
+    rest.len().checked_sub(u64::from_le_bytes(length.try_into().ok()?) as usize)
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::literal_value::LiteralValue;
+    use crate::parser::Parser;
+
+    #[test]
+    fn bundles_run_without_the_module_files() {
+        let dir = std::env::temp_dir().join("recolon_bundle");
+        fs::create_dir_all(&dir).unwrap();
+        let (shapes, util) = (dir.join("shapes"), dir.join("util"));
+        fs::write(util.with_extension("rcn"), "fn double(n) { return n * 2; }").unwrap();
+        fs::write(shapes.with_extension("rcn"), format!("import \"{}\" as util;\nfn area(w, h) {{ return util.double(w * h) / 2; }}", util.display())).unwrap();
+        fs::write(dir.join("main.rcn"), format!("import \"{}\" as shapes;\nimport \"{}\" as u;\nvar result = shapes.area(3, 4) + u.double(1);", shapes.display(), util.display())).unwrap();
+
+        let bundled = bundle(&dir.join("main.rcn"), &[]).unwrap();
+        assert_eq!(bundled.matches("module \"").count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut interpreter = Interpreter::new();
+        let tokens = Scanner::new(&bundled).scan_tokens().unwrap();
+        interpreter.interpret(Parser::new(tokens).parse().unwrap()).unwrap();
+        assert_eq!(interpreter.environment().borrow().get("result"), Some(LiteralValue::Int(14)));
+    }
+
+    #[test]
+    fn built_executables_carry_one_script() {
+        let dir = std::env::temp_dir().join("recolon_build");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.rcn"), "print(1);").unwrap();
+
+        build(&dir.join("main.rcn"), &[], &dir.join("app")).unwrap();
+        let binary = fs::read(dir.join("app")).unwrap();
+        let start = embedded_start(&binary).unwrap();
+        assert_eq!(&binary[start..start + 9], b"print(1);");
+        assert_eq!(embedded_start(&binary[..start]), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,431 @@
+/// A kind of error with a stable code, shown with the error and described by `recolon explain`.
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub title: &'static str,
+    wordings: &'static [&'static str], // Parts of the messages of this kind, checked in table order
+    pub explanation: &'static str,
+}
+
+/// Errors are plain messages, so their kind is found from the wording they are built from.
+/// Messages that fit no entry get `OTHER`.
+pub const DIAGNOSTICS: &[Diagnostic] = &[
+    Diagnostic {
+        code: "E0001",
+        title: "Unrecognized character",
+        wordings: &["Unrecognized token"],
+        explanation: "The source contains a character that is not part of the language, like `%` or `@`.
+
+    var rest = 7 % 2;   # no % operator
+
+Check for a typo, or text that belongs inside a string.",
+    },
+    Diagnostic {
+        code: "E0002",
+        title: "Unterminated string",
+        wordings: &["String not closed", "Raw string not closed", "after '$'"],
+        explanation: "A string is missing its closing quote, so the rest of the file would be part of it.
+
+    print(\"hello);
+
+Close it with `\"`. Raw strings open and close with `\"\"\"`, interpolated ones with `$\"\"\"` and `\"\"\"`.",
+    },
+    Diagnostic {
+        code: "E0003",
+        title: "Invalid number",
+        wordings: &["Could not parse number", "Could not parse decimal"],
+        explanation: "A number literal could not be read, for instance one with two decimal points.
+
+    var version = 1.2.3;
+
+Write version-like values as strings: `\"1.2.3\"`.",
+    },
+    Diagnostic {
+        code: "E0010",
+        title: "Invalid assignment target",
+        wordings: &["Invalid assignment target", "Only elements of a variable or field", "Cannot assign to a slice"],
+        explanation: "The left side of `=` is not something that can hold a value.
+
+    1 + x = 3;
+    f()[0] = 1;
+
+Assign to a variable, a field like `point.x` or an element like `items[0]`.",
+    },
+    Diagnostic {
+        code: "E0011",
+        title: "Statement in the wrong place",
+        wordings: &["can only be used inside", "outside of a loop", "must be the last arm", "can only run inside", "Only loops can be labeled", "Unknown loop label"],
+        explanation: "A statement appears where it has no meaning: `yield` outside a fn, `break` or `continue`
+outside a loop, or an `else` arm of a match that is not the last arm.
+
+    fn first(items) { break; }
+
+Move the statement into the construct it belongs to.",
+    },
+    Diagnostic {
+        code: "E0012",
+        title: "Invalid match pattern",
+        wordings: &["Match arms take patterns"],
+        explanation: "A match arm starts with something that is not a pattern.
+
+    match (n) { -x => print(x); }
+
+Arms take literals (`1`, `\"text\"`, `nil`), names to bind (`x`, or `_` for anything),
+arrays (`[first, ..rest]`), tuples (`(a, b)`) and structs (`Point { x, y: 0 }`).",
+    },
+    Diagnostic {
+        code: "E0013",
+        title: "Redefinition",
+        wordings: &["redefines the one declared"],
+        explanation: "A fn, struct or interface is declared twice in one source. This is a warning, or an
+error with `--strict`.
+
+    fn area(r) { return 3 * r * r; }
+    fn area(w, h) { return w * h; }
+
+Rename one of them; fns are told apart by name, not by their parameters.",
+    },
+    Diagnostic {
+        code: "E0020",
+        title: "Undefined variable",
+        wordings: &["Undefined variable", "has not been declared", "Struct definition '", "unknown struct", "unknown interface"],
+        explanation: "A name is used that no `var`, `const`, `fn`, `struct` or `import` declared in scope.
+
+    fn total() { return count + 1; }   # count is never declared
+
+Declare it first, or check the spelling; the error suggests a close name when there is one.",
+    },
+    Diagnostic {
+        code: "E0021",
+        title: "Unknown field or member",
+        wordings: &["not found in struct", "not found in namespace", "has no member", "has no fn", "has no field", "has no such field", "non-struct instance", "Template key", "Missing '"],
+        explanation: "A struct instance has no field by that name, a module has no such member, or a struct
+has no associated fn by that name.
+
+    struct Point { x: 0, y: 0 }
+    print(Point {}.z);
+
+Fields are the ones listed in the struct, including those of the struct it extends.",
+    },
+    Diagnostic {
+        code: "E0022",
+        title: "Unknown module function",
+        wordings: &["has no function '", "is a module function and must be called", "Unknown identifier", "not implemented."],
+        explanation: "A built-in module like `math` or `io` has no function by that name, or a module function
+is used as a value.
+
+    math.squareroot(4);
+    var f = math.sqrt;
+
+Module functions must be called where they are named; wrap them in a fn to pass them around.",
+    },
+    Diagnostic {
+        code: "E0023",
+        title: "Unknown method",
+        wordings: &["Unknown method '", "method not available on"],
+        explanation: "A value has no method by that name.
+
+    [1, 2].len();
+
+Arrays have `length()`, `push()`, `pop()` and more; the error names the type the call was made on.",
+    },
+    Diagnostic {
+        code: "E0024",
+        title: "Wrong number of arguments",
+        wordings: &["arguments but got", "takes exactly", "takes no arguments", "takes 0 or 1", "takes at most", "takes an optional", "takes three", "more than 255 arguments"],
+        explanation: "A fn or method was called with more or fewer arguments than it takes.
+
+    fn add(a, b) { return a + b; }
+    add(1);
+
+Pass one value for every parameter.",
+    },
+    Diagnostic {
+        code: "E0025",
+        title: "Unsupported operation",
+        wordings: &["Cannot apply '", "Cannot use - for", "by zero", "is not a valid operator", "Invalid token in logical", "Cannot repeat a string", "Cannot check whether"],
+        explanation: "An operator was used on values it doesn't support, like adding a number to nil.
+
+    var total = nil + 1;
+    var mixed = decimal(\"1.5\") + 0.5;
+
+Convert one side first, e.g. with `num()`, `str()` or `decimal()`.",
+    },
+    Diagnostic {
+        code: "E0026",
+        title: "Bad index",
+        wordings: &["is out of bounds", "index must be a whole number", "index a non-array", "Range bounds", "Slice ", "Index out of bounds", "Index must be a whole number", "Array is empty", "has more than", "Cannot slice"],
+        explanation: "An element was looked up past the end of an array, with an index that is not a whole
+number, or on a value that has no elements.
+
+    var items = [1, 2];
+    print(items[2]);
+
+Indexes start at 0, and negative ones count from the end: `items[-1]` is the last element.",
+    },
+    Diagnostic {
+        code: "E0027",
+        title: "Missing map key",
+        wordings: &["not found in map"],
+        explanation: "A map was read with a key it doesn't contain.
+
+    var ages = {\"ada\": 36};
+    print(ages[\"bob\"]);
+
+Check with `in` first: `if (\"bob\" in ages) { ... }`.",
+    },
+    Diagnostic {
+        code: "E0028",
+        title: "Constant changed",
+        wordings: &["Cannot reassign constant", "Cannot shadow constant", "is already defined", "frozen struct", "cannot be changed"],
+        explanation: "A `const`, a built-in function or a frozen struct was assigned or declared again.
+
+    const LIMIT = 10;
+    LIMIT = 20;
+
+Use `var` for values that change, or copy a frozen struct with `clone()`.",
+    },
+    Diagnostic {
+        code: "E0029",
+        title: "Module not found",
+        wordings: &["Failed to load module", "Module '", "Failed to reload module"],
+        explanation: "An `import` names a file that doesn't exist in the working directory or the module roots.
+
+    import \"utils\" as utils;   # looks for utils.rcn
+
+Roots come from project.rcn and RCN_PATH. Use `import \"name\" as x else nil;` for optional modules.",
+    },
+    Diagnostic {
+        code: "E0030",
+        title: "File error",
+        wordings: &["Failed to read", "Failed to load", "Could not write", "Could not redirect", "Could not create", "Error reading '", "Error reading file", "Error writing", "Error opening", "Error seeking", "Error deleting", "is already closed", "one file at a time", "Could not open", "Could not add", "Could not extract", "for upload", "is not a zip file", "outside the destination"],
+        explanation: "A file could not be read or written, because it doesn't exist, is a directory, or the
+script lacks permission.
+
+    io.open_file(\"missing.txt\");
+
+Check the path; relative paths start from the working directory, `__dir__` holds the script's.",
+    },
+    Diagnostic {
+        code: "E0031",
+        title: "Wrong kind of argument",
+        wordings: &[" expects ", " requires ", "for field access", "Cannot iterate over", "Cannot unpack", "must be", "should be", " needs ", " takes a", "looks for", "looked up by name", "is not callable", "as a map key", "Cannot assign by index", "Cannot convert", "cannot write", "can only write", "can only pass", "Cannot serialize", "Unsupported", "is not supported", "may only hold", "cannot contain line breaks"],
+        explanation: "A value of the wrong type was passed or used, like a number where a path is expected.
+
+    fs.watch(42, on_change);
+    for (c in 12) { }
+
+The error names the type that was given; convert the value or pass the one that was meant.",
+    },
+    Diagnostic {
+        code: "E0032",
+        title: "Cancelled",
+        wordings: &["Execution cancelled"],
+        explanation: "The run was stopped from outside, by `--timeout` or by the program embedding the
+interpreter. A script stuck in a loop is the usual cause.",
+    },
+    Diagnostic {
+        code: "E0033",
+        title: "Fatal error",
+        wordings: &["FATAL"],
+        explanation: "The script ended itself with `fatal(...)`. Unlike `err(...)`, which reports and carries
+on, and `throw`, which `try`/`catch` can handle, `fatal` always stops the run.",
+    },
+    Diagnostic {
+        code: "E0034",
+        title: "Uncaught throw",
+        wordings: &[],
+        explanation: "A value raised with `throw` reached the top of the script without a `try`/`catch`.
+
+    fn parse(text) { if (text == \"\") { throw \"empty input\"; } }
+    parse(\"\");
+
+Wrap the call in `try { ... } catch (e) { ... }`; `e.value` is the thrown value and `e.line` where.",
+    },
+    Diagnostic {
+        code: "E0035",
+        title: "Connection error",
+        wordings: &["Could not resolve", "Could not connect", "SMTP", "webhook", "Webhook", "the clipboard", "Clipboard", "standard input", "Input ended", "worker thread"],
+        explanation: "Something outside the script did not answer: a host that can't be resolved or reached,
+a mail server or webhook that refused, a missing clipboard, or standard input that ended.
+
+    net.resolve(\"no-such-host.invalid\");
+
+The message includes the reason the system gave; check the address and that the service is up.",
+    },
+    Diagnostic {
+        code: "E0036",
+        title: "Not allowed in a sandbox",
+        wordings: &["in a sandbox"],
+        explanation: "A script run with `--sandbox` used something that reaches outside it, like files, the
+network, processes or modules that are not bundled into the script.
+
+    io.open_file(\"secrets.txt\");   # with --sandbox
+
+Run without `--sandbox` if the script is trusted, or pass the data in another way.",
+    },
+    Diagnostic {
+        code: "E0037",
+        title: "Invalid data",
+        wordings: &["Failed to parse", "JSON", "Invalid escape", "columns, expected", "Unterminated quoted cell", "Invalid serialized value",
+            "msgpack.decode", "does not decode to UTF-8", "Invalid percent escape", "zip failed", "in template", "Message '", "Message keys", "object of messages"],
+        explanation: "Text or bytes handed to a decoder are not in the format it reads: broken JSON or CSV,
+serialized values, MessagePack, compressed data, templates or translation files.
+
+    import \"broken.json\" as config;   # holds { \"port\": }
+
+The message names the position or the part that could not be read.",
+    },
+    Diagnostic {
+        code: "E0038",
+        title: "Math error",
+        wordings: &["not a finite number", "Logarithm undefined", "too many digits", "overflow", "zero-length vector", "is undefined when",
+            "cannot multiply a", "only defined for"],
+        explanation: "A calculation has no result that a number can hold, because it overflows or is undefined
+for the given values.
+
+    math.lgm(0);
+
+Check the values before the calculation, or catch the error with `try`.",
+    },
+    Diagnostic {
+        code: "E0039",
+        title: "Interface not implemented",
+        wordings: &["does not implement"],
+        explanation: "A struct names an interface it implements but lacks one of the fns the interface requires.
+
+    interface Shape { fn area(self); }
+    struct Square: Shape { side: 1 }
+
+Add the missing fn to the struct's `impl`, or drop the interface from its declaration.",
+    },
+    Diagnostic {
+        code: "E0040",
+        title: "Exit",
+        wordings: &["Exited with status"],
+        explanation: "The script ended itself with `os.exit(status)`. Handlers registered with `os.on_exit` have
+run, and the status becomes the exit status of the interpreter.",
+    },
+    Diagnostic {
+        code: "E0009",
+        title: "Syntax error",
+        wordings: &["Expected", "Unknown log level", "interpolat"],
+        explanation: "The parser found something other than what the grammar allows at that point, usually a
+missing `;`, `)` or `}`.
+
+    var x = (1 + 2;
+    if (x > 1) print(x)
+
+The message names what was expected; the mistake is often just before the reported line.",
+    },
+];
+
+/// The code of an error raised with `throw`, whose message is whatever the script threw.
+pub const UNCAUGHT_THROW: &str = "E0034";
+
+/// Errors that fit no other entry.
+pub const OTHER: Diagnostic = Diagnostic {
+    code: "E0000",
+    title: "Error",
+    wordings: &[],
+    explanation: "An error without a more specific code. Its message describes what went wrong.",
+};
+
+/// The kind of error `message` describes.
+pub fn classify(message: &str) -> &'static Diagnostic {
+    DIAGNOSTICS.iter()
+        .find(|diagnostic| diagnostic.wordings.iter().any(|wording| message.contains(wording)))
+        .unwrap_or(&OTHER)
+}
+
+/// The diagnostic with `code`, in any case, e.g. for `recolon explain e0020`.
+pub fn find(code: &str) -> Option<&'static Diagnostic> {
+    DIAGNOSTICS.iter().chain([&OTHER]).find(|diagnostic| diagnostic.code.eq_ignore_ascii_case(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn code_of(source: &str) -> &'static str {
+        let error = Scanner::new(source).scan_tokens()
+            .and_then(|tokens| Parser::new(tokens).parse())
+            .and_then(|stmts| Interpreter::new().interpret(stmts).map(|_| ()))
+            .unwrap_err();
+        classify(&error).code
+    }
+
+    #[test]
+    fn errors_get_the_code_of_their_kind() {
+        assert_eq!(code_of("var x = 7 % 2;"), "E0001");
+        assert_eq!(code_of("print(\"open);"), "E0002");
+        assert_eq!(code_of("var x = (1 + 2;"), "E0009");
+        assert_eq!(code_of("print(missing);"), "E0020");
+        assert_eq!(code_of("struct P { x: 0 } print(P {}.y);"), "E0021");
+        assert_eq!(code_of("math.sqr(4);"), "E0022");
+        assert_eq!(code_of("[1].len();"), "E0023");
+        assert_eq!(code_of("fn f(a) { return a; } f(1, 2);"), "E0024");
+        assert_eq!(code_of("var x = nil + 1;"), "E0025");
+        assert_eq!(code_of("var x = [1][3];"), "E0026");
+        assert_eq!(code_of("const A = 1; A = 2;"), "E0028");
+        assert_eq!(code_of("fatal(\"stop\");"), "E0033");
+        assert_eq!(code_of("io.open_file(\"/nope\");"), "E0030");
+        assert_eq!(code_of("import \"nope\" as x;"), "E0029");
+        assert_eq!(classify("Something unusual").code, "E0000");
+    }
+
+    #[test]
+    fn codes_are_unique_and_explained() {
+        for (i, diagnostic) in DIAGNOSTICS.iter().enumerate() {
+            assert!(DIAGNOSTICS[i + 1..].iter().all(|other| other.code != diagnostic.code), "{} is used twice", diagnostic.code);
+            assert!(!diagnostic.explanation.is_empty());
+        }
+        assert_eq!(find("e0020").map(|diagnostic| diagnostic.title), Some("Undefined variable"));
+    }
+
+    // Files of the command line around the interpreter, whose errors are never classified
+    const HOST_FILES: &[&str] = &["main.rs", "project.rs", "threaded.rs", "learn.rs", "doc.rs", "bundle.rs", "diagnostics.rs"];
+
+    // The messages errors are built from: the first string on each line that returns or maps an error.
+    // Lowercase fragments like "invalid int" are wrapped into a full message elsewhere.
+    fn error_messages(dir: &Path, messages: &mut Vec<(String, String)>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                error_messages(&path, messages);
+                continue;
+            }
+            let name = path.file_name().unwrap().to_string_lossy();
+            if !name.ends_with(".rs") || HOST_FILES.contains(&name.as_ref()) {
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            let code = source.split("#[cfg(test)]").next().unwrap();
+            for (number, line) in code.lines().enumerate() {
+                let Some(start) = ["Err(", "map_err(", "ok_or"].iter().find_map(|marker| line.find(marker)) else { continue };
+                let Some(message) = line[start..].split('"').nth(1) else { continue };
+                let first_word = message.split(' ').next().unwrap();
+                let fragment = first_word.chars().all(|c| c.is_ascii_lowercase()) && message.contains(' ');
+                let has_words = message.replace("\\n", "").split(['{', '}']).step_by(2).any(|part| part.chars().any(char::is_alphabetic));
+                if has_words && !fragment {
+                    messages.push((format!("{}:{}", path.display(), number + 1), message.to_string()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_error_of_the_interpreter_is_unclassified() {
+        let mut messages = vec![];
+        error_messages(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut messages);
+        assert!(messages.len() > 300, "only found {} messages", messages.len());
+        for (place, message) in messages {
+            assert_ne!(classify(&message).code, OTHER.code, "{} raises \"{}\", which fits no diagnostic", place, message);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::stmt::Stmt;
+
+/// Output format of `recolon doc`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+// One documented top-level declaration
+struct DocItem {
+    kind: &'static str,
+    signature: String,
+    doc: Option<String>,
+    fields: Vec<(String, String)>,
+}
+
+/// Renders the functions, structs and constants declared at the top level of a module.
+pub fn render(module_name: &str, stmts: &[Stmt], format: DocFormat) -> String {
+    let items = collect_items(stmts);
+
+    match format {
+        DocFormat::Markdown => render_markdown(module_name, &items),
+        DocFormat::Html => render_html(module_name, &items),
+    }
+}
+
+fn collect_items(stmts: &[Stmt]) -> Vec<DocItem> {
+    let mut items = vec![];
+
+    for stmt in stmts {
+        match stmt {
+            Stmt::FuncStmt { name, parameters, doc, .. } => {
+                let parameters: Vec<&str> = parameters.iter().map(|param| param.lexeme.as_str()).collect();
+                items.push(DocItem {
+                    kind: "Functions",
+                    signature: format!("fn {}({})", name, parameters.join(", ")),
+                    doc: doc.clone(),
+                    fields: vec![("Arity".to_string(), parameters.len().to_string())],
+                });
+            }
+            Stmt::StructStmt { name, parent, interfaces, params, doc, .. } => {
+                let mut signature = format!("struct {}", name);
+                if let Some(parent) = parent {
+                    signature.push_str(&format!(" : {}", parent));
+                }
+                if !interfaces.is_empty() {
+                    signature.push_str(&format!(" implements {}", interfaces.join(", ")));
+                }
+
+                let mut fields: Vec<(String, String)> = params.iter()
+                    .map(|(field, default)| (field.clone(), default.to_string()))
+                    .collect();
+                fields.sort();
+
+                items.push(DocItem { kind: "Structs", signature, doc: doc.clone(), fields });
+            }
+            Stmt::Const { name, initializer, doc, .. } => {
+                items.push(DocItem {
+                    kind: "Constants",
+                    signature: format!("const {}", name.lexeme),
+                    doc: doc.clone(),
+                    fields: vec![("Value".to_string(), initializer.to_string())],
+                });
+            }
+            _ => (),
+        }
+    }
+
+    items
+}
+
+fn render_markdown(module_name: &str, items: &[DocItem]) -> String {
+    let mut output = format!("# Module `{}`\n", module_name);
+
+    for kind in ["Functions", "Structs", "Constants"] {
+        let section: Vec<&DocItem> = items.iter().filter(|item| item.kind == kind).collect();
+        if section.is_empty() {
+            continue;
+        }
+
+        output.push_str(&format!("\n## {}\n", kind));
+        for item in section {
+            output.push_str(&format!("\n### `{}`\n\n", item.signature));
+            if let Some(doc) = &item.doc {
+                output.push_str(&format!("{}\n\n", doc));
+            }
+
+            if kind == "Structs" {
+                if !item.fields.is_empty() {
+                    output.push_str("| Field | Default |\n| --- | --- |\n");
+                    for (field, default) in &item.fields {
+                        output.push_str(&format!("| `{}` | `{}` |\n", field, default));
+                    }
+                }
+            } else {
+                for (label, value) in &item.fields {
+                    output.push_str(&format!("{}: `{}`\n", label, value));
+                }
+            }
+        }
+    }
+
+    output
+}
+
+fn render_html(module_name: &str, items: &[DocItem]) -> String {
+    let mut output = format!("<h1>Module <code>{}</code></h1>\n", escape_html(module_name));
+
+    for kind in ["Functions", "Structs", "Constants"] {
+        let section: Vec<&DocItem> = items.iter().filter(|item| item.kind == kind).collect();
+        if section.is_empty() {
+            continue;
+        }
+
+        output.push_str(&format!("<h2>{}</h2>\n", kind));
+        for item in section {
+            output.push_str(&format!("<h3><code>{}</code></h3>\n", escape_html(&item.signature)));
+            if let Some(doc) = &item.doc {
+                output.push_str(&format!("<p>{}</p>\n", escape_html(doc).replace('\n', "<br>\n")));
+            }
+
+            if kind == "Structs" {
+                if !item.fields.is_empty() {
+                    output.push_str("<table>\n<tr><th>Field</th><th>Default</th></tr>\n");
+                    for (field, default) in &item.fields {
+                        output.push_str(&format!("<tr><td><code>{}</code></td><td><code>{}</code></td></tr>\n", escape_html(field), escape_html(default)));
+                    }
+                    output.push_str("</table>\n");
+                }
+            } else {
+                for (label, value) in &item.fields {
+                    output.push_str(&format!("<p>{}: <code>{}</code></p>\n", label, escape_html(value)));
+                }
+            }
+        }
+    }
+
+    output
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn markdown_lists_documented_declarations() {
+        let source = "## Adds two numbers.\nfn add(a, b) { return a + b; }\n## A point.\nstruct Point { x: 0, y: 0 }\nconst LIMIT = 3;";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+
+        let markdown = render("geometry", &stmts, DocFormat::Markdown);
+        assert!(markdown.contains("### `fn add(a, b)`\n\nAdds two numbers.\n\nArity: `2`"));
+        assert!(markdown.contains("### `struct Point`\n\nA point.\n\n| Field | Default |"));
+        assert!(markdown.contains("| `x` | `0` |"));
+        assert!(markdown.contains("### `const LIMIT`\n\nValue: `3`"));
+    }
+}
@@ -8,6 +8,7 @@ pub struct Environment {
     pub(crate) values: HashMap<String, LiteralValue>,
     pub(crate) constants: HashMap<String, bool>,
     pub enclosing: Option<Rc<RefCell<Environment>>>,
+    pub(crate) sandboxed: bool, // Set on the globals of a `--sandbox` run, seen by every scope inside
 }
 
 impl Environment {
@@ -17,6 +18,7 @@ impl Environment {
             values: HashMap::new(),
             constants: HashMap::new(), // Initialize the constants map
             enclosing: None,
+            sandboxed: false,
         }
     }
 
@@ -26,6 +28,7 @@ impl Environment {
             values: HashMap::new(),
             constants: HashMap::new(),
             enclosing: Some(enclosing),
+            sandboxed: false,
         }
     }
 
@@ -37,6 +40,18 @@ impl Environment {
         }
     }
 
+    // Define a constant; it may shadow a variable of an enclosing scope, but not another constant
+    pub fn define_const(&mut self, name: String, value: LiteralValue) -> Result<(), String> {
+        if self.is_const(&name) {
+            return Err(format!("Constant '{}' is already defined.", name));
+        }
+        if self.values.contains_key(&name) {
+            return Err(format!("'{}' is already defined as a variable in this scope; use a new name for the constant.", name));
+        }
+        self.define(name, value, true);
+        Ok(())
+    }
+
     // Get the value of a variable, searching enclosing environments if necessary
     pub fn get(&self, name: &str) -> Option<LiteralValue> {
         if let Some(val) = self.values.get(name) {
@@ -48,30 +63,72 @@ impl Environment {
         None
     }
 
+    // Every name visible from here, for suggesting one when a lookup fails
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.keys().cloned().collect();
+        if let Some(enclosing) = &self.enclosing {
+            names.extend(enclosing.borrow().names());
+        }
+        names
+    }
 
-    // Assign a value to an existing variable, searching enclosing environments if necessary
-    pub fn assign(&mut self, name: &str, value: LiteralValue) -> bool {
-        if let Some(is_const) = self.constants.get(name) {
-            if *is_const {
-                // Prevent reassignment to a constant
-                println!("Cannot reassign to constant '{}'.", name);
-                return false;
-            }
+
+    // Copy of this environment and its enclosing scopes. Mutable values like struct instances are
+    // deep-copied so later changes don't leak into the snapshot; everything else is shared.
+    pub fn snapshot(&self) -> Environment {
+        Environment {
+            values: self.values.iter().map(|(name, value)| (name.clone(), value.deep_clone())).collect(),
+            constants: self.constants.clone(),
+            enclosing: self.enclosing.as_ref().map(|enclosing| Rc::new(RefCell::new(enclosing.borrow().snapshot()))),
+            sandboxed: self.sandboxed,
+        }
+    }
+
+    // Put back the bindings of a snapshot taken from this environment. The scopes are updated in
+    // place, so closures holding on to them see the restored bindings.
+    pub fn restore(&mut self, snapshot: Environment) {
+        self.values = snapshot.values;
+        self.constants = snapshot.constants;
+
+        if let (Some(enclosing), Some(saved)) = (&self.enclosing, snapshot.enclosing) {
+            let saved = Rc::try_unwrap(saved).map(RefCell::into_inner).unwrap_or_else(|shared| shared.borrow().clone());
+            enclosing.borrow_mut().restore(saved);
+        }
+    }
+
+    // Whether this scope or one enclosing it is sandboxed, which keeps scripts from files, the
+    // network and other processes
+    pub fn sandboxed(&self) -> bool {
+        self.sandboxed || self.enclosing.as_ref().is_some_and(|enclosing| enclosing.borrow().sandboxed())
+    }
+
+    // Whether `name` resolves to a constant, looking through enclosing environments
+    pub fn is_const(&self, name: &str) -> bool {
+        if self.values.contains_key(name) {
+            return self.constants.contains_key(name);
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().is_const(name),
+            None => false,
         }
+    }
 
+    // Assign a value to an existing variable, searching enclosing environments if necessary
+    pub fn assign(&mut self, name: &str, value: LiteralValue) -> Result<(), String> {
         if self.values.contains_key(name) {
+            if self.constants.contains_key(name) {
+                return Err(format!("Cannot reassign constant '{}'.", name));
+            }
             self.values.insert(name.to_string(), value);
-            true
+            Ok(())
         } else if let Some(ref enclosing) = self.enclosing {
             enclosing.borrow_mut().assign(name, value)
         } else {
-            println!("Failed to assign variable: '{}' with value '{}'", name, value.to_string());
-            false
+            Err(format!("Variable '{}' has not been declared.", name))
         }
     }
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,4 +137,43 @@ mod tests {
     fn try_init() {
         let _environment = Environment::new();
     }
+
+    #[test]
+    fn restore_undoes_changes_since_snapshot() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("count".to_string(), LiteralValue::Int(1), false);
+        let mut inner = Environment::new_with_enclosing(globals.clone());
+        inner.define("items".to_string(), LiteralValue::Array(vec![]), false);
+
+        let snapshot = inner.snapshot();
+        inner.assign("count", LiteralValue::Int(2)).unwrap();
+        inner.assign("items", LiteralValue::Array(vec![LiteralValue::Nil])).unwrap();
+        inner.define("added".to_string(), LiteralValue::True, true);
+
+        inner.restore(snapshot);
+        assert_eq!(globals.borrow().get("count"), Some(LiteralValue::Int(1)));
+        assert_eq!(inner.get("items"), Some(LiteralValue::Array(vec![])));
+        assert!(inner.get("added").is_none() && !inner.is_const("added"));
+    }
+
+    #[test]
+    fn constants_are_protected_through_enclosing_scopes() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("LIMIT".to_string(), LiteralValue::Int(3), true);
+        globals.borrow_mut().define("count".to_string(), LiteralValue::Int(0), false);
+
+        let mut inner = Environment::new_with_enclosing(globals.clone());
+        assert!(inner.is_const("LIMIT"));
+        assert_eq!(inner.assign("LIMIT", LiteralValue::Int(4)), Err("Cannot reassign constant 'LIMIT'.".to_string()));
+        assert!(inner.assign("count", LiteralValue::Int(1)).is_ok());
+        assert!(inner.assign("missing", LiteralValue::Nil).is_err());
+        assert_eq!(globals.borrow().get("count"), Some(LiteralValue::Int(1)));
+
+        // A constant may shadow an outer variable, but not a constant or a variable of its own scope
+        assert!(inner.define_const("count".to_string(), LiteralValue::Int(9)).is_ok());
+        assert_eq!(inner.define_const("LIMIT".to_string(), LiteralValue::Int(4)), Err("Constant 'LIMIT' is already defined.".to_string()));
+        inner.define("local".to_string(), LiteralValue::Nil, false);
+        assert!(inner.define_const("local".to_string(), LiteralValue::Nil).unwrap_err().contains("already defined as a variable"));
+        assert_eq!(globals.borrow().get("count"), Some(LiteralValue::Int(1)));
+    }
 }
\ No newline at end of file
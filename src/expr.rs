@@ -1,80 +1,188 @@
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
 use std::rc::Rc;
-use crate::scanner::{Token, TokenType};
+use crate::scanner::{Span, Token, TokenType};
 use crate::environment::Environment;
 
 use LiteralValue::*;
-use crate::literal_value::LiteralValue;
-use crate::modules::{rcn_io, rcn_math};
-use crate::types::rcn_struct::StructInstance;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::modules::{self, rcn_archive, rcn_clipboard, rcn_compress, rcn_debug, rcn_fs, rcn_html, rcn_http, rcn_io, rcn_mail, rcn_math, rcn_msgpack, rcn_net, rcn_notify, rcn_stats, rcn_template, rcn_term};
+use crate::typecheck::Type;
+use crate::suggest::did_you_mean;
+use crate::types::rcn_struct::{StructDefinition, StructInstance};
+
+thread_local! {
+    // The Err a `?` returns from the fn it is in, while its error unwinds to the fn's call
+    static PROPAGATED: RefCell<Option<LiteralValue>> = const { RefCell::new(None) };
+}
+
+/// The Err a `?` is returning, once the error it raised reaches the fn the `?` is in.
+pub(crate) fn take_propagated() -> Option<LiteralValue> {
+    PROPAGATED.with(|propagated| propagated.borrow_mut().take())
+}
+
+/// Whether a `?` is returning from its fn, which `catch` lets pass.
+pub(crate) fn propagating() -> bool {
+    PROPAGATED.with(|propagated| propagated.borrow().is_some())
+}
 
 #[derive(Clone)]
 pub enum Expr {
-    Array { elements: Vec<Expr> },
-    Assign { name: Token, value: Box<Expr>, },
-    Binary { left: Box<Expr>, operator: Token, right: Box<Expr> },
-    Call { callee: Box<Expr>, paren: Token, arguments: Vec<Expr>,  }, // Function calls
-    FieldAccess { object: Box<Expr>, field: Token }, // Access to fields in struct instance
-    FieldAssign { object: Box<Expr>, field: Token, value: Box<Expr> },
-    Grouping { expression: Box<Expr> },
-    Index { array: Box<Expr>, index: Box<Expr> }, // Array indexing
-    Literal { value: LiteralValue },
-    Logical { left: Box<Expr>, operator: Token, right: Box<Expr> },
-    MethodCall { object: Box<Expr>, method_name: String, arguments: Vec<Expr> },
-    PreFunction { module: String, name: String, args: Vec<Expr> }, // Pre-built functions
+    Array { elements: Vec<Expr>, span: Span },
+    Assign { name: Token, value: Box<Expr>, span: Span },
+    Binary { left: Box<Expr>, operator: Token, right: Box<Expr>, span: Span },
+    Call { callee: Box<Expr>, arguments: Vec<Expr>, span: Span }, // Function calls
+    FieldAccess { object: Box<Expr>, field: Token, span: Span }, // Access to fields in struct instance
+    FieldAssign { object: Box<Expr>, field: Token, value: Box<Expr>, span: Span },
+    Grouping { expression: Box<Expr>, span: Span },
+    Interpolate { parts: Vec<Expr>, span: Span }, // Interpolated heredoc string
+    Index { array: Box<Expr>, index: Box<Expr>, span: Span }, // Array indexing
+    IndexAssign { object: Box<Expr>, index: Box<Expr>, value: Box<Expr>, span: Span }, // `items[i] = value` or `map[key] = value`
+    Literal { value: LiteralValue, span: Span },
+    Logical { left: Box<Expr>, operator: Token, right: Box<Expr>, span: Span },
+    Map { entries: Vec<(Expr, Expr)>, span: Span }, // `{"key": value}` literal
+    MethodCall { object: Box<Expr>, method_name: String, arguments: Vec<Expr>, span: Span },
+    Module { name: String, function: Result<Box<Expr>, String>, member: Box<Expr>, span: Span }, // `stats.mean(xs)`: the module's function, or a member of a variable named `stats` if one is in scope
+    PreFunction { module: String, name: String, args: Vec<Expr>, span: Span }, // Pre-built functions
+    Propagate { result: Box<Expr>, span: Span }, // `result?`: the value of an Ok, or return the Err from the fn
+    Range { start: Box<Expr>, end: Box<Expr>, inclusive: bool, span: Span }, // `start..end` or `start..=end`
     StructInst {
         name: String,
         fields: HashMap<String, Expr>,
+        span: Span,
     }, // Struct Instance
-    Unary { operator: Token, right: Box<Expr> },
-    Variable { name: Token, },
-    Const { name: String, value: Box<Expr> },
+    Tuple { elements: Vec<Expr>, span: Span }, // The values of `return a, b;`
+    Unary { operator: Token, right: Box<Expr>, span: Span },
+    Variable { name: Token, span: Span },
+    Const { name: String, value: Box<Expr>, span: Span },
 }
 
 impl fmt::Debug for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>)-> fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self)
     }
 }
 
-impl Expr {
-    pub fn to_string(&self) -> String {
-        match self {
-            Expr::Array { elements} => format!("({elements:?}"),
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Expr::Array { elements, .. } => format!("({elements:?}"),
             Expr::Assign {
                 name,
-                value
-            } => format!("({name:?} = {}", value.to_string()),
+                value,
+                ..
+            } => format!("({name:?} = {}", value),
             Expr::Binary {
                 left,
                 operator,
                 right,
+                ..
             } => format!(
                 "({} {} {})",
                 operator.lexeme,
-                left.to_string(),
-                right.to_string()
+                left,
+                right
             ),
-            Expr::Call { callee, paren: _, arguments } => format!("({} {:?}", (*callee).to_string(), arguments),
-            Expr::Grouping { expression } => format!("(group {})", expression.to_string()),
-            Expr::Literal { value } => format!("{}", value.to_string()),
-            Expr::Unary { operator, right } => {
+            Expr::Call { callee, arguments, .. } => format!("({} {:?}", callee, arguments),
+            Expr::Grouping { expression, .. } => format!("(group {})", expression),
+            Expr::Interpolate { parts, .. } => format!("(interpolate {:?})", parts),
+            Expr::Literal { value, .. } => value.to_string(),
+            Expr::Unary { operator, right, .. } => {
                 let operator_str = operator.lexeme.clone();
                 let right_str = (*right).to_string();
                 format!("({} {})", operator_str, right_str)
             }
-            Expr::Variable { name } => format!("(var {})", name.lexeme),
-            Expr::Const { name, value } => format!("(const {})", name),
-            Expr::Logical { left, operator, right } => format!("({} {} {})", operator.to_string(), left.to_string(), right.to_string()),
-            _ => todo!()
+            Expr::Variable { name, .. } => format!("(var {})", name.lexeme),
+            Expr::Const { name, value: _, .. } => format!("(const {})", name),
+            Expr::Logical { left, operator, right, .. } => format!("({} {} {})", operator, left, right),
+            Expr::FieldAccess { object, field, .. } => format!("(. {} {})", object, field.lexeme),
+            Expr::FieldAssign { object, field, value, .. } => format!("(= (. {} {}) {})", object, field.lexeme, value),
+            Expr::Index { array, index, .. } => format!("(index {} {})", array, index),
+            Expr::IndexAssign { object, index, value, .. } => format!("(= (index {} {}) {})", object, index, value),
+            Expr::Map { entries, .. } => format!("(map {})", entries.iter().map(|(key, value)| format!("{}: {}", key, value)).collect::<Vec<_>>().join(", ")),
+            Expr::MethodCall { object, method_name, arguments, .. } => format!("(. {} {} {:?})", object, method_name, arguments),
+            Expr::Module { function: Ok(function), .. } => function.to_string(),
+            Expr::Module { member, .. } => member.to_string(),
+            Expr::PreFunction { module, name, args, .. } => format!("({}.{} {:?})", module, name, args),
+            Expr::Propagate { result, .. } => format!("(? {})", result),
+            Expr::Range { start, end, inclusive, .. } => format!("({} {} {})", if *inclusive { "..=" } else { ".." }, start, end),
+            Expr::StructInst { name, fields: _, .. } => format!("(struct {})", name),
+            Expr::Tuple { elements, .. } => format!("(tuple {})", elements.iter().map(|element| element.to_string()).collect::<Vec<_>>().join(" ")),
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl Expr {
+    /// Where this node starts in the source.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Array { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::FieldAccess { span, .. }
+            | Expr::FieldAssign { span, .. }
+            | Expr::Grouping { span, .. }
+            | Expr::Interpolate { span, .. }
+            | Expr::Index { span, .. }
+            | Expr::IndexAssign { span, .. }
+            | Expr::Literal { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Map { span, .. }
+            | Expr::MethodCall { span, .. }
+            | Expr::Module { span, .. }
+            | Expr::PreFunction { span, .. }
+            | Expr::Propagate { span, .. }
+            | Expr::Range { span, .. }
+            | Expr::StructInst { span, .. }
+            | Expr::Tuple { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Variable { span, .. }
+            | Expr::Const { span, .. } => *span,
         }
     }
 
-    pub fn evaluate(&self, environment: &RefCell<Environment>) -> Result<LiteralValue, String> {
+    // Replaces the span of this node, used for nodes built by modules outside the parser
+    pub fn with_span(mut self, new_span: Span) -> Expr {
+        match &mut self {
+            Expr::Array { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::FieldAccess { span, .. }
+            | Expr::FieldAssign { span, .. }
+            | Expr::Grouping { span, .. }
+            | Expr::Interpolate { span, .. }
+            | Expr::Index { span, .. }
+            | Expr::IndexAssign { span, .. }
+            | Expr::Literal { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Map { span, .. }
+            | Expr::MethodCall { span, .. }
+            | Expr::Module { span, .. }
+            | Expr::PreFunction { span, .. }
+            | Expr::Propagate { span, .. }
+            | Expr::Range { span, .. }
+            | Expr::StructInst { span, .. }
+            | Expr::Tuple { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Variable { span, .. }
+            | Expr::Const { span, .. } => *span = new_span,
+        }
+        self
+    }
+
+    /// Evaluates the expression, prefixing any error with the position of the innermost failing node.
+    pub fn evaluate(&self, environment: &Rc<RefCell<Environment>>) -> Result<LiteralValue, String> {
+        self.evaluate_node(environment).map_err(|msg| self.span().locate(msg))
+    }
+
+    fn evaluate_node(&self, environment: &Rc<RefCell<Environment>>) -> Result<LiteralValue, String> {
         match self {
-            Expr::Array { elements } => {
+            Expr::Array { elements, .. } => {
                 let mut evaluated_elements = Vec::new();
                 for element in elements {
                     evaluated_elements.push(element.evaluate(environment)?);
@@ -83,50 +191,28 @@ impl Expr {
                 Ok(Array(evaluated_elements))
 
             },
-            Expr::Assign { name, value } => {
+            Expr::Map { entries, .. } => build_map(entries, environment),
+            Expr::IndexAssign { object, index, value, .. } => assign_index(object, index, value, environment),
+            Expr::Tuple { elements, .. } => {
+                let values = elements.iter().map(|element| element.evaluate(environment)).collect::<Result<_, _>>()?;
+                Ok(Tuple(values))
+            },
+            Expr::Assign { name, value, .. } => {
                 let new_value = value.evaluate(environment)?; // Evaluate the assigned value
 
-                // Check if the value is a struct, and if so, create a new instance
-                let new_value = match new_value {
-                    StructInst(ref struct_obj) => {
-                        // Create a new struct instance with the same fields
-                        let mut new_fields = HashMap::new();
-                        for (field_name, field_value) in &struct_obj.fields {
-                            new_fields.insert(field_name.clone(), field_value.clone());
-                        }
-
-                        LiteralValue::StructInst(StructInstance {
-                            name: struct_obj.name.clone(),
-                            fields: new_fields,
-                        })
-                    }
-                    _ => new_value,
-                };
-
-                // Check if the variable is a constant
-                if environment.borrow().constants.contains_key(&name.lexeme) {
-                    return Err(format!("Cannot reassign to constant '{}'.", name.lexeme));
-                }
-
-                // Assign the new value to the variable in the environment
-                let assign_success = environment.borrow_mut().assign(&name.lexeme, new_value.clone());
-
-                if assign_success {
-                    Ok(new_value)
-                } else {
-                    Err(format!("Variable {} has not been declared.", name.lexeme))
-                }
+                environment.borrow_mut().assign(&name.lexeme, new_value.clone())?;
+                Ok(new_value)
             },
-            Expr::FieldAccess { object, field } => {
+            Expr::FieldAccess { object, field, .. } => {
                 let object_value = object.evaluate(environment)?;
 
                 match object_value {
                     StructInst(struct_instance) => {
+                        let struct_instance = struct_instance.borrow();
                         if let Some(value) = struct_instance.get_field(&field.lexeme) {
                             Ok(value.clone())
                         } else {
-                            print!("Field '{}' not found in struct '{}'.", field.lexeme, struct_instance.name);
-                            Err(format!("Field '{}' not found in struct '{}'.", field.lexeme, struct_instance.name))
+                            Err(struct_instance.missing_field(&field.lexeme))
                         }
                     }
                     Namespace(namespace_env) => {
@@ -137,33 +223,39 @@ impl Expr {
                                 _ => Ok(value.clone()), // Variable
                             }
                         } else {
-                            println!("Namespace {:?} is found", namespace_env);
-                            Err(format!("Variable or function '{}' not found in namespace.", field.lexeme))
+                            Err(missing_member(&namespace_env.borrow(), &field.lexeme))
                         }
                     }
 
-                    _ =>  {
-                        println!("Expected a struct or namespace for field access, but got '{}'.", object_value.to_type());
-                        Err(format!("Expected a struct or namespace for field access, but got '{}'.", object_value.to_type()))
+                    Map(entries) => {
+                        entries.get(&MapKey::from(field.lexeme.as_str())).cloned()
+                            .ok_or_else(|| format!("Key '{}' not found in map.", field.lexeme))
                     }
+                    StructDef(definition) => associated_function(&definition, &field.lexeme),
+                    _ => Err(format!("Expected a struct or namespace for field access, but got '{}'.", object_value.to_type())),
                 }
             },
-            Expr::FieldAssign { object, field, value } => {
-                let mut struct_instance_value = object.evaluate(environment)?;
+            Expr::FieldAssign { object, field, value, .. } => {
+                let struct_instance_value = object.evaluate(environment)?;
 
                 let evaluated_value = value.evaluate(environment)?;
 
-                match struct_instance_value.update_struct_field(field.lexeme.clone(), evaluated_value.clone()) {
-                    Ok(_) => {
-                        if let Expr::Variable { name } = &**object {
-                            environment.borrow_mut().assign(&name.lexeme, struct_instance_value.clone());
-                        }
-                        Ok(struct_instance_value)
-                    },
-                    Err(e) => Err(e)
+                // `config.key = value` sets a map entry, like it reads one
+                if let Map(mut entries) = struct_instance_value {
+                    entries.insert(MapKey::from(field.lexeme.as_str()), evaluated_value.clone());
+                    store(object, Map(entries), environment)?;
+                    return Ok(evaluated_value);
                 }
+
+                // Instances are shared references, so updating the field is visible through every binding
+                struct_instance_value.update_struct_field(field.lexeme.clone(), evaluated_value)?;
+                Ok(struct_instance_value)
             }
-            Expr::Variable { name } => {
+            Expr::Variable { name, .. } => {
+                // Natives of the interpreter are named like `io.capture`
+                if let Some((module, function)) = name.lexeme.split_once('.') {
+                    modules::check_sandbox(module, function, environment)?;
+                }
                 match environment.borrow().get(&name.lexeme) {
                     Some(value) => {
                         match value {
@@ -177,16 +269,14 @@ impl Expr {
                             }
                         }
                     },
-                    None => {
-                        print!("Undefined variable or namespace '{}'.", &name.lexeme);
-                        Err(format!("Undefined variable or namespace '{}'.", name.lexeme))
-                    },
+                    None => Err(undefined_variable(&name.lexeme, environment)),
                 }
             },
             Expr::Logical {
                 left,
                 operator,
                 right,
+                ..
             } => match operator.token_type {
                 TokenType::Or => {
                     let lhs_true = left.evaluate(environment)?.is_truthy();
@@ -214,34 +304,36 @@ impl Expr {
                         }
                     }
                 }
-                t_type => {
-                    print!("Invalid token in logical expression: {}", t_type);
-                    Err(format!("Invalid token in logical expression: {}", t_type))
-                }
+                t_type => Err(format!("Invalid token in logical expression: {}", t_type)),
             },
-            Expr::Literal { value } => Ok((*value).clone()),
-            Expr::Grouping { expression } => expression.evaluate(environment),
-            Expr::Unary { operator, right } => {
+            Expr::Literal { value, .. } => Ok((*value).clone()),
+            Expr::Grouping { expression, .. } => expression.evaluate(environment),
+            Expr::Interpolate { parts, .. } => {
+                let mut text = String::new();
+                for part in parts {
+                    text.push_str(&part.evaluate(environment)?.to_string());
+                }
+
+                Ok(StringValue(text))
+            }
+            Expr::Unary { operator, right, .. } => {
                 let right = right.evaluate(environment)?;
 
                 match (&right, operator.token_type) {
-                    (Number(x), TokenType::Minus) => Ok(Number(-x)),
-                    (_, TokenType::Minus) => {
-                        print!("Cannot use - for {:?}", right.to_type());
-                        Err(format!("Cannot use - for {:?}", right.to_type()))
-                    },
+                    (Int(_) | BigInt(_), TokenType::Minus) => Ok(LiteralValue::from_bigint(-right.as_bigint().unwrap_or_default())),
+                    (Float(x), TokenType::Minus) => Ok(Float(-x)),
+                    (Decimal(x), TokenType::Minus) => Ok(Decimal(-x)),
+                    (_, TokenType::Minus) => Err(format!("Cannot use - for {:?}", right.to_type())),
 
                     (any, TokenType::Bang) => Ok(any.is_falsy()),
-                    (_, t_type) => {
-                        print!("{} is not a valid operator.", t_type.to_string());
-                        Err(format!("{} is not a valid operator.", t_type.to_string()))
-                    }
+                    (_, t_type) => Err(format!("{} is not a valid operator.", t_type)),
                 }
             }
             Expr::Binary {
                 left,
                 operator,
                 right,
+                ..
             } => {
                 let left = left.evaluate(environment)?;
                 let right = right.evaluate(environment)?;
@@ -249,39 +341,78 @@ impl Expr {
                 match (&left, operator.token_type, &right) {
 
                     //PLUS
-                    (Number(x), TokenType::Plus, Number(y)) => Ok(Number(x + y)),
+                    (Int(_) | BigInt(_), TokenType::Plus | TokenType::Minus | TokenType::Star, Int(_) | BigInt(_)) => Ok(integer_arithmetic(&left, operator, &right)),
                     (StringValue(s1), TokenType::Plus, StringValue(s2)) => { Ok(StringValue(format!("{}{}", s1, s2))) }
-                    (StringValue(s1), TokenType::Plus, Number(x)) => Ok(StringValue(format!("{}{}", s1, x.to_string()))),
-                    (Number(x), TokenType::Plus, StringValue(s1)) => Ok(StringValue(format!("{}{}", x.to_string(), s1))),
+                    (StringValue(s1), TokenType::Plus, x @ (Int(_) | BigInt(_) | Float(_) | Decimal(_))) => Ok(StringValue(format!("{}{}", s1, x))),
+                    (x @ (Int(_) | BigInt(_) | Float(_) | Decimal(_)), TokenType::Plus, StringValue(s1)) => Ok(StringValue(format!("{}{}", x, s1))),
+                    (Array(a1), TokenType::Plus, Array(a2)) => Ok(Array(a1.iter().chain(a2).cloned().collect())),
 
-                    (Number(x), TokenType::Minus, Number(y)) => Ok(Number(x - y)),
-                    (StringValue(_s1), TokenType::Minus, StringValue(_s2)) => Err("NaN".to_string()),
-                    (StringValue(_s1), TokenType::Minus, Number(_x)) => Err("NaN".to_string()),
-                    (Number(_x), TokenType::Minus, StringValue(_s1)) => Err("NaN".to_string()),
-
-                    (Number(x), TokenType::Slash, Number(y)) => Ok(Number(x / y)),
-                    (Number(x), TokenType::Star, Number(y)) => Ok(Number(x * y)),
+                    (StringValue(s), TokenType::Star, n @ (Int(_) | Float(_))) | (n @ (Int(_) | Float(_)), TokenType::Star, StringValue(s)) => {
+                        match n.as_int() {
+                            Some(n) if n >= 0 => match s.len().checked_mul(n as usize) {
+                                Some(length) if length <= MAX_RANGE_LENGTH as usize => Ok(StringValue(s.repeat(n as usize))),
+                                _ => Err(format!("Cannot repeat a string {} times; the result would be longer than {} bytes.", n, MAX_RANGE_LENGTH)),
+                            },
+                            _ => Err(format!("Cannot repeat a string {} times.", n)),
+                        }
+                    }
+                    // Decimals stay exact with Ints, but mixing in a Float would bring the rounding back
+                    (Decimal(_), TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash, Int(_) | Decimal(_))
+                    | (Int(_), TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash, Decimal(_)) => decimal_arithmetic(&left, operator, &right),
+                    (Decimal(_), TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash, Float(_))
+                    | (Float(_), TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash, Decimal(_)) => {
+                        Err(format!("Cannot apply '{}' to {} and {}. Use decimal() to convert the Float.", operator.lexeme, left.to_type(), right.to_type()))
+                    }
+                    // `/` and any mix with a Float promote to Float
+                    (Int(_) | BigInt(_) | Float(_), TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash, Int(_) | BigInt(_) | Float(_)) => {
+                        let (x, y) = (left.as_f64().unwrap_or_default(), right.as_f64().unwrap_or_default());
+                        Ok(Float(match operator.token_type {
+                            TokenType::Plus => x + y,
+                            TokenType::Minus => x - y,
+                            TokenType::Star => x * y,
+                            _ => x / y,
+                        }))
+                    }
 
-                    (Number(x), TokenType::Greater, Number(y)) => Ok(LiteralValue::check_bool(x > y)),
+                    (Int(_) | BigInt(_) | Float(_) | Decimal(_), TokenType::Greater, Int(_) | BigInt(_) | Float(_) | Decimal(_)) => Ok(LiteralValue::check_bool(compare_numbers(&left, &right) == Some(Ordering::Greater))),
                     (StringValue(s1), TokenType::Greater, StringValue(s2)) => Ok(LiteralValue::check_bool(s1 > s2)),
-                    (Number(x), TokenType::GreaterEqual, Number(y)) => Ok(LiteralValue::check_bool(x >= y)),
+                    (Int(_) | BigInt(_) | Float(_) | Decimal(_), TokenType::GreaterEqual, Int(_) | BigInt(_) | Float(_) | Decimal(_)) => Ok(LiteralValue::check_bool(matches!(compare_numbers(&left, &right), Some(Ordering::Greater | Ordering::Equal)))),
                     (StringValue(s1), TokenType::GreaterEqual, StringValue(s2)) => Ok(LiteralValue::check_bool(s1 >= s2)),
 
-                    (Number(x), TokenType::Less, Number(y)) => Ok(LiteralValue::check_bool(x < y)),
+                    (Int(_) | BigInt(_) | Float(_) | Decimal(_), TokenType::Less, Int(_) | BigInt(_) | Float(_) | Decimal(_)) => Ok(LiteralValue::check_bool(compare_numbers(&left, &right) == Some(Ordering::Less))),
                     (StringValue(s1), TokenType::Less, StringValue(s2)) => Ok(LiteralValue::check_bool(s1 < s2)),
-                    (Number(x), TokenType::LessEqual, Number(y)) => Ok(LiteralValue::check_bool(x <= y)),
+                    (Int(_) | BigInt(_) | Float(_) | Decimal(_), TokenType::LessEqual, Int(_) | BigInt(_) | Float(_) | Decimal(_)) => Ok(LiteralValue::check_bool(matches!(compare_numbers(&left, &right), Some(Ordering::Less | Ordering::Equal)))),
                     (StringValue(s1), TokenType::LessEqual, StringValue(s2)) => Ok(LiteralValue::check_bool(s1 <= s2)),
 
+                    // Membership: array elements, map keys and substrings
+                    (x, TokenType::In, Array(elements) | Tuple(elements)) => Ok(LiteralValue::check_bool(elements.contains(x))),
+                    (key, TokenType::In, Map(entries)) => Ok(LiteralValue::check_bool(entries.contains_key(&MapKey::from_value(key)?))),
+                    (StringValue(part), TokenType::In, StringValue(text)) => Ok(LiteralValue::check_bool(text.contains(part.as_str()))),
+                    (x, TokenType::In, y) => Err(format!("Cannot check whether {} is in {}.", x.to_type(), y.to_type())),
+
                     (x, TokenType::BangEqual, y) => Ok(LiteralValue::check_bool(x != y)),
                     (x, TokenType::EqualEqual, y) => Ok(LiteralValue::check_bool(x == y)),
-                    (_x, t_type, _y) => {
-                        print!("{} has not been implemented", t_type.to_string());
-                        Err(format!("{} has not been implemented", t_type.to_string()))
+                    // Only `+` and `*` mix strings with numbers; everything else needs an explicit str() or num()
+                    (x, _, y) => {
+                        let hint = match (x, y) {
+                            (StringValue(_), Int(_) | BigInt(_) | Float(_) | Decimal(_)) | (Int(_) | BigInt(_) | Float(_) | Decimal(_), StringValue(_)) => " Use num() or str() to convert one side.",
+                            _ => "",
+                        };
+                        Err(format!("Cannot apply '{}' to {} and {}.{}", operator.lexeme, x.to_type(), y.to_type(), hint))
                     }
                 }
             }
-            Expr::PreFunction { module, name, args } => {
+            Expr::Propagate { result, .. } => propagate(result, environment),
+            Expr::Module { name, function, member, .. } => {
+                if environment.borrow().get(name).is_some() {
+                    member.evaluate(environment)
+                } else {
+                    function.as_ref().map_err(Clone::clone)?.evaluate(environment)
+                }
+            }
+            Expr::PreFunction { module, name, args, .. } => {
                 let function = name;
+                modules::check_sandbox(module, function, environment)?;
 
                 // Evaluate arguments
                 let evaluated_args: Result<Vec<_>, _> = args.iter().map(|arg| arg.evaluate(environment)).collect();
@@ -305,6 +436,12 @@ impl Expr {
                         "tan" => rcn_math::tan(evaluated_args),
                         "degrees" => rcn_math::degrees(evaluated_args),
                         "radians" => rcn_math::radians(evaluated_args),
+                        "vec2" => rcn_math::vec2(evaluated_args),
+                        "vec3" => rcn_math::vec3(evaluated_args),
+                        "dot" => rcn_math::dot(evaluated_args),
+                        "cross" => rcn_math::cross(evaluated_args),
+                        "normalize" => rcn_math::normalize(evaluated_args),
+                        "matmul" => rcn_math::matmul(evaluated_args),
                         // Add more math functions here
                         _ => {
                             Err(format!("Function '{}.{}' not implemented.", module, function))
@@ -313,7 +450,12 @@ impl Expr {
                 } else if module == "io" {
                     match function.as_str() {
                         "read_input" => rcn_io::read_input(),
+                        "lines_stdin" => rcn_io::lines_stdin(),
+                        "confirm" => rcn_io::confirm(evaluated_args),
+                        "select" => rcn_io::select(evaluated_args),
+                        "password" => rcn_io::password(evaluated_args),
                         "open_file" => rcn_io::open_file(evaluated_args),
+                        "open_handle" => rcn_io::open_handle(evaluated_args),
                         "write_file" => rcn_io::write_file(evaluated_args),
                         "file_exists" => rcn_io::file_exists(evaluated_args),
                         "delete_file" => rcn_io::delete_file(evaluated_args),
@@ -321,16 +463,126 @@ impl Expr {
                             Err(format!("Function '{}.{}' not implemented.", module, function))
                         },
                     }
+                } else if module == "debug" {
+                    match function.as_str() {
+                        "env" => rcn_debug::env(environment),
+                        "globals" => rcn_debug::globals(environment),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "fs" {
+                    match function.as_str() {
+                        "glob" => rcn_fs::glob(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "archive" {
+                    match function.as_str() {
+                        "zip" => rcn_archive::zip(evaluated_args),
+                        "unzip" => rcn_archive::unzip(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "compress" {
+                    match function.as_str() {
+                        "gzip" => rcn_compress::gzip(evaluated_args),
+                        "gunzip" => rcn_compress::gunzip(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "html" {
+                    match function.as_str() {
+                        "parse" => rcn_html::parse(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "msgpack" {
+                    match function.as_str() {
+                        "encode" => rcn_msgpack::encode(evaluated_args),
+                        "decode" => rcn_msgpack::decode(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "http" {
+                    match function.as_str() {
+                        "encode_query" => rcn_http::encode_query(evaluated_args),
+                        "parse_query" => rcn_http::parse_query(evaluated_args),
+                        "parse_cookies" => rcn_http::parse_cookies(evaluated_args),
+                        "multipart" => rcn_http::multipart(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "mail" {
+                    match function.as_str() {
+                        "send" => rcn_mail::send(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "net" {
+                    match function.as_str() {
+                        "resolve" => rcn_net::resolve(evaluated_args),
+                        "ping" => rcn_net::ping(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "notify" {
+                    match function.as_str() {
+                        "webhook" => rcn_notify::webhook(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "stats" {
+                    match function.as_str() {
+                        "mean" => rcn_stats::mean(evaluated_args),
+                        "stddev" => rcn_stats::stddev(evaluated_args),
+                        "percentile" => rcn_stats::percentile(evaluated_args),
+                        "correlation" => rcn_stats::correlation(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "clipboard" {
+                    match function.as_str() {
+                        "get" => rcn_clipboard::get(),
+                        "set" => rcn_clipboard::set(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "term" {
+                    match function.as_str() {
+                        "progress" => rcn_term::progress(evaluated_args),
+                        "spinner" => rcn_term::spinner(),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
+                } else if module == "template" {
+                    match function.as_str() {
+                        "render" => rcn_template::render(evaluated_args),
+                        _ => {
+                            Err(format!("Function '{}.{}' not implemented.", module, function))
+                        },
+                    }
                 } else {
                     Err(format!("Module '{}' not found.", module))
                 }
             }
-            Expr::Call { callee, paren: _, arguments} => {
+            Expr::Call { callee, arguments, .. } => {
                 let callable = callee.evaluate(environment)?;
                 match callable {
                     Callable { name, arity, fun } => {
                         if arguments.len() != arity.try_into().unwrap() {
-                            print!("Callable {} expected {} arguments but got {}", name, arity, arguments.len());
                             return Err(format!("Callable {} expected {} arguments but got {}", name, arity, arguments.len()));
                         }
 
@@ -340,29 +592,60 @@ impl Expr {
                             arg_vals.push(val);
                         }
 
-                        let result = fun(Rc::from(environment.clone()), &arg_vals);
-                        Ok(result)
+                        fun(environment.clone(), &arg_vals)
                     }
-                    _ => {
-                        print!("'{}' is not callable", callee.to_string());
-                        Err(format!("'{}' is not callable", callee.to_string()))
-                    },
+                    _ => Err(format!("'{}' is not callable", callee)),
                 }
             }
-            Expr::MethodCall { object, method_name, arguments } => {
+            Expr::MethodCall { object, method_name, arguments, .. } => {
                 let mut obj_value = object.evaluate(environment)?;
+                let arg_vals = arguments.iter().map(|arg| arg.evaluate(environment)).collect::<Result<Vec<_>, _>>()?;
 
-                // Call the method, which modifies `obj_value` in place
-                let result = obj_value.call_method(&method_name, arguments.iter().map(|arg| arg.evaluate(environment)).collect::<Result<Vec<_>, _>>()?)?;
+                // Functions exported by an imported module, associated with a struct or stored in a struct field, are called directly
+                let member = match &obj_value {
+                    Namespace(namespace_env) => Some(namespace_env.borrow().get(method_name)),
+                    StructDef(definition) => Some(Some(associated_function(definition, method_name)?)),
+                    StructInst(instance) => match instance.borrow().get_field(method_name) {
+                        Some(Callable { .. }) => Some(instance.borrow().get_field(method_name).cloned()),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let Some(function) = member {
+                    return match function {
+                        Some(Callable { name, arity, fun }) => {
+                            if arg_vals.len() != arity as usize {
+                                return Err(format!("Callable {} expected {} arguments but got {}", name, arity, arg_vals.len()));
+                            }
+                            fun(environment.clone(), &arg_vals)
+                        }
+                        _ => Err(match &obj_value {
+                            Namespace(namespace) => missing_member(&namespace.borrow(), method_name),
+                            _ => format!("Variable or function '{}' not found in namespace.", method_name),
+                        }),
+                    };
+                }
 
-                // If the object was a variable, update it in the environment
-                if let Expr::Variable { name } = &**object {
-                    environment.borrow_mut().assign(&name.lexeme, obj_value.clone());
+                // `par_map` runs on threads the interpreter manages, through the native it defines
+                if method_name == "par_map" && matches!(obj_value, Array(_)) {
+                    let native = environment.borrow().get("array.par_map");
+                    if let Some(Callable { fun, .. }) = native {
+                        let [function] = arg_vals.as_slice() else {
+                            return Err(format!("par_map method takes exactly one argument, got {}.", arg_vals.len()));
+                        };
+                        return fun(environment.clone(), &[obj_value.clone(), function.clone()]);
+                    }
                 }
 
+                // Call the method, which modifies `obj_value` in place
+                let result = obj_value.call_method(method_name, arg_vals)?;
+
+                // Write the modified value back to the variable, struct field or element it came from
+                store(object, obj_value, environment)?;
+
                 Ok(result)
             }
-            Expr::StructInst { name, fields } => {
+            Expr::StructInst { name, fields, .. } => {
                 // Retrieve the struct definition
                 let struct_def = match environment.borrow().get(name) {
                     Some(LiteralValue::StructDef(def)) => def.clone(),
@@ -380,15 +663,16 @@ impl Expr {
                     if let Some(expected_expr) = struct_def.fields.get(field_name) {
                         let value = expr.evaluate(environment)?;
 
-                        // Optionally: Check if the type of the evaluated value matches the expected type.
-                        let expected_value = expected_expr.evaluate(environment)?;
+                        // The default value decides which values the field accepts
+                        let expected_type = Type::of_value(&expected_expr.evaluate(environment)?);
+                        let value_type = Type::of_value(&value);
 
-                        if value.to_type() != expected_value.to_type() {
+                        if !field_accepts(&expected_type, &value_type, environment) {
                             return Err(format!(
-                                "Type mismatch for field '{}': expected {:?}, got {:?}",
+                                "Type mismatch for field '{}': expected {}, got {}",
                                 field_name,
-                                expected_value.to_type(),
-                                value.to_type()
+                                expected_type,
+                                value_type
                             ));
                         }
 
@@ -410,58 +694,332 @@ impl Expr {
                     }
                 }
 
-                Ok(LiteralValue::StructInst(StructInstance {
+                Ok(LiteralValue::StructInst(Rc::new(RefCell::new(StructInstance {
                     name: struct_def.name.clone(),
                     fields: evaluated_fields,
-                }))
+                    frozen: false,
+                }))))
             }
-            Expr::Index { array, index } => {
+            Expr::Range { start, end, inclusive, .. } => {
+                let (start, end) = range_bounds(start, end, *inclusive, environment)?;
+                if end.saturating_sub(start) > MAX_RANGE_LENGTH {
+                    return Err(format!("Range {}..{} has more than {} elements.", start, end, MAX_RANGE_LENGTH));
+                }
+                Ok(Array((start..end.max(start)).map(Int).collect()))
+            }
+            Expr::Index { array, index, .. } => {
                 let array_value = array.evaluate(environment)?;
+
+                // `items[1..3]` takes a slice instead of one element
+                if let Expr::Range { start, end, inclusive, .. } = &**index {
+                    let (start, end) = range_bounds(start, end, false, environment)?;
+                    return slice(array_value, start, end, *inclusive);
+                }
+
                 let index_value = index.evaluate(environment)?;
 
-                if let Array(arr) = array_value {
-                    if let Number(idx) = index_value {
-                        let idx = idx as usize;
-                        if idx < arr.len() {
-                            Ok(arr[idx].clone())
+                if let Map(entries) = &array_value {
+                    let key = MapKey::from_value(&index_value)?;
+                    return entries.get(&key).cloned().ok_or_else(|| format!("Key {} not found in map.", key));
+                }
+
+                // `lib[name]` looks a module member up by a name only known at runtime, e.g. from members(lib)
+                if let Namespace(namespace) = &array_value {
+                    let StringValue(name) = &index_value else {
+                        return Err(format!("Module members are looked up by name, got {}.", index_value.to_type()));
+                    };
+                    return namespace.borrow().values.get(name).cloned()
+                        .ok_or_else(|| format!("Module has no member '{}'.{}", name, did_you_mean(name, namespace.borrow().values.keys().map(String::as_str))));
+                }
+
+                if let Array(arr) | Tuple(arr) = array_value {
+                    if let Some(idx) = index_value.as_int() {
+                        if let Some(position) = element_position(idx, arr.len()) {
+                            Ok(arr[position].clone())
                         } else {
-                            print!("{}", "Array index out of bounds".to_string());
-                            Err("Array index out of bounds".to_string())
+                            Err(format!("Array index {} is out of bounds for {} elements.", idx, arr.len()))
                         }
                     } else {
-                        print!("{}", "Array index must be a number".to_string());
-                        Err("Array index must be a number".to_string())
+                        Err("Array index must be a whole number".to_string())
                     }
                 } else {
-                    print!("{}", "Attempt to index a non-array value".to_string());
                     Err("Attempt to index a non-array value".to_string())
                 }
             }
-            Expr::Const { name, value } => {
+            Expr::Const { name, value, .. } => {
                 let evaluated_value = value.evaluate(environment)?;
 
-                // Attempt to assign this value as a constant in the environment
-                if environment.borrow().get(name).is_none() {
-                    environment.borrow_mut().define(name.clone(), evaluated_value.clone(), true);
-                    Ok(evaluated_value)
-                } else {
-                    Err(format!("Constant '{}' is already defined.", name))
+                environment.borrow_mut().define_const(name.clone(), evaluated_value.clone())?;
+                Ok(evaluated_value)
+            }
+        }
+    }
+}
+
+// `Point.new`, a fn declared in the body of a struct
+fn associated_function(definition: &StructDefinition, name: &str) -> Result<LiteralValue, String> {
+    definition.functions.get(name).cloned().ok_or_else(|| {
+        format!("Struct '{}' has no fn '{}'.{}", definition.name, name, did_you_mean(name, definition.functions.keys().map(String::as_str)))
+    })
+}
+
+// The lookup errors below suggest the closest name when the one used looks like a typo
+
+fn undefined_variable(name: &str, environment: &RefCell<Environment>) -> String {
+    let names = environment.borrow().names();
+    format!("Undefined variable or namespace '{}'.{}", name, did_you_mean(name, names.iter().map(String::as_str)))
+}
+
+fn missing_member(namespace: &Environment, name: &str) -> String {
+    format!("Variable or function '{}' not found in namespace.{}", name, did_you_mean(name, namespace.values.keys().map(String::as_str)))
+}
+
+// The value of an Ok, or an error carrying an Err out to the fn it is returned from
+fn propagate(result: &Expr, environment: &Rc<RefCell<Environment>>) -> Result<LiteralValue, String> {
+    let value = result.evaluate(environment)?;
+    if let StructInst(instance) = &value {
+        let instance = instance.borrow();
+        match instance.name.as_str() {
+            "Ok" => return Ok(instance.get_field("value").cloned().unwrap_or(Nil)),
+            "Err" => {
+                let message = format!("Err returned by '?' outside of a fn: {}", instance.get_field("error").cloned().unwrap_or(Nil));
+                PROPAGATED.with(|propagated| *propagated.borrow_mut() = Some(value.clone()));
+                return Err(message);
+            }
+            _ => (),
+        }
+    }
+    Err(format!("'?' expects an Ok or Err result, got {}.", value.to_type()))
+}
+
+fn build_map(entries: &[(Expr, Expr)], environment: &Rc<RefCell<Environment>>) -> Result<LiteralValue, String> {
+    let mut map = HashMap::new();
+    for (key, value) in entries {
+        let key = MapKey::from_value(&key.evaluate(environment)?)?;
+        map.insert(key, value.evaluate(environment)?);
+    }
+    Ok(Map(map))
+}
+
+// `object[index] = value` on a map or array
+fn assign_index(object: &Expr, index: &Expr, value: &Expr, environment: &Rc<RefCell<Environment>>) -> Result<LiteralValue, String> {
+    let mut container = object.evaluate(environment)?;
+    let index_value = index.evaluate(environment)?;
+    let new_value = value.evaluate(environment)?;
+
+    match &mut container {
+        Map(entries) => {
+            entries.insert(MapKey::from_value(&index_value)?, new_value.clone());
+        }
+        Array(elements) => {
+            let length = elements.len();
+            let position = index_value.as_int().ok_or("Array index must be a whole number")?;
+            let slot = element_position(position, length)
+                .and_then(|idx| elements.get_mut(idx))
+                .ok_or_else(|| format!("Array index {} is out of bounds for {} elements.", index_value, length))?;
+            *slot = new_value.clone();
+        }
+        Tuple(_) => return Err("Tuples cannot be changed; build a new one instead.".to_string()),
+        other => return Err(format!("Cannot assign by index into {}.", other.to_type())),
+    }
+
+    // Maps and arrays are values, so the changed copy replaces the one it came from
+    store(object, container, environment)?;
+    Ok(new_value)
+}
+
+// Puts `value` back where `target` read it from, so changing a copy of a map or array that sits
+// in a variable, struct field, map entry or array element changes the original
+fn store(target: &Expr, value: LiteralValue, environment: &Rc<RefCell<Environment>>) -> Result<(), String> {
+    match target {
+        Expr::Variable { name, .. } => environment.borrow_mut().assign(&name.lexeme, value),
+        Expr::FieldAccess { object: owner, field, .. } => match owner.evaluate(environment)? {
+            owner @ StructInst(_) => owner.update_struct_field(field.lexeme.clone(), value),
+            Map(mut entries) => {
+                entries.insert(MapKey::from(field.lexeme.as_str()), value);
+                store(owner, Map(entries), environment)
+            }
+            _ => Ok(()),
+        },
+        Expr::Index { array: owner, index, .. } if !matches!(**index, Expr::Range { .. }) => {
+            let mut container = owner.evaluate(environment)?;
+            let index = index.evaluate(environment)?;
+            match &mut container {
+                Map(entries) => {
+                    entries.insert(MapKey::from_value(&index)?, value);
                 }
+                Array(elements) => match index.as_int().and_then(|idx| element_position(idx, elements.len())).and_then(|idx| elements.get_mut(idx)) {
+                    Some(slot) => *slot = value,
+                    None => return Ok(()),
+                },
+                Tuple(_) => return Err("Tuples cannot be changed; build a new one instead.".to_string()),
+                _ => return Ok(()),
             }
+            store(owner, container, environment)
+        }
+        _ => Ok(()),
+    }
+}
+
+// Ranges and repeated strings longer than this are almost certainly a mistake and would exhaust memory
+const MAX_RANGE_LENGTH: i64 = 10_000_000;
+
+// The whole-number bounds of a range, with the end made exclusive
+fn range_bounds(start: &Expr, end: &Expr, inclusive: bool, environment: &Rc<RefCell<Environment>>) -> Result<(i64, i64), String> {
+    let bound = |expr: &Expr| {
+        let value = expr.evaluate(environment)?;
+        value.as_int().ok_or_else(|| format!("Range bounds must be whole numbers, got {} {}.", value.to_type(), value))
+    };
+    let (start, end) = (bound(start)?, bound(end)?);
+    Ok((start, if inclusive { end.saturating_add(1) } else { end }))
+}
+
+// Where index `index` points in `length` elements, with negative indices counting back from the end
+fn element_position(index: i64, length: usize) -> Option<usize> {
+    let position = if index < 0 { index.checked_add(length as i64)? } else { index };
+    usize::try_from(position).ok().filter(|position| *position < length)
+}
+
+// The elements or characters from `start` up to `end`, where negative bounds count from the end
+fn slice(value: LiteralValue, start: i64, end: i64, inclusive: bool) -> Result<LiteralValue, String> {
+    let length = match &value {
+        Array(elements) => elements.len(),
+        StringValue(text) => text.chars().count(),
+        other => return Err(format!("Cannot slice {}; slicing takes an array or string.", other.to_type())),
+    };
+    let from_end = |bound: i64| if bound < 0 { bound.saturating_add(length as i64) } else { bound };
+    let (first, last) = (from_end(start), from_end(end));
+    let last = if inclusive { last.saturating_add(1) } else { last };
+    if first < 0 || last < first || last as usize > length {
+        return Err(format!("Slice {}{}{} is out of bounds for length {}.", start, if inclusive { "..=" } else { ".." }, end, length));
+    }
+
+    let (start, end) = (first as usize, last as usize);
+    Ok(match value {
+        Array(elements) => Array(elements[start..end].to_vec()),
+        StringValue(text) => StringValue(text.chars().skip(start).take(end - start).collect()),
+        _ => unreachable!(),
+    })
+}
 
-            _ => todo!()
+// Integer `+`, `-` and `*`, which move to a BigInt rather than wrap when the result leaves the i64 range
+fn integer_arithmetic(left: &LiteralValue, operator: &Token, right: &LiteralValue) -> LiteralValue {
+    if let (Int(x), Int(y)) = (left, right) {
+        let result = match operator.token_type {
+            TokenType::Plus => x.checked_add(*y),
+            TokenType::Minus => x.checked_sub(*y),
+            _ => x.checked_mul(*y),
+        };
+        if let Some(result) = result {
+            return Int(result);
+        }
+    }
+
+    let (x, y) = (left.as_bigint().unwrap_or_default(), right.as_bigint().unwrap_or_default());
+    LiteralValue::from_bigint(match operator.token_type {
+        TokenType::Plus => x + y,
+        TokenType::Minus => x - y,
+        _ => x * y,
+    })
+}
+
+// Decimal `+`, `-`, `*` and `/`, with Ints converted exactly
+fn decimal_arithmetic(left: &LiteralValue, operator: &Token, right: &LiteralValue) -> Result<LiteralValue, String> {
+    let as_decimal = |value: &LiteralValue| match value {
+        Int(x) => rust_decimal::Decimal::from(*x),
+        Decimal(x) => *x,
+        _ => rust_decimal::Decimal::ZERO,
+    };
+    let (x, y) = (as_decimal(left), as_decimal(right));
+
+    let result = match operator.token_type {
+        TokenType::Plus => x.checked_add(y),
+        TokenType::Minus => x.checked_sub(y),
+        TokenType::Star => x.checked_mul(y),
+        _ if y.is_zero() => return Err("Cannot divide a Decimal by zero.".to_string()),
+        _ => x.checked_div(y),
+    };
+    result.map(Decimal).ok_or_else(|| format!("Decimal overflow in {} {} {}.", left, operator.lexeme, right))
+}
+
+// Integers and Decimals compare exactly; as soon as one side is a Float both are compared as floats
+fn compare_numbers(left: &LiteralValue, right: &LiteralValue) -> Option<Ordering> {
+    match (left, right) {
+        (Int(_) | BigInt(_), Int(_) | BigInt(_)) => Some(left.as_bigint()?.cmp(&right.as_bigint()?)),
+        (Decimal(x), Decimal(y)) => Some(x.cmp(y)),
+        (Decimal(x), Int(y)) => Some(x.cmp(&rust_decimal::Decimal::from(*y))),
+        (Int(x), Decimal(y)) => Some(rust_decimal::Decimal::from(*x).cmp(y)),
+        _ => left.as_f64()?.partial_cmp(&right.as_f64()?),
+    }
+}
+
+// Whether a struct field whose default has type `expected` may hold a value of type `actual`.
+// A nil default accepts anything, an array default with mixed or no elements accepts any array,
+// and a struct default accepts instances of the same struct or of structs extending it.
+pub(crate) fn field_accepts(expected: &Type, actual: &Type, environment: &RefCell<Environment>) -> bool {
+    match (expected, actual) {
+        (Type::Nil, _) | (Type::Function { .. }, Type::Function { .. }) => true,
+        (Type::Array(_), Type::Array(element)) if **element == Type::Unknown => true,
+        (Type::Array(expected_element), Type::Array(element)) => {
+            **expected_element == Type::Unknown || field_accepts(expected_element, element, environment)
         }
+        (Type::Struct(expected_name), Type::Struct(name)) => extends(name, expected_name, environment),
+        // Any integer fits an integer field, and a Float field also takes them, the same promotion arithmetic does
+        (Type::Int | Type::BigInt | Type::Float, Type::Int | Type::BigInt) => true,
+        _ => expected == actual,
     }
+}
 
-    pub fn print(&self) {
-        println!("{}", self.to_string());
+// Whether struct `name` is `ancestor` or extends it, directly or through its parents
+pub(crate) fn extends(name: &str, ancestor: &str, environment: &RefCell<Environment>) -> bool {
+    let mut current = Some(name.to_string());
+    while let Some(name) = current {
+        if name == ancestor {
+            return true;
+        }
+        current = match environment.borrow().get(&name) {
+            Some(StructDef(definition)) => definition.parent.clone(),
+            _ => None,
+        };
     }
+    false
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::scanner::TokenType;
+    use crate::parser::Parser;
+    use crate::scanner::{Scanner, TokenType};
+    use crate::stmt::Stmt;
+
+    // Evaluate `var` declarations and expression statements, returning the last expression's value
+    fn evaluate_source(environment: &Rc<RefCell<Environment>>, source: &str) -> Result<LiteralValue, String> {
+        let tokens = Scanner::new(source).scan_tokens()?;
+        let stmts = Parser::new(tokens).parse()?;
+
+        let mut last = Nil;
+        for stmt in stmts {
+            match stmt {
+                Stmt::Var { name, initializer, .. } => {
+                    let value = initializer.evaluate(environment)?;
+                    environment.borrow_mut().define(name.lexeme, value, false);
+                }
+                Stmt::Expression { expression, .. } => last = expression.evaluate(environment)?,
+                other => panic!("Unsupported statement in test source: {}", other),
+            }
+        }
+
+        Ok(last)
+    }
+
+    // Declares a struct whose fields default to the given values, like `struct Point { x: 0 }` would
+    fn define_struct(environment: &Rc<RefCell<Environment>>, name: &str, fields: &[(&str, LiteralValue)]) {
+        let fields = fields.iter()
+            .map(|(field, value)| (field.to_string(), Expr::Literal { value: value.clone(), span: Span::default() }))
+            .collect();
+        let definition = crate::types::rcn_struct::StructDefinition { name: name.to_string(), parent: None, interfaces: vec![], fields, functions: Rc::default() };
+        environment.borrow_mut().define(name.to_string(), StructDef(definition), false);
+    }
 
     #[test]
     fn print_ast() {
@@ -470,16 +1028,20 @@ mod tests {
             lexeme: "-".to_string(),
             literal: None,
             line_number: 0,
+            column: 0,
         };
 
         let new_number = Expr::Literal {
-            value: LiteralValue::Number(123.0),
+            value: LiteralValue::Int(123),
+            span: Span::default(),
         };
 
         let group = Expr::Grouping {
             expression: Box::new(Expr::Literal {
-                value: LiteralValue::Number(45.67),
+                value: LiteralValue::Float(45.67),
+                span: Span::default(),
             }),
+            span: Span::default(),
         };
 
         let multi_token = Token {
@@ -487,19 +1049,314 @@ mod tests {
             lexeme: "*".to_string(),
             literal: None,
             line_number: 0,
+            column: 0,
         };
 
         let ast = Expr::Binary {
             left: Box::new(Expr::Unary {
                 operator: minus_token,
                 right: Box::new(new_number),
+                span: Span::default(),
             }),
             operator: multi_token,
             right: Box::new(group),
+            span: Span::default(),
         };
 
         // Print the abstract syntax tree.
         let result = ast.to_string();
         assert_eq!(result, "(* (- 123) (group 45.67))");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn frozen_struct_rejects_field_assignment() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        define_struct(&environment, "Config", &[("port", Int(80))]);
+        environment.borrow_mut().define("freeze".to_string(), Callable { name: "freeze".to_string(), arity: 1, fun: Rc::new(crate::modules::rcn_std::freeze) }, false);
+
+        let error = evaluate_source(&environment, "var config = Config {}; freeze(config); config.port = 8080;").unwrap_err();
+        assert!(error.ends_with("Cannot assign to field 'port' of frozen struct 'Config'."), "{}", error);
+        assert_eq!(evaluate_source(&environment, "config.port;").unwrap(), Int(80));
+    }
+
+    #[test]
+    fn struct_assignment_shares_instance() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        define_struct(&environment, "Point", &[("x", Int(0))]);
+
+        let shared = evaluate_source(&environment, "var a = Point { x: 1 }; var b = a; b.x = 5; a.x;").unwrap();
+        assert_eq!(shared, Int(5));
+
+        let copied = evaluate_source(&environment, "var c = a.clone(); c.x = 7; a.x;").unwrap();
+        assert_eq!(copied, Int(5));
+    }
+
+    #[test]
+    fn pretty_string_stops_at_cycles() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        define_struct(&environment, "Node", &[("next", Nil)]);
+
+        let node = evaluate_source(&environment, "var a = Node {}; a.next = a;").unwrap();
+        assert_eq!(node.to_pretty_string(true), "Node { next: <cycle Node> }");
+        assert_eq!(node.to_pretty_string(false), "Node {\n    next: <cycle Node>,\n}");
+    }
+
+    #[test]
+    fn clones_keep_cycles_inside_the_copy() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        define_struct(&environment, "Node", &[("next", Nil)]);
+
+        let original = evaluate_source(&environment, "var a = Node {}; a.next = a;").unwrap();
+        let StructInst(copy) = evaluate_source(&environment, "var b = a.clone(); b;").unwrap() else { panic!("Expected an instance") };
+        let StructInst(original) = original else { panic!("Expected an instance") };
+        let Some(StructInst(next)) = copy.borrow().get_field("next").cloned() else { panic!("Expected an instance") };
+        assert!(Rc::ptr_eq(&next, &copy) && !Rc::ptr_eq(&copy, &original));
+    }
+
+    #[test]
+    fn debug_env_shadows_outer_bindings() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("count".to_string(), Int(3), false);
+        globals.borrow_mut().define("name".to_string(), StringValue("outer".to_string()), false);
+
+        let environment = Rc::new(RefCell::new(Environment::new_with_enclosing(globals)));
+        environment.borrow_mut().define("name".to_string(), StringValue("inner".to_string()), false);
+
+        let bindings = evaluate_source(&environment, "var e = debug.env(); e.name;").unwrap();
+        assert_eq!(bindings, StringValue("String: inner".to_string()));
+
+        let globals = evaluate_source(&environment, "var g = debug.globals(); g.name;").unwrap();
+        assert_eq!(globals, StringValue("String: outer".to_string()));
+    }
+
+    #[test]
+    fn mixed_operands_need_explicit_conversion() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+
+        assert_eq!(evaluate_source(&environment, "\"n\" + 1;").unwrap(), StringValue("n1".to_string()));
+        assert_eq!(
+            evaluate_source(&environment, "\"10\" < 2;").unwrap_err(),
+            "Line 1:6: Cannot apply '<' to String and Int. Use num() or str() to convert one side."
+        );
+        assert_eq!(evaluate_source(&environment, "true - nil;").unwrap_err(), "Line 1:6: Cannot apply '-' to Bool and nil.");
+    }
+
+    #[test]
+    fn methods_apply_to_any_receiver() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        environment.borrow_mut().define("config".to_string(), Map(HashMap::from([
+            (MapKey::from("port"), Int(80)),
+            (MapKey::from("host"), StringValue("local".to_string())),
+        ])), false);
+
+        assert_eq!(evaluate_source(&environment, "[1, 2, 3].length();").unwrap(), Int(3));
+        assert_eq!(evaluate_source(&environment, "\" Hi \".trim().upper();").unwrap(), StringValue("HI".to_string()));
+        assert_eq!(evaluate_source(&environment, "config.keys();").unwrap(), Array(vec![StringValue("host".to_string()), StringValue("port".to_string())]));
+        assert_eq!(evaluate_source(&environment, "(1 + 2).length();").unwrap_err(), "Line 1:1: 'length' method not available on Int");
+    }
+
+    #[test]
+    fn arrays_concatenate_and_compare_by_elements() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+
+        let joined = evaluate_source(&environment, "var a = [1, 2]; var b = [3]; a + b;").unwrap();
+        assert_eq!(joined, Array(vec![Int(1), Int(2), Int(3)]));
+        assert_eq!(evaluate_source(&environment, "var c = [1, 2, 3]; a + b == c;").unwrap(), True);
+        assert_eq!(evaluate_source(&environment, "a == b;").unwrap(), False);
+        assert_eq!(evaluate_source(&environment, "a;").unwrap(), Array(vec![Int(1), Int(2)]));
+    }
+
+    #[test]
+    fn string_times_number_repeats() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+
+        assert_eq!(evaluate_source(&environment, "\"-\" * 3;").unwrap(), StringValue("---".to_string()));
+        assert_eq!(evaluate_source(&environment, "2 * \"ab\";").unwrap(), StringValue("abab".to_string()));
+        assert!(evaluate_source(&environment, "\"-\" * 1.5;").is_err());
+
+        let error = evaluate_source(&environment, "\"ab\" * 9223372036854775807;").unwrap_err();
+        assert!(error.ends_with("Cannot repeat a string 9223372036854775807 times; the result would be longer than 10000000 bytes."), "{}", error);
+    }
+
+    #[test]
+    fn ints_stay_exact_until_a_float_is_involved() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        let type_of = |source: &str| evaluate_source(&environment, source).unwrap().to_type();
+
+        assert_eq!(evaluate_source(&environment, "9007199254740993 + 2;").unwrap().to_string(), "9007199254740995");
+        assert_eq!(type_of("2 * 3 - 1;"), "Int");
+        assert_eq!(type_of("2 + 0.5;"), "Float");
+        assert_eq!(type_of("6 / 3;"), "Float");
+        assert_eq!(evaluate_source(&environment, "7 / 2;").unwrap(), Float(3.5));
+        assert_eq!(evaluate_source(&environment, "1 == 1.0;").unwrap(), True);
+
+        assert_eq!(evaluate_source(&environment, "[10, 20][1.0];").unwrap(), Int(20));
+        assert_eq!(evaluate_source(&environment, "[10, 20][0.5];").unwrap_err(), "Line 1:1: Array index must be a whole number");
+    }
+
+    #[test]
+    fn ints_grow_into_bigints_instead_of_overflowing() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        let text_of = |source: &str| evaluate_source(&environment, source).unwrap().to_string();
+        let type_of = |source: &str| evaluate_source(&environment, source).unwrap().to_type();
+
+        assert_eq!(text_of("9223372036854775807 + 1;"), "9223372036854775808");
+        assert_eq!(type_of("9223372036854775807 + 1;"), "BigInt");
+        assert_eq!(text_of("99999999999999999999 * 99999999999999999999;"), "9999999999999999999800000000000000000001");
+        assert_eq!(type_of("99999999999999999999 - 99999999999999999998;"), "Int");
+        assert_eq!(text_of("-(0 - 9223372036854775807 - 1);"), "9223372036854775808");
+        assert_eq!(evaluate_source(&environment, "99999999999999999999 > 9223372036854775807;").unwrap(), True);
+        assert_eq!(evaluate_source(&environment, "100000000000000000000 == 100000000000000000000;").unwrap(), True);
+    }
+
+    #[test]
+    fn ranges_build_arrays_and_slice() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        let text_of = |source: &str| evaluate_source(&environment, source).unwrap().to_string();
+
+        assert_eq!(text_of("1..4;"), text_of("[1, 2, 3];"));
+        assert_eq!(text_of("1..=4;"), text_of("[1, 2, 3, 4];"));
+        assert_eq!(text_of("5..2;"), text_of("[];"));
+        assert_eq!(text_of("0..2 + 1;"), text_of("[0, 1, 2];"));
+        assert_eq!(text_of("[10, 20, 30, 40][1..3];"), text_of("[20, 30];"));
+        assert_eq!(text_of("\"recolon\"[0..=1];"), "re");
+        assert_eq!(evaluate_source(&environment, "[1, 2][1..5];").unwrap_err(), "Line 1:1: Slice 1..5 is out of bounds for length 2.");
+        assert_eq!(text_of("[10, 20, 30, 40][-1];"), "40");
+        assert_eq!(text_of("[10, 20, 30, 40][1..-1];"), text_of("[20, 30];"));
+        assert_eq!(text_of("\"recolon\"[-3..=-1];"), "lon");
+        assert_eq!(evaluate_source(&environment, "[1, 2][-3];").unwrap_err(), "Line 1:1: Array index -3 is out of bounds for 2 elements.");
+        assert_eq!(text_of("var items = [1, 2, 3]; items[-1] = 9; items;"), text_of("[1, 2, 9];"));
+        assert_eq!(evaluate_source(&environment, "1..2.5;").unwrap_err(), "Line 1:2: Range bounds must be whole numbers, got Float 2.5.");
+    }
+
+    #[test]
+    fn decimals_add_up_exactly() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        let text_of = |source: &str| evaluate_source(&environment, source).unwrap().to_string();
+
+        assert_eq!(text_of("0.1d + 0.2d;"), "0.3");
+        assert_eq!(evaluate_source(&environment, "0.1d + 0.2d == 0.3d;").unwrap(), True);
+        assert_eq!(text_of("19.99d * 3;"), "59.97");
+        assert_eq!(text_of("(10d / 3).round(2);"), "3.33");
+        assert_eq!(text_of("1234567.5d.format(2);"), "1,234,567.50");
+        assert_eq!(text_of("(0d - 2.675d).format(2);"), "-2.68");
+        assert!(evaluate_source(&environment, "1.5d > 1;").unwrap() == True);
+        assert_eq!(
+            evaluate_source(&environment, "1.5d + 0.5;").unwrap_err(),
+            "Line 1:6: Cannot apply '+' to Decimal and Float. Use decimal() to convert the Float."
+        );
+    }
+
+    #[test]
+    fn map_keys_must_be_hashable() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        environment.borrow_mut().define("scores".to_string(), Map(HashMap::from([
+            (MapKey::from_value(&Int(1)).unwrap(), StringValue("one".to_string())),
+            (MapKey::from_value(&True).unwrap(), StringValue("yes".to_string())),
+            (MapKey::from("1"), StringValue("text".to_string())),
+            (MapKey::from_value(&Int(0)).unwrap(), StringValue("zero".to_string())),
+        ])), false);
+
+        assert_eq!(evaluate_source(&environment, "scores[1] + scores[\"1\"] + scores[true];").unwrap(), StringValue("onetextyes".to_string()));
+        assert_eq!(evaluate_source(&environment, "scores[-0];").unwrap(), StringValue("zero".to_string()));
+        assert_eq!(
+            evaluate_source(&environment, "[1] in scores;").unwrap_err(),
+            "Line 1:5: Array cannot be used as a map key; only numbers, strings and bools are hashable."
+        );
+        assert_eq!(evaluate_source(&environment, "scores[2];").unwrap_err(), "Line 1:1: Key 2 not found in map.");
+        assert_eq!(Map(HashMap::new()).to_pretty_string(true), "{}");
+    }
+
+    #[test]
+    fn map_literals_can_be_indexed_assigned_and_changed() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        let source = "var config = {\"name\": \"app\", \"ports\": [80, 443], 1: true};\n\
+                      config[\"name\"] = \"server\";\n\
+                      config[\"ports\"][0] = 8080;\n\
+                      config.debug = false;\n\
+                      var removed = config.remove(1);\n\
+                      [config.keys(), config[\"ports\"], config.name, config.has(\"debug\"), config.has(1), removed, {}.length()];";
+
+        let text = |s: &str| StringValue(s.to_string());
+        assert_eq!(evaluate_source(&environment, source).unwrap(), Array(vec![
+            Array(vec![text("debug"), text("name"), text("ports")]),
+            Array(vec![Int(8080), Int(443)]),
+            text("server"),
+            True,
+            False,
+            True,
+            Int(0),
+        ]));
+        assert_eq!(
+            evaluate_source(&environment, "config[[1]] = 2;").unwrap_err(),
+            "Line 1:1: Array cannot be used as a map key; only numbers, strings and bools are hashable."
+        );
+    }
+
+    #[test]
+    fn nested_elements_are_read_and_written_in_place() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        let source = "var grid = [[0, 0], [0, 0]];\n\
+                      grid[1][0] = 5;\n\
+                      grid[0][1] = grid[1][0] * 2;\n\
+                      var board = {\"cells\": [[\"-\", \"-\"]]};\n\
+                      board.cells[0][1] = \"x\";\n\
+                      [grid, board.cells[0]];";
+
+        let text = |s: &str| StringValue(s.to_string());
+        assert_eq!(evaluate_source(&environment, source).unwrap(), Array(vec![
+            Array(vec![Array(vec![Int(0), Int(10)]), Array(vec![Int(5), Int(0)])]),
+            Array(vec![text("-"), text("x")]),
+        ]));
+        assert_eq!(evaluate_source(&environment, "grid[2][0];").unwrap_err(), "Line 1:1: Array index 2 is out of bounds for 2 elements.");
+        assert_eq!(evaluate_source(&environment, "[[1]][0][0] = 2;").unwrap_err(), "Only elements of a variable or field can be assigned.");
+    }
+
+    #[test]
+    fn array_elements_are_assigned_in_place() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        let source = "var items = [1, 2, 3];\n\
+                      var copy = items;\n\
+                      items[0] = 10;\n\
+                      items[-1] = items[0] + 20;\n\
+                      [items, copy];";
+
+        assert_eq!(evaluate_source(&environment, source).unwrap(), Array(vec![
+            Array(vec![Int(10), Int(2), Int(30)]),
+            Array(vec![Int(1), Int(2), Int(3)]),
+        ]));
+        assert_eq!(evaluate_source(&environment, "items[3] = 4;").unwrap_err(), "Line 1:1: Array index 3 is out of bounds for 3 elements.");
+        assert_eq!(evaluate_source(&environment, "items[\"first\"] = 4;").unwrap_err(), "Line 1:1: Array index must be a whole number");
+    }
+
+    #[test]
+    fn in_checks_membership_by_value() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        environment.borrow_mut().define("config".to_string(), Map(HashMap::from([(MapKey::from("port"), Int(80))])), false);
+
+        assert_eq!(evaluate_source(&environment, "var three = [3]; var pairs = [three]; var copy = [3]; copy in pairs;").unwrap(), True);
+        assert_eq!(evaluate_source(&environment, "var names = [\"a\"]; \"b\" in names;").unwrap(), False);
+        assert_eq!(evaluate_source(&environment, "\"port\" in config;").unwrap(), True);
+        assert_eq!(evaluate_source(&environment, "\"ell\" in \"hello\";").unwrap(), True);
+        assert!(evaluate_source(&environment, "1 in 2;").is_err());
+    }
+
+    #[test]
+    fn reflection_reads_and_writes_fields() {
+        use crate::modules::rcn_std;
+
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        define_struct(&environment, "User", &[("name", StringValue(String::new())), ("age", Int(0))]);
+        environment.borrow_mut().define("fields".to_string(), Callable { name: "fields".to_string(), arity: 1, fun: Rc::new(rcn_std::fields) }, false);
+        environment.borrow_mut().define("get_field".to_string(), Callable { name: "get_field".to_string(), arity: 2, fun: Rc::new(rcn_std::get_field) }, false);
+        environment.borrow_mut().define("set_field".to_string(), Callable { name: "set_field".to_string(), arity: 3, fun: Rc::new(rcn_std::set_field) }, false);
+
+        let names = evaluate_source(&environment, "var u = User { name: \"Ann\", age: 30 }; fields(u);").unwrap();
+        assert_eq!(names.to_pretty_string(true), "[\"age\", \"name\"]");
+
+        let age = evaluate_source(&environment, "set_field(u, \"age\", 31); get_field(u, \"age\");").unwrap();
+        assert_eq!(age, Int(31));
+
+        assert!(evaluate_source(&environment, "get_field(u, \"email\");").is_err());
+    }
+}
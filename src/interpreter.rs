@@ -1,22 +1,225 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
 use colored::Colorize;
 
+use crate::analysis;
+use crate::diagnostics::{self, Diagnostic};
 use crate::environment::Environment;
-use crate::stmt::Stmt;
-use crate::literal_value::LiteralValue;
-use crate::modules::{rcn_std};
+use crate::expr::{self, extends, Expr};
+use crate::stmt::{LogLevel, Pattern, Stmt};
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::modules::{rcn_data, rcn_debug, rcn_fs, rcn_serial, rcn_std};
+use crate::modules::rcn_fs::Watch;
+use crate::modules::rcn_i18n::Catalog;
+use crate::parallel::{self, SharedValue};
 use crate::parser::Parser;
-use crate::scanner::Scanner;
-use crate::types::rcn_struct::StructDefinition;
+use crate::scanner::{Scanner, Span, Token};
+use crate::types::rcn_generator::{Generator, YieldFn};
+use crate::types::rcn_interface::InterfaceDefinition;
+use crate::types::rcn_struct::{StructDefinition, StructInstance};
 
 pub struct Interpreter {
     environment: Rc<RefCell<Environment>>,
+    state: Rc<RunState>,
+    yield_to: Option<YieldFn>, // The loop walking the generator whose body this interpreter runs
+}
+
+// How often the event loop checks watched paths for changes
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+type Function = dyn Fn(Rc<RefCell<Environment>>, &[LiteralValue]) -> Result<LiteralValue, String>;
+
+// Settings and status shared by every interpreter of one run: functions, modules and eval
+struct RunState {
+    log_level: Cell<LogLevel>,
+    error_reported: Cell<bool>,
+    fatal_raised: Cell<bool>,
+    strict: Cell<bool>,
+    check: Cell<bool>, // Whether to analyze sources for probable type errors before running them
+    interrupted: Arc<AtomicBool>,
+    output: RefCell<Box<dyn Write>>, // Where log, err and print write, stdout unless the host sets one
+    redirect: RefCell<Option<Box<dyn Write>>>, // Set by the script with io.redirect_output
+    captures: RefCell<Vec<Vec<u8>>>, // Buffers of the io.capture calls in progress, innermost last
+    watches: RefCell<Vec<Watch>>, // Paths from fs.watch, polled by the event loop
+    functions: RefCell<HashMap<String, (Weak<Function>, String)>>, // Top-level fns and their source, for par_map
+    exit_hooks: RefCell<Vec<LiteralValue>>, // Callbacks from os.on_exit, run when the run ends
+    exit_code: Cell<Option<i32>>, // Status given to os.exit
+    args: RefCell<Vec<String>>, // Command line arguments for the script, returned by os.args
+    module_roots: RefCell<Vec<PathBuf>>, // Directories searched by import after the working directory
+    bundled: RefCell<HashMap<String, Vec<Stmt>>>, // Modules declared with `module "name" { ... }`
+    messages: RefCell<Catalog>, // Translations from i18n.load, looked up by `t`
+    thrown: RefCell<Option<(String, LiteralValue)>>, // The error of the last `throw`, by the message it raised
+    breakpoints: RefCell<Vec<Environment>>, // Snapshots taken by debug.breakpoint, read back with debug.at
+}
+
+impl RunState {
+    fn write_line(&self, line: std::fmt::Arguments) {
+        if let Some(buffer) = self.captures.borrow_mut().last_mut() {
+            let _ = buffer.write_fmt(format_args!("{}\n", line));
+            return;
+        }
+
+        let mut redirect = self.redirect.borrow_mut();
+        let mut output = self.output.borrow_mut();
+        let output = redirect.as_mut().unwrap_or(&mut *output);
+        // Output is best effort: a closed pipe must not fail the script
+        let _ = output.write_fmt(format_args!("{}\n", line));
+        let _ = output.flush();
+    }
+}
+
+/// Stops a running script from another thread, e.g. a GUI's stop button.
+/// The script ends with a cancelled error before its next statement.
+#[derive(Clone)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
 }
 
 pub enum ControlFlow {
-    Continue,
+    Continue, // Carry on with the next statement
     Return(LiteralValue),
+    Break(Option<String>), // Leave the innermost loop, or the loop with this label
+    Next(Option<String>), // Skip to the next iteration of the innermost or labeled loop
+}
+
+// How a loop labeled `label` handles the flow out of one run of its body:
+// None keeps looping, Some(flow) ends the loop and hands `flow` to the enclosing statements
+fn after_iteration(flow: ControlFlow, label: &Option<String>) -> Option<ControlFlow> {
+    match flow {
+        ControlFlow::Continue | ControlFlow::Next(None) => None,
+        ControlFlow::Next(Some(target)) if label.as_ref() == Some(&target) => None,
+        ControlFlow::Break(None) => Some(ControlFlow::Continue),
+        ControlFlow::Break(Some(target)) if label.as_ref() == Some(&target) => Some(ControlFlow::Continue),
+        other => Some(other),
+    }
+}
+
+// The fields of a struct instance, or entries of a map, named by `names` in `var { x, y } = value;`
+fn unpack_fields(value: &LiteralValue, names: &[Token]) -> Result<Vec<LiteralValue>, String> {
+    names.iter().map(|name| match value {
+        LiteralValue::StructInst(instance) => {
+            let instance = instance.borrow();
+            instance.get_field(&name.lexeme).cloned()
+                .ok_or_else(|| format!("Cannot unpack '{}'; struct '{}' has no such field.", name.lexeme, instance.name))
+        }
+        LiteralValue::Map(entries) => entries.get(&MapKey::from(name.lexeme.as_str())).cloned()
+            .ok_or_else(|| format!("Cannot unpack '{}'; the map has no such key.", name.lexeme)),
+        other => Err(format!("Cannot unpack {} by field; that takes a struct or map.", other.to_type())),
+    }).collect()
+}
+
+// `__file__`, `__dir__` and `__main__` for the script at `path`, or for source without a file like
+// the REPL or `-e`, whose directory is the working directory
+fn define_script_metadata(environment: &mut Environment, path: Option<&str>, is_main: bool) {
+    let file = path.map(|path| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)));
+    let dir = match &file {
+        Some(file) => file.parent().map(Path::to_path_buf).unwrap_or_default(),
+        None => std::env::current_dir().unwrap_or_default(),
+    };
+
+    let file = file.map_or(LiteralValue::Nil, |file| LiteralValue::StringValue(file.display().to_string()));
+    environment.define("__file__".to_string(), file, true);
+    environment.define("__dir__".to_string(), LiteralValue::StringValue(dir.display().to_string()), true);
+    environment.define("__main__".to_string(), LiteralValue::check_bool(is_main), true);
+}
+
+// Whether `value` fits `pattern`, collecting the names the pattern binds
+fn match_pattern(pattern: &Pattern, value: &LiteralValue, environment: &Rc<RefCell<Environment>>, bindings: &mut Vec<(String, LiteralValue)>) -> Result<bool, String> {
+    match (pattern, value) {
+        (Pattern::Literal(expected), _) => Ok(expected.evaluate(environment)? == *value),
+        (Pattern::Bind(name), _) => {
+            if name != "_" {
+                bindings.push((name.clone(), value.clone()));
+            }
+            Ok(true)
+        }
+        (Pattern::Array { elements, rest }, LiteralValue::Array(values)) => {
+            let fits = if rest.is_some() { values.len() >= elements.len() } else { values.len() == elements.len() };
+            if !fits || !match_elements(elements, values, environment, bindings)? {
+                return Ok(false);
+            }
+            if let Some(rest) = rest.as_ref().filter(|rest| *rest != "_") {
+                bindings.push((rest.clone(), LiteralValue::Array(values[elements.len()..].to_vec())));
+            }
+            Ok(true)
+        }
+        (Pattern::Tuple(elements), LiteralValue::Tuple(values)) => {
+            Ok(values.len() == elements.len() && match_elements(elements, values, environment, bindings)?)
+        }
+        // Instances of a struct extending `name` fit too, like they fit a field of that struct
+        (Pattern::Struct { name, fields }, LiteralValue::StructInst(instance)) => {
+            let instance = instance.borrow();
+            if !extends(&instance.name, name, environment) {
+                return Ok(false);
+            }
+            for (field, pattern) in fields {
+                let field_value = instance.get_field(field)
+                    .ok_or_else(|| format!("Struct '{}' has no field '{}' to match.", instance.name, field))?;
+                if !match_pattern(pattern, field_value, environment, bindings)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn match_elements(patterns: &[Pattern], values: &[LiteralValue], environment: &Rc<RefCell<Environment>>, bindings: &mut Vec<(String, LiteralValue)>) -> Result<bool, String> {
+    for (pattern, value) in patterns.iter().zip(values) {
+        if !match_pattern(pattern, value, environment, bindings)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// What `catch (e)` binds: an `Error` struct with the message and its code, the line and column
+// it happened on when the error has them, and the value given to `throw`, or nil for a runtime error
+fn error_value(message: &str, value: LiteralValue, code: &str) -> LiteralValue {
+    let located = message.strip_prefix("Line ").and_then(|rest| rest.split_once(": ")).and_then(|(location, message)| {
+        let (line, column) = location.split_once(':').map_or((location, None), |(line, column)| (line, Some(column)));
+        let column = match column {
+            Some(column) => LiteralValue::Int(column.parse().ok()?),
+            None => LiteralValue::Nil,
+        };
+        Some((LiteralValue::Int(line.parse().ok()?), column, message))
+    });
+    let (line, column, message) = located.unwrap_or((LiteralValue::Nil, LiteralValue::Nil, message));
+
+    LiteralValue::StructInst(Rc::new(RefCell::new(StructInstance {
+        name: "Error".to_string(),
+        fields: HashMap::from([
+            ("message".to_string(), LiteralValue::StringValue(message.to_string())),
+            ("code".to_string(), LiteralValue::StringValue(code.to_string())),
+            ("line".to_string(), line),
+            ("column".to_string(), column),
+            ("value".to_string(), value),
+        ]),
+        frozen: false,
+    })))
+}
+
+/// The file `import "name"` refers to: `name.rcn`, or `name` itself for data files,
+/// looked up in the working directory first and then in each of `roots`.
+pub fn find_module(name: &str, roots: &[PathBuf]) -> Option<PathBuf> {
+    let name = rcn_fs::native_path(name);
+    std::iter::once(Path::new("")).chain(roots.iter().map(PathBuf::as_path))
+        .flat_map(|root| [root.join(format!("{}.rcn", name.display())), root.join(&name)])
+        .find(|path| path.is_file())
 }
 
 impl Interpreter {
@@ -24,219 +227,2191 @@ impl Interpreter {
         let mut globals = Environment::new();
 
         Self::define_std(&mut globals);
+        define_script_metadata(&mut globals, None, true);
+
+        let state = Rc::new(RunState {
+            log_level: Cell::new(LogLevel::from_env()),
+            error_reported: Cell::new(false),
+            fatal_raised: Cell::new(false),
+            strict: Cell::new(false),
+            check: Cell::new(false),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            output: RefCell::new(Box::new(io::stdout())),
+            redirect: RefCell::new(None),
+            captures: RefCell::new(vec![]),
+            watches: RefCell::new(vec![]),
+            functions: RefCell::new(HashMap::new()),
+            exit_hooks: RefCell::new(vec![]),
+            exit_code: Cell::new(None),
+            args: RefCell::new(vec![]),
+            module_roots: RefCell::new(vec![]),
+            bundled: RefCell::new(HashMap::new()),
+            messages: RefCell::new(Catalog::default()),
+            thrown: RefCell::new(None),
+            breakpoints: RefCell::new(vec![]),
+        });
+
+        let eval_state = state.clone();
+        globals.define("eval".to_string(), LiteralValue::Callable {
+            name: "eval".to_string(),
+            arity: 1,
+            fun: Rc::new(move |call_env, args: &[LiteralValue]| {
+                match &args[0] {
+                    LiteralValue::StringValue(source) => {
+                        Interpreter::for_closure(call_env, eval_state.clone()).eval_source(source)
+                    },
+                    _ => Err("eval function requires source code as a string.".to_string()),
+                }
+            }),
+        }, false);
+        Self::define_output_natives(&mut globals, &state);
+        Self::define_watch_natives(&mut globals, &state);
+        Self::define_parallel_natives(&mut globals, &state);
+        Self::define_exit_natives(&mut globals, &state);
+        Self::define_reload_native(&mut globals, &state);
+        Self::define_i18n_natives(&mut globals, &state);
+        Self::define_debug_natives(&mut globals, &state);
 
         Self {
             environment: Rc::new(RefCell::from(globals)),
+            state,
+            yield_to: None,
         }
     }
-    fn for_closure(parent: Rc<RefCell<Environment>>) -> Self {
+    fn for_closure(parent: Rc<RefCell<Environment>>, state: Rc<RunState>) -> Self {
         let environment = Rc::new(RefCell::new(Environment::new()));
         environment.borrow_mut().enclosing = Some(parent);
 
         Self {
-            environment
+            environment,
+            state,
+            yield_to: None,
+        }
+    }
+
+    // Hide log.debug/info/warn statements below this level
+    pub fn set_log_level(&mut self, log_level: LogLevel) {
+        self.state.log_level.set(log_level);
+    }
+
+    // The file being run, for `__file__` and `__dir__`
+    pub fn set_script_path(&mut self, path: &str) {
+        define_script_metadata(&mut self.environment.borrow_mut(), Some(path), true);
+    }
+
+    // Let `import` find modules in `root` too, e.g. the directories a project.rcn lists
+    pub fn add_module_root(&mut self, root: impl Into<PathBuf>) {
+        self.state.module_roots.borrow_mut().push(root.into());
+    }
+
+    // Make redefinitions of a fn, struct or interface in the same source an error instead of a warning
+    pub fn set_strict(&mut self, strict: bool) {
+        self.state.strict.set(strict);
+    }
+
+    // Warn about top-level functions, structs and interfaces declared twice in one source.
+    // Redefining something from an earlier source, like a previous REPL line, is allowed.
+    pub fn check_redefinitions(&self, stmts: &[Stmt]) -> Result<(), String> {
+        let mut defined: HashMap<&str, Span> = HashMap::new();
+
+        for stmt in stmts {
+            let (kind, name) = match stmt {
+                Stmt::FuncStmt { name, .. } => ("fn", name),
+                Stmt::StructStmt { name, .. } => ("struct", name),
+                Stmt::InterfaceStmt { name, .. } => ("interface", name),
+                _ => continue,
+            };
+
+            if let Some(first) = defined.insert(name, stmt.span()) {
+                let message = stmt.span().locate(format!("{} '{}' redefines the one declared on line {}.", kind, name, first.line));
+                if self.state.strict.get() {
+                    return Err(message);
+                }
+                self.write_line(format_args!("{} \"{}\"", "WARN".yellow(), message));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Keep scripts, and the code they eval, from files, the network and other processes: imports
+    // of files and the modules that reach outside the script fail
+    pub fn set_sandbox(&mut self, sandboxed: bool) {
+        self.environment.borrow_mut().sandboxed = sandboxed;
+    }
+
+    // The command line arguments meant for the script, which it reads with `os.args()`
+    pub fn set_args(&mut self, args: Vec<String>) {
+        *self.state.args.borrow_mut() = args;
+    }
+
+    // Analyze each source for probable type errors before running it
+    pub fn set_check(&mut self, check: bool) {
+        self.state.check.set(check);
+    }
+
+    // With checking on, warn about the probable type errors in a source, or fail on them when strict
+    pub fn check_types(&self, stmts: &[Stmt]) -> Result<(), String> {
+        if !self.state.check.get() {
+            return Ok(());
+        }
+
+        let problems = analysis::analyze(stmts, &self.environment);
+        if self.state.strict.get() && !problems.is_empty() {
+            return Err(problems.join("\n"));
+        }
+        for message in problems {
+            self.write_line(format_args!("{} \"{}\"", "WARN".yellow(), message));
+        }
+        Ok(())
+    }
+
+    // Send script output to `output` instead of stdout, e.g. a GUI console or a buffer under test
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        *self.state.output.borrow_mut() = Box::new(output);
+    }
+
+    fn write_line(&self, line: std::fmt::Arguments) {
+        self.state.write_line(line);
+    }
+
+    // Natives that write to or change the run's output close over the state, like `eval`.
+    // The parser turns `io.redirect_output` and `io.capture` into calls of names scripts cannot spell.
+    fn define_output_natives(globals: &mut Environment, state: &Rc<RunState>) {
+        type Render = fn(&LiteralValue) -> Result<String, String>;
+        let renderers: [(&str, Render); 2] = [
+            ("log_json", rcn_data::to_json),
+            ("log_table", rcn_data::render_table),
+        ];
+        for (name, render) in renderers {
+            let render_state = state.clone();
+            globals.define(name.to_string(), LiteralValue::Callable {
+                name: name.to_string(),
+                arity: 1,
+                fun: Rc::new(move |_, args: &[LiteralValue]| {
+                    render_state.write_line(format_args!("{}", render(&args[0])?));
+                    Ok(LiteralValue::Nil)
+                }),
+            }, false);
+        }
+
+        let redirect_state = state.clone();
+        globals.define("io.redirect_output".to_string(), LiteralValue::Callable {
+            name: "io.redirect_output".to_string(),
+            arity: 1,
+            fun: Rc::new(move |_, args: &[LiteralValue]| {
+                // A path sends output to that file from now on, nil goes back to the normal output
+                let redirect: Option<Box<dyn Write>> = match &args[0] {
+                    LiteralValue::StringValue(path) => match fs::File::create(rcn_fs::native_path(path)) {
+                        Ok(file) => Some(Box::new(file)),
+                        Err(e) => return Err(format!("Could not redirect output to '{}': {}", path, e)),
+                    },
+                    LiteralValue::Nil => None,
+                    other => return Err(format!("io.redirect_output expects a file path or nil, got {}.", other.to_type())),
+                };
+                *redirect_state.redirect.borrow_mut() = redirect;
+                Ok(LiteralValue::Nil)
+            }),
+        }, false);
+
+        let capture_state = state.clone();
+        globals.define("io.capture".to_string(), LiteralValue::Callable {
+            name: "io.capture".to_string(),
+            arity: 1,
+            fun: Rc::new(move |call_env, args: &[LiteralValue]| {
+                // Runs the function and returns what it printed instead of printing it
+                let fun = match &args[0] {
+                    LiteralValue::Callable { arity: 0, fun, .. } => fun.clone(),
+                    LiteralValue::Callable { name, arity, .. } => return Err(format!("io.capture expects a function without parameters, '{}' takes {}.", name, arity)),
+                    other => return Err(format!("io.capture expects a function, got {}.", other.to_type())),
+                };
+
+                capture_state.captures.borrow_mut().push(vec![]);
+                let result = fun(call_env, &[]);
+                let captured = capture_state.captures.borrow_mut().pop().unwrap_or_default();
+                result?;
+                Ok(LiteralValue::StringValue(String::from_utf8_lossy(&captured).into_owned()))
+            }),
+        }, false);
+    }
+
+    fn define_watch_natives(globals: &mut Environment, state: &Rc<RunState>) {
+        let watch_state = state.clone();
+        globals.define("fs.watch".to_string(), LiteralValue::Callable {
+            name: "fs.watch".to_string(),
+            arity: 2,
+            fun: Rc::new(move |_, args: &[LiteralValue]| {
+                let path = match &args[0] {
+                    LiteralValue::StringValue(path) => path.clone(),
+                    other => return Err(format!("fs.watch expects a path, got {}.", other.to_type())),
+                };
+                match &args[1] {
+                    LiteralValue::Callable { arity: 0 | 1, .. } => (),
+                    other => return Err(format!("fs.watch expects a function taking the changed path, got {}.", other.to_type())),
+                }
+                watch_state.watches.borrow_mut().push(Watch::new(path, args[1].clone()));
+                Ok(LiteralValue::Nil)
+            }),
+        }, false);
+
+        let unwatch_state = state.clone();
+        globals.define("fs.unwatch".to_string(), LiteralValue::Callable {
+            name: "fs.unwatch".to_string(),
+            arity: 1,
+            fun: Rc::new(move |_, args: &[LiteralValue]| {
+                let path = match &args[0] {
+                    LiteralValue::StringValue(path) => path,
+                    other => return Err(format!("fs.unwatch expects a path, got {}.", other.to_type())),
+                };
+                unwatch_state.watches.borrow_mut().retain(|watch| &watch.path != path);
+                Ok(LiteralValue::Nil)
+            }),
+        }, false);
+    }
+
+    fn define_exit_natives(globals: &mut Environment, state: &Rc<RunState>) {
+        let hook_state = state.clone();
+        globals.define("os.on_exit".to_string(), LiteralValue::Callable {
+            name: "os.on_exit".to_string(),
+            arity: 1,
+            fun: Rc::new(move |_, args: &[LiteralValue]| {
+                match &args[0] {
+                    LiteralValue::Callable { arity: 0, .. } => (),
+                    other => return Err(format!("os.on_exit expects a function taking no arguments, got {}.", other.to_type())),
+                }
+                hook_state.exit_hooks.borrow_mut().push(args[0].clone());
+                Ok(LiteralValue::Nil)
+            }),
+        }, false);
+
+        // Ends the run like a fatal error, so nothing after it runs, but with the given status
+        let exit_state = state.clone();
+        globals.define("os.exit".to_string(), LiteralValue::Callable {
+            name: "os.exit".to_string(),
+            arity: 1,
+            fun: Rc::new(move |_, args: &[LiteralValue]| {
+                let code = args[0].as_int().and_then(|code| i32::try_from(code).ok())
+                    .ok_or_else(|| format!("os.exit expects a whole number status, got {}.", args[0]))?;
+                exit_state.exit_code.set(Some(code));
+                exit_state.fatal_raised.set(true);
+                Err(format!("Exited with status {}.", code))
+            }),
+        }, false);
+
+        let args_state = state.clone();
+        globals.define("os.args".to_string(), LiteralValue::Callable {
+            name: "os.args".to_string(),
+            arity: 0,
+            fun: Rc::new(move |_, _: &[LiteralValue]| {
+                let args = args_state.args.borrow().iter().map(|arg| LiteralValue::StringValue(arg.clone())).collect();
+                Ok(LiteralValue::Array(args))
+            }),
+        }, false);
+    }
+
+    // `debug.breakpoint()` snapshots the scopes it is called from and returns the snapshot's
+    // number. `debug.at(number)` describes the bindings as they were then, like `debug.env()`
+    // does for the current scope, so earlier state can be inspected without running again.
+    fn define_debug_natives(globals: &mut Environment, state: &Rc<RunState>) {
+        let breakpoint_state = state.clone();
+        globals.define("debug.breakpoint".to_string(), LiteralValue::Callable {
+            name: "debug.breakpoint".to_string(),
+            arity: 0,
+            fun: Rc::new(move |call_env, _: &[LiteralValue]| {
+                let mut breakpoints = breakpoint_state.breakpoints.borrow_mut();
+                breakpoints.push(call_env.borrow().snapshot());
+                Ok(LiteralValue::Int(breakpoints.len() as i64 - 1))
+            }),
+        }, false);
+
+        let at_state = state.clone();
+        globals.define("debug.at".to_string(), LiteralValue::Callable {
+            name: "debug.at".to_string(),
+            arity: 1,
+            fun: Rc::new(move |_, args: &[LiteralValue]| {
+                let breakpoints = at_state.breakpoints.borrow();
+                let snapshot = args[0].as_int()
+                    .and_then(|number| usize::try_from(number).ok())
+                    .and_then(|number| breakpoints.get(number))
+                    .ok_or_else(|| format!("debug.at expects the number of a breakpoint taken so far, got {}.", args[0]))?;
+                rcn_debug::env(&RefCell::new(snapshot.clone()))
+            }),
+        }, false);
+    }
+
+    // `reload(lib)` runs the module's file again in its namespace, so everything holding `lib`
+    // sees the new code. If the new code fails, the namespace keeps its old bindings.
+    fn define_reload_native(globals: &mut Environment, state: &Rc<RunState>) {
+        let reload_state = state.clone();
+        globals.define("reload".to_string(), LiteralValue::Callable {
+            name: "reload".to_string(),
+            arity: 1,
+            fun: Rc::new(move |_, args: &[LiteralValue]| {
+                let LiteralValue::Namespace(namespace) = &args[0] else {
+                    return Err(format!("reload expects an imported module, got {}.", args[0].to_type()));
+                };
+                let path = match namespace.borrow().values.get("__file__") {
+                    Some(LiteralValue::StringValue(path)) => path.clone(),
+                    _ => return Err("reload expects a module imported from a file.".to_string()),
+                };
+
+                let source = fs::read_to_string(&path).map_err(|e| format!("Failed to reload module '{}': {}", path, e))?;
+                let statements = Parser::new(Scanner::new(&source).scan_tokens()?).parse()?;
+                let previous = {
+                    let mut namespace = namespace.borrow_mut();
+                    (std::mem::take(&mut namespace.values), std::mem::take(&mut namespace.constants))
+                };
+
+                let module_interpreter = Interpreter {
+                    environment: namespace.clone(),
+                    state: reload_state.clone(),
+                    yield_to: None,
+                };
+                if let Err(msg) = module_interpreter.run_module(Some(&path), statements) {
+                    let mut namespace = namespace.borrow_mut();
+                    (namespace.values, namespace.constants) = previous;
+                    return Err(msg);
+                }
+                Ok(LiteralValue::Nil)
+            }),
+        }, false);
+    }
+
+    // `i18n.load(path)` adds a file of translations to the run's catalog, which `t` looks up
+    fn define_i18n_natives(globals: &mut Environment, state: &Rc<RunState>) {
+        let load_state = state.clone();
+        globals.define("i18n.load".to_string(), LiteralValue::Callable {
+            name: "i18n.load".to_string(),
+            arity: 1,
+            fun: Rc::new(move |_, args: &[LiteralValue]| {
+                match &args[0] {
+                    LiteralValue::StringValue(path) => load_state.messages.borrow_mut().load(&rcn_fs::native_path(path).to_string_lossy())?,
+                    other => return Err(format!("i18n.load expects a path, got {}.", other.to_type())),
+                }
+                Ok(LiteralValue::Nil)
+            }),
+        }, false);
+
+        let translate_state = state.clone();
+        globals.define("t".to_string(), LiteralValue::Callable {
+            name: "t".to_string(),
+            arity: 2,
+            fun: Rc::new(move |_, args: &[LiteralValue]| {
+                match &args[0] {
+                    LiteralValue::StringValue(key) => Ok(LiteralValue::StringValue(translate_state.messages.borrow().translate(key, &args[1])?)),
+                    other => Err(format!("t expects a message key, got {}.", other.to_type())),
+                }
+            }),
+        }, false);
+    }
+
+    // `items.par_map(f)` calls `array.par_map`, which needs the sources of the script's fns
+    fn define_parallel_natives(globals: &mut Environment, state: &Rc<RunState>) {
+        let par_map_state = state.clone();
+        globals.define("array.par_map".to_string(), LiteralValue::Callable {
+            name: "par_map".to_string(),
+            arity: 2,
+            fun: Rc::new(move |call_env, args: &[LiteralValue]| {
+                let (LiteralValue::Array(elements), LiteralValue::Callable { name, arity, fun }) = (&args[0], &args[1]) else {
+                    return Err(format!("par_map expects a function, got {}.", args[1].to_type()));
+                };
+                if *arity != 1 {
+                    return Err(format!("par_map expects a function taking one element, but {} takes {}.", name, arity));
+                }
+
+                // Only top-level fns can be declared again on another thread; closures would lose what they captured
+                let functions = par_map_state.functions.borrow();
+                if !functions.get(name).is_some_and(|(declared, _)| declared.upgrade().is_some_and(|declared| Rc::ptr_eq(&declared, fun))) {
+                    return Err(format!("par_map needs a fn declared at the top level of the script, and {} is not.", name));
+                }
+                let sources: Vec<String> = functions.values().map(|(_, source)| source.clone()).collect();
+                drop(functions);
+
+                let elements = elements.iter().map(SharedValue::from_value).collect::<Result<Vec<_>, _>>()?;
+                let sandboxed = call_env.borrow().sandboxed();
+                let results = parallel::par_map(&sources, name, elements, sandboxed)?;
+                Ok(LiteralValue::Array(results.into_iter().map(SharedValue::into_value).collect()))
+            }),
+        }, false);
+    }
+
+    // Runs after the script's top-level statements: polls the paths registered with `fs.watch` and
+    // calls their callbacks on changes, until no watches are left or the run is interrupted.
+    // Timers and other events can be driven from here as well.
+    pub fn run_event_loop(&mut self) -> Result<(), String> {
+        while !self.state.watches.borrow().is_empty() {
+            std::thread::sleep(WATCH_POLL_INTERVAL);
+            if self.state.interrupted.swap(false, Ordering::Relaxed) {
+                self.state.fatal_raised.set(true);
+                return Err("Execution cancelled.".to_string());
+            }
+
+            // Collected first, since a callback may add or remove watches
+            let changed: Vec<(String, LiteralValue)> = self.state.watches.borrow_mut().iter_mut()
+                .filter_map(|watch| watch.poll().then(|| (watch.path.clone(), watch.callback.clone())))
+                .collect();
+            for (path, callback) in changed {
+                if let LiteralValue::Callable { arity, fun, .. } = callback {
+                    let args = if arity == 1 { vec![LiteralValue::StringValue(path)] } else { vec![] };
+                    fun(self.environment.clone(), &args)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The status the script asked for with os.exit, if it did
+    pub fn exit_code(&self) -> Option<i32> {
+        self.state.exit_code.get()
+    }
+
+    // Runs the os.on_exit callbacks once, the most recently added first. Every callback runs
+    // even when an earlier one fails, and the first failure is returned.
+    pub fn run_exit_hooks(&mut self) -> Result<(), String> {
+        let hooks = self.state.exit_hooks.take();
+        let mut first_error = None;
+        for hook in hooks.into_iter().rev() {
+            if let LiteralValue::Callable { fun, .. } = hook {
+                if let Err(msg) = fun(self.environment.clone(), &[]) {
+                    first_error.get_or_insert(msg);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle { interrupted: self.state.interrupted.clone() }
+    }
+
+    pub fn environment(&self) -> &Rc<RefCell<Environment>> {
+        &self.environment
+    }
+
+    // The kind of an error the run ended with, to report it with its code
+    pub fn diagnostic(&self, message: &str) -> &'static Diagnostic {
+        match &*self.state.thrown.borrow() {
+            Some((thrown, _)) if thrown == message => diagnostics::find(diagnostics::UNCAUGHT_THROW).unwrap_or(&diagnostics::OTHER),
+            _ => diagnostics::classify(message),
+        }
+    }
+
+    // Whether the script reported an error with `err`
+    pub fn error_reported(&self) -> bool {
+        self.state.error_reported.get()
+    }
+
+    // Run `source` and return the value of its last expression statement, or of a top-level `return`
+    fn eval_source(&mut self, source: &str) -> Result<LiteralValue, String> {
+        // Allow a trailing expression without ';', as in `eval("x + 1")`
+        let trimmed = source.trim_end();
+        let source = if trimmed.ends_with(';') || trimmed.ends_with('}') || trimmed.is_empty() {
+            source.to_string()
+        } else {
+            format!("{};", trimmed)
+        };
+
+        let tokens = Scanner::new(&source).scan_tokens()?;
+        let stmts = Parser::new(tokens).parse()?;
+
+        let mut last = LiteralValue::Nil;
+        for stmt in stmts {
+            match stmt {
+                Stmt::Expression { expression, .. } => last = expression.evaluate(&self.environment)?,
+                other => {
+                    if let ControlFlow::Return(value) = self.interpret(vec![other])? {
+                        return Ok(value);
+                    }
+                    last = LiteralValue::Nil;
+                }
+            }
         }
+
+        Ok(last)
     }
 
+    // Natives are plain globals rather than constants, so a script may still declare a variable
+    // that happens to share a builtin's name
     fn define_std(globals: &mut Environment) {
         globals.define("clock".to_string(), LiteralValue::Callable {
             name: "clock".to_string(),
             arity: 0,
-            fun: Rc::new(|_env, _args| rcn_std::clock_impl(_env, _args)),
-        }, true);
+            fun: Rc::new(rcn_std::clock_impl),
+        }, false);
         globals.define("wait_ms".to_string(), LiteralValue::Callable {
             name: "wait_ms".to_string(),
             arity: 1,
-            fun: Rc::new(|_env, _args| rcn_std::wait_ms(_env, _args)),
-        }, true);
+            fun: Rc::new(rcn_std::wait_ms),
+        }, false);
         globals.define("color_console".to_string(), LiteralValue::Callable {
             name: "color_console".to_string(),
             arity: 3,
-            fun: Rc::new(|_env, _args| rcn_std::color_console(_env, _args)),
-        }, true);
+            fun: Rc::new(rcn_std::color_console),
+        }, false);
+        globals.define("Ok".to_string(), LiteralValue::Callable {
+            name: "Ok".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::ok),
+        }, false);
+        globals.define("Err".to_string(), LiteralValue::Callable {
+            name: "Err".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::err),
+        }, false);
+        globals.define("serialize".to_string(), LiteralValue::Callable {
+            name: "serialize".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_serial::serialize),
+        }, false);
+        globals.define("deserialize".to_string(), LiteralValue::Callable {
+            name: "deserialize".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_serial::deserialize),
+        }, false);
+        globals.define("str".to_string(), LiteralValue::Callable {
+            name: "str".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::str),
+        }, false);
+        globals.define("num".to_string(), LiteralValue::Callable {
+            name: "num".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::num),
+        }, false);
+        globals.define("members".to_string(), LiteralValue::Callable {
+            name: "members".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::members),
+        }, false);
+        globals.define("bigint".to_string(), LiteralValue::Callable {
+            name: "bigint".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::bigint),
+        }, false);
+        globals.define("decimal".to_string(), LiteralValue::Callable {
+            name: "decimal".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::decimal),
+        }, false);
+        globals.define("sorted".to_string(), LiteralValue::Callable {
+            name: "sorted".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::sorted),
+        }, false);
+        globals.define("unique".to_string(), LiteralValue::Callable {
+            name: "unique".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::unique),
+        }, false);
+        globals.define("binary_search".to_string(), LiteralValue::Callable {
+            name: "binary_search".to_string(),
+            arity: 2,
+            fun: Rc::new(rcn_std::binary_search),
+        }, false);
+        globals.define("memo".to_string(), LiteralValue::Callable {
+            name: "memo".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::memo),
+        }, false);
+        globals.define("string_builder".to_string(), LiteralValue::Callable {
+            name: "string_builder".to_string(),
+            arity: 0,
+            fun: Rc::new(rcn_std::string_builder),
+        }, false);
+        globals.define("freeze".to_string(), LiteralValue::Callable {
+            name: "freeze".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::freeze),
+        }, false);
+        globals.define("satisfies".to_string(), LiteralValue::Callable {
+            name: "satisfies".to_string(),
+            arity: 2,
+            fun: Rc::new(rcn_std::satisfies),
+        }, false);
+        globals.define("fields".to_string(), LiteralValue::Callable {
+            name: "fields".to_string(),
+            arity: 1,
+            fun: Rc::new(rcn_std::fields),
+        }, false);
+        globals.define("get_field".to_string(), LiteralValue::Callable {
+            name: "get_field".to_string(),
+            arity: 2,
+            fun: Rc::new(rcn_std::get_field),
+        }, false);
+        globals.define("set_field".to_string(), LiteralValue::Callable {
+            name: "set_field".to_string(),
+            arity: 3,
+            fun: Rc::new(rcn_std::set_field),
+        }, false);
+    }
+
+    // Runs the body of the first arm with a pattern that fits the value, with the names it binds in scope
+    fn match_arms(&mut self, subject: &Expr, arms: Vec<(Vec<Pattern>, Box<Stmt>)>, default: Option<Box<Stmt>>) -> Result<ControlFlow, String> {
+        let value = subject.evaluate(&self.environment)?;
+
+        for (patterns, body) in arms {
+            for pattern in &patterns {
+                let mut bindings = vec![];
+                if !match_pattern(pattern, &value, &self.environment, &mut bindings)? {
+                    continue;
+                }
+
+                let old_env = self.environment.clone();
+                self.environment = Rc::new(RefCell::new(Environment::new_with_enclosing(old_env.clone())));
+                for (name, value) in bindings {
+                    self.environment.borrow_mut().define(name, value, false);
+                }
+                let result = self.interpret(vec![*body]);
+                self.environment = old_env;
+                return result;
+            }
+        }
+
+        match default {
+            Some(default) => self.interpret(vec![*default]),
+            None => Ok(ControlFlow::Continue),
+        }
+    }
+
+    // Runs `body` with `name` bound to the resource, then calls `name.close()` however the body ended.
+    // Close runs like the script wrote that call, so structs can bring their own close fn
+    fn with_resource(&mut self, name: Token, initializer: &Expr, body: Stmt) -> Result<ControlFlow, String> {
+        let resource = initializer.evaluate(&self.environment)?;
+        let old_env = self.environment.clone();
+        self.environment = Rc::new(RefCell::new(Environment::new_with_enclosing(old_env.clone())));
+        self.environment.borrow_mut().define(name.lexeme.clone(), resource, false);
+
+        let result = self.interpret(vec![body]);
+        let span = name.span();
+        let close = Expr::MethodCall {
+            object: Box::new(Expr::Variable { name, span }),
+            method_name: "close".to_string(),
+            arguments: vec![],
+            span,
+        };
+        let closed = close.evaluate(&self.environment);
+        self.environment = old_env;
+
+        // An error from the body matters more than one from closing
+        let flow = result?;
+        closed?;
+        Ok(flow)
+    }
+
+    // Runs `body`, and on an error runs `handler` with the error bound to `name`. A `fatal`,
+    // os.exit or an interrupt is not caught, since those are meant to end the run.
+    fn try_catch(&mut self, body: Stmt, name: Option<Token>, handler: Stmt) -> Result<ControlFlow, String> {
+        let old_env = self.environment.clone();
+        let was_fatal = self.state.fatal_raised.replace(false);
+        let result = self.interpret(vec![body]);
+        let fatal = self.state.fatal_raised.get();
+        self.state.fatal_raised.set(was_fatal || fatal);
+        self.environment = old_env.clone();
+
+        let message = match result {
+            Err(message) if !fatal && !expr::propagating() => message,
+            result => return result,
+        };
+        // The message tells a `throw` apart from an error of the interpreter's own
+        let error = match self.state.thrown.take() {
+            Some((thrown, error)) if thrown == message => error,
+            _ => error_value(&message, LiteralValue::Nil, diagnostics::classify(&message).code),
+        };
+        self.environment = Rc::new(RefCell::new(Environment::new_with_enclosing(old_env.clone())));
+        if let Some(name) = name {
+            self.environment.borrow_mut().define(name.lexeme, error, false);
+        }
+        let result = self.interpret(vec![handler]);
+        self.environment = old_env;
+        result
+    }
+
+    // Raises `value` as an error at `span`. The error `catch` binds holds the value and the throw
+    // site, unless it is a caught Error thrown again, which keeps where it first happened.
+    fn throw(&self, value: LiteralValue, span: Span) -> String {
+        let is_error = matches!(&value, LiteralValue::StructInst(instance) if instance.borrow().name == "Error");
+        let text = match &value {
+            LiteralValue::StructInst(instance) if is_error => instance.borrow().get_field("message").map_or(String::new(), |message| message.to_string()),
+            other => other.to_string(),
+        };
+
+        let message = span.locate(text);
+        let error = if is_error { value } else { error_value(&message, value, diagnostics::UNCAUGHT_THROW) };
+        *self.state.thrown.borrow_mut() = Some((message.clone(), error));
+        message
+    }
+
+    // What a call of a fn that yields returns: its body, waiting to run with `args` each time a loop walks it
+    fn generator(name: &str, params: &[Token], body: &[Stmt], defining_env: &Rc<RefCell<Environment>>, state: &Rc<RunState>, args: &[LiteralValue]) -> LiteralValue {
+        let (params, body, defining_env, state, args) = (params.to_vec(), body.to_vec(), defining_env.clone(), state.clone(), args.to_vec());
+
+        LiteralValue::Generator(Rc::new(Generator {
+            name: name.to_string(),
+            run: Box::new(move |yield_to| {
+                let mut generator_int = Interpreter::for_closure(defining_env.clone(), state.clone());
+                generator_int.yield_to = Some(yield_to);
+                for (param, arg) in params.iter().zip(&args) {
+                    generator_int.environment.borrow_mut().define(param.lexeme.clone(), arg.clone(), false);
+                }
+
+                for stmt in body.iter() {
+                    match generator_int.interpret(vec![stmt.clone()]) {
+                        Ok(ControlFlow::Return(_)) => break,
+                        Ok(_) => (),
+                        // Like a return, a `?` ends the generator
+                        Err(message) => return expr::take_propagated().map(|_| ()).ok_or(message),
+                    }
+                }
+                Ok(())
+            }),
+        }))
+    }
+
+    // Runs a generator, and the body of the for-in walking it for each value as it is yielded
+    fn walk_generator(&self, generator: &Generator, name: &Token, body: &Stmt, label: &Option<String>) -> Result<ControlFlow, String> {
+        let ended_by = Rc::new(RefCell::new(None)); // The flow that left the loop early, like a break or return
+        let (scope, state, yield_to) = (self.environment.clone(), self.state.clone(), self.yield_to.clone());
+        let (name, body, label, loop_end) = (name.lexeme.clone(), body.clone(), label.clone(), ended_by.clone());
+
+        let each: YieldFn = Rc::new(RefCell::new(move |element| {
+            let mut body_int = Interpreter::for_closure(scope.clone(), state.clone());
+            body_int.yield_to = yield_to.clone(); // A loop inside a generator may yield from its body
+            body_int.environment.borrow_mut().define(name.clone(), element, false);
+
+            match after_iteration(body_int.interpret(vec![body.clone()])?, &label) {
+                None => Ok(true),
+                Some(flow) => {
+                    *loop_end.borrow_mut() = Some(flow);
+                    Ok(false)
+                }
+            }
+        }));
+        (generator.run)(each)?;
+
+        let flow = ended_by.borrow_mut().take();
+        Ok(flow.unwrap_or(ControlFlow::Continue))
+    }
+
+    // The file `name` is imported from, looked up in the module roots
+    fn resolve_module(&self, name: &str) -> Option<PathBuf> {
+        find_module(name, &self.state.module_roots.borrow())
+    }
+
+    // The module's path and source
+    fn load_module(&self, module_name: String) -> Result<(String, String), String> {
+        let stripped_module_name = module_name.trim_matches('"');
+        let module_path = match self.resolve_module(stripped_module_name) {
+            Some(path) => path.display().to_string(),
+            None => format!("{}.rcn", rcn_fs::native_path(stripped_module_name).display()),
+        };
+        let source = std::fs::read_to_string(&module_path).map_err(|e| format!("Failed to load module '{}': {}", module_name, e))?;
+        Ok((module_path, source))
+    }
+
+    // Run a module in this interpreter's environment, which is the module's namespace
+    fn run_module(mut self, path: Option<&str>, statements: Vec<Stmt>) -> Result<(), String> {
+        self.check_redefinitions(&statements)?;
+
+        define_script_metadata(&mut self.environment.borrow_mut(), path, false);
+        self.interpret(statements)?;
+        Ok(())
     }
 
-    fn load_module(&self, module_name: String) -> Result<String, String> {
+    fn struct_definition(&self, name: String, parent: Option<String>, interfaces: Vec<String>, params: HashMap<String, Expr>, functions: Vec<Stmt>) -> Result<StructDefinition, String> {
+        // Start from the parent's fields, fns and interfaces so the child only overrides or adds to them
+        let (mut fields, mut associated, mut all_interfaces) = match &parent {
+            Some(parent_name) => match self.environment.borrow().get(parent_name) {
+                Some(LiteralValue::StructDef(parent_def)) => (parent_def.fields.clone(), (*parent_def.functions).clone(), parent_def.interfaces.clone()),
+                _ => return Err(format!("Struct '{}' cannot extend unknown struct '{}'.", name, parent_name)),
+            },
+            None => (HashMap::new(), HashMap::new(), vec![]),
+        };
+        fields.extend(params);
+        associated.extend(self.associated_functions(functions)?);
+        all_interfaces.extend(interfaces);
+        let interfaces = all_interfaces;
+
+        // Every declared interface method must be a field holding a callable of the same arity
+        for interface_name in &interfaces {
+            let interface = match self.environment.borrow().get(interface_name) {
+                Some(LiteralValue::Interface(interface)) => interface,
+                _ => return Err(format!("Struct '{}' implements unknown interface '{}'.", name, interface_name)),
+            };
+
+            let lookup = |method: &str| fields.get(method).and_then(|expr| expr.evaluate(&self.environment).ok());
+            if let Some(missing) = interface.missing_method(lookup) {
+                return Err(format!("Struct '{}' does not implement '{}' required by interface '{}'.", name, missing, interface_name));
+            }
+        }
+
+        Ok(StructDefinition {
+            name,
+            parent,
+            interfaces,
+            fields,
+            functions: Rc::new(associated),
+        })
+    }
+
+    // Declares a struct's `fn`s in a scope of their own and returns them by name
+    fn associated_functions(&self, functions: Vec<Stmt>) -> Result<HashMap<String, LiteralValue>, String> {
+        if functions.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let mut scope = Interpreter::for_closure(self.environment.clone(), self.state.clone());
+        scope.interpret(functions)?;
+        let functions = scope.environment.borrow().values.clone();
+        Ok(functions)
+    }
+
+    // `import "name" as alias`, binding the module's namespace, a data file's value or the fallback
+    fn import(&mut self, module_name: String, alias_name: String, fallback: Option<Expr>) -> Result<(), String> {
         let stripped_module_name = module_name.trim_matches('"');
-        let module_path = format!("{}.rcn", stripped_module_name);
-        std::fs::read_to_string(module_path).map_err(|e| format!("Failed to load module '{}': {}", module_name, e))
+        let bundled = self.state.bundled.borrow().get(stripped_module_name).cloned();
+        let resolved = self.resolve_module(stripped_module_name);
+
+        // An optional module that isn't there binds the fallback; a module that fails to load still fails
+        if let (Some(fallback), None, None) = (fallback, &bundled, &resolved) {
+            let value = fallback.evaluate(&self.environment)?;
+            self.environment.borrow_mut().define(alias_name, value, false);
+            return Ok(());
+        }
+
+        let (module_path, module_statements) = match bundled {
+            Some(statements) => (None, statements),
+            None => {
+                if self.environment.borrow().sandboxed() {
+                    return Err(format!("Cannot import '{}' in a sandbox; only modules bundled into the script can be.", stripped_module_name));
+                }
+
+                // Data files are loaded as values instead of being executed
+                let data_path = resolved.map_or_else(|| stripped_module_name.to_string(), |path| path.display().to_string());
+                if let Some(data) = rcn_data::load_data_file(&data_path)? {
+                    self.environment.borrow_mut().define(alias_name, data, false);
+                    return Ok(());
+                }
+
+                // Load the module code from the file system
+                let (module_path, module_code) = self.load_module(module_name)?;
+                let tokens = Scanner::new(&module_code).scan_tokens()?;
+                (Some(module_path), Parser::new(tokens).parse()?)
+            }
+        };
+
+        // Create a new environment for the module
+        let module_environment = Rc::new(RefCell::new(Environment::new_with_enclosing(self.environment.clone())));
+
+        // Create an interpreter for the module using the new environment
+        let module_interpreter = Interpreter {
+            environment: module_environment.clone(),
+            state: self.state.clone(),
+            yield_to: None,
+        };
+
+        // Interpret each statement in the module within its environment
+        module_interpreter.run_module(module_path.as_deref(), module_statements)?;
+
+        // Store the module's environment under the alias in the current environment
+        self.environment.borrow_mut().define(alias_name, LiteralValue::Namespace(module_environment), false);
+        Ok(())
     }
 
     pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<ControlFlow, String> {
         for stmt in stmts {
-            match stmt {
-                Stmt::Expression { expression} => {
-                    let value = expression.evaluate(&self.environment)?;
-                    // You can do something with `value` here if needed
+            let span = stmt.span();
+            match self.execute(stmt).map_err(|msg| span.locate(msg))? {
+                ControlFlow::Continue => (),
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(ControlFlow::Continue)
+    }
+
+    // Run every top-level statement even when earlier ones fail, collecting the errors.
+    // A `fatal` or an interrupt still stops the run since they are meant to abort.
+    pub fn interpret_all(&mut self, stmts: Vec<Stmt>) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        for stmt in stmts {
+            if let Err(msg) = self.interpret(vec![stmt]) {
+                errors.push(msg);
+                if self.state.fatal_raised.get() {
+                    break;
                 }
-                Stmt::Log { expression } => {
-                    let value = expression.evaluate(&self.environment)?;
-                    println!("{} \"{}\"", "LOG".bright_blue(), value.to_string());
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    // Run statements as they arrive, e.g. from a streaming `Parser`, so a huge script
+    // starts running before it is fully parsed. Stops at the first parse or runtime error.
+    pub fn interpret_stream(&mut self, stmts: impl IntoIterator<Item = Result<Stmt, String>>) -> Result<(), String> {
+        for stmt in stmts {
+            self.interpret(vec![stmt?])?;
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, stmt: Stmt) -> Result<ControlFlow, String> {
+        // Cleared once seen so the interpreter can run again afterwards
+        if self.state.interrupted.swap(false, Ordering::Relaxed) {
+            self.state.fatal_raised.set(true);
+            return Err("Execution cancelled.".to_string());
+        }
+
+        match stmt {
+            Stmt::Expression { expression, ..} => {
+                expression.evaluate(&self.environment)?;
+            }
+            Stmt::Log { expressions, compact, level, .. } => {
+                if level.is_some_and(|level| level < self.state.log_level.get()) {
+                    return Ok(ControlFlow::Continue);
                 }
-                Stmt::Err { expression } => {
-                    let value = expression.evaluate(&self.environment)?;
-                    println!("{} \"{}\"", "ERR!".red(), value.to_string());
+
+                let mut values = vec![];
+                for expression in expressions {
+                    values.push(expression.evaluate(&self.environment)?);
+                }
+
+                let label = match level {
+                    None => "LOG".bright_blue(),
+                    Some(LogLevel::Debug) => "DEBUG".dimmed(),
+                    Some(LogLevel::Info) => "INFO".green(),
+                    Some(LogLevel::Warn) => "WARN".yellow(),
+                };
+
+                if values.len() == 1 && values[0].is_compound() {
+                    self.write_line(format_args!("{} {}", label, values[0].to_pretty_string(compact)));
+                } else {
+                    // Several values are joined by spaces, with nested data kept on one line
+                    let text = values.iter()
+                        .map(|value| if value.is_compound() { value.to_pretty_string(true) } else { value.to_string() })
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    self.write_line(format_args!("{} \"{}\"", label, text));
                 }
-                Stmt::Print { expression } => {
+            }
+            Stmt::Err { expression, fatal, .. } => {
+                let value = expression.evaluate(&self.environment)?;
+                if fatal {
+                    self.state.fatal_raised.set(true);
+                    return Err(format!("{} \"{}\"", "FATAL".red(), value));
+                }
+
+                self.write_line(format_args!("{} \"{}\"", "ERR!".red(), value));
+                self.state.error_reported.set(true);
+            }
+            Stmt::Print { expressions, .. } => {
+                // Plain output: no label, strings as-is and nested data on one line
+                let mut values = vec![];
+                for expression in expressions {
                     let value = expression.evaluate(&self.environment)?;
-                    println!("{}", value.to_string());
+                    values.push(if value.is_compound() { value.to_pretty_string(true) } else { value.to_string() });
+                }
+                self.write_line(format_args!("{}", values.join(" ")));
+            }
+            Stmt::Var { name, initializer, .. } => {
+                if self.environment.borrow().is_const(&name.lexeme) {
+                    return Err(format!("Cannot shadow constant '{}'.", name.lexeme));
+                }
+                let value = initializer.evaluate(&self.environment)?;
+                self.environment.borrow_mut().define(name.lexeme, value, false);
+            }
+            Stmt::Unpack { names, by_field, initializer, .. } => {
+                if let Some(name) = names.iter().find(|name| self.environment.borrow().is_const(&name.lexeme)) {
+                    return Err(format!("Cannot shadow constant '{}'.", name.lexeme));
                 }
-                Stmt::Var { name, initializer } => {
-                    let value = initializer.evaluate(&self.environment)?;
+                let value = initializer.evaluate(&self.environment)?;
+                let values = if by_field {
+                    unpack_fields(&value, &names)?
+                } else {
+                    match value {
+                        LiteralValue::Tuple(values) | LiteralValue::Array(values) if values.len() == names.len() => values,
+                        LiteralValue::Tuple(values) | LiteralValue::Array(values) => {
+                            return Err(format!("Cannot unpack {} values into {} variables.", values.len(), names.len()));
+                        }
+                        other => return Err(format!("Cannot unpack {} by position; that takes a tuple or array.", other.to_type())),
+                    }
+                };
+                for (name, value) in names.into_iter().zip(values) {
                     self.environment.borrow_mut().define(name.lexeme, value, false);
                 }
-                Stmt::Const { name, initializer } => {
-                    let value = initializer.evaluate(&self.environment)?;
+            }
+            Stmt::Const { name, initializer, .. } => {
+                let value = initializer.evaluate(&self.environment)?;
+                self.environment.borrow_mut().define_const(name.lexeme, value)?;
+            }
+            Stmt::Block { statements, .. } => {
+                // Create a new environment for the block
+                let old_env = self.environment.clone();
+                self.environment = Rc::new(RefCell::new(Environment::new()));
+                self.environment.borrow_mut().enclosing = Some(old_env.clone());
 
-                    if self.environment.borrow().get(&name.lexeme).is_some() {
-                        return Err(format!("Constant '{}' is already defined.", name.lexeme));
-                    }
+                // Interpret the block
+                let block_result = self.interpret(statements.clone());
+                self.environment = old_env; // Restore the old environment
 
-                    self.environment.borrow_mut().define(name.lexeme, value, true);
-                }
-                Stmt::Block { statements } => {
-                    // Create a new environment for the block
-                    let old_env = self.environment.clone();
-                    self.environment = Rc::new(RefCell::new(Environment::new()));
-                    self.environment.borrow_mut().enclosing = Some(old_env.clone());
+                // A return, break or continue inside the block applies to the enclosing statements
+                return block_result;
+            }
+            Stmt::IfStmt { predicate, then, elifs, els, .. } => {
+                let truth_value = predicate.evaluate(&self.environment)?;
 
-                    // Interpret the block
-                    let block_result = self.interpret(statements.clone());
-                    self.environment = old_env; // Restore the old environment
+                if truth_value.is_truthy() == LiteralValue::True {
+                    return self.interpret(vec![*then]);
+                }
 
-                    if let Ok(ControlFlow::Return(value)) = block_result {
-                        return Ok(ControlFlow::Return(value));
+                // Check elif conditions
+                for (elif_predicate, elif_body) in elifs {
+                    let elif_truth_value = elif_predicate.evaluate(&self.environment)?;
+                    if elif_truth_value.is_truthy() == LiteralValue::True {
+                        return self.interpret(vec![*elif_body]);
                     }
                 }
-                Stmt::IfStmt { predicate, then, elifs, els } => {
-                    let truth_value = predicate.evaluate(&self.environment)?;
-
-                    if truth_value.is_truthy() == LiteralValue::True {
-                        self.interpret(vec![*then])?;
-                    } else {
-                        let mut executed = false;
-
-                        // Check elif conditions
-                        for (elif_predicate, elif_body) in elifs {
-                            let elif_truth_value = elif_predicate.evaluate(&self.environment)?;
-                            if elif_truth_value.is_truthy() == LiteralValue::True {
-                                self.interpret(vec![*elif_body.clone()])?;
-                                executed = true;
-                                break;
-                            }
-                        }
 
-                        // If no elif was executed, check else
-                        if !executed {
-                            if let Some(els_stmt) = els {
-                                self.interpret(vec![*els_stmt])?;
-                            }
-                        }
-                    }
+                // If no elif was executed, check else
+                if let Some(els_stmt) = els {
+                    return self.interpret(vec![*els_stmt]);
                 }
-                Stmt::WhileStmt { condition, body } => {
-                    while condition.evaluate(&self.environment)?.is_truthy() == LiteralValue::True {
-                        self.interpret(vec![(*body).clone()])?;
+            }
+            Stmt::Match { subject, arms, default, .. } => return self.match_arms(&subject, arms, default),
+            Stmt::WhileStmt { condition, body, increment, label, .. } => {
+                while condition.evaluate(&self.environment)?.is_truthy() == LiteralValue::True {
+                    if let Some(flow) = after_iteration(self.interpret(vec![(*body).clone()])?, &label) {
+                        return Ok(flow);
+                    }
+                    if let Some(increment) = &increment {
+                        increment.evaluate(&self.environment)?;
                     }
                 }
-                Stmt::LoopStmt { body } => {
-                    loop {
-                        self.interpret(vec![(*body).clone()])?; // Dereference the Box to clone the Stmt
+            }
+            Stmt::With { name, initializer, body, .. } => return self.with_resource(name, &initializer, *body),
+            Stmt::Try { body, name, handler, .. } => return self.try_catch(*body, name, *handler),
+            Stmt::Throw { value, span } => {
+                let value = value.evaluate(&self.environment)?;
+                return Err(self.throw(value, span));
+            }
+            Stmt::ForIn { name, iterable, body, label, .. } => {
+                // Elements are taken up front, so changing the collection in the body doesn't affect the loop
+                let elements = match iterable.evaluate(&self.environment)? {
+                    LiteralValue::Generator(generator) => return self.walk_generator(&generator, &name, &body, &label),
+                    LiteralValue::Array(elements) | LiteralValue::Tuple(elements) => elements,
+                    LiteralValue::Map(entries) => {
+                        let mut keys: Vec<MapKey> = entries.into_keys().collect();
+                        keys.sort();
+                        keys.iter().map(MapKey::to_value).collect()
+                    }
+                    LiteralValue::StringValue(text) => text.chars().map(|c| LiteralValue::StringValue(c.to_string())).collect(),
+                    other => return Err(format!("Cannot iterate over {}; for-in takes an array, tuple, map, string or generator.", other.to_type())),
+                };
+
+                for element in elements {
+                    let old_env = self.environment.clone();
+                    self.environment = Rc::new(RefCell::new(Environment::new_with_enclosing(old_env.clone())));
+                    self.environment.borrow_mut().define(name.lexeme.clone(), element, false);
+                    let result = self.interpret(vec![(*body).clone()]);
+                    self.environment = old_env;
+
+                    if let Some(flow) = after_iteration(result?, &label) {
+                        return Ok(flow);
                     }
                 }
-                Stmt::ReturnStmt { keyword: _, value } => {
+            }
+            Stmt::LoopStmt { body, count, label, .. } => {
+                let count = match count {
+                    None => None,
+                    Some(count) => match count.evaluate(&self.environment)? {
+                        value if value.as_int().is_some_and(|n| n >= 0) => value.as_int().map(|n| n as usize),
+                        other => return Err(format!("compose count must be a whole number of at least 0, got {}.", other)),
+                    },
+                };
 
-                    let eval_val = if let Some(expr) = value {
-                        expr.evaluate(&self.environment.clone())?
-                    } else {
-                        LiteralValue::Nil
-                    };
+                let mut iteration = 0;
+                while count.is_none_or(|count| iteration < count) {
+                    if let Some(flow) = after_iteration(self.interpret(vec![(*body).clone()])?, &label) {
+                        return Ok(flow);
+                    }
+                    iteration += 1;
+                }
+            }
+            Stmt::Yield { value, .. } => {
+                let value = value.evaluate(&self.environment)?;
+                let Some(yield_to) = self.yield_to.clone() else {
+                    return Err("'yield' can only run inside a generator.".to_string());
+                };
 
-                    return Ok(ControlFlow::Return(eval_val));
+                // The loop ended early, e.g. with a break, so the generator stops as if it returned
+                let keep_going = (yield_to.borrow_mut())(value)?;
+                if !keep_going {
+                    return Ok(ControlFlow::Return(LiteralValue::Nil));
                 }
-                Stmt::FuncStmt { name, parameters, body } => {
-                    let arity = parameters.len() as i32;
+            }
+            Stmt::Break { label, .. } => return Ok(ControlFlow::Break(label)),
+            Stmt::Continue { label, .. } => return Ok(ControlFlow::Next(label)),
+            Stmt::ReturnStmt { keyword: _, value, .. } => {
 
-                    let params = parameters.clone();
-                    let body = body.clone();
+                let eval_val = if let Some(expr) = value {
+                    expr.evaluate(&self.environment.clone())?
+                } else {
+                    LiteralValue::Nil
+                };
 
-                    let defining_env = self.environment.clone();  // Capture the environment where the function is defined
+                return Ok(ControlFlow::Return(eval_val));
+            }
+            Stmt::FuncStmt { name, parameters, body, source, generator, .. } => {
+                let arity = parameters.len() as i32;
 
-                    let fun_impl = move |call_env, args: &Vec<LiteralValue>| {
-                        let mut closure_int = Interpreter::for_closure(defining_env.clone());
+                let params = parameters.clone();
+                let body = body.clone();
 
-                        for (i, arg) in args.iter().enumerate() {
-                            // println!("Defining parameter {}: {:?}", params[i].lexeme, arg);
-                            closure_int.environment.borrow_mut().define(params[i].lexeme.clone(), (*arg).clone(), false);
-                        }
+                let defining_env = self.environment.clone();  // Capture the environment where the function is defined
+                let state = self.state.clone();
+                let generator_name = name.clone();
 
-                        // Execute the function body
-                        for stmt in body.iter() {
-                            match closure_int.interpret(vec![*stmt.clone()]) {
-                                Ok(ControlFlow::Return(return_value)) => return return_value,
-                                Ok(ControlFlow::Continue) => continue,
-                                Err(e) => {
-                                    eprintln!("Error executing statement: {:?}", e);
-                                    return LiteralValue::Nil;
-                                }
-                            }
-                        }
+                let fun_impl = move |_call_env, args: &[LiteralValue]| {
+                    if generator {
+                        return Ok(Interpreter::generator(&generator_name, &params, &body, &defining_env, &state, args));
+                    }
 
-                        LiteralValue::Nil
-                    };
+                    let mut closure_int = Interpreter::for_closure(defining_env.clone(), state.clone());
 
-                    let callable = LiteralValue::Callable {
-                        name: name.clone(),
-                        arity,
-                        fun: Rc::new(fun_impl),
-                    };
+                    for (i, arg) in args.iter().enumerate() {
+                        // println!("Defining parameter {}: {:?}", params[i].lexeme, arg);
+                        closure_int.environment.borrow_mut().define(params[i].lexeme.clone(), (*arg).clone(), false);
+                    }
 
-                    // println!("Assigning function {} to environment", name);
+                    // Execute the function body
+                    for stmt in body.iter() {
+                        match closure_int.interpret(vec![stmt.clone()]) {
+                            Ok(ControlFlow::Return(return_value)) => return Ok(return_value),
+                            Ok(_) => (),
+                            // A `?` in the body returns its Err from here
+                            Err(message) => return expr::take_propagated().ok_or(message),
+                        }
+                    }
 
-                    self.environment.borrow_mut().define(name.clone(), callable, false);
+                    Ok(LiteralValue::Nil)
+                };
 
-                    // println!("Function {} defined successfully", name);
+                let fun: Rc<Function> = Rc::new(fun_impl);
+                if self.environment.borrow().enclosing.is_none() {
+                    self.state.functions.borrow_mut().insert(name.clone(), (Rc::downgrade(&fun), source));
                 }
-                Stmt::StructStmt { name, params } => {
-                    let struct_def = LiteralValue::StructDef(StructDefinition {
-                        name: name.clone(),
-                        fields: params.clone(),
-                    });
+                let callable = LiteralValue::Callable {
+                    name: name.clone(),
+                    arity,
+                    fun,
+                };
 
-                    self.environment.borrow_mut().define(name, struct_def, false);
-                }
-                Stmt::Import { module_name, alias_name } => {
-                    // Load the module code from the file system
-                    let module_code = self.load_module(module_name)?;
+                // println!("Assigning function {} to environment", name);
+
+                self.environment.borrow_mut().define(name.clone(), callable, false);
+
+                // println!("Function {} defined successfully", name);
+            }
+            Stmt::StructStmt { name, parent, interfaces, params, functions, .. } => {
+                let struct_def = self.struct_definition(name.clone(), parent, interfaces, params, functions)?;
+                self.environment.borrow_mut().define(name, LiteralValue::StructDef(struct_def), false);
+            }
+            Stmt::InterfaceStmt { name, methods, .. } => {
+                let interface = LiteralValue::Interface(InterfaceDefinition {
+                    name: name.clone(),
+                    methods,
+                });
 
-                    let mut scanner = Scanner::new(module_code.as_str());
-                    let tokens = scanner.scan_tokens()?;
+                self.environment.borrow_mut().define(name, interface, false);
+            }
+            Stmt::Import { module_name, alias_name, fallback, .. } => {
+                self.import(module_name, alias_name, fallback)?;
+            }
+            Stmt::Module { name, body, .. } => {
+                self.state.bundled.borrow_mut().insert(name, body);
+            }
+        };
 
-                    let mut parser = Parser::new(tokens);
-                    let module_statements = parser.parse()?;
+        Ok(ControlFlow::Continue)
+    }
 
-                    // Create a new environment for the module
-                    let module_environment = Rc::new(RefCell::new(Environment::new_with_enclosing(self.environment.clone())));
+}
 
-                    // Create an interpreter for the module using the new environment
-                    let mut module_interpreter = Interpreter {
-                        environment: module_environment.clone(),
-                    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                    // Interpret each statement in the module within its environment
-                    module_interpreter.interpret(module_statements)?;
+    fn run(interpreter: &mut Interpreter, source: &str) -> Option<LiteralValue> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        interpreter.interpret(Parser::new(tokens).parse().unwrap()).unwrap();
+        interpreter.environment.borrow().get("result")
+    }
 
-                    // println!("Created module environment: {:?}", &module_environment);
-                    // Store the module's environment under the alias in the current environment
-                    self.environment.borrow_mut().define(alias_name.clone(), LiteralValue::Namespace(module_environment), false);
-                }
-                _ => todo!()
-            };
+    #[test]
+    fn eval_returns_last_expression_in_child_scope() {
+        let mut interpreter = Interpreter::new();
+
+        let value = run(&mut interpreter, "var base = 10; var result = eval(\"var bonus = 5; base * 2 + bonus\");");
+        assert_eq!(value, Some(LiteralValue::Int(25)));
+        assert!(interpreter.environment.borrow().get("bonus").is_none());
+
+        let returned = run(&mut interpreter, "var result = eval(\"fn sq(x) { return x * x; } sq(4)\");");
+        assert_eq!(returned, Some(LiteralValue::Int(16)));
+
+        // Assignments reach the caller's variables, in whatever scope eval is called from
+        let assigned = run(&mut interpreter, "var result = 1;\nfn bump() { var local = 1; eval(\"local = local + 1; result = local\"); }\nbump();");
+        assert_eq!(assigned, Some(LiteralValue::Int(2)));
+    }
+
+    #[test]
+    fn sandboxed_runs_and_their_eval_cannot_reach_outside() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_sandbox(true);
+        let source = "var result = [math.sqrt(16)];\n\
+                      var root = \"/\";\n\
+                      try { eval(\"io.file_exists(root)\"); } catch (e) { result.push(e.message); }\n\
+                      try { os.exit(1); } catch (e) { result.push(e.message); }";
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            LiteralValue::Float(4.0), text("io.file_exists is not available in a sandbox."), text("os.exit is not available in a sandbox."),
+        ])));
+
+        let tokens = Scanner::new("import \"helpers\" as h;").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert!(error.contains("Cannot import 'helpers' in a sandbox"), "{}", error);
+    }
+
+    #[test]
+    fn postfix_chains_compose() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct Point { x: 0 }\n\
+                      var points = [Point { x: 1 }, Point { x: 2 }];\n\
+                      struct Bag { items: points }\n\
+                      fn first() { return points[0]; }\n\
+                      var bag = Bag { items: points };\n\
+                      bag.items.push(Point { x: 3 });\n\
+                      var result = first().x + points[1].x + bag.items[2].x + bag.items.length();";
+
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Int(9)));
+    }
+
+    #[test]
+    fn struct_fields_hold_arrays_and_nested_structs() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct Point { x: 0 }\n\
+                      struct Point3 : Point { z: 0 }\n\
+                      struct Path { points: [], tags: [\"a\"], start: Point {} }\n\
+                      var one = Path { points: [Point { x: 1 }], tags: [\"b\", \"c\"], start: Point3 { x: 2 } };\n\
+                      var two = Path {};\n\
+                      two.points.push(one.start);\n\
+                      var result = one.points.length() + one.tags.length() + two.points[0].x + Path {}.points.length();";
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Int(5)));
+
+        let tokens = Scanner::new("Path { tags: [1] };").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert_eq!(error, "Line 1:1: Type mismatch for field 'tags': expected Array<String>, got Array<Int>");
+    }
+
+    #[test]
+    fn struct_fields_are_assigned_through_every_binding() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct Point { x: 0, y: 0 }\n\
+                      struct Line { start: Point {}, end: Point {} }\n\
+                      fn move_right(point) { point.x = point.x + 1; }\n\
+                      var a = Point { x: 1 };\n\
+                      move_right(a);\n\
+                      var line = Line { start: a };\n\
+                      line.start.y = 4;\n\
+                      var points = [Point {}, Point {}];\n\
+                      points[1].x = 7;\n\
+                      var result = [a.x, a.y, line.end.y, points[0].x, points[1].x];";
+        let ints = |values: &[i64]| LiteralValue::Array(values.iter().map(|&value| LiteralValue::Int(value)).collect());
+        assert_eq!(run(&mut interpreter, source), Some(ints(&[2, 4, 0, 0, 7])));
+
+        let tokens = Scanner::new("a.colour = 1;").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert_eq!(error, "Line 1:3: Field 'colour' not found in struct 'Point'.");
+    }
+
+    #[test]
+    fn omitted_struct_fields_take_their_defaults() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct Config { host: \"localhost\", port: 80, tags: [] }\n\
+                      var one = Config { port: 8080 };\n\
+                      var two = Config {};\n\
+                      one.tags.push(\"web\");\n\
+                      var result = [one.host, one.port, two.port, one.tags.length(), two.tags.length()];";
+        let text = |s: &str| LiteralValue::StringValue(s.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            text("localhost"), LiteralValue::Int(8080), LiteralValue::Int(80), LiteralValue::Int(1), LiteralValue::Int(0),
+        ])));
+
+        let tokens = Scanner::new("Config { colour: 1 };").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert_eq!(error, "Line 1:1: Field 'colour' does not exist in struct definition 'Config'");
+    }
+
+    #[test]
+    fn nested_struct_fields_are_read_and_assigned() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct Customer { name: \"\" }\n\
+                      struct Order { id: 0, customer: Customer {} }\n\
+                      var order = Order { id: 7, customer: Customer { name: \"Ada\" } };\n\
+                      var before = order.customer.name;\n\
+                      order.customer.name = \"Grace\";\n\
+                      var result = [before, order.customer.name, Order {}.customer.name];";
+        let text = |s: &str| LiteralValue::StringValue(s.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![text("Ada"), text("Grace"), text("")])));
+
+        let tokens = Scanner::new("Order { customer: 5 };").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert_eq!(error, "Line 1:1: Type mismatch for field 'customer': expected Customer, got Int");
+    }
+
+    #[test]
+    fn string_builder_appends_in_place() {
+        let mut interpreter = Interpreter::new();
+        let source = "var out = string_builder();\n\
+                      for (var i = 0; i < 3; i = i + 1) { out.append(i).append(\",\"); }\n\
+                      var copy = out;\n\
+                      copy.append([1]);\n\
+                      var result = out.build() + out.length();";
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::StringValue("0,1,2,[1]9".to_string())));
+    }
+
+    #[test]
+    fn loops_support_counts_labels_and_early_exits() {
+        let mut interpreter = Interpreter::new();
+        let source = "var hits = 0;\n\
+                      compose(4) { hits = hits + 1; }\n\
+                      outer: for (var i = 0; i < 5; i = i + 1) {\n\
+                          var j = 0;\n\
+                          compose() {\n\
+                              j = j + 1;\n\
+                              if (j == 2) { continue outer; }\n\
+                              if (i == 3) { break outer; }\n\
+                              hits = hits + 10;\n\
+                          }\n\
+                      }\n\
+                      fn find(limit) { var n = 0; while (true) { n = n + 1; if (n == limit) { return n * 100; } } }\n\
+                      var result = hits + find(3);";
+
+        // 4 from compose(4), 10 for each of i = 0..2, then `break outer` at i = 3
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Int(334)));
+    }
+
+    #[test]
+    fn match_runs_the_first_arm_with_an_equal_value() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn describe(code) {\n\
+                          match (code) {\n\
+                              200, 204 => return \"ok\";\n\
+                              -1 => return \"unknown\";\n\
+                              \"teapot\" => { var word = \"short\"; return word + \" and stout\"; }\n\
+                              else => return \"error\";\n\
+                          }\n\
+                      }\n\
+                      var result = [describe(204), describe(-1), describe(\"teapot\"), describe(500), describe(200.0)];";
+
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            text("ok"), text("unknown"), text("short and stout"), text("error"), text("ok"),
+        ])));
+
+        let parse = |source: &str| Parser::new(Scanner::new(source).scan_tokens().unwrap()).parse().unwrap_err();
+        assert!(parse("match (1) { else => print(1); 2 => print(2); }").starts_with("Line 1: The 'else' arm must be the last arm of a match."));
+        assert!(parse("match (1) { -x => print(1); }").starts_with("Line 1: Match arms take patterns like 1, \"text\", name, [a, ..rest] or Point { x }."));
+    }
+
+    #[test]
+    fn match_arms_destructure_and_bind() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct Shape { kind: \"\" }\n\
+                      struct Point : Shape { x: 0, y: 0 }\n\
+                      fn pair(a, b) { return a, b; }\n\
+                      fn describe(value) {\n\
+                          match (value) {\n\
+                              Point { x: 0, y } => return \"on the y axis at \" + y;\n\
+                              Shape { kind } => return \"a \" + kind;\n\
+                              [] => return \"empty\";\n\
+                              [first, ..rest] => return first + rest.length();\n\
+                              (_, 0) => return \"no second\";\n\
+                              n => return n;\n\
+                          }\n\
+                      }\n\
+                      var result = [describe(Point { x: 0, y: 3 }), describe(Point { kind: \"dot\", x: 1 }), describe([]), describe([10, 2, 3]), describe(pair(1, 0)), describe(7)];";
+
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            text("on the y axis at 3"), text("a dot"), text("empty"), LiteralValue::Int(12), text("no second"), LiteralValue::Int(7),
+        ])));
+        assert!(interpreter.environment.borrow().get("rest").is_none());
+
+        let tokens = Scanner::new("match (Point {}) { Point { z } => print(1); }").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert!(error.contains("Struct 'Point' has no field 'z' to match."));
+    }
+
+    #[test]
+    fn try_catches_runtime_errors_as_values() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn safe_index(items, i) {\n\
+                          try { return items[i]; } catch (e) { return e.message; }\n\
+                      }\n\
+                      var result = [safe_index([1, 2], 1)];\n\
+                      try {\n\
+                          var x = 1 + missing;\n\
+                      } catch (e) {\n\
+                          result.push(e.line);\n\
+                          match (e) { Error { message } => result.push(message); }\n\
+                      }\n\
+                      try { io.open_file(\"/no/such/file\"); } catch { result.push(\"no file\"); }\n\
+                      result.push(safe_index([1, 2], 5) != nil);";
+
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            LiteralValue::Int(2), LiteralValue::Int(6), text("Undefined variable or namespace 'missing'."), text("no file"), LiteralValue::True,
+        ])));
+
+        let code = run(&mut interpreter, "var result = nil;\ntry { [1].len(); } catch (e) { result = e.code; }");
+        assert_eq!(code, Some(LiteralValue::StringValue("E0023".to_string())));
+
+        // A fatal is meant to end the run, so it passes through
+        let tokens = Scanner::new("try { fatal(\"stop\"); } catch { print(1); }").scan_tokens().unwrap();
+        assert!(interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap().contains("stop"));
+    }
+
+    #[test]
+    fn throw_raises_values_that_catch_receives_with_the_line() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct ParseError { input: \"\" }\n\
+                      fn parse(text) {\n\
+                          if (text == \"\") { throw ParseError { input: text }; }\n\
+                          return text;\n\
+                      }\n\
+                      var result = [];\n\
+                      try { parse(\"\"); } catch (e) { result.push(e.line); result.push(e.value.input == \"\"); }\n\
+                      try { throw \"too late\"; } catch (e) { result.push(e.message); result.push(e.value); }\n\
+                      try {\n\
+                          try { throw 42; } catch (e) { throw e; }\n\
+                      } catch (e) { result.push(e.value); result.push(e.line); }";
+
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            LiteralValue::Int(3), LiteralValue::True, text("too late"), text("too late"), LiteralValue::Int(42), LiteralValue::Int(10),
+        ])));
+
+        // Uncaught, it ends the run with the thrown value and where it was thrown
+        let tokens = Scanner::new("\nthrow \"no config\";").scan_tokens().unwrap();
+        assert_eq!(interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap(), "Line 2:1: no config");
+    }
+
+    #[test]
+    fn question_mark_returns_the_err_from_the_fn() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn parse_port(text) {\n\
+                          try { return Ok(num(text)); } catch { return Err(\"not a number: \" + text); }\n\
+                      }\n\
+                      fn address(host, port) {\n\
+                          try { return Ok(host + \":\" + str(parse_port(port)?)); } catch { return Err(\"unreachable\"); }\n\
+                      }\n\
+                      var result = [];\n\
+                      for (port in [\"80\", \"http\"]) {\n\
+                          match (address(\"localhost\", port)) {\n\
+                              Ok { value } => result.push(value);\n\
+                              Err { error } => result.push(error);\n\
+                          }\n\
+                      }";
+
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            text("localhost:80"), text("not a number: http"),
+        ])));
+
+        let tokens = Scanner::new("fn f() { return Ok(1); }\nvar x = f()?;").scan_tokens().unwrap();
+        assert!(Parser::new(tokens).parse().unwrap_err().contains("'?' can only be used inside a fn"));
+        let tokens = Scanner::new("fn f() { return 1?; }\nf();").scan_tokens().unwrap();
+        assert!(interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap().contains("'?' expects an Ok or Err result, got Int."));
+    }
+
+    #[test]
+    fn lookup_errors_suggest_the_closest_name() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "struct Player { score: 0, name: \"\" }\nvar player = Player {};\nvar counter = 1;");
+
+        let mut error = |source: &str| {
+            let tokens = Scanner::new(source).scan_tokens().unwrap();
+            match Parser::new(tokens).parse() {
+                Ok(stmts) => interpreter.interpret(stmts).err().unwrap(),
+                Err(msg) => msg,
+            }
+        };
+        assert!(error("print(countr);").ends_with("Undefined variable or namespace 'countr'. Did you mean 'counter'?"));
+        assert!(error("print(player.scroe);").ends_with("Field 'scroe' not found in struct 'Player'. Did you mean 'score'?"));
+        assert!(error("player.nme = 1;").ends_with("Did you mean 'name'?"));
+        assert!(error("math.sqr(4);").ends_with("math has no function 'sqr'. Did you mean 'sqrt'?"));
+        assert!(error("print(elephant);").ends_with("'elephant'."));
+    }
+
+    #[test]
+    fn functions_are_values() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn apply(f, v) { return f(v); }\n\
+                      fn double(x) { return x * 2; }\n\
+                      fn adder(n) { fn add(x) { return x + n; } return add; }\n\
+                      fn maybe(callback) { if (callback) { return callback(1); } return 0; }\n\
+                      struct Handler { run: nil }\n\
+                      var handler = Handler { run: double };\n\
+                      var steps = [double, adder(10)];\n\
+                      var result = [apply(double, 4), steps[1](1), handler.run(5), maybe(double), maybe(nil), double == double, adder(1) == adder(1)];";
+
+        let int = LiteralValue::Int;
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            int(8), int(11), int(10), int(2), int(0), LiteralValue::True, LiteralValue::False,
+        ])));
+
+        let parse = |source: &str| Parser::new(Scanner::new(source).scan_tokens().unwrap()).parse().unwrap_err();
+        assert!(parse("var f = math.sqrt;").starts_with("Line 1: math.sqrt is a module function and must be called; wrap it in a fn to pass it as a value."));
+    }
+
+    #[test]
+    fn sorted_unique_and_binary_search_work_on_arrays() {
+        let mut interpreter = Interpreter::new();
+        let source = "var ids = sorted([30, 4.5, 1, 30, \"b\", true, \"a\"]);\n\
+                      var result = [ids, unique([3, 1, 3, 2, 1.0]), binary_search(ids, \"a\"), binary_search(ids, 2)];";
+
+        let (int, text) = (LiteralValue::Int, |value: &str| LiteralValue::StringValue(value.to_string()));
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            LiteralValue::Array(vec![LiteralValue::True, int(1), LiteralValue::Float(4.5), int(30), int(30), text("a"), text("b")]),
+            LiteralValue::Array(vec![int(3), int(1), int(2)]),
+            int(5),
+            LiteralValue::Nil,
+        ])));
+    }
+
+    #[test]
+    fn par_map_runs_top_level_fns_on_threads() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn square(x) { return x * x; }\n\
+                      fn describe(n) { return [n, square(n), \"n = \" + str(n)]; }\n\
+                      var result = (1..=40).par_map(describe);";
 
+        let expected: Vec<LiteralValue> = (1..=40).map(|n| LiteralValue::Array(vec![
+            LiteralValue::Int(n), LiteralValue::Int(n * n), LiteralValue::StringValue(format!("n = {}", n)),
+        ])).collect();
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(expected)));
+
+        let mut error = |source: &str| {
+            let tokens = Scanner::new(source).scan_tokens().unwrap();
+            interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap()
+        };
+        assert_eq!(
+            error("fn adder(n) { fn add(x) { return x + n; } return add; } [1].par_map(adder(1));"),
+            "Line 1:57: par_map needs a fn declared at the top level of the script, and add is not.",
+        );
+        assert_eq!(error("fn check(x) {\n    return fields(x);\n}\n[1].par_map(check);"), "Line 2:12: fields function requires a struct instance as the argument.");
+    }
+
+    #[test]
+    fn memo_caches_results_by_argument() {
+        let mut interpreter = Interpreter::new();
+        let source = "var calls = 0;\n\
+                      fn fib(n) { calls = calls + 1; if (n < 2) { return n; } return fib(n - 1) + fib(n - 2); }\n\
+                      fib = memo(fib);\n\
+                      var result = [fib(12), calls, fib(12), calls];";
+
+        let int = LiteralValue::Int;
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            int(144), int(13), int(144), int(13),
+        ])));
+
+        let tokens = Scanner::new("fn first(a) { return a[0]; } var cached = memo(first); cached([1]);").scan_tokens().unwrap();
+        assert_eq!(
+            interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap(),
+            "Line 1:56: Memoized first needs hashable arguments: Array cannot be used as a map key; only numbers, strings and bools are hashable.",
+        );
+    }
+
+    #[test]
+    fn for_in_walks_a_range() {
+        let mut interpreter = Interpreter::new();
+        let source = "var total = 0;\n\
+                      for (i in 1..=10) { total = total + i; }\n\
+                      var result = total;";
+
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Int(55)));
+    }
+
+    #[test]
+    fn generators_yield_values_to_for_in() {
+        let mut interpreter = Interpreter::new();
+        let source = "var produced = 0;\n\
+                      fn count(n) { var i = 0; while (i < n) { yield i; i = i + 1; } }\n\
+                      fn naturals() { var i = 0; while (true) { produced = produced + 1; yield i; i = i + 1; } }\n\
+                      fn doubled(n) { for (x in count(n)) { yield x * 2; } }\n\
+                      fn find(target) { for (n in naturals()) { if (n == target) { return n; } } }\n\
+                      var total = 0;\n\
+                      for (i in count(5)) { total = total + i; }\n\
+                      var first = 0;\n\
+                      for (n in naturals()) { if (n == 3) { break; } first = first + n; }\n\
+                      var sum = 0;\n\
+                      for (n in doubled(3)) { sum = sum + n; }\n\
+                      var result = [total, first, sum, find(2), produced];";
+
+        let int = LiteralValue::Int;
+        // naturals() only ran as far as the loops walking it asked for: 4 values, then 3
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![int(10), int(3), int(6), int(2), int(7)])));
+
+        let parse = |source: &str| Parser::new(Scanner::new(source).scan_tokens().unwrap()).parse().unwrap_err();
+        assert!(parse("yield 1;").starts_with("Line 1: 'yield' can only be used inside a fn."));
+    }
+
+    #[test]
+    fn functions_return_several_values_as_a_tuple() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn divmod(a, b) { var q = math.floor(a / b); return q, a - q * b; }\n\
+                      var pair = divmod(17, 5);\n\
+                      var q, r = pair;\n\
+                      var first, second = [\"a\", \"b\"];\n\
+                      var result = [q, r, pair[1], pair.length(), 2 in pair, first + second, str(pair), pair == divmod(17, 5)];";
+
+        let int = LiteralValue::Int;
+        let text = |s: &str| LiteralValue::StringValue(s.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            int(3), int(2), int(2), int(2), LiteralValue::True, text("ab"), text("(3, 2)"), LiteralValue::True,
+        ])));
+
+        let tokens = Scanner::new("var a, b, c = [1, 2];").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert_eq!(error, "Line 1:1: Cannot unpack 2 values into 3 variables.");
+    }
+
+    #[test]
+    fn var_destructures_tuples_arrays_and_structs() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct Point { x: 0, y: 0 }\n\
+                      fn bounds() { return 1, 9; }\n\
+                      var (low, high) = bounds();\n\
+                      var [first, second] = [\"a\", \"b\"];\n\
+                      var { y, x } = Point { x: 3, y: 4 };\n\
+                      var result = [low, high, first + second, x, y];";
+
+        let int = LiteralValue::Int;
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            int(1), int(9), LiteralValue::StringValue("ab".to_string()), int(3), int(4),
+        ])));
+
+        let tokens = Scanner::new("var { z } = Point { x: 1, y: 2 };").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert_eq!(error, "Line 1:1: Cannot unpack 'z'; struct 'Point' has no such field.");
+    }
+
+    #[test]
+    fn bigint_parses_digits_and_pow_grows_past_i64() {
+        let mut interpreter = Interpreter::new();
+        let source = "var id = bigint(\"123456789012345678901234567890\") + 1;\n\
+                      var small = bigint(\"42\");\n\
+                      var result = [id, small, math.pow(2, 100), math.abs(0 - id)];";
+
+        let big = |digits: &str| LiteralValue::BigInt(digits.parse().unwrap());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            big("123456789012345678901234567891"), LiteralValue::Int(42),
+            big("1267650600228229401496703205376"), big("123456789012345678901234567891"),
+        ])));
+    }
+
+    #[test]
+    fn redefinitions_in_one_source_fail_when_strict() {
+        let mut interpreter = Interpreter::new();
+        let stmts = |source: &str| Parser::new(Scanner::new(source).scan_tokens().unwrap()).parse().unwrap();
+
+        assert!(interpreter.check_redefinitions(&stmts("fn a() {}\nfn a() {}")).is_ok());
+
+        interpreter.set_strict(true);
+        assert!(interpreter.check_redefinitions(&stmts("fn a() {}\nstruct B {}")).is_ok());
+        assert_eq!(
+            interpreter.check_redefinitions(&stmts("struct B {}\nvar x = 1;\n  struct B { y: 2 }")),
+            Err("Line 3:3: struct 'B' redefines the one declared on line 1.".to_string())
+        );
+    }
+
+    #[test]
+    fn runtime_errors_report_line_and_column() {
+        let mut interpreter = Interpreter::new();
+        let tokens = Scanner::new("fn one(a) { return a; }
+var x =  one(1, 2);").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert!(error.starts_with("Line 2:10: "), "{}", error);
+
+        let tokens = Scanner::new("\n  struct Child : Missing {}").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert_eq!(error, "Line 2:3: Struct 'Child' cannot extend unknown struct 'Missing'.");
+    }
+
+    #[test]
+    fn interpret_all_continues_after_failed_statements() {
+        let mut interpreter = Interpreter::new();
+        let tokens = Scanner::new("var a = 1;\nstruct A : Missing {}\nvar b = a + 1;\none();\nvar result = b;").scan_tokens().unwrap();
+        let errors = interpreter.interpret_all(Parser::new(tokens).parse().unwrap()).unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("Line 2:1: "), "{}", errors[0]);
+        assert!(errors[1].starts_with("Line 4:"), "{}", errors[1]);
+        assert_eq!(interpreter.environment.borrow().get("result"), Some(LiteralValue::Int(2)));
+
+        let tokens = Scanner::new("fatal(\"stop\");\nvar after = 1;").scan_tokens().unwrap();
+        assert_eq!(interpreter.interpret_all(Parser::new(tokens).parse().unwrap()).unwrap_err().len(), 1);
+        assert!(interpreter.environment.borrow().get("after").is_none());
+    }
+
+    #[test]
+    fn interrupt_cancels_before_the_next_statement() {
+        let mut interpreter = Interpreter::new();
+        let handle = interpreter.interrupt_handle();
+
+        // Interrupt from inside the script, like a host thread would while it runs
+        interpreter.environment().borrow_mut().define("stop".to_string(), LiteralValue::Callable {
+            name: "stop".to_string(),
+            arity: 0,
+            fun: Rc::new(move |_, _: &[LiteralValue]| {
+                handle.interrupt();
+                Ok(LiteralValue::Nil)
+            }),
+        }, false);
+
+        let tokens = Scanner::new("var result = 0; compose() { result = result + 1; if (result == 3) { stop(); } }").scan_tokens().unwrap();
+        assert!(interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap().contains("Execution cancelled."));
+        assert_eq!(interpreter.environment().borrow().get("result"), Some(LiteralValue::Int(3)));
+
+        assert_eq!(run(&mut interpreter, "var result = 1;"), Some(LiteralValue::Int(1)));
+    }
+
+    // Collects output in memory, shared with the test so it can be read back
+    struct Captured(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
         }
 
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
 
-        Ok(ControlFlow::Continue)
+    #[test]
+    fn output_goes_to_the_configured_sink() {
+        colored::control::set_override(false);
+        let captured = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(Captured(captured.clone()));
+
+        run(&mut interpreter, "fn shout(x) { print(x, \"!\"); } shout(1); log(\"hi\"); err(\"bad\");");
+        assert_eq!(String::from_utf8(captured.borrow().clone()).unwrap(), "1 !\nLOG \"hi\"\nERR! \"bad\"\n");
+    }
+
+    #[test]
+    fn failed_field_access_writes_nothing() {
+        let module = std::env::temp_dir().join("recolon_quiet_module");
+        fs::write(module.with_extension("rcn"), "var name = 1;").unwrap();
+        let captured = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(Captured(captured.clone()));
+
+        let source = format!("import \"{}\" as lib;\n\
+                              var n = 5;\n\
+                              var errors = [];\n\
+                              fn probe() {{\n\
+                                  try {{ n.size; }} catch (e) {{ errors.push(e.message); }}\n\
+                                  try {{ lib.nmae; }} catch (e) {{ errors.push(e.message); }}\n\
+                              }}\n\
+                              var result = [io.capture(probe), errors.length()];", module.display());
+        assert_eq!(run(&mut interpreter, &source), Some(LiteralValue::Array(vec![
+            LiteralValue::StringValue(String::new()),
+            LiteralValue::Int(2),
+        ])));
+        assert!(captured.borrow().is_empty());
+        let _ = fs::remove_file(module.with_extension("rcn"));
+    }
+
+    #[test]
+    fn scripts_capture_and_redirect_their_output() {
+        colored::control::set_override(false);
+        let captured = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(Captured(captured.clone()));
+
+        let path = std::env::temp_dir().join("recolon_redirect_output.txt");
+        let source = format!("fn inner() {{ print(\"inner\"); }}\n\
+                              fn outer() {{ print(\"before\"); print(io.capture(inner).length()); }}\n\
+                              var result = io.capture(outer);\n\
+                              io.redirect_output(\"{}\");\n\
+                              print(\"to file\");\n\
+                              io.redirect_output(nil);\n\
+                              print(\"back\");", path.display());
+
+        assert_eq!(run(&mut interpreter, &source), Some(LiteralValue::StringValue("before\n6\n".to_string())));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "to file\n");
+        assert_eq!(String::from_utf8(captured.borrow().clone()).unwrap(), "back\n");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn with_closes_the_resource_however_the_block_ends() {
+        let mut interpreter = Interpreter::new();
+        let (input, output) = (std::env::temp_dir().join("recolon_with_input.txt"), std::env::temp_dir().join("recolon_with_output.txt"));
+        fs::write(&input, "one\ntwo\r\nthree\n").unwrap();
+        let source = format!("with (var out = io.open_handle(\"{}\", \"w\")) {{ out.write(\"first\"); out.write(\" second\"); }}\n\
+                              var lines = [];\n\
+                              var handle = nil;\n\
+                              with (var file = io.open_handle(\"{}\")) {{\n\
+                                  handle = file;\n\
+                                  var line = file.read_line();\n\
+                                  while (line != nil) {{ if (line == \"three\") {{ break; }} lines.push(line); line = file.read_line(); }}\n\
+                              }}\n\
+                              struct Lock {{ close: nil }}\n\
+                              var closed = 0;\n\
+                              fn release() {{ closed = closed + 1; }}\n\
+                              fn guarded() {{ with (var lock = Lock {{ close: release }}) {{ return 5; }} }}\n\
+                              var result = [lines, str(handle), guarded(), closed];", output.display(), input.display());
+
+        let text = |s: &str| LiteralValue::StringValue(s.to_string());
+        assert_eq!(run(&mut interpreter, &source), Some(LiteralValue::Array(vec![
+            LiteralValue::Array(vec![text("one"), text("two")]),
+            text(&format!("<file {} (closed)>", input.display())),
+            LiteralValue::Int(5),
+            LiteralValue::Int(1),
+        ])));
+
+        let tokens = Scanner::new("with (var lock = Lock { close: release }) { var broken = 1 + nil; }").scan_tokens().unwrap();
+        assert!(interpreter.interpret(Parser::new(tokens).parse().unwrap()).is_err());
+        assert_eq!(interpreter.environment.borrow().get("closed"), Some(LiteralValue::Int(2)));
+        assert_eq!(fs::read_to_string(&output).unwrap(), "first second");
+        let _ = (fs::remove_file(input), fs::remove_file(output));
     }
 
+    #[test]
+    fn exit_hooks_run_last_first_however_the_run_ends() {
+        colored::control::set_override(false);
+        let captured = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(Captured(captured.clone()));
+
+        let source = "var count = 0;\n\
+                      fn flush() { print(\"flushed\"); }\n\
+                      fn summary() { print(\"processed \" + str(count)); }\n\
+                      os.on_exit(flush);\n\
+                      os.on_exit(summary);\n\
+                      count = 2;\n\
+                      os.exit(3);\n\
+                      count = 10;";
+        let stmts = Parser::new(Scanner::new(source).scan_tokens().unwrap()).parse().unwrap();
+        assert_eq!(interpreter.interpret_all(stmts), Err(vec!["Line 7:1: Exited with status 3.".to_string()]));
+        assert_eq!(interpreter.run_exit_hooks(), Ok(()));
+        assert_eq!(interpreter.exit_code(), Some(3));
+        assert_eq!(String::from_utf8(captured.borrow().clone()).unwrap(), "processed 2\nflushed\n");
+
+        // Hooks run once
+        assert_eq!(interpreter.run_exit_hooks(), Ok(()));
+        assert_eq!(captured.borrow().len(), "processed 2\nflushed\n".len());
+    }
+
+    #[test]
+    fn variables_may_share_a_builtins_name() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn f() { var num = 3; return num; }\n\
+                      var fields = [1];\n\
+                      var clock = 1;\n\
+                      const LIMIT = 1;\n\
+                      fn g() { var LIMIT = 2; }\n\
+                      var shadowed = nil;\n\
+                      try { g(); } catch (e) { shadowed = e.message; }\n\
+                      var result = [f(), fields, clock, shadowed];";
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            LiteralValue::Int(3),
+            LiteralValue::Array(vec![LiteralValue::Int(1)]),
+            LiteralValue::Int(1),
+            LiteralValue::StringValue("Cannot shadow constant 'LIMIT'.".to_string()),
+        ])));
+    }
+
+    #[test]
+    fn variables_hide_a_module_of_the_same_name() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(run(&mut interpreter, "var result = stats.mean([1, 2, 3]);"), Some(LiteralValue::Float(2.0)));
+
+        let source = "var stats = {\"count\": 1, \"mean\": 4};\n\
+                      stats.count = stats.count + 1;\n\
+                      var result = [stats.count, stats.mean, stats.has(\"count\")];";
+        assert_eq!(run(&mut Interpreter::new(), source), Some(LiteralValue::Array(vec![
+            LiteralValue::Int(2),
+            LiteralValue::Int(4),
+            LiteralValue::True,
+        ])));
+
+        let source = "struct Term { x: 0 }\n\
+                      var term = Term { x: 2 };\n\
+                      fn f() { var os = [3]; return os[0]; }\n\
+                      var result = [term.x, f(), os.args()];";
+        assert_eq!(run(&mut Interpreter::new(), source), Some(LiteralValue::Array(vec![
+            LiteralValue::Int(2),
+            LiteralValue::Int(3),
+            LiteralValue::Array(vec![]),
+        ])));
+    }
+
+    #[test]
+    fn scripts_read_their_arguments() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(run(&mut interpreter, "var result = os.args();"), Some(LiteralValue::Array(vec![])));
+
+        interpreter.set_args(vec!["--fast".to_string(), "data.csv".to_string()]);
+        let args = vec![LiteralValue::StringValue("--fast".to_string()), LiteralValue::StringValue("data.csv".to_string())];
+        assert_eq!(run(&mut interpreter, "var result = os.args();"), Some(LiteralValue::Array(args)));
+    }
+
+    #[test]
+    fn breakpoints_keep_the_state_they_saw() {
+        let mut interpreter = Interpreter::new();
+        let source = "var items = [];\n\
+                      fn step(n) { var local = n * 2; items.push(n); return debug.breakpoint(); }\n\
+                      var first = step(1);\n\
+                      var second = step(2);\n\
+                      var then = debug.at(first);\n\
+                      var result = [first, second, then[\"local\"], then[\"items\"], debug.at(second)[\"items\"], then.has(\"second\")];";
+
+        let text = |s: &str| LiteralValue::StringValue(s.to_string());
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            LiteralValue::Int(0), LiteralValue::Int(1), text("Int: 2"), text("Array: [1]"), text("Array: [1, 2]"), LiteralValue::False,
+        ])));
+        let missing = run(&mut interpreter, "var result = nil; try { debug.at(2); } catch (e) { result = e.message; }");
+        assert_eq!(missing, Some(text("debug.at expects the number of a breakpoint taken so far, got 2.")));
+    }
+
+    #[test]
+    fn scripts_and_modules_know_their_file() {
+        let module = std::env::temp_dir().join("recolon_metadata_module");
+        fs::write(module.with_extension("rcn"), "var file = __file__; var main = __main__;").unwrap();
+
+        let mut interpreter = Interpreter::new();
+        let cwd = std::env::current_dir().unwrap().display().to_string();
+        let source = format!("import \"{}\" as lib;\n\
+                              var result = [__file__, __dir__, __main__, lib.file, lib.main];", module.display());
+
+        let text = |s: String| LiteralValue::StringValue(s);
+        let module_file = fs::canonicalize(module.with_extension("rcn")).unwrap().display().to_string();
+        assert_eq!(run(&mut interpreter, &source), Some(LiteralValue::Array(vec![
+            LiteralValue::Nil, text(cwd), LiteralValue::True, text(module_file), LiteralValue::False,
+        ])));
+
+        interpreter.set_script_path(&module.with_extension("rcn").display().to_string());
+        assert_eq!(run(&mut interpreter, "var result = __dir__;"), Some(text(std::env::temp_dir().canonicalize().unwrap().display().to_string())));
+        let _ = fs::remove_file(module.with_extension("rcn"));
+    }
+
+    #[test]
+    fn missing_optional_modules_bind_the_fallback() {
+        let module = std::env::temp_dir().join("recolon_optional_module");
+        fs::write(module.with_extension("rcn"), "var name = \"present\";").unwrap();
+
+        let mut interpreter = Interpreter::new();
+        let source = format!("import \"recolon_not_installed\" as missing else nil;\n\
+                              import \"{}\" as present else nil;\n\
+                              var result = [missing == nil, present.name];", module.display());
+        assert_eq!(run(&mut interpreter, &source), Some(LiteralValue::Array(vec![
+            LiteralValue::True, LiteralValue::StringValue("present".to_string()),
+        ])));
+
+        let _ = fs::remove_file(module.with_extension("rcn"));
+        let tokens = Scanner::new("import \"recolon_not_installed\" as missing;").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert!(error.contains("Failed to load module"), "{}", error);
+    }
+
+    #[test]
+    fn reload_swaps_a_module_in_place() {
+        let module = std::env::temp_dir().join("recolon_reload_module");
+        fs::write(module.with_extension("rcn"), "fn speed() { return 1; }").unwrap();
+
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, &format!("import \"{}\" as logic;\nfn tick() {{ return logic.speed(); }}", module.display()));
+
+        fs::write(module.with_extension("rcn"), "fn speed() { return 2; }").unwrap();
+        assert_eq!(run(&mut interpreter, "reload(logic);\nvar result = tick();"), Some(LiteralValue::Int(2)));
+
+        // A broken edit is reported and the module keeps working as before
+        fs::write(module.with_extension("rcn"), "fn speed() { return missing; }\nvar x = speed();").unwrap();
+        let tokens = Scanner::new("reload(logic);").scan_tokens().unwrap();
+        assert!(interpreter.interpret(Parser::new(tokens).parse().unwrap()).is_err());
+        assert_eq!(run(&mut interpreter, "var result = tick();"), Some(LiteralValue::Int(2)));
+        let _ = fs::remove_file(module.with_extension("rcn"));
+    }
+
+    #[test]
+    fn t_translates_with_arguments_and_plurals() {
+        let dir = std::env::temp_dir().join("recolon_i18n");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("en.json"), r#"{ "greeting": "Hello {name}", "bye": "Bye" }"#).unwrap();
+        fs::write(dir.join("messages.ru.json"), r#"{
+            "greeting": "Привет {name}",
+            "files": { "zero": "Нет файлов", "one": "{count} файл", "few": "{count} файла", "many": "{count} файлов" },
+            "menu": { "open": "Открыть" }
+        }"#).unwrap();
+
+        let mut interpreter = Interpreter::new();
+        let source = format!("i18n.load(\"{}\");\n\
+                              i18n.load(\"{}\");\n\
+                              var result = [t(\"greeting\", {{\"name\": \"Ada\"}}), t(\"bye\", nil), t(\"menu.open\", nil), t(\"missing\", nil)];\n\
+                              for (n in [0, 1, 3, 11, 22]) {{ result.push(t(\"files\", {{\"count\": n}})); }}",
+                             dir.join("en.json").display(), dir.join("messages.ru.json").display());
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        assert_eq!(run(&mut interpreter, &source), Some(LiteralValue::Array(vec![
+            text("Привет Ada"), text("Bye"), text("Открыть"), text("missing"),
+            text("Нет файлов"), text("1 файл"), text("3 файла"), text("11 файлов"), text("22 файла"),
+        ])));
+
+        let tokens = Scanner::new("t(\"files\", nil);").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert!(error.contains("Message 'files' has plural forms and needs a 'count' argument."));
+        assert_eq!(run(&mut interpreter, "var t = 1; var result = t;"), Some(LiteralValue::Int(1)));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn members_lists_a_module_for_lookup_by_name() {
+        let module = std::env::temp_dir().join("recolon_members_module");
+        fs::write(module.with_extension("rcn"), "fn test_add() { return 1; }\nfn test_sub() { return 2; }\nvar helper = 3;").unwrap();
+
+        let mut interpreter = Interpreter::new();
+        let source = format!("import \"{}\" as suite;\n\
+                              var result = 0;\n\
+                              for (name in members(suite)) {{ if (name != \"helper\") {{ result = result * 10 + suite[name](); }} }}", module.display());
+        assert_eq!(run(&mut interpreter, &source), Some(LiteralValue::Int(12)));
+        assert_eq!(run(&mut interpreter, "var result = members(suite);"), Some(LiteralValue::Array(vec![
+            LiteralValue::StringValue("helper".to_string()),
+            LiteralValue::StringValue("test_add".to_string()),
+            LiteralValue::StringValue("test_sub".to_string()),
+        ])));
+        let _ = fs::remove_file(module.with_extension("rcn"));
+    }
+
+    #[test]
+    fn structs_declare_associated_functions() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct Point {\n\
+                          x: 0, y: 0,\n\
+                          fn new(x, y) { return Point { x: x, y: y }; }\n\
+                          fn origin() { return Point.new(0, 0); }\n\
+                      }\n\
+                      struct Pixel : Point { color: \"\" }\n\
+                      var p = Pixel.new(3, 4);\n\
+                      var make = Point.new;\n\
+                      var result = [p.x + p.y, Point.origin().x, make(5, 6).y];";
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![
+            LiteralValue::Int(7), LiteralValue::Int(0), LiteralValue::Int(6),
+        ])));
+
+        let tokens = Scanner::new("Point.missing();").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert!(error.ends_with("Struct 'Point' has no fn 'missing'."), "{}", error);
+    }
+
+    #[test]
+    fn log_json_and_log_table_write_to_the_output() {
+        colored::control::set_override(false);
+        let captured = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(Captured(captured.clone()));
+
+        run(&mut interpreter, "struct Item { id: 0 }\nlog_json([1, \"a\"]);\nlog_table([Item { id: 12 }, Item {}]);");
+        assert_eq!(String::from_utf8(captured.borrow().clone()).unwrap(), "[\n  1,\n  \"a\"\n]\n+----+\n| id |\n+----+\n| 12 |\n|  0 |\n+----+\n");
+    }
+
+    #[test]
+    fn for_in_visits_elements_keys_and_characters() {
+        let mut interpreter = Interpreter::new();
+        let source = "var items = [1, 2, 3, 4];\n\
+                      var total = 0;\n\
+                      for (item in items) { if (item == 2) { continue; } items.push(10); total = total + item; }\n\
+                      var letters = \"\";\n\
+                      for (var c in \"abc\") { letters = c + letters; }\n\
+                      var keys = [];\n\
+                      for (key in debug.globals()) { if (key == \"total\") { keys.push(key); break; } }\n\
+                      var result = str(total) + letters + keys.length();";
+
+        // The pushes inside the loop don't extend it
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::StringValue("8cba1".to_string())));
+        assert!(interpreter.environment.borrow().get("item").is_none());
+
+        let tokens = Scanner::new("for (x in 3) {}").scan_tokens().unwrap();
+        let error = interpreter.interpret(Parser::new(tokens).parse().unwrap()).err().unwrap();
+        assert_eq!(error, "Line 1:1: Cannot iterate over Int; for-in takes an array, tuple, map, string or generator.");
+    }
+
+    #[test]
+    fn watched_paths_call_back_on_change() {
+        let path = std::env::temp_dir().join("recolon_watch.txt");
+        fs::write(&path, "one").unwrap();
+
+        let mut interpreter = Interpreter::new();
+        let source = format!("var changes = [];\n\
+                              fn changed(path) {{ changes.push(path); fs.unwatch(path); }}\n\
+                              fs.watch(\"{}\", changed);", path.display());
+        run(&mut interpreter, &source);
+
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            // Past the file system's timestamp granularity
+            std::thread::sleep(Duration::from_millis(1100));
+            fs::write(writer_path, "two").unwrap();
+        });
+        interpreter.run_event_loop().unwrap();
+        writer.join().unwrap();
+
+        let changes = interpreter.environment.borrow().get("changes");
+        assert_eq!(changes, Some(LiteralValue::Array(vec![LiteralValue::StringValue(path.display().to_string())])));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn selected_elements_can_be_used_as_conditions() {
+        let mut interpreter = Interpreter::new();
+        let source = "var page = html.parse(\"<ul><li>one</li></ul>\");\n\
+                      var result = [];\n\
+                      if (page.first(\"li\")) { result.push(\"li\"); }\n\
+                      if (page.first(\"td\")) { result.push(\"td\"); }\n\
+                      if (!page.first(\"li\")) { result.push(\"not li\"); }";
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(vec![LiteralValue::StringValue("li".to_string())])));
+    }
+
+    #[test]
+    fn every_value_can_be_a_condition() {
+        let mut interpreter = Interpreter::new();
+        let source = "struct P { x: 0 }\n\
+                      var values = [[], [0], {}, {1: 2}, P { x: 0 }, P, string_builder()];\n\
+                      var result = [];\n\
+                      for (value in values) { if (value) { result.push(\"yes\"); } else { result.push(\"no\"); } result.push(!value); }";
+        let (yes, no) = (LiteralValue::StringValue("yes".to_string()), LiteralValue::StringValue("no".to_string()));
+        let mut expected = vec![no.clone(), LiteralValue::True, yes.clone(), LiteralValue::False, no, LiteralValue::True];
+        for _ in 0..4 {
+            expected.extend([yes.clone(), LiteralValue::False]);
+        }
+        assert_eq!(run(&mut interpreter, source), Some(LiteralValue::Array(expected)));
+    }
+
+    #[test]
+    fn break_and_continue_leave_the_loop_they_name() {
+        let mut interpreter = Interpreter::new();
+        let source = "var result = [];\n\
+                      var i = 0;\n\
+                      while (i < 4) { i = i + 1; if (i == 2) { continue; } result.push(\"w\" + str(i)); }\n\
+                      for (var j = 0; j < 4; j = j + 1) { if (j == 1) { continue; } if (j == 3) { break; } result.push(\"f\" + str(j)); }\n\
+                      var step = 0;\n\
+                      compose(3) { step = step + 1; if (step == 2) { continue; } result.push(\"c\" + str(step)); }\n\
+                      for (var a = 0; a < 2; a = a + 1) { for (b in [0, 1, 2]) { if (b == 1) { break; } result.push(str(a) + str(b)); } }\n\
+                      rows: for (var row = 0; row < 3; row = row + 1) { for (var col = 0; col < 3; col = col + 1) { if (col > row) { continue rows; } result.push(\"r\" + str(row) + str(col)); } }\n\
+                      outer: while (true) { while (true) { result.push(\"in\"); break outer; } result.push(\"never\"); }";
+        let Some(LiteralValue::Array(items)) = run(&mut interpreter, source) else { panic!("Expected an array") };
+        let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+        assert_eq!(items, ["w1", "w3", "w4", "f0", "f2", "c1", "c3", "00", "10", "r00", "r10", "r11", "r20", "r21", "r22", "in"]);
+    }
 }
@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+
+/// An example program shipped with `recolon learn`, and what it prints.
+pub struct Lesson {
+    pub title: &'static str,
+    pub source: &'static str,
+    pub expected: &'static str,
+}
+
+/// The lessons in the order a new user should read them. Each is `lessons/<name>.rcn`
+/// with its output in `lessons/<name>.out`, built into the binary.
+pub const LESSONS: &[Lesson] = &[
+    Lesson {
+        title: "Values and printing",
+        source: include_str!("lessons/01-hello.rcn"),
+        expected: include_str!("lessons/01-hello.out"),
+    },
+    Lesson {
+        title: "Decisions and loops",
+        source: include_str!("lessons/02-control-flow.rcn"),
+        expected: include_str!("lessons/02-control-flow.out"),
+    },
+    Lesson {
+        title: "Functions",
+        source: include_str!("lessons/03-functions.rcn"),
+        expected: include_str!("lessons/03-functions.out"),
+    },
+    Lesson {
+        title: "Structs",
+        source: include_str!("lessons/04-structs.rcn"),
+        expected: include_str!("lessons/04-structs.out"),
+    },
+    Lesson {
+        title: "Match",
+        source: include_str!("lessons/05-match.rcn"),
+        expected: include_str!("lessons/05-match.out"),
+    },
+];
+
+// Collects what a lesson prints so it can be compared with the expected output
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs `source`, a lesson or the learner's version of one, and returns what it printed.
+/// Callers turn colors off first, so `log` output compares the same in a terminal and a pipe.
+pub fn run(source: &str) -> Result<String, String> {
+    let printed = Rc::new(RefCell::new(vec![]));
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Output(printed.clone()));
+
+    let stmts = Parser::new(Scanner::new(source).scan_tokens()?).parse()?;
+    interpreter.interpret(stmts)?;
+    let printed = String::from_utf8_lossy(&printed.borrow()).into_owned();
+    Ok(printed)
+}
+
+/// Whether `output` is what the lesson expects, ignoring line endings and trailing whitespace.
+pub fn matches(lesson: &Lesson, output: &str) -> bool {
+    let lines = |text: &str| text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim_end().to_string();
+    lines(output) == lines(lesson.expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_lesson_prints_its_expected_output() {
+        colored::control::set_override(false);
+        for lesson in LESSONS {
+            let output = run(lesson.source).unwrap_or_else(|e| panic!("'{}' failed: {}", lesson.title, e));
+            assert!(matches(lesson, &output), "'{}' printed:\n{}", lesson.title, output);
+        }
+        assert!(!matches(&LESSONS[0], "Hello"));
+    }
+}
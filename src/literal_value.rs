@@ -1,45 +1,158 @@
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
 use crate::environment::Environment;
+use crate::modules::rcn_html::Element;
+use crate::modules::rcn_io::{self, FileHandle};
+use crate::modules::rcn_term::Progress;
+use crate::types::rcn_generator::Generator;
 use crate::scanner;
 use crate::scanner::{Token, TokenType};
+use crate::types::rcn_interface::InterfaceDefinition;
 use crate::types::rcn_struct::{StructDefinition, StructInstance};
 
+/// A hashable map key. Only numbers, strings and bools can be used as keys.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Bool(bool),
+    Int(i64),
+    BigInt(BigInt), // Only for values outside the i64 range, like LiteralValue::BigInt
+    Float(u64), // Bits of the f64; whole floats are stored as Int so `m[1.0]` finds `m[1]`
+    String(String),
+}
+
+impl MapKey {
+    pub fn from_value(value: &LiteralValue) -> Result<MapKey, String> {
+        match value {
+            LiteralValue::StringValue(s) => Ok(MapKey::String(s.clone())),
+            LiteralValue::True => Ok(MapKey::Bool(true)),
+            LiteralValue::False => Ok(MapKey::Bool(false)),
+            LiteralValue::Int(x) => Ok(MapKey::Int(*x)),
+            LiteralValue::BigInt(x) => Ok(MapKey::BigInt(x.clone())),
+            LiteralValue::Float(x) if x.is_nan() => Err("NaN cannot be used as a map key.".to_string()),
+            LiteralValue::Float(x) => match value.as_int() {
+                Some(x) => Ok(MapKey::Int(x)),
+                None => Ok(MapKey::Float(x.to_bits())),
+            },
+            other => Err(format!("{} cannot be used as a map key; only numbers, strings and bools are hashable.", other.to_type())),
+        }
+    }
+
+    pub fn to_value(&self) -> LiteralValue {
+        match self {
+            MapKey::Bool(b) => LiteralValue::check_bool(*b),
+            MapKey::Int(x) => LiteralValue::Int(*x),
+            MapKey::BigInt(x) => LiteralValue::BigInt(x.clone()),
+            MapKey::Float(bits) => LiteralValue::Float(f64::from_bits(*bits)),
+            MapKey::String(s) => LiteralValue::StringValue(s.clone()),
+        }
+    }
+}
+
+impl From<&str> for MapKey {
+    fn from(key: &str) -> Self {
+        MapKey::String(key.to_string())
+    }
+}
+
+// Strings are quoted so they can be told apart from numbers and bools
+impl std::fmt::Display for MapKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapKey::String(s) => write!(f, "{:?}", s),
+            other => write!(f, "{}", other.to_value()),
+        }
+    }
+}
+
+// Bools, then numbers by value, then strings, so printed maps have a stable order
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (MapKey::Bool(x), MapKey::Bool(y)) => x.cmp(y),
+            (MapKey::String(x), MapKey::String(y)) => x.cmp(y),
+            (MapKey::Int(x), MapKey::Int(y)) => x.cmp(y),
+            (MapKey::String(_), _) => std::cmp::Ordering::Greater,
+            (MapKey::Bool(_), _) | (_, MapKey::String(_)) => std::cmp::Ordering::Less,
+            (_, MapKey::Bool(_)) => std::cmp::Ordering::Greater,
+            // Numbers of different kinds: exactly for two integers, otherwise as floats
+            (x, y) => match (x.to_value().as_bigint(), y.to_value().as_bigint()) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                _ => {
+                    let (x, y) = (x.to_value().as_f64().unwrap_or_default(), y.to_value().as_f64().unwrap_or_default());
+                    x.total_cmp(&y)
+                }
+            },
+        }
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub type NativeFn = Rc<dyn Fn(Rc<RefCell<Environment>>, &[LiteralValue]) -> Result<LiteralValue, String>>;
+
 #[derive(Clone)]
 pub enum LiteralValue {
     Array(Vec<LiteralValue>),
-    Callable { name: String, arity: i32, fun: Rc<dyn Fn(Rc<RefCell<Environment>>, &Vec<LiteralValue>) -> LiteralValue> },
-    Number(f32),
+    Tuple(Vec<LiteralValue>), // Fixed group of values, like the results of `return a, b;`
+    Map(HashMap<MapKey, LiteralValue>),
+    Callable { name: String, arity: i32, fun: NativeFn },
+    Int(i64),
+    BigInt(BigInt), // Integers outside the i64 range; arithmetic that fits again gives an Int
+    Float(f64),
+    Decimal(Decimal), // Exact base-10 number for money, from `19.99d` or decimal("19.99")
     StringValue(String),
     True,
     False,
     Nil,
     StructDef(StructDefinition),
-    StructInst(StructInstance),
+    StructInst(Rc<RefCell<StructInstance>>),
+    Interface(InterfaceDefinition),
     Namespace(Rc<RefCell<Environment>>),
+    StringBuilder(Rc<RefCell<String>>), // Shared buffer so appending in a loop doesn't copy the text
+    Progress(Rc<RefCell<Progress>>), // Handle from term.progress or term.spinner
+    FileHandle(Rc<RefCell<FileHandle>>), // Open file from io.open_handle
+    Generator(Rc<Generator>), // Returned by calling a fn that yields
+    HtmlElement(Rc<Element>), // A page from html.parse, or an element selected from one
 }
 
 
 impl PartialEq for LiteralValue {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (LiteralValue::Number(x), LiteralValue::Number(y)) => x == y,
-            (
-                LiteralValue::Callable {
-                    name,
-                    arity,
-                    fun: _,
-                },
-                LiteralValue::Callable {
-                    name: name2,
-                    arity: arity2,
-                    fun: _,
-                },
-            ) => name == name2 && arity == arity2,
+            (LiteralValue::Int(x), LiteralValue::Int(y)) => x == y,
+            (LiteralValue::Float(x), LiteralValue::Float(y)) => x == y,
+            // An Int equals the Float with the same value, as `1 == 1.0` promotes the Int
+            (LiteralValue::Int(x), LiteralValue::Float(y)) | (LiteralValue::Float(y), LiteralValue::Int(x)) => *x as f64 == *y,
+            (LiteralValue::BigInt(x), LiteralValue::BigInt(y)) => x == y,
+            (LiteralValue::BigInt(_), LiteralValue::Float(_)) | (LiteralValue::Float(_), LiteralValue::BigInt(_)) => self.as_f64() == other.as_f64(),
+            (LiteralValue::Decimal(x), LiteralValue::Decimal(y)) => x == y,
+            (LiteralValue::Decimal(x), LiteralValue::Int(y)) | (LiteralValue::Int(y), LiteralValue::Decimal(x)) => *x == Decimal::from(*y),
+            // Functions are equal when they are the same function, not just the same name,
+            // so closures made by separate calls of one fn differ
+            (LiteralValue::Callable { fun, .. }, LiteralValue::Callable { fun: fun2, .. }) => Rc::ptr_eq(fun, fun2),
             (LiteralValue::StringValue(x), LiteralValue::StringValue(y)) => x == y,
             (LiteralValue::True, LiteralValue::True) => true,
             (LiteralValue::False, LiteralValue::False) => true,
             (LiteralValue::Nil, LiteralValue::Nil) => true,
+            // Compound values compare by contents
+            (LiteralValue::Array(x), LiteralValue::Array(y)) => x == y,
+            (LiteralValue::Tuple(x), LiteralValue::Tuple(y)) => x == y,
+            (LiteralValue::Map(x), LiteralValue::Map(y)) => x == y,
+            (LiteralValue::HtmlElement(x), LiteralValue::HtmlElement(y)) => Rc::ptr_eq(x, y),
+            (LiteralValue::StructInst(x), LiteralValue::StructInst(y)) => {
+                Rc::ptr_eq(x, y) || {
+                    let (x, y) = (x.borrow(), y.borrow());
+                    x.name == y.name && x.fields == y.fields
+                }
+            }
             _ => false,
         }
     }
@@ -47,60 +160,222 @@ impl PartialEq for LiteralValue {
 
 impl std::fmt::Debug for LiteralValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>)-> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self)
     }
 }
 
-fn unwrap_as_f32(literal: Option<scanner::LiteralValue>) -> f32 {
+fn unwrap_as_number(literal: Option<scanner::LiteralValue>) -> LiteralValue {
     match literal {
-        Some(scanner::LiteralValue::IntValue(x)) => x as f32,
-        Some(scanner::LiteralValue::FloatValue(x)) => x as f32,
-        _ => panic!("Could not unwrap as f32"),
+        Some(scanner::LiteralValue::IntValue(x)) => LiteralValue::Int(x),
+        Some(scanner::LiteralValue::BigIntValue(x)) => LiteralValue::BigInt(x),
+        Some(scanner::LiteralValue::FloatValue(x)) => LiteralValue::Float(x),
+        Some(scanner::LiteralValue::DecimalValue(x)) => LiteralValue::Decimal(x),
+        _ => panic!("Could not unwrap as number"),
     }
 }
 
 fn unwrap_as_string(literal: Option<scanner::LiteralValue>) -> String {
     match literal {
         Some(scanner::LiteralValue::StringValue(s)) => s.clone(),
-        Some(scanner::LiteralValue::IdentifierValue(s)) => s.clone(),
         _ => panic!("Could not unwrap as string"),
     }
 }
 
-impl LiteralValue {
-    pub fn to_string(&self) -> String {
-        match self {
-            LiteralValue::Number(x) => x.to_string(),
+impl std::fmt::Display for LiteralValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            LiteralValue::Int(x) => x.to_string(),
+            LiteralValue::BigInt(x) => x.to_string(),
+            LiteralValue::Float(x) => x.to_string(),
+            LiteralValue::Decimal(x) => x.to_string(),
             LiteralValue::StringValue(x) => x.clone(),
             LiteralValue::True => "true".to_string(),
             LiteralValue::False => "false".to_string(),
             LiteralValue::Nil => "nil".to_string(),
             LiteralValue::Callable { name, arity, fun: _ } => format!("{name}/{arity}"),
             LiteralValue::StructDef(struct_value) =>  {
-                format!("{} {:?}", struct_value.name, struct_value.fields)
+                match &struct_value.parent {
+                    Some(parent) => format!("{} : {} {:?}", struct_value.name, parent, struct_value.fields),
+                    None => format!("{} {:?}", struct_value.name, struct_value.fields),
+                }
             },
-            LiteralValue::StructInst(struct_value) => format!("{{ name: \"{}\", fields: {:?} }}", struct_value.name, struct_value.fields),
+            LiteralValue::StructInst(struct_value) => {
+                let struct_value = struct_value.borrow();
+                format!("{{ name: \"{}\", fields: {:?} }}", struct_value.name, struct_value.fields)
+            },
+            LiteralValue::Interface(interface) => format!("interface {}", interface.name),
             LiteralValue::Array(elements) => format!("{elements:?}"),
+            LiteralValue::Tuple(_) | LiteralValue::Map(_) => self.to_pretty_string(true),
             LiteralValue::Namespace(env) => format!("Namespace {{ values: {:?} }}", env.borrow().values),
-            _ => todo!()
+            LiteralValue::StringBuilder(buffer) => buffer.borrow().clone(),
+            LiteralValue::Progress(progress) => progress.borrow().to_string(),
+            LiteralValue::FileHandle(handle) => handle.borrow().to_string(),
+            LiteralValue::Generator(generator) => format!("<generator {}>", generator.name),
+            LiteralValue::HtmlElement(element) => element.to_string(),
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// Nesting depth after which pretty output is abbreviated
+const MAX_PRETTY_DEPTH: usize = 8;
+
+impl LiteralValue {
+    /// Renders arrays and struct instances over multiple indented lines, or on one line when `compact`.
+    /// Struct instances that contain themselves are printed as `<cycle Name>`.
+    pub fn to_pretty_string(&self, compact: bool) -> String {
+        let mut output = String::new();
+        self.write_pretty(&mut output, 0, compact, &mut vec![]);
+        output
+    }
+
+    fn write_pretty(&self, output: &mut String, depth: usize, compact: bool, seen: &mut Vec<*const RefCell<StructInstance>>) {
+        let indent = "    ".repeat(depth + 1);
+        let closing_indent = "    ".repeat(depth);
+
+        match self {
+            LiteralValue::StringValue(s) if depth > 0 => output.push_str(&format!("{:?}", s)),
+            LiteralValue::Array(elements) => {
+                if elements.is_empty() {
+                    output.push_str("[]");
+                } else if depth >= MAX_PRETTY_DEPTH {
+                    output.push_str("[...]");
+                } else if compact || elements.iter().all(|element| !element.is_compound()) {
+                    output.push('[');
+                    for (i, element) in elements.iter().enumerate() {
+                        if i > 0 {
+                            output.push_str(", ");
+                        }
+                        element.write_pretty(output, depth + 1, true, seen);
+                    }
+                    output.push(']');
+                } else {
+                    output.push_str("[\n");
+                    for element in elements {
+                        output.push_str(&indent);
+                        element.write_pretty(output, depth + 1, compact, seen);
+                        output.push_str(",\n");
+                    }
+                    output.push_str(&closing_indent);
+                    output.push(']');
+                }
+            }
+            // Tuples are short, so they always stay on one line
+            LiteralValue::Tuple(elements) => {
+                output.push('(');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        output.push_str(", ");
+                    }
+                    element.write_pretty(output, depth + 1, true, seen);
+                }
+                output.push(')');
+            }
+            LiteralValue::StructInst(instance) => {
+                let pointer = Rc::as_ptr(instance);
+                let instance = instance.borrow();
+
+                if seen.contains(&pointer) {
+                    output.push_str(&format!("<cycle {}>", instance.name));
+                    return;
+                }
+                if depth >= MAX_PRETTY_DEPTH {
+                    output.push_str(&format!("{} {{...}}", instance.name));
+                    return;
+                }
+
+                let mut names: Vec<&String> = instance.fields.keys().collect();
+                names.sort();
+
+                seen.push(pointer);
+                if compact {
+                    let fields: Vec<String> = names.iter().map(|name| {
+                        let mut value = String::new();
+                        instance.fields[*name].write_pretty(&mut value, depth + 1, true, seen);
+                        format!("{}: {}", name, value)
+                    }).collect();
+                    output.push_str(&format!("{} {{ {} }}", instance.name, fields.join(", ")));
+                } else {
+                    output.push_str(&format!("{} {{\n", instance.name));
+                    for name in names {
+                        output.push_str(&format!("{}{}: ", indent, name));
+                        instance.fields[name].write_pretty(output, depth + 1, compact, seen);
+                        output.push_str(",\n");
+                    }
+                    output.push_str(&closing_indent);
+                    output.push('}');
+                }
+                seen.pop();
+            }
+            LiteralValue::Map(entries) => {
+                if entries.is_empty() {
+                    output.push_str("{}");
+                    return;
+                }
+                if depth >= MAX_PRETTY_DEPTH {
+                    output.push_str("{...}");
+                    return;
+                }
+
+                let mut keys: Vec<&MapKey> = entries.keys().collect();
+                keys.sort();
+
+                if compact {
+                    let entries: Vec<String> = keys.iter().map(|key| {
+                        let mut value = String::new();
+                        entries[*key].write_pretty(&mut value, depth + 1, true, seen);
+                        format!("{}: {}", key, value)
+                    }).collect();
+                    output.push_str(&format!("{{ {} }}", entries.join(", ")));
+                } else {
+                    output.push_str("{\n");
+                    for key in keys {
+                        output.push_str(&format!("{}{}: ", indent, key));
+                        entries[key].write_pretty(output, depth + 1, compact, seen);
+                        output.push_str(",\n");
+                    }
+                    output.push_str(&closing_indent);
+                    output.push('}');
+                }
+            }
+            other => output.push_str(&other.to_string()),
         }
     }
 
+    // Values that the pretty printer spreads over several lines
+    pub fn is_compound(&self) -> bool {
+        matches!(self, LiteralValue::Array(_) | LiteralValue::Map(_) | LiteralValue::StructInst(_))
+    }
+
     pub fn to_type(&self) -> String {
         match self {
-            LiteralValue::Number(_) => "Number".to_string(),
+            LiteralValue::Int(_) => "Int".to_string(),
+            LiteralValue::BigInt(_) => "BigInt".to_string(),
+            LiteralValue::Float(_) => "Float".to_string(),
+            LiteralValue::Decimal(_) => "Decimal".to_string(),
             LiteralValue::StringValue(_) => "String".to_string(),
             LiteralValue::True => "Bool".to_string(),
             LiteralValue::False => "Bool".to_string(),
             LiteralValue::Nil => "nil".to_string(),
             LiteralValue::StructDef(_) => "Struct".to_string(),
-            _ => todo!()
+            LiteralValue::Interface(_) => "Interface".to_string(),
+            LiteralValue::Callable { .. } => "Function".to_string(),
+            LiteralValue::Map(_) => "Map".to_string(),
+            LiteralValue::Namespace(_) => "Namespace".to_string(),
+            LiteralValue::Array(_) => "Array".to_string(),
+            LiteralValue::Tuple(_) => "Tuple".to_string(),
+            LiteralValue::StructInst(instance) => instance.borrow().name.clone(),
+            LiteralValue::StringBuilder(_) => "StringBuilder".to_string(),
+            LiteralValue::Progress(_) => "Progress".to_string(),
+            LiteralValue::FileHandle(_) => "FileHandle".to_string(),
+            LiteralValue::Generator(_) => "Generator".to_string(),
+            LiteralValue::HtmlElement(_) => "HtmlElement".to_string(),
         }
     }
 
     pub fn from_token(token: Token) -> Self {
         match token.token_type {
-            TokenType::Number => LiteralValue::Number(unwrap_as_f32(token.literal)),
+            TokenType::Number => unwrap_as_number(token.literal),
             TokenType::String => LiteralValue::StringValue(unwrap_as_string(token.literal)),
             TokenType::False => LiteralValue::False,
             TokenType::True => LiteralValue::True,
@@ -109,13 +384,6 @@ impl LiteralValue {
         }
     }
 
-    pub fn get_field(&self, field_name: &str) -> Option<LiteralValue> {
-        match self {
-            LiteralValue::Namespace(env) => env.borrow().get(field_name),
-            _ => None,
-        }
-    }
-
     pub fn check_bool(b: bool) -> Self {
         if b {
             LiteralValue::True
@@ -124,17 +392,63 @@ impl LiteralValue {
         }
     }
 
+    /// The value of any number as a float, for functions that take any number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            LiteralValue::Int(x) => Some(*x as f64),
+            LiteralValue::BigInt(x) => x.to_f64(),
+            LiteralValue::Float(x) => Some(*x),
+            LiteralValue::Decimal(x) => x.to_f64(),
+            _ => None,
+        }
+    }
+
+    /// Parses whole numbers as Ints (or BigInts when they don't fit) and anything else that is a number as a Float.
+    pub fn parse_number(text: &str) -> Option<LiteralValue> {
+        match text.parse::<BigInt>() {
+            Ok(x) => Some(LiteralValue::from_bigint(x)),
+            Err(_) => text.parse::<f64>().ok().map(LiteralValue::Float),
+        }
+    }
+
+    /// An Int when the value fits in one, so BigInts only hold values that need them.
+    pub fn from_bigint(x: BigInt) -> LiteralValue {
+        match x.to_i64() {
+            Some(x) => LiteralValue::Int(x),
+            None => LiteralValue::BigInt(x),
+        }
+    }
+
+    /// An Int or BigInt as a BigInt.
+    pub fn as_bigint(&self) -> Option<BigInt> {
+        match self {
+            LiteralValue::Int(x) => Some(BigInt::from(*x)),
+            LiteralValue::BigInt(x) => Some(x.clone()),
+            _ => None,
+        }
+    }
+
+    /// An Int, or a Float without a fractional part, as an integer.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            LiteralValue::Int(x) => Some(*x),
+            LiteralValue::Float(x) if x.fract() == 0.0 && (i64::MIN as f64..i64::MAX as f64).contains(x) => Some(*x as i64),
+            LiteralValue::Decimal(x) if x.fract().is_zero() => x.to_i64(),
+            _ => None,
+        }
+    }
+
     pub fn is_falsy(&self) -> LiteralValue {
         match self {
-            LiteralValue::Number(x) => {
-                if *x == 0.0f32 {
+            LiteralValue::Int(_) | LiteralValue::BigInt(_) | LiteralValue::Float(_) | LiteralValue::Decimal(_) => {
+                if self.as_f64() == Some(0.0) {
                     LiteralValue::True
                 } else {
                     LiteralValue::False
                 }
             }
             LiteralValue::StringValue(s) => {
-                if s.len() == 0 {
+                if s.is_empty() {
                     LiteralValue::True
                 } else {
                     LiteralValue::False
@@ -143,22 +457,25 @@ impl LiteralValue {
             LiteralValue::True => LiteralValue::False,
             LiteralValue::False => LiteralValue::True,
             LiteralValue::Nil => LiteralValue::False,
-            LiteralValue::Callable{ name: _, arity: _, fun: _ } => panic!("Can not use callable as falsy value"),
-            _ => todo!()
+            LiteralValue::Array(elements) => LiteralValue::check_bool(elements.is_empty()),
+            LiteralValue::Map(entries) => LiteralValue::check_bool(entries.is_empty()),
+            LiteralValue::Callable { .. } | LiteralValue::Generator(_) | LiteralValue::Tuple(_) | LiteralValue::HtmlElement(_) => LiteralValue::False,
+            LiteralValue::StructDef(_) | LiteralValue::StructInst(_) | LiteralValue::Interface(_) | LiteralValue::Namespace(_) => LiteralValue::False,
+            LiteralValue::StringBuilder(_) | LiteralValue::Progress(_) | LiteralValue::FileHandle(_) => LiteralValue::False,
         }
     }
 
     pub fn is_truthy(&self) -> LiteralValue {
         match self {
-            LiteralValue::Number(x) => {
-                if *x == 0.0f32 {
+            LiteralValue::Int(_) | LiteralValue::BigInt(_) | LiteralValue::Float(_) | LiteralValue::Decimal(_) => {
+                if self.as_f64() == Some(0.0) {
                     LiteralValue::False
                 } else {
                     LiteralValue::True
                 }
             }
             LiteralValue::StringValue(s) => {
-                if s.len() == 0 {
+                if s.is_empty() {
                     LiteralValue::False
                 } else {
                     LiteralValue::True
@@ -167,43 +484,91 @@ impl LiteralValue {
             LiteralValue::True => LiteralValue::True,
             LiteralValue::False => LiteralValue::False,
             LiteralValue::Nil => LiteralValue::False,
-            LiteralValue::Callable{ name: _, arity: _, fun: _ } => panic!("Can not use callable as truthy value"),
-            _ => todo!()
+            // A function is always truthy, so `if (callback)` checks an optional callback was given
+            // So is an element, so `if (page.first("li"))` checks the selector found one
+            LiteralValue::Callable { .. } | LiteralValue::Generator(_) | LiteralValue::Tuple(_) | LiteralValue::HtmlElement(_) => LiteralValue::True,
+            // Empty collections are falsy like the empty string, so `if (items)` checks there are any
+            LiteralValue::Array(elements) => LiteralValue::check_bool(!elements.is_empty()),
+            LiteralValue::Map(entries) => LiteralValue::check_bool(!entries.is_empty()),
+            // Instances, definitions and handles always are
+            LiteralValue::StructDef(_) | LiteralValue::StructInst(_) | LiteralValue::Interface(_) | LiteralValue::Namespace(_) => LiteralValue::True,
+            LiteralValue::StringBuilder(_) | LiteralValue::Progress(_) | LiteralValue::FileHandle(_) => LiteralValue::True,
         }
     }
 
-    pub fn update_struct_field(&mut self, field_name: String, new_value: LiteralValue) -> Result<(), String> {
-        if let LiteralValue::StructInst(ref mut struct_instance) = self {
-            if struct_instance.fields.contains_key(&field_name) {
-                struct_instance.fields.insert(field_name, new_value);
+    pub fn update_struct_field(&self, field_name: String, new_value: LiteralValue) -> Result<(), String> {
+        if let LiteralValue::StructInst(struct_instance) = self {
+            let mut struct_instance = struct_instance.borrow_mut();
+            if struct_instance.frozen {
+                return Err(format!("Cannot assign to field '{}' of frozen struct '{}'.", field_name, struct_instance.name));
+            }
+
+            if let Some(field) = struct_instance.fields.get_mut(&field_name) {
+                *field = new_value;
                 return Ok(());
             } else {
-                print!("Field '{}' not found in struct '{}'.", field_name, struct_instance.name);
-                return Err(format!("Field '{}' not found in struct '{}'.", field_name, struct_instance.name));
+                return Err(struct_instance.missing_field(&field_name));
             }
         }
         Err("Tried to update a field on a non-struct instance.".to_string())
     }
 
+    // Copy a value including everything it references, so struct instances no longer share state.
+    // An instance reached twice is copied once, so shared and cyclic references keep their shape.
+    pub fn deep_clone(&self) -> LiteralValue {
+        self.deep_clone_with(&mut vec![])
+    }
+
+    // `copies` pairs the address of each instance copied so far with its copy
+    fn deep_clone_with(&self, copies: &mut Vec<(usize, Rc<RefCell<StructInstance>>)>) -> LiteralValue {
+        match self {
+            LiteralValue::StructInst(instance) => {
+                let pointer = Rc::as_ptr(instance) as usize;
+                if let Some((_, copy)) = copies.iter().find(|(copied, _)| *copied == pointer) {
+                    return LiteralValue::StructInst(copy.clone());
+                }
+                // Copies are never frozen
+                let original = instance.borrow();
+                let copy = Rc::new(RefCell::new(StructInstance { name: original.name.clone(), fields: HashMap::new(), frozen: false }));
+                copies.push((pointer, copy.clone()));
+                let fields = original.fields.iter().map(|(name, value)| (name.clone(), value.deep_clone_with(copies))).collect();
+                copy.borrow_mut().fields = fields;
+                LiteralValue::StructInst(copy)
+            }
+            LiteralValue::Array(elements) => {
+                LiteralValue::Array(elements.iter().map(|element| element.deep_clone_with(copies)).collect())
+            }
+            LiteralValue::Tuple(elements) => {
+                LiteralValue::Tuple(elements.iter().map(|element| element.deep_clone_with(copies)).collect())
+            }
+            LiteralValue::Map(entries) => {
+                LiteralValue::Map(entries.iter().map(|(key, value)| (key.clone(), value.deep_clone_with(copies))).collect())
+            }
+            LiteralValue::StringBuilder(buffer) => {
+                LiteralValue::StringBuilder(Rc::new(RefCell::new(buffer.borrow().clone())))
+            }
+            _ => self.clone(),
+        }
+    }
+
     pub fn call_method(&mut self, method_name: &str, args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
         match self {
             LiteralValue::Array(ref mut vec) => {
                 match method_name {
                     "pop" => {
-                        if args.len() == 0 {
+                        if args.is_empty() {
                             // Remove and return the last element
                             vec.pop().ok_or_else(|| "Array is empty".to_string())
                         } else if args.len() == 1 {
                             // Remove and return the element at the specified index
-                            if let LiteralValue::Number(idx) = args[0] {
-                                let idx = idx as usize;
-                                if idx < vec.len() {
-                                    Ok(vec.remove(idx))
+                            if let Some(idx) = args[0].as_int() {
+                                if idx >= 0 && (idx as usize) < vec.len() {
+                                    Ok(vec.remove(idx as usize))
                                 } else {
                                     Err("Index out of bounds".to_string())
                                 }
                             } else {
-                                Err("Index must be a number.".to_string())
+                                Err("Index must be a whole number.".to_string())
                             }
                         } else {
                             Err("pop method takes 0 or 1 arguments".to_string())
@@ -218,18 +583,151 @@ impl LiteralValue {
                         }
                     }
                     "length" => {
-                        if args.len() != 0 {
+                        if !args.is_empty() {
                             Err("length method takes no arguments.".to_string())
                         } else {
-                            Ok(LiteralValue::Number(vec.len() as f32))
+                            Ok(LiteralValue::Int(vec.len() as i64))
+                        }
+                    }
+                    "clone" => {
+                        if !args.is_empty() {
+                            Err("clone method takes no arguments.".to_string())
+                        } else {
+                            Ok(self.deep_clone())
                         }
                     }
                     // Handle other array methods like push, etc.
                     _ => Err(format!("Unknown method '{}' for arrays", method_name)),
                 }
             }
+            LiteralValue::Tuple(elements) => {
+                if !args.is_empty() {
+                    return Err(format!("{} method takes no arguments.", method_name));
+                }
+                match method_name {
+                    "length" => Ok(LiteralValue::Int(elements.len() as i64)),
+                    "clone" => Ok(self.deep_clone()),
+                    _ => Err(format!("Unknown method '{}' for tuples", method_name)),
+                }
+            }
+            LiteralValue::StructInst(_) => {
+                match method_name {
+                    "clone" => {
+                        if !args.is_empty() {
+                            Err("clone method takes no arguments.".to_string())
+                        } else {
+                            Ok(self.deep_clone())
+                        }
+                    }
+                    _ => Err(format!("Unknown method '{}' for structs", method_name)),
+                }
+            }
+            LiteralValue::StringValue(text) => {
+                if !args.is_empty() {
+                    return Err(format!("{} method takes no arguments.", method_name));
+                }
+                match method_name {
+                    "length" => Ok(LiteralValue::Int(text.chars().count() as i64)),
+                    "upper" => Ok(LiteralValue::StringValue(text.to_uppercase())),
+                    "lower" => Ok(LiteralValue::StringValue(text.to_lowercase())),
+                    "trim" => Ok(LiteralValue::StringValue(text.trim().to_string())),
+                    // Split on "\n" or "\r\n", so text from any OS gives the same lines
+                    "lines" => Ok(LiteralValue::Array(text.lines().map(|line| LiteralValue::StringValue(line.to_string())).collect())),
+                    "clone" => Ok(LiteralValue::StringValue(text.clone())),
+                    _ => Err(format!("Unknown method '{}' for strings", method_name)),
+                }
+            }
+            LiteralValue::Map(entries) if matches!(method_name, "has" | "remove") => {
+                let [key] = args.as_slice() else {
+                    return Err(format!("{} method takes exactly one key.", method_name));
+                };
+                let key = MapKey::from_value(key)?;
+                match method_name {
+                    "has" => Ok(LiteralValue::check_bool(entries.contains_key(&key))),
+                    // The removed value, or nil when the key wasn't there
+                    _ => Ok(entries.remove(&key).unwrap_or(LiteralValue::Nil)),
+                }
+            }
+            LiteralValue::Map(entries) => {
+                if !args.is_empty() {
+                    return Err(format!("{} method takes no arguments.", method_name));
+                }
+                // Keys are sorted so the order is stable between runs
+                let mut keys: Vec<&MapKey> = entries.keys().collect();
+                keys.sort();
+                match method_name {
+                    "length" => Ok(LiteralValue::Int(entries.len() as i64)),
+                    "keys" => Ok(LiteralValue::Array(keys.into_iter().map(MapKey::to_value).collect())),
+                    "values" => Ok(LiteralValue::Array(keys.into_iter().map(|key| entries[key].clone()).collect())),
+                    "clone" => Ok(self.deep_clone()),
+                    _ => Err(format!("Unknown method '{}' for maps", method_name)),
+                }
+            }
+            LiteralValue::StringBuilder(buffer) => {
+                match method_name {
+                    "append" => {
+                        if args.len() != 1 {
+                            return Err("append method takes exactly one argument.".to_string());
+                        }
+                        let text = if args[0].is_compound() { args[0].to_pretty_string(true) } else { args[0].to_string() };
+                        buffer.borrow_mut().push_str(&text);
+                        // Return the builder so appends can be chained
+                        Ok(self.clone())
+                    }
+                    "build" if args.is_empty() => Ok(LiteralValue::StringValue(buffer.borrow().clone())),
+                    "length" if args.is_empty() => Ok(LiteralValue::Int(buffer.borrow().chars().count() as i64)),
+                    "clear" if args.is_empty() => {
+                        buffer.borrow_mut().clear();
+                        Ok(LiteralValue::Nil)
+                    }
+                    "build" | "length" | "clear" => Err(format!("{} method takes no arguments.", method_name)),
+                    _ => Err(format!("Unknown method '{}' for string builders", method_name)),
+                }
+            }
+            LiteralValue::Decimal(x) => {
+                let places = match args.as_slice() {
+                    [places] => match places.as_int() {
+                        Some(places @ 0..=28) => places as u32,
+                        _ => return Err(format!("{} method takes a number of decimal places from 0 to 28.", method_name)),
+                    },
+                    _ => return Err(format!("{} method takes exactly one argument.", method_name)),
+                };
+                // Halves round away from zero, the way prices are usually rounded
+                let rounded = x.round_dp_with_strategy(places, RoundingStrategy::MidpointAwayFromZero);
+                match method_name {
+                    "round" => Ok(LiteralValue::Decimal(rounded)),
+                    "format" => Ok(LiteralValue::StringValue(format_grouped(rounded, places))),
+                    _ => Err(format!("Unknown method '{}' for decimals", method_name)),
+                }
+            }
+            LiteralValue::Progress(progress) => progress.borrow_mut().call_method(method_name, args),
+            LiteralValue::FileHandle(handle) if method_name == "lines" => rcn_io::lines(handle.clone(), args),
+            LiteralValue::FileHandle(handle) => handle.borrow_mut().call_method(method_name, args),
+            LiteralValue::HtmlElement(element) => element.call_method(method_name, args),
             // Handle method calls for other LiteralValue types if needed
-            _ => Err(format!("'{}' method not available on this type", method_name)),
+            _ => Err(format!("'{}' method not available on {}", method_name, self.to_type())),
         }
     }
 }
+
+// `1234.5` with 2 places becomes `1,234.50`
+fn format_grouped(value: Decimal, places: u32) -> String {
+    let text = format!("{:.*}", places as usize, value.abs());
+    let (whole, fraction) = text.split_once('.').map_or((text.as_str(), None), |(whole, fraction)| (whole, Some(fraction)));
+
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push('.');
+        grouped.push_str(fraction);
+    }
+    if value.is_sign_negative() && !value.is_zero() {
+        grouped.insert(0, '-');
+    }
+    grouped
+}
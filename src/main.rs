@@ -1,9 +1,21 @@
 mod scanner;
+mod bundle;
 mod expr;
 mod stmt;
 mod parser;
 mod interpreter;
 mod environment;
+mod doc;
+mod typecheck;
+mod analysis;
+mod parallel;
+mod project;
+mod diagnostics;
+mod suggest;
+mod learn;
+#[cfg(feature = "threaded")]
+#[allow(dead_code)] // For hosts embedding the interpreter, the binary itself never needs it
+mod threaded;
 
 mod modules;
 mod types;
@@ -12,33 +24,82 @@ mod literal_value;
 use crate::scanner::*;
 use crate::parser::*;
 use crate::interpreter::*;
+use crate::stmt::LogLevel;
+use crate::doc::DocFormat;
 
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::thread;
+use std::time::Duration;
 use std::io::{self, BufRead, Write};
 
-fn run_file(path: &str) -> Result<(), String> {
-	let mut interpreter = Interpreter::new();
+fn run_file(interpreter: &mut Interpreter, path: &str, keep_going: bool) -> Result<(), String> {
 	match fs::read_to_string(path) {
 		Err(msg) => Err(msg.to_string()),
-		Ok(contents) => run(&mut interpreter, &contents),
+		Ok(contents) => {
+			run(interpreter, &contents, keep_going)?;
+			interpreter.run_event_loop()
+		},
 	}
 }
 
-fn run(interpreter: &mut Interpreter, contents: &str) -> Result<(), String> {
+// With `keep_going` a failing top-level statement is reported at the end instead of stopping the run
+fn run(interpreter: &mut Interpreter, contents: &str, keep_going: bool) -> Result<(), String> {
 	let mut scanner = Scanner::new(contents);
 	let tokens = scanner.scan_tokens()?;
 
 	let mut parser = Parser::new(tokens);
 	let stmts = parser.parse()?;
-	let _ = interpreter.interpret(stmts)?;
+	interpreter.check_redefinitions(&stmts)?;
+	interpreter.check_types(&stmts)?;
+	if !keep_going {
+		let _ = interpreter.interpret(stmts)?;
+		return Ok(());
+	}
+
+	match interpreter.interpret_all(stmts) {
+		Ok(()) => Ok(()),
+		Err(errors) if errors.len() == 1 => Err(errors[0].clone()),
+		Err(errors) => Err(format!("{} statements failed:\n{}", errors.len(), errors.join("\n"))),
+	}
+}
+
+// `--stream` reads the file as it runs, each statement running as soon as it is parsed, instead of
+// reading and parsing the whole file first
+fn stream_file(interpreter: &mut Interpreter, path: &str) -> Result<(), String> {
+	let file = fs::File::open(path).map_err(|e| e.to_string())?;
+	interpreter.interpret_stream(Parser::streaming(Scanner::from_reader(io::BufReader::new(file))))?;
+	interpreter.run_event_loop()
+}
+
+// `--explain` prints the inferred type of each top-level declaration instead of running the file
+fn explain_file(interpreter: &Interpreter, path: &str) -> Result<(), String> {
+	let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+	let tokens = Scanner::new(&contents).scan_tokens()?;
+	let stmts = Parser::new(tokens).parse()?;
 
+	for line in typecheck::explain(&stmts, &interpreter.environment().borrow()) {
+		println!("{}", line);
+	}
 	Ok(())
 }
 
-fn run_prompt() -> Result<(), String> {
-	let mut interpreter = Interpreter::new();
+// The REPL's `:type <expr>` command, using the current bindings for variable types
+fn type_command(interpreter: &Interpreter, source: &str) -> Result<String, String> {
+	let tokens = Scanner::new(source).scan_tokens()?;
+	let mut parser = Parser::new(tokens);
+	let expr = parser.expression()?;
+
+	let type_env = typecheck::TypeEnv::from_environment(&interpreter.environment().borrow());
+	Ok(format!("{} : {}", source.trim(), type_env.infer(&expr)))
+}
+
+fn run_prompt(interpreter: &mut Interpreter) -> Result<(), String> {
+	// Bindings from before each line, for `:undo`
+	let mut history = vec![];
+
 	loop {
 		print!("> ");
 		match io::stdout().flush() {
@@ -59,34 +120,397 @@ fn run_prompt() -> Result<(), String> {
 			Err(_) => return Err("Couldnt read line".to_string()),
 		}
 
+		if let Some(source) = buffer.trim().strip_prefix(":type") {
+			match type_command(interpreter, source) {
+				Ok(description) => println!("{}", description),
+				Err(msg) => println!("{}", msg),
+			}
+			continue;
+		}
+
+		if buffer.trim() == ":undo" {
+			match history.pop() {
+				Some(snapshot) => interpreter.environment().borrow_mut().restore(snapshot),
+				None => println!("Nothing to undo."),
+			}
+			continue;
+		}
+
 		println!("ECHO: {}", buffer);
-		match run(&mut interpreter, &buffer) {
+		history.push(interpreter.environment().borrow().snapshot());
+		match run(interpreter, &buffer, true) {
 			Ok(_) => (),
 			Err(msg) => println!("{}", msg),
 		}
 	}
 }
 
+// `recolon doc <script> [--html]` prints the script's API documentation
+fn run_doc(args: &[String]) -> Result<(), String> {
+	let format = if args.iter().any(|arg| arg == "--html") { DocFormat::Html } else { DocFormat::Markdown };
+	let path = match args.iter().find(|arg| !arg.starts_with("--")) {
+		Some(path) => path,
+		None => return Err("Usage: Recolon doc <script> [--html]".to_string()),
+	};
+
+	let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+	let tokens = Scanner::new(&contents).scan_tokens()?;
+	let stmts = Parser::new(tokens).parse()?;
+
+	let module_name = Path::new(path).file_stem().and_then(|stem| stem.to_str()).unwrap_or(path);
+	print!("{}", doc::render(module_name, &stmts, format));
+	Ok(())
+}
+
+// The `<script> [-o file]` arguments of `recolon bundle` and `recolon build`
+fn script_and_output<'a>(command: &str, args: &'a [String]) -> Result<(&'a String, Option<&'a String>), String> {
+	let usage = || format!("Usage: Recolon {} <script> [-o file]", command);
+	let mut script = None;
+	let mut output = None;
+
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		if arg == "-o" {
+			output = Some(args.next().ok_or_else(usage)?);
+		} else if script.is_none() {
+			script = Some(arg);
+		} else {
+			return Err(usage());
+		}
+	}
+	Ok((script.ok_or_else(usage)?, output))
+}
+
+// Imports are resolved like `recolon run` would, so a project's module roots and RCN_PATH count too
+fn project_roots() -> Result<Vec<PathBuf>, String> {
+	let cwd = env::current_dir().map_err(|e| e.to_string())?;
+	let mut roots = match project::find(&cwd) {
+		Some(manifest) => project::load(&manifest)?.roots,
+		None => vec![],
+	};
+	roots.extend(env_module_roots());
+	Ok(roots)
+}
+
+// RCN_PATH lists more directories for `import` to search, separated like PATH
+fn env_module_roots() -> Vec<PathBuf> {
+	env::var_os("RCN_PATH").map(|paths| env::split_paths(&paths).collect()).unwrap_or_default()
+}
+
+// RCN_FLAGS holds flags applied to every run, e.g. `--strict --timeout=60` in CI.
+// They come before the command line's own, so a later --log-level there still wins.
+// NO_COLOR needs nothing here: `colored` leaves output plain when it is set or stdout isn't a terminal.
+fn env_flags() -> Vec<String> {
+	env::var("RCN_FLAGS").map(|flags| flags.split_whitespace().map(String::from).collect()).unwrap_or_default()
+}
+
+// `recolon bundle <script> [-o file]` writes the script with the modules it imports inlined,
+// to stdout unless a file is given
+fn run_bundle(args: &[String]) -> Result<(), String> {
+	let (script, output) = script_and_output("bundle", args)?;
+	let bundled = bundle::bundle(Path::new(script), &project_roots()?)?;
+	match output {
+		Some(path) => fs::write(path, bundled).map_err(|e| format!("Could not write '{}': {}", path, e)),
+		None => {
+			print!("{}", bundled);
+			Ok(())
+		}
+	}
+}
+
+// `recolon build <script> [-o file]` makes an executable that runs the script, named after it by default
+fn run_build(args: &[String]) -> Result<(), String> {
+	let (script, output) = script_and_output("build", args)?;
+	let output = match output {
+		Some(output) => PathBuf::from(output),
+		None => Path::new(script).with_extension(env::consts::EXE_EXTENSION),
+	};
+	if output == Path::new(script) {
+		return Err(format!("Building '{}' would overwrite the script; pass -o to name the executable.", script));
+	}
+
+	bundle::build(Path::new(script), &project_roots()?, &output)?;
+	println!("Built {}", output.display());
+	Ok(())
+}
+
+// `recolon explain <code>` describes an error code with examples, `recolon explain` lists the codes
+fn run_explain(args: &[String]) -> Result<(), String> {
+	let Some(code) = args.first() else {
+		let mut listed: Vec<_> = diagnostics::DIAGNOSTICS.iter().collect();
+		listed.sort_by_key(|diagnostic| diagnostic.code);
+		for diagnostic in listed {
+			println!("{}  {}", diagnostic.code, diagnostic.title);
+		}
+		return Ok(());
+	};
+
+	let diagnostic = diagnostics::find(code).ok_or_else(|| format!("Unknown error code '{}'; run `recolon explain` to list them.", code))?;
+	println!("{}: {}\n\n{}", diagnostic.code, diagnostic.title, diagnostic.explanation);
+	Ok(())
+}
+
+// `recolon learn` runs every lesson and lists them, `recolon learn <n>` shows lesson n with its
+// output, and `recolon learn <n> <script>` checks the learner's script prints the same
+fn run_learn(args: &[String]) -> Result<(), String> {
+	colored::control::set_override(false);
+	let Some(number) = args.first() else {
+		for (i, lesson) in learn::LESSONS.iter().enumerate() {
+			let status = match learn::run(lesson.source) {
+				Ok(output) if learn::matches(lesson, &output) => "ok",
+				_ => "FAILED",
+			};
+			println!("{:>2}. {:<24} {}", i + 1, lesson.title, status);
+		}
+		println!("\nRun `recolon learn <number>` to read a lesson.");
+		return Ok(());
+	};
+
+	let count = learn::LESSONS.len();
+	let lesson = number.parse::<usize>().ok().filter(|n| (1..=count).contains(n)).map(|n| &learn::LESSONS[n - 1])
+		.ok_or_else(|| format!("There are lessons 1 to {}, not '{}'.", count, number))?;
+
+	match args.get(1) {
+		None => {
+			println!("Lesson {}: {}\n\n{}", number, lesson.title, lesson.source.trim_end());
+			println!("\nOutput:\n{}", learn::run(lesson.source)?.trim_end());
+			println!("\nCopy it, change it, and check your version with `recolon learn {} <script>`.", number);
+			Ok(())
+		},
+		Some(script) => {
+			let source = fs::read_to_string(script).map_err(|e| format!("Failed to read '{}': {}", script, e))?;
+			let output = learn::run(&source)?;
+			if !learn::matches(lesson, &output) {
+				return Err(format!("'{}' printed:\n{}\n\nLesson {} expects:\n{}", script, output.trim_end(), number, lesson.expected.trim_end()));
+			}
+			println!("'{}' prints what lesson {} expects.", script, number);
+			Ok(())
+		},
+	}
+}
+
+// `recolon run [flags]` runs the entry script of the closest project.rcn
+fn find_project() -> Result<project::Project, String> {
+	let cwd = env::current_dir().map_err(|e| e.to_string())?;
+	match project::find(&cwd) {
+		Some(manifest) => project::load(&manifest),
+		None => Err(format!("No {} found in {} or its parents.", project::MANIFEST, cwd.display())),
+	}
+}
+
 fn main() {
-	let args: Vec<String> = env::args().collect();
+	let mut args: Vec<String> = env::args().skip(1).collect();
+
+	// An executable made by `recolon build` runs its script like `-e` would, and all its arguments
+	// are the script's. Otherwise the script gets the ones after `--`.
+	let embedded = bundle::embedded_script();
+	let script_args = if embedded.is_some() {
+		std::mem::take(&mut args)
+	} else if let Some(dashes) = args.iter().position(|arg| arg == "--") {
+		let script_args = args.split_off(dashes + 1);
+		args.pop();
+		script_args
+	} else {
+		vec![]
+	};
+
+	if args.first().is_some_and(|arg| arg == "doc") {
+		if let Err(msg) = run_doc(&args[1..]) {
+			println!("ERROR:\n{}", msg);
+			exit(64);
+		}
+		return;
+	}
+	if args.first().is_some_and(|arg| arg == "bundle") {
+		if let Err(msg) = run_bundle(&args[1..]) {
+			println!("ERROR:\n{}", msg);
+			exit(64);
+		}
+		return;
+	}
+	if args.first().is_some_and(|arg| arg == "explain") {
+		if let Err(msg) = run_explain(&args[1..]) {
+			println!("ERROR:\n{}", msg);
+			exit(64);
+		}
+		return;
+	}
+	if args.first().is_some_and(|arg| arg == "learn") {
+		if let Err(msg) = run_learn(&args[1..]) {
+			println!("ERROR:\n{}", msg);
+			exit(64);
+		}
+		return;
+	}
+	if args.first().is_some_and(|arg| arg == "build") {
+		if let Err(msg) = run_build(&args[1..]) {
+			println!("ERROR:\n{}", msg);
+			exit(64);
+		}
+		return;
+	}
 
-	if args.len() > 2 {
-		println!("Usage: Recolon [script]");
+	let mut module_roots = vec![];
+	if args.first().is_some_and(|arg| arg == "run") {
+		let project = match find_project() {
+			Ok(project) => project,
+			Err(msg) => {
+				println!("ERROR:\n{}", msg);
+				exit(64);
+			}
+		};
+		if args.iter().any(|arg| arg == "--version") {
+			println!("{} {}", project.name.as_deref().unwrap_or("(unnamed)"), project.version.as_deref().unwrap_or("(no version)"));
+			return;
+		}
+
+		args.remove(0);
+		args.push(project.entry.display().to_string());
+		module_roots = project.roots;
+	}
+	module_roots.extend(env_module_roots());
+	args.splice(0..0, env_flags());
+
+	let mut log_level = LogLevel::from_env();
+	let mut allow_err = false;
+	let mut explain = false;
+	let mut check = false;
+	let mut keep_going = false;
+	let mut strict = false;
+	let mut sandbox = false;
+	let mut stream = false;
+	let mut timeout = None;
+	let mut output = None;
+	let mut program = embedded;
+	let mut scripts = vec![];
+
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		if let Some(name) = arg.strip_prefix("--log-level=") {
+			match LogLevel::from_name(name) {
+				Some(level) => log_level = level,
+				None => {
+					println!("Unknown log level '{}', expected debug, info or warn.", name);
+					exit(64);
+				}
+			}
+		} else if arg == "-v" || arg == "--verbose" {
+			log_level = LogLevel::Debug;
+		} else if arg == "-q" || arg == "--quiet" {
+			log_level = LogLevel::Warn;
+		} else if arg == "--allow-err" {
+			allow_err = true;
+		} else if arg == "--explain" {
+			explain = true;
+		} else if arg == "--check" {
+			check = true;
+		} else if arg == "--keep-going" {
+			keep_going = true;
+		} else if arg == "--strict" {
+			strict = true;
+		} else if arg == "--sandbox" {
+			sandbox = true;
+		} else if let Some(seconds) = arg.strip_prefix("--timeout=") {
+			match seconds.parse::<f64>() {
+				Ok(seconds) if seconds > 0.0 => timeout = Some(Duration::from_secs_f64(seconds)),
+				_ => {
+					println!("Invalid timeout '{}', expected a number of seconds.", seconds);
+					exit(64);
+				}
+			}
+		} else if let Some(path) = arg.strip_prefix("--output=") {
+			output = Some(path.to_string());
+		} else if arg == "--stream" {
+			stream = true;
+		} else if arg == "-e" {
+			// `-e 'program'` runs the given source, e.g. as a filter in a shell pipeline
+			match args.next() {
+				Some(source) => program = Some(source),
+				None => {
+					println!("-e expects the program to run.");
+					exit(64);
+				}
+			}
+		} else {
+			scripts.push(arg);
+		}
+	}
+
+	if scripts.len() > 1 || (program.is_some() && !scripts.is_empty()) {
+		println!("Usage: Recolon [--log-level=debug|info|warn] [-v] [-q] [--allow-err] [--explain] [--check] [--keep-going] [--strict] [--sandbox] [--stream] [--timeout=seconds] [--output=file] [script | -e program | run] [-- args]");
 		exit(64);
-	} else if args.len() == 2 {
-		match run_file(&args[1]) {
-			Ok(_) => (),
-			Err(msg) => println!("ERROR:\n{}", msg),
+	}
+
+	let mut interpreter = Interpreter::new();
+	if let [script] = scripts.as_slice() {
+		interpreter.set_script_path(script);
+	}
+	for root in module_roots {
+		interpreter.add_module_root(root);
+	}
+	interpreter.set_log_level(log_level);
+	interpreter.set_strict(strict);
+	interpreter.set_check(check);
+	interpreter.set_sandbox(sandbox);
+	interpreter.set_args(script_args);
+
+	// `--output` writes what the script logs and prints to a file instead of the terminal
+	if let Some(path) = output {
+		match fs::File::create(&path) {
+			Ok(file) => interpreter.set_output(file),
+			Err(msg) => {
+				println!("Could not create output file '{}': {}", path, msg);
+				exit(64);
+			}
 		}
+	}
+
+	// `--timeout` stops a script that runs too long, e.g. one stuck in a loop
+	if let Some(timeout) = timeout {
+		let handle = interpreter.interrupt_handle();
+		thread::spawn(move || {
+			thread::sleep(timeout);
+			handle.interrupt();
+		});
+	}
+
+	let result = if let Some(source) = &program {
+		run(&mut interpreter, source, keep_going).and_then(|_| interpreter.run_event_loop())
+	} else if explain && scripts.len() == 1 {
+		explain_file(&interpreter, &scripts[0])
+	} else if stream && scripts.len() == 1 {
+		stream_file(&mut interpreter, &scripts[0])
+	} else if scripts.len() == 1 {
+		run_file(&mut interpreter, &scripts[0], keep_going)
 	} else {
-		match run_prompt() {
-			Ok(_) => (),
-			Err(msg) => println!("ERROR:\n{}", msg),
+		run_prompt(&mut interpreter)
+	};
+
+	// os.on_exit callbacks run however the script ended; an error from the script itself is the one reported
+	let result = result.and(interpreter.run_exit_hooks());
+
+	// os.exit sets the status itself. Otherwise runtime errors and `fatal` exit with 70,
+	// a reported `err` with 1 unless --allow-err is given
+	let status = match (result, interpreter.exit_code()) {
+		(_, Some(code)) => code,
+		(Ok(_), None) if interpreter.error_reported() && !allow_err => 1,
+		(Ok(_), None) => 0,
+		(Err(msg), None) => {
+			let diagnostic = interpreter.diagnostic(&msg);
+			println!("ERROR[{}]:\n{}", diagnostic.code, msg);
+			if diagnostic.code != diagnostics::OTHER.code {
+				println!("For more about this error, run `recolon explain {}`.", diagnostic.code);
+			}
+			70
 		}
+	};
+
+	// Wait for user input before closing, unless the program came from `-e` and its output may be piped on
+	if program.is_none() {
+		println!("Press Enter to exit...");
+		let _ = io::stdout().flush();
+		io::stdin().read_line(&mut String::new()).unwrap();
 	}
 
-	// Wait for user input before closing
-	println!("Press Enter to exit...");
-	let _ = io::stdout().flush();
-	io::stdin().read_line(&mut String::new()).unwrap();
+	exit(status);
 }
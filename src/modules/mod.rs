@@ -1,3 +1,134 @@
+use std::cell::RefCell;
+
+use crate::environment::Environment;
+use crate::expr::Expr;
+use crate::scanner::{Span, Token, TokenType};
+
 pub mod rcn_std;
 pub mod rcn_io;
-pub mod rcn_math;
\ No newline at end of file
+pub mod rcn_math;
+pub mod rcn_template;
+pub mod rcn_debug;
+pub mod rcn_data;
+pub mod rcn_term;
+pub mod rcn_clipboard;
+pub mod rcn_fs;
+pub mod rcn_archive;
+pub mod rcn_compress;
+pub mod rcn_http;
+pub mod rcn_mail;
+pub mod rcn_net;
+pub mod rcn_notify;
+pub mod rcn_stats;
+pub mod rcn_os;
+pub mod rcn_i18n;
+pub mod rcn_html;
+pub mod rcn_msgpack;
+pub mod rcn_serial;
+
+// Modules that reach outside the script: files, the network, other processes and the clipboard.
+// A sandboxed run can use every other module.
+const OUTSIDE_MODULES: &[&str] = &["archive", "clipboard", "fs", "http", "i18n", "io", "mail", "net", "notify", "os"];
+
+// The error for calling `module.name` from a sandboxed scope, if the module isn't allowed there
+pub(crate) fn check_sandbox(module: &str, name: &str, environment: &RefCell<Environment>) -> Result<(), String> {
+    if OUTSIDE_MODULES.contains(&module) && environment.borrow().sandboxed() {
+        return Err(format!("{}.{} is not available in a sandbox.", module, name));
+    }
+    Ok(())
+}
+
+// What `module.name` can name in each built-in module, to suggest the closest for a typo
+pub(crate) fn module_functions(module: &str) -> &'static [&'static str] {
+    match module {
+        "archive" => rcn_archive::FUNCTIONS,
+        "clipboard" => rcn_clipboard::FUNCTIONS,
+        "compress" => rcn_compress::FUNCTIONS,
+        "debug" => rcn_debug::FUNCTIONS,
+        "fs" => rcn_fs::FUNCTIONS,
+        "html" => rcn_html::FUNCTIONS,
+        "http" => rcn_http::FUNCTIONS,
+        "i18n" => rcn_i18n::FUNCTIONS,
+        "io" => rcn_io::FUNCTIONS,
+        "mail" => rcn_mail::FUNCTIONS,
+        "math" => rcn_math::FUNCTIONS,
+        "msgpack" => rcn_msgpack::FUNCTIONS,
+        "net" => rcn_net::FUNCTIONS,
+        "notify" => rcn_notify::FUNCTIONS,
+        "os" => rcn_os::FUNCTIONS,
+        "stats" => rcn_stats::FUNCTIONS,
+        "template" => rcn_template::FUNCTIONS,
+        "term" => rcn_term::FUNCTIONS,
+        _ => &[],
+    }
+}
+
+// Modules whose names the parser always reads as the module; the names of the others may still be
+// used for variables, which then hide the module
+pub(crate) const RESERVED_MODULES: &[&str] = &["io", "math"];
+
+// Functions that need the run's state, like the output or the event loop, are natives the
+// interpreter defines under a dotted name such as `io.capture`, which scripts cannot spell
+pub(crate) fn interpreter_native(module: &str, name: &str) -> Expr {
+    Expr::Variable {
+        name: Token {
+            token_type: TokenType::Identifier,
+            lexeme: format!("{}.{}", module, name),
+            literal: None,
+            line_number: 0,
+            column: 0,
+        },
+        span: Span::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::stmt::Stmt;
+
+    const MODULES: &[&str] = &["archive", "clipboard", "compress", "debug", "fs", "html", "http", "i18n", "io", "mail", "math", "msgpack", "net", "notify", "os", "stats", "template", "term"];
+
+    #[test]
+    fn listed_functions_are_the_ones_modules_accept() {
+        for &module in MODULES {
+            assert!(!module_functions(module).is_empty(), "{} lists no functions", module);
+            for name in module_functions(module) {
+                let tokens = Scanner::new(&format!("{}.{};", module, name)).scan_tokens().unwrap();
+                let msg = match Parser::new(tokens).parse() {
+                    Ok(statements) => match &statements[0] {
+                        Stmt::Expression { expression: Expr::Module { function: Err(msg), .. }, .. } => msg.clone(),
+                        _ => continue,
+                    },
+                    Err(msg) => msg,
+                };
+                assert!(!msg.contains("has no function"), "{}", msg);
+            }
+        }
+    }
+
+    // The other direction: every name a module's `check_type` matches on is in its list
+    #[test]
+    fn accepted_functions_are_listed() {
+        for &module in MODULES {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/modules").join(format!("rcn_{}.rs", module));
+            let source = fs::read_to_string(path).unwrap();
+            let start = source.find("pub fn check_type").unwrap_or_else(|| panic!("rcn_{} has no check_type", module));
+            let end = source[start..].find("\n}\n").map_or(source.len(), |end| start + end);
+            let mut accepted = 0;
+            for line in source[start..end].lines() {
+                let Some((pattern, _)) = line.split_once("=>") else { continue };
+                for name in pattern.split('|').filter_map(|name| name.trim().strip_prefix('"')?.strip_suffix('"')) {
+                    assert!(module_functions(module).contains(&name), "{}.{} is accepted but not listed", module, name);
+                    accepted += 1;
+                }
+            }
+            assert!(accepted > 0, "found no names in rcn_{}'s check_type", module);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::expr::Expr;
+use crate::literal_value::LiteralValue;
+use crate::modules::rcn_fs::native_path;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["zip", "unzip"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "zip" | "unzip" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            let source = parser.expression()?; // Parse the paths to pack, or the archive to unpack
+            parser.consume(TokenType::Comma, "Expected ',' after first argument")?;
+            let target = parser.expression()?; // Parse the archive to write, or the destination directory
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(fn_archive(&identifier, source, target))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_archive(name: &str, source: Expr, target: Expr) -> Expr {
+    Expr::PreFunction {
+        module: "archive".to_string(),
+        name: name.to_string(),
+        args: vec![source, target],
+        span: Span::default(),
+    }
+}
+
+fn string_arg<'a>(args: &'a [LiteralValue], index: usize, what: &str) -> Result<&'a str, String> {
+    match args.get(index) {
+        Some(LiteralValue::StringValue(text)) => Ok(text),
+        _ => Err(format!("{} must be a string.", what)),
+    }
+}
+
+// Packs files and directories (recursively) into a new zip file, returning the entry names
+pub fn zip(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let paths: Vec<&str> = match args.first() {
+        Some(LiteralValue::StringValue(path)) => vec![path],
+        Some(LiteralValue::Array(paths)) => paths.iter().map(|path| match path {
+            LiteralValue::StringValue(path) => Ok(path.as_str()),
+            other => Err(format!("archive.zip paths must be strings, got {}.", other.to_type())),
+        }).collect::<Result<_, _>>()?,
+        _ => return Err("archive.zip expects a path or an array of paths.".to_string()),
+    };
+    let out = string_arg(&args, 1, "Archive path")?;
+
+    let file = File::create(native_path(out)).map_err(|e| format!("Could not create '{}': {}", out, e))?;
+    let mut writer = ZipWriter::new(file);
+    let mut entries = vec![];
+    for path in paths {
+        add_to_zip(&mut writer, &native_path(path), &mut entries).map_err(|e| format!("Could not add '{}' to '{}': {}", path, out, e))?;
+    }
+    writer.finish().map_err(|e| format!("Could not write '{}': {}", out, e))?;
+
+    Ok(LiteralValue::Array(entries.into_iter().map(LiteralValue::StringValue).collect()))
+}
+
+fn add_to_zip(writer: &mut ZipWriter<File>, path: &Path, entries: &mut Vec<String>) -> io::Result<()> {
+    // Entries keep the path as given, without a leading `/` or `./`
+    let name = path.to_string_lossy().replace('\\', "/");
+    let name = name.trim_start_matches("./").trim_start_matches('/').to_string();
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    if path.is_dir() {
+        writer.add_directory(format!("{}/", name.trim_end_matches('/')), options)?;
+        let mut children: Vec<_> = fs::read_dir(path)?.flatten().map(|entry| entry.path()).collect();
+        children.sort();
+        for child in children {
+            add_to_zip(writer, &child, entries)?;
+        }
+    } else {
+        writer.start_file(name.as_str(), options)?;
+        io::copy(&mut File::open(path)?, writer)?;
+        entries.push(name);
+    }
+    Ok(())
+}
+
+// Extracts every file into `dest`, returning the extracted paths. Entries that would land
+// outside `dest`, like `../x`, are refused.
+pub fn unzip(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let archive_path = string_arg(&args, 0, "Archive path")?;
+    let dest = native_path(string_arg(&args, 1, "Destination directory")?);
+
+    let file = File::open(native_path(archive_path)).map_err(|e| format!("Could not open '{}': {}", archive_path, e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("'{}' is not a zip file: {}", archive_path, e))?;
+
+    let mut extracted = vec![];
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        if entry.is_file() {
+            match entry.enclosed_name() {
+                Some(name) => extracted.push(dest.join(name).to_string_lossy().into_owned()),
+                None => return Err(format!("'{}' has an entry outside the destination: {}", archive_path, entry.name())),
+            }
+        }
+    }
+    archive.extract(&dest).map_err(|e| format!("Could not extract '{}': {}", archive_path, e))?;
+
+    Ok(LiteralValue::Array(extracted.into_iter().map(LiteralValue::StringValue).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zipped_directories_unzip_to_the_same_files() {
+        let root = std::env::temp_dir().join("recolon_archive");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("site/css")).unwrap();
+        fs::write(root.join("site/index.html"), "<h1>hi</h1>").unwrap();
+        fs::write(root.join("site/css/main.css"), "h1 {}").unwrap();
+
+        let site = root.join("site").display().to_string();
+        // Windows separators work everywhere
+        let out = format!("{}\\site.zip", root.display());
+        let added = zip(vec![LiteralValue::StringValue(site.clone()), LiteralValue::StringValue(out.clone())]).unwrap();
+        let LiteralValue::Array(added) = added else { panic!("Expected the added entries") };
+        assert_eq!(added.len(), 2);
+
+        let dest = format!("{}\\restored", root.display());
+        unzip(vec![LiteralValue::StringValue(out), LiteralValue::StringValue(dest)]).unwrap();
+        let restored = root.join("restored").join(site.trim_start_matches('/')).join("css/main.css");
+        assert_eq!(fs::read_to_string(restored).unwrap(), "h1 {}");
+        let _ = fs::remove_dir_all(root);
+    }
+}
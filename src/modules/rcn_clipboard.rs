@@ -0,0 +1,81 @@
+use crate::expr::Expr;
+use crate::literal_value::LiteralValue;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["get", "set"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "get" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'get'")?;
+            parser.consume(TokenType::RightParen, "Expected ')' after '('")?;
+
+            Ok(fn_clipboard("get", vec![]))
+        },
+        "set" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'set'")?;
+            let text = parser.expression()?; // Parse the text to copy
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(fn_clipboard("set", vec![text]))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_clipboard(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::PreFunction {
+        module: "clipboard".to_string(),
+        name: name.to_string(),
+        args,
+        span: Span::default(),
+    }
+}
+
+pub fn get() -> Result<LiteralValue, String> {
+    Ok(LiteralValue::StringValue(backend::get_text()?))
+}
+
+pub fn set(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let text = match args.first() {
+        Some(LiteralValue::StringValue(text)) => text.clone(),
+        Some(value) if value.is_compound() => value.to_pretty_string(true),
+        Some(value) => value.to_string(),
+        None => return Err("clipboard.set requires the text to copy.".to_string()),
+    };
+
+    backend::set_text(text)?;
+    Ok(LiteralValue::Nil)
+}
+
+// The system clipboard pulls in platform crates, so it is only built with the `clipboard` feature
+#[cfg(feature = "clipboard")]
+mod backend {
+    use arboard::Clipboard;
+
+    fn open() -> Result<Clipboard, String> {
+        Clipboard::new().map_err(|e| format!("Clipboard is not available: {}", e))
+    }
+
+    pub fn get_text() -> Result<String, String> {
+        open()?.get_text().map_err(|e| format!("Could not read the clipboard: {}", e))
+    }
+
+    pub fn set_text(text: String) -> Result<(), String> {
+        open()?.set_text(text).map_err(|e| format!("Could not write the clipboard: {}", e))
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+mod backend {
+    const UNAVAILABLE: &str = "The clipboard module is not available in this build; enable the 'clipboard' feature.";
+
+    pub fn get_text() -> Result<String, String> {
+        Err(UNAVAILABLE.to_string())
+    }
+
+    pub fn set_text(_text: String) -> Result<(), String> {
+        Err(UNAVAILABLE.to_string())
+    }
+}
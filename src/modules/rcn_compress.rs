@@ -0,0 +1,91 @@
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::expr::Expr;
+use crate::literal_value::LiteralValue;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["gzip", "gunzip"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "gzip" | "gunzip" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            let data = parser.expression()?; // Parse the string or byte array
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(fn_compress(&identifier, data))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_compress(name: &str, data: Expr) -> Expr {
+    Expr::PreFunction {
+        module: "compress".to_string(),
+        name: name.to_string(),
+        args: vec![data],
+        span: Span::default(),
+    }
+}
+
+// Bytes of a string, or of an array of numbers from 0 to 255
+pub(crate) fn bytes_arg(args: &[LiteralValue], function: &str) -> Result<Vec<u8>, String> {
+    match args.first() {
+        Some(LiteralValue::StringValue(text)) => Ok(text.as_bytes().to_vec()),
+        Some(LiteralValue::Array(values)) => values.iter().map(|value| match value {
+            LiteralValue::Int(n) if (0..=255).contains(n) => Ok(*n as u8),
+            other => Err(format!("{} byte arrays may only hold whole numbers from 0 to 255, got {}.", function, other)),
+        }).collect(),
+        _ => Err(format!("{} expects a string or an array of bytes.", function)),
+    }
+}
+
+pub(crate) fn byte_array(bytes: Vec<u8>) -> LiteralValue {
+    LiteralValue::Array(bytes.into_iter().map(|byte| LiteralValue::Int(byte as i64)).collect())
+}
+
+// Compressed data is binary, so it comes back as an array of bytes
+pub fn gzip(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let data = bytes_arg(&args, "gzip")?;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&data).map_err(|e| format!("gzip failed: {}", e))?;
+    let compressed = encoder.finish().map_err(|e| format!("gzip failed: {}", e))?;
+    Ok(byte_array(compressed))
+}
+
+// Text comes back as a string, anything that isn't valid UTF-8 as an array of bytes
+pub fn gunzip(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let data = bytes_arg(&args, "gunzip")?;
+    let mut decompressed = vec![];
+    GzDecoder::new(data.as_slice()).read_to_end(&mut decompressed).map_err(|e| format!("gunzip failed: {}", e))?;
+
+    match String::from_utf8(decompressed) {
+        Ok(text) => Ok(LiteralValue::StringValue(text)),
+        Err(e) => Ok(byte_array(e.into_bytes())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gzip_round_trips_text_and_bytes() {
+        let text = LiteralValue::StringValue("hello ".repeat(100));
+        let compressed = gzip(vec![text.clone()]).unwrap();
+        let LiteralValue::Array(bytes) = &compressed else { panic!("Expected a byte array") };
+        assert!(bytes.len() < 100);
+        assert_eq!(gunzip(vec![compressed]).unwrap(), text);
+
+        let binary = byte_array(vec![0, 159, 255]);
+        assert_eq!(gunzip(vec![gzip(vec![binary.clone()]).unwrap()]).unwrap(), binary);
+
+        assert!(gunzip(vec![LiteralValue::StringValue("not gzip".to_string())]).is_err());
+        assert!(gzip(vec![LiteralValue::Array(vec![LiteralValue::Int(256)])]).is_err());
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use colored::Colorize;
+
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::modules::rcn_fs::native_path;
+
+// Load a `.json` or `.csv` file as plain values; other extensions are left to the module loader
+pub fn load_data_file(path: &str) -> Result<Option<LiteralValue>, String> {
+    let extension = Path::new(path).extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase());
+    let parse: fn(&str) -> Result<LiteralValue, String> = match extension.as_deref() {
+        Some("json") => parse_json,
+        Some("csv") => parse_csv,
+        _ => return Ok(None),
+    };
+
+    let contents = fs::read_to_string(native_path(path)).map_err(|e| format!("Failed to load data file '{}': {}", path, e))?;
+    parse(&contents).map(Some).map_err(|e| format!("Failed to parse '{}': {}", path, e))
+}
+
+pub fn parse_json(text: &str) -> Result<LiteralValue, String> {
+    let mut parser = JsonParser { chars: text.chars().collect(), current: 0 };
+    let value = parser.value()?;
+
+    parser.skip_whitespace();
+    if parser.current < parser.chars.len() {
+        return Err(format!("Unexpected '{}' after JSON value", parser.chars[parser.current]));
+    }
+    Ok(value)
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    current: usize,
+}
+
+impl JsonParser {
+    fn value(&mut self) -> Result<LiteralValue, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(LiteralValue::StringValue(self.string()?)),
+            Some('t') => self.keyword("true", LiteralValue::True),
+            Some('f') => self.keyword("false", LiteralValue::False),
+            Some('n') => self.keyword("null", LiteralValue::Nil),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("Unexpected '{}' in JSON", c)),
+            None => Err("Unexpected end of JSON".to_string()),
+        }
+    }
+
+    fn object(&mut self) -> Result<LiteralValue, String> {
+        self.expect('{')?;
+        let mut entries = HashMap::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.current += 1;
+            return Ok(LiteralValue::Map(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = MapKey::String(self.string()?);
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value()?;
+            entries.insert(key, value);
+
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => continue,
+                Some('}') => return Ok(LiteralValue::Map(entries)),
+                _ => return Err("Expected ',' or '}' in JSON object".to_string()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<LiteralValue, String> {
+        self.expect('[')?;
+        let mut elements = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.current += 1;
+            return Ok(LiteralValue::Array(elements));
+        }
+
+        loop {
+            elements.push(self.value()?);
+
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => continue,
+                Some(']') => return Ok(LiteralValue::Array(elements)),
+                _ => return Err("Expected ',' or ']' in JSON array".to_string()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.advance() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('r') => text.push('\r'),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.advance()).collect();
+                        let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("Invalid escape '\\u{}'", hex))?;
+                        text.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    Some(c) => text.push(c),
+                    None => return Err("Unterminated JSON string".to_string()),
+                },
+                Some(c) => text.push(c),
+                None => return Err("Unterminated JSON string".to_string()),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<LiteralValue, String> {
+        let start = self.current;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                self.current += 1;
+            } else {
+                break;
+            }
+        }
+
+        let text: String = self.chars[start..self.current].iter().collect();
+        LiteralValue::parse_number(&text).ok_or_else(|| format!("Invalid number '{}' in JSON", text))
+    }
+
+    fn keyword(&mut self, word: &str, value: LiteralValue) -> Result<LiteralValue, String> {
+        for expected in word.chars() {
+            if self.advance() != Some(expected) {
+                return Err(format!("Expected '{}' in JSON", word));
+            }
+        }
+        Ok(value)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.advance() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("Expected '{}' but found '{}' in JSON", expected, c)),
+            None => Err(format!("Expected '{}' but reached the end of JSON", expected)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.current += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.current).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.current += 1;
+        c
+    }
+}
+
+// The first row names the columns; every other row becomes a map from column name to cell.
+// Cells that look like numbers are loaded as numbers.
+pub fn parse_csv(text: &str) -> Result<LiteralValue, String> {
+    let mut rows = parse_csv_rows(text)?.into_iter();
+    let header = match rows.next() {
+        Some(header) => header,
+        None => return Ok(LiteralValue::Array(vec![])),
+    };
+
+    let mut records = vec![];
+    for (line, row) in rows.enumerate() {
+        if row.len() != header.len() {
+            return Err(format!("Row {} has {} columns, expected {}", line + 2, row.len(), header.len()));
+        }
+
+        let record = header.iter().map(|column| MapKey::from(column.as_str())).zip(row.into_iter().map(|cell| {
+            LiteralValue::parse_number(cell.trim()).unwrap_or(LiteralValue::StringValue(cell))
+        })).collect();
+        records.push(LiteralValue::Map(record));
+    }
+
+    Ok(LiteralValue::Array(records))
+}
+
+// Split CSV text into rows of cells, honouring double-quoted cells with "" escapes and embedded newlines
+fn parse_csv_rows(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => row.push(std::mem::take(&mut cell)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut cell));
+                // Blank lines are skipped
+                if row.len() == 1 && row[0].is_empty() {
+                    row.clear();
+                } else {
+                    rows.push(std::mem::take(&mut row));
+                }
+            }
+            _ => cell.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err("Unterminated quoted cell".to_string());
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+// Pretty JSON with two-space indentation and sorted keys, for `log_json`
+pub fn to_json(value: &LiteralValue) -> Result<String, String> {
+    let mut output = String::new();
+    write_json(value, &mut output, 0, &mut vec![])?;
+    Ok(output)
+}
+
+fn write_json(value: &LiteralValue, output: &mut String, depth: usize, seen: &mut Vec<usize>) -> Result<(), String> {
+    let indent = "  ".repeat(depth + 1);
+    let closing_indent = "  ".repeat(depth);
+
+    let scalar = match value {
+        LiteralValue::Nil => Some("null".to_string()),
+        LiteralValue::True | LiteralValue::False | LiteralValue::Int(_) | LiteralValue::BigInt(_) | LiteralValue::Float(_) | LiteralValue::Decimal(_) => Some(value.to_string()),
+        LiteralValue::StringValue(s) => Some(json_string(s)),
+        LiteralValue::Array(elements) if elements.is_empty() => Some("[]".to_string()),
+        _ => None,
+    };
+    if let Some(text) = scalar {
+        output.push_str(&text);
+        return Ok(());
+    }
+
+    // Maps and struct instances both become objects
+    let entries: Vec<(String, &LiteralValue)> = match value {
+        LiteralValue::Array(elements) => {
+            output.push_str("[\n");
+            for (i, element) in elements.iter().enumerate() {
+                output.push_str(&indent);
+                write_json(element, output, depth + 1, seen)?;
+                output.push_str(if i + 1 < elements.len() { ",\n" } else { "\n" });
+            }
+            output.push_str(&closing_indent);
+            output.push(']');
+            return Ok(());
+        }
+        LiteralValue::Map(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            entries.into_iter().map(|(key, value)| (key.to_value().to_string(), value)).collect()
+        }
+        LiteralValue::StructInst(instance) => {
+            let pointer = Rc::as_ptr(instance) as usize;
+            if seen.contains(&pointer) {
+                return Err("Cannot convert a cyclic value to JSON.".to_string());
+            }
+            seen.push(pointer);
+            let instance = instance.borrow();
+            let mut fields: Vec<_> = instance.fields.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            write_json_object(fields.iter().map(|(name, value)| (name.clone(), value)).collect(), output, depth, seen)?;
+            seen.pop();
+            return Ok(());
+        }
+        other => return Err(format!("Cannot convert {} to JSON.", other.to_type())),
+    };
+    write_json_object(entries, output, depth, seen)
+}
+
+fn write_json_object(entries: Vec<(String, &LiteralValue)>, output: &mut String, depth: usize, seen: &mut Vec<usize>) -> Result<(), String> {
+    if entries.is_empty() {
+        output.push_str("{}");
+        return Ok(());
+    }
+
+    let indent = "  ".repeat(depth + 1);
+    output.push_str("{\n");
+    for (i, (key, value)) in entries.iter().enumerate() {
+        output.push_str(&format!("{}{}: ", indent, json_string(key)));
+        write_json(value, output, depth + 1, seen)?;
+        output.push_str(if i + 1 < entries.len() { ",\n" } else { "\n" });
+    }
+    output.push_str(&"  ".repeat(depth));
+    output.push('}');
+    Ok(())
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// An aligned ASCII table, for `log_table`. Rows are maps or struct instances and every key
+// becomes a column; a single map is shown as key/value pairs.
+pub fn render_table(value: &LiteralValue) -> Result<String, String> {
+    let rows: Vec<Vec<(String, LiteralValue)>> = match value {
+        LiteralValue::Array(rows) => rows.iter().map(table_row).collect::<Result<_, _>>()?,
+        LiteralValue::Map(_) => table_row(value)?.into_iter()
+            .map(|(key, value)| vec![("key".to_string(), LiteralValue::StringValue(key)), ("value".to_string(), value)])
+            .collect(),
+        other => return Err(format!("log_table expects an array of maps or a map, got {}.", other.to_type())),
+    };
+
+    // Columns in the order they first appear
+    let mut columns: Vec<String> = vec![];
+    for row in &rows {
+        for (key, _) in row {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+
+    let cells: Vec<Vec<(String, bool)>> = rows.iter().map(|row| columns.iter().map(|column| {
+        match row.iter().find(|(key, _)| key == column).map(|(_, value)| value) {
+            None => (String::new(), false),
+            Some(value @ (LiteralValue::Int(_) | LiteralValue::BigInt(_) | LiteralValue::Float(_) | LiteralValue::Decimal(_))) => (value.to_string(), true),
+            Some(value) if value.is_compound() => (value.to_pretty_string(true), false),
+            Some(value) => (value.to_string(), false),
+        }
+    }).collect()).collect();
+
+    let widths: Vec<usize> = columns.iter().enumerate().map(|(i, column)| {
+        cells.iter().map(|row| row[i].0.chars().count()).chain([column.chars().count()]).max().unwrap_or(0)
+    }).collect();
+
+    let border = format!("+{}+", widths.iter().map(|width| "-".repeat(width + 2)).collect::<Vec<_>>().join("+"));
+    let mut lines = vec![border.clone()];
+    // Pad before coloring so the escape codes don't count towards the width
+    lines.push(format!("| {} |", columns.iter().zip(&widths)
+        .map(|(column, width)| format!("{:<width$}", column, width = width).bold().to_string())
+        .collect::<Vec<_>>().join(" | ")));
+    lines.push(border.clone());
+    for row in &cells {
+        lines.push(format!("| {} |", row.iter().zip(&widths)
+            .map(|((text, numeric), width)| if *numeric { format!("{:>width$}", text, width = width) } else { format!("{:<width$}", text, width = width) })
+            .collect::<Vec<_>>().join(" | ")));
+    }
+    lines.push(border);
+    Ok(lines.join("\n"))
+}
+
+// A row's cells by column name, with map keys in sorted order
+fn table_row(row: &LiteralValue) -> Result<Vec<(String, LiteralValue)>, String> {
+    match row {
+        LiteralValue::Map(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            Ok(entries.into_iter().map(|(key, value)| (key.to_value().to_string(), value.clone())).collect())
+        }
+        LiteralValue::StructInst(instance) => {
+            let mut fields: Vec<_> = instance.borrow().fields.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Ok(fields)
+        }
+        other => Err(format!("log_table rows must be maps or structs, got {}.", other.to_type())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_objects_become_maps() {
+        let value = parse_json(r#"{"port": 8080, "tags": ["a", "b"], "db": {"pool": null}, "on": true}"#).unwrap();
+        assert_eq!(value.to_pretty_string(true), r#"{ "db": { "pool": nil }, "on": true, "port": 8080, "tags": ["a", "b"] }"#);
+
+        assert!(parse_json(r#"{"port": 8080,}"#).is_err());
+    }
+
+    #[test]
+    fn csv_rows_become_maps_keyed_by_header() {
+        let value = parse_csv("id,name\n1,Ann\n2,\"Smith, \"\"Bob\"\"\"\n").unwrap();
+        assert_eq!(value.to_pretty_string(true), r#"[{ "id": 1, "name": "Ann" }, { "id": 2, "name": "Smith, \"Bob\"" }]"#);
+
+        assert!(parse_csv("id,name\n1\n").is_err());
+    }
+
+    #[test]
+    fn values_render_as_json() {
+        let value = parse_json(r#"{"name": "a\"b", "tags": [1, true], "none": null, "empty": {}}"#).unwrap();
+        assert_eq!(to_json(&value).unwrap(), "{\n  \"empty\": {},\n  \"name\": \"a\\\"b\",\n  \"none\": null,\n  \"tags\": [\n    1,\n    true\n  ]\n}");
+    }
+
+    #[test]
+    fn rows_render_as_aligned_table() {
+        colored::control::set_override(false);
+        let rows = parse_json(r#"[{"name": "Ann", "age": 31}, {"name": "Bartholomew", "age": 7}]"#).unwrap();
+        assert_eq!(render_table(&rows).unwrap(), "\
++-----+-------------+
+| age | name        |
++-----+-------------+
+|  31 | Ann         |
+|   7 | Bartholomew |
++-----+-------------+");
+
+        assert!(render_table(&LiteralValue::Int(1)).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::environment::Environment;
+use crate::expr::Expr;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::modules::interpreter_native;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["env", "globals", "breakpoint", "at"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "env" | "globals" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            parser.consume(TokenType::RightParen, "Expected ')' after '('")?;
+
+            Ok(fn_inspect(identifier))
+        },
+        // Breakpoints are kept with the run's state, so these are natives of the interpreter
+        "breakpoint" | "at" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            parser.finish_call(interpreter_native("debug", &identifier))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_inspect(name: String) -> Expr {
+    Expr::PreFunction {
+        module: "debug".to_string(),
+        name,
+        args: Vec::new(),
+        span: Span::default(),
+    }
+}
+
+// Every binding visible from the current scope, with inner scopes shadowing outer ones
+pub fn env(environment: &RefCell<Environment>) -> Result<LiteralValue, String> {
+    let mut bindings = HashMap::new();
+    describe_bindings(&environment.borrow(), &mut bindings);
+
+    let mut scope = environment.borrow().enclosing.clone();
+    while let Some(current) = scope {
+        describe_bindings(&current.borrow(), &mut bindings);
+        scope = current.borrow().enclosing.clone();
+    }
+
+    Ok(LiteralValue::Map(bindings))
+}
+
+// Bindings of the outermost scope only
+pub fn globals(environment: &RefCell<Environment>) -> Result<LiteralValue, String> {
+    let mut bindings = HashMap::new();
+
+    match environment.borrow().enclosing.clone() {
+        None => describe_bindings(&environment.borrow(), &mut bindings),
+        Some(mut scope) => {
+            while let Some(enclosing) = scope.clone().borrow().enclosing.clone() {
+                scope = enclosing;
+            }
+            describe_bindings(&scope.borrow(), &mut bindings);
+        }
+    }
+
+    Ok(LiteralValue::Map(bindings))
+}
+
+fn describe_bindings(scope: &Environment, bindings: &mut HashMap<MapKey, LiteralValue>) {
+    for (name, value) in &scope.values {
+        bindings.entry(MapKey::from(name.as_str())).or_insert_with(|| {
+            LiteralValue::StringValue(format!("{}: {}", value.to_type(), value.to_pretty_string(true)))
+        });
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::expr::Expr;
+use crate::literal_value::LiteralValue;
+use crate::modules::interpreter_native;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["watch", "unwatch", "glob"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "watch" | "unwatch" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            parser.finish_call(interpreter_native("fs", &identifier))
+        },
+        "glob" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'glob'")?;
+            let pattern = parser.expression()?; // Parse the pattern
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(fn_glob(pattern))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+/// `path` as the OS expects it, whichever separator the script used. Windows accepts both
+/// already; elsewhere the `\` of a script written on Windows becomes `/`.
+pub fn native_path(path: &str) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(path)
+    } else {
+        PathBuf::from(path.replace('\\', "/"))
+    }
+}
+
+pub(crate) fn fn_glob(pattern: Expr) -> Expr {
+    Expr::PreFunction {
+        module: "fs".to_string(),
+        name: "glob".to_string(),
+        args: vec![pattern],
+        span: Span::default(),
+    }
+}
+
+// Sorted paths matching a pattern like `src/**/*.rcn`. `*`, `?` and `[a-z]` match within one
+// path component, `**` matches any number of directories. Hidden entries need an explicit `.`.
+pub fn glob(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let pattern = match args.first() {
+        Some(LiteralValue::StringValue(pattern)) => pattern,
+        _ => return Err("glob requires a pattern string.".to_string()),
+    };
+
+    // Either separator works, and `C:/` starts from a drive like `/` starts from the root
+    let pattern = pattern.replace('\\', "/");
+    let (base, parts) = match pattern.split_once('/') {
+        Some((root, rest)) if root.is_empty() || (root.len() == 2 && root.ends_with(':')) => (PathBuf::from(format!("{}/", root)), rest),
+        _ => (PathBuf::new(), pattern.as_str()),
+    };
+    let parts: Vec<&str> = parts.split('/').filter(|part| !part.is_empty() && *part != ".").collect();
+
+    let mut matches = vec![];
+    walk(&base, &parts, &mut matches);
+    matches.sort();
+    matches.dedup();
+
+    Ok(LiteralValue::Array(matches.into_iter().map(LiteralValue::StringValue).collect()))
+}
+
+fn walk(path: &Path, parts: &[&str], matches: &mut Vec<String>) {
+    let Some((part, rest)) = parts.split_first() else {
+        if !path.as_os_str().is_empty() {
+            matches.push(path.to_string_lossy().into_owned());
+        }
+        return;
+    };
+
+    let dir = if path.as_os_str().is_empty() { Path::new(".") } else { path };
+    if *part == "**" {
+        // Zero directories, or one more and still inside the `**`
+        walk(path, rest, matches);
+        for entry in entries(dir) {
+            let child = path.join(&entry);
+            if child.is_dir() && !entry.starts_with('.') {
+                walk(&child, parts, matches);
+            }
+        }
+    } else if !part.contains(['*', '?', '[']) {
+        let child = path.join(part);
+        if child.exists() {
+            walk(&child, rest, matches);
+        }
+    } else {
+        let pattern: Vec<char> = part.chars().collect();
+        for entry in entries(dir) {
+            if entry.starts_with('.') && !part.starts_with('.') {
+                continue;
+            }
+            let child = path.join(&entry);
+            if wildcard_match(&pattern, &entry.chars().collect::<Vec<_>>()) && (rest.is_empty() || child.is_dir()) {
+                walk(&child, rest, matches);
+            }
+        }
+    }
+}
+
+fn entries(dir: &Path) -> Vec<String> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries.flatten().map(|entry| entry.file_name().to_string_lossy().into_owned()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| wildcard_match(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && wildcard_match(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(close) = pattern.iter().skip(2).position(|c| *c == ']').map(|i| i + 2) else {
+                // No closing bracket, so it is a literal '['
+                return name.first() == Some(&'[') && wildcard_match(&pattern[1..], &name[1..]);
+            };
+            let Some(c) = name.first() else { return false };
+            let (negated, set) = match pattern[1] {
+                '!' | '^' => (true, &pattern[2..close]),
+                _ => (false, &pattern[1..close]),
+            };
+            let mut in_set = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    in_set |= (set[i]..=set[i + 2]).contains(c);
+                    i += 3;
+                } else {
+                    in_set |= set[i] == *c;
+                    i += 1;
+                }
+            }
+            in_set != negated && wildcard_match(&pattern[close + 1..], &name[1..])
+        }
+        Some(literal) => name.first() == Some(literal) && wildcard_match(&pattern[1..], &name[1..]),
+    }
+}
+
+/// A path registered with `fs.watch` and the callback to run when it changes.
+pub struct Watch {
+    pub path: String,
+    pub callback: LiteralValue,
+    last_modified: Option<SystemTime>,
+}
+
+impl Watch {
+    pub fn new(path: String, callback: LiteralValue) -> Self {
+        let last_modified = last_modified(&native_path(&path));
+        Self { path, callback, last_modified }
+    }
+
+    // Whether the path was modified, created or removed since the last poll
+    pub fn poll(&mut self) -> bool {
+        let modified = last_modified(&native_path(&self.path));
+        let changed = modified != self.last_modified;
+        self.last_modified = modified;
+        changed
+    }
+}
+
+// Latest modification time of a file, or of anything inside a directory
+fn last_modified(path: &Path) -> Option<SystemTime> {
+    let metadata = fs::metadata(path).ok()?;
+    let mut latest = metadata.modified().ok();
+
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path).ok()?.flatten() {
+            let entry_modified = last_modified(&entry.path());
+            latest = latest.max(entry_modified);
+        }
+    }
+    latest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        wildcard_match(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn wildcards_match_within_a_component() {
+        assert!(matches("*.rcn", "main.rcn") && matches("*", ""));
+        assert!(!matches("*.rcn", "main.rs"));
+        assert!(matches("file?.txt", "file1.txt") && !matches("file?.txt", "file.txt"));
+        assert!(matches("[a-c]*", "beta") && !matches("[!a-c]*", "beta") && matches("[xyz]", "y"));
+    }
+
+    #[test]
+    fn double_star_descends_into_directories() {
+        let root = std::env::temp_dir().join("recolon_glob");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        for file in ["top.rcn", "a/one.rcn", "a/b/two.rcn", "a/b/skip.txt"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let pattern = format!("{}/**/*.rcn", root.display());
+        let found = glob(vec![LiteralValue::StringValue(pattern)]).unwrap();
+        let expected: Vec<LiteralValue> = ["a/b/two.rcn", "a/one.rcn", "top.rcn"].iter()
+            .map(|file| LiteralValue::StringValue(root.join(file).display().to_string()))
+            .collect();
+        assert_eq!(found, LiteralValue::Array(expected.clone()));
+
+        // A pattern written with Windows separators finds the same files
+        let pattern = format!("{}\\**\\*.rcn", root.display());
+        assert_eq!(glob(vec![LiteralValue::StringValue(pattern)]).unwrap(), LiteralValue::Array(expected));
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::expr::Expr;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["parse"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "parse" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'parse'")?;
+            let text = parser.expression()?;
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(Expr::PreFunction {
+                module: "html".to_string(),
+                name: identifier,
+                args: vec![text],
+                span: Span::default(),
+            })
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+// Elements without content or closing tag
+const VOID: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"];
+// Elements whose content is kept as it is, up to their closing tag
+const RAW_TEXT: &[&str] = &["script", "style"];
+// Elements an opening tag of the same kind closes, like a `<li>` after an unclosed `<li>`
+const SELF_CLOSING_SIBLINGS: &[&str] = &["p", "li", "dt", "dd", "tr", "td", "th", "option"];
+
+/// An element of a parsed page; the page itself is an element with the tag `#document`.
+pub struct Element {
+    tag: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+enum Node {
+    Element(Rc<Element>),
+    Text(String),
+}
+
+impl Element {
+    pub fn call_method(self: &Rc<Element>, method_name: &str, args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+        match (method_name, args.as_slice()) {
+            ("select", [LiteralValue::StringValue(selector)]) => {
+                let selectors = parse_selectors(selector)?;
+                let mut found = vec![];
+                self.select(&selectors, &mut vec![], &mut found);
+                Ok(LiteralValue::Array(found.into_iter().map(LiteralValue::HtmlElement).collect()))
+            }
+            ("first", [LiteralValue::StringValue(selector)]) => {
+                let selectors = parse_selectors(selector)?;
+                let mut found = vec![];
+                self.select(&selectors, &mut vec![], &mut found);
+                Ok(found.into_iter().next().map_or(LiteralValue::Nil, LiteralValue::HtmlElement))
+            }
+            ("select" | "first", _) => Err(format!("{} expects one CSS selector string, like \"a.external\".", method_name)),
+            ("text", []) => Ok(LiteralValue::StringValue(self.text())),
+            ("tag", []) => Ok(LiteralValue::StringValue(self.tag.clone())),
+            ("attr", [LiteralValue::StringValue(name)]) => Ok(self.attribute(&name.to_lowercase())
+                .map_or(LiteralValue::Nil, |value| LiteralValue::StringValue(value.to_string()))),
+            ("attr", _) => Err("attr expects the name of an attribute.".to_string()),
+            ("attrs", []) => Ok(LiteralValue::Map(self.attributes.iter()
+                .map(|(name, value)| (MapKey::from(name.as_str()), LiteralValue::StringValue(value.clone())))
+                .collect::<HashMap<_, _>>())),
+            ("text" | "tag" | "attrs", _) => Err(format!("{} method takes no arguments.", method_name)),
+            _ => Err(format!("Unknown method '{}' for html elements", method_name)),
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(attribute, _)| attribute == name).map(|(_, value)| value.as_str())
+    }
+
+    // The text inside, with runs of whitespace as single spaces the way a browser shows it.
+    // Scripts and styles are left out, as they aren't part of what the page reads, and a `<br>`
+    // separates the words around it.
+    fn text(&self) -> String {
+        let mut raw = String::new();
+        self.collect_text(&mut raw);
+        raw.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn collect_text(&self, output: &mut String) {
+        for child in &self.children {
+            match child {
+                Node::Text(text) => output.push_str(text),
+                Node::Element(element) if !RAW_TEXT.contains(&element.tag.as_str()) && element.tag != "br" => element.collect_text(output),
+                Node::Element(_) => output.push(' '),
+            }
+        }
+    }
+
+    // The elements below this one that match, in the order they appear. `path` holds the
+    // elements from this one down to the one being visited, for matching combinators.
+    fn select<'a>(self: &'a Rc<Element>, selectors: &[Selector], path: &mut Vec<Visit<'a>>, found: &mut Vec<Rc<Element>>) {
+        if path.is_empty() {
+            path.push(Visit { element: self, position: 1, siblings: 1 });
+        }
+        let children: Vec<&Rc<Element>> = self.children.iter()
+            .filter_map(|child| match child {
+                Node::Element(element) => Some(element),
+                Node::Text(_) => None,
+            })
+            .collect();
+
+        for (i, child) in children.iter().enumerate() {
+            path.push(Visit { element: child, position: i + 1, siblings: children.len() });
+            if selectors.iter().any(|selector| selector.matches(path)) {
+                found.push(Rc::clone(child));
+            }
+            child.select(selectors, path, found);
+            path.pop();
+        }
+    }
+}
+
+impl std::fmt::Display for Element {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}", self.tag)?;
+        for (name, value) in &self.attributes {
+            write!(f, " {}=\"{}\"", name, value)?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// `html.parse(text)`: the page as an element to `select` from. Like a browser, the parser
+/// accepts what real pages contain, such as unclosed `<p>` and `<li>` tags or stray end tags.
+pub fn parse(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let LiteralValue::StringValue(text) = &args[0] else {
+        return Err(format!("html.parse expects a string, got {}.", args[0].to_type()));
+    };
+    Ok(LiteralValue::HtmlElement(parse_document(text)))
+}
+
+// An element whose end tag hasn't been reached yet
+struct Open {
+    tag: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Open {
+    fn new(tag: &str, attributes: Vec<(String, String)>) -> Open {
+        Open { tag: tag.to_string(), attributes, children: vec![] }
+    }
+
+    fn close(self) -> Rc<Element> {
+        Rc::new(Element { tag: self.tag, attributes: self.attributes, children: self.children })
+    }
+}
+
+fn parse_document(text: &str) -> Rc<Element> {
+    let mut stack = vec![Open::new("#document", vec![])];
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            push_text(&mut stack, &decode_entities(rest));
+            break;
+        };
+        push_text(&mut stack, &decode_entities(&rest[..start]));
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+        } else if let Some(end_tag) = rest.strip_prefix("</") {
+            let end = end_tag.find('>').unwrap_or(end_tag.len());
+            close_element(&mut stack, &end_tag[..end].trim().to_lowercase());
+            rest = end_tag.get(end + 1..).unwrap_or("");
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (tag, attributes, self_closing, after) = parse_start_tag(&rest[1..]);
+            rest = after;
+            if SELF_CLOSING_SIBLINGS.contains(&tag.as_str()) {
+                let closes_sibling = stack.last().is_some_and(|open| open.tag == tag || matches!((open.tag.as_str(), tag.as_str()), ("td", "th") | ("th", "td")));
+                if closes_sibling {
+                    let sibling = stack.pop().unwrap();
+                    push_element(&mut stack, sibling.close());
+                }
+            }
+
+            if VOID.contains(&tag.as_str()) || self_closing {
+                push_element(&mut stack, Open::new(&tag, attributes).close());
+            } else if RAW_TEXT.contains(&tag.as_str()) {
+                let end = find_ignoring_case(rest, &format!("</{}", tag)).unwrap_or(rest.len());
+                let mut element = Open::new(&tag, attributes);
+                element.children.push(Node::Text(rest[..end].to_string()));
+                push_element(&mut stack, element.close());
+                rest = &rest[end..];
+                rest = rest.find('>').map_or("", |close| &rest[close + 1..]);
+            } else {
+                stack.push(Open::new(&tag, attributes));
+            }
+        } else {
+            // A `<` that starts no tag, like in `a < b`, is text
+            push_text(&mut stack, "<");
+            rest = &rest[1..];
+        }
+    }
+
+    while stack.len() > 1 {
+        let open = stack.pop().unwrap();
+        push_element(&mut stack, open.close());
+    }
+    stack.pop().unwrap().close()
+}
+
+fn push_text(stack: &mut [Open], text: &str) {
+    if !text.is_empty() {
+        stack.last_mut().unwrap().children.push(Node::Text(text.to_string()));
+    }
+}
+
+fn push_element(stack: &mut [Open], element: Rc<Element>) {
+    stack.last_mut().unwrap().children.push(Node::Element(element));
+}
+
+// Closes the innermost open element with the tag, and any left open inside it. End tags with
+// nothing open to close are ignored.
+fn close_element(stack: &mut Vec<Open>, tag: &str) {
+    let Some(depth) = stack.iter().skip(1).rposition(|open| open.tag == tag) else {
+        return;
+    };
+    while stack.len() > depth + 1 {
+        let open = stack.pop().unwrap();
+        push_element(stack, open.close());
+    }
+}
+
+// The tag name, attributes, whether it ends in `/>`, and the text after it. `text` starts
+// right after the `<`.
+fn parse_start_tag(text: &str) -> (String, Vec<(String, String)>, bool, &str) {
+    let name_end = text.find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(text.len());
+    let tag = text[..name_end].to_lowercase();
+    let mut rest = &text[name_end..];
+    let mut attributes: Vec<(String, String)> = vec![];
+
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("/>") {
+            return (tag, attributes, true, after);
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return (tag, attributes, false, after);
+        }
+        if rest.is_empty() {
+            return (tag, attributes, false, rest);
+        }
+        if let Some(after) = rest.strip_prefix('/') {
+            rest = after;
+            continue;
+        }
+
+        let name_end = rest.find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '/')).unwrap_or(rest.len()).max(1);
+        let name = rest[..name_end].to_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, after) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = after[1..].find(quote).map_or(after.len(), |end| end + 1);
+                        (&after[1..end], after.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = after.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    }
+                };
+                rest = after;
+                decode_entities(value)
+            }
+            None => String::new(),
+        };
+        // The first of repeated attributes wins, as in a browser
+        if !attributes.iter().any(|(existing, _)| *existing == name) {
+            attributes.push((name, value));
+        }
+    }
+}
+
+fn find_ignoring_case(text: &str, pattern: &str) -> Option<usize> {
+    text.to_ascii_lowercase().find(pattern)
+}
+
+// Replaces character references like `&amp;` and `&#39;`; unknown ones are kept as they are
+fn decode_entities(text: &str) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..].find(';').filter(|end| *end <= 10).and_then(|end| {
+            let name = &rest[1..end + 1];
+            let character = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => match name.strip_prefix('#') {
+                    Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok().and_then(char::from_u32),
+                    Some(decimal) => decimal.parse().ok().and_then(char::from_u32),
+                    None => None,
+                },
+            };
+            character.map(|character| (character, end + 2))
+        });
+        match decoded {
+            Some((character, length)) => {
+                output.push(character);
+                rest = &rest[length..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+// An element being visited by `select`, with its place among its parent's elements
+struct Visit<'a> {
+    element: &'a Element,
+    position: usize, // From 1, as in `:nth-child(1)`
+    siblings: usize,
+}
+
+// One selector of a comma-separated list, as its compounds from left to right. Each compound
+// after the first says how it relates to the one before: as a child or any descendant.
+struct Selector {
+    compounds: Vec<(Combinator, Compound)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Default)]
+struct Compound {
+    tag: Option<String>, // None for `*` or no tag
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: Vec<(String, AttributeTest)>,
+    positions: Vec<Position>,
+}
+
+enum AttributeTest {
+    Present,
+    Equals(String),
+    StartsWith(String),
+    EndsWith(String),
+    Contains(String),
+    HasWord(String),
+}
+
+enum Position {
+    First,
+    Last,
+    Nth(usize),
+}
+
+impl Selector {
+    // Whether the last element of `path` matches, checking the compounds from the right
+    fn matches(&self, path: &[Visit]) -> bool {
+        matches_from(&self.compounds, path)
+    }
+}
+
+fn matches_from(compounds: &[(Combinator, Compound)], path: &[Visit]) -> bool {
+    let (Some(((combinator, compound), before)), Some((visit, ancestors))) = (compounds.split_last(), path.split_last()) else {
+        return false;
+    };
+    if !compound.matches(visit) {
+        return false;
+    }
+    if before.is_empty() {
+        return true;
+    }
+    match combinator {
+        Combinator::Child => matches_from(before, ancestors),
+        Combinator::Descendant => (1..=ancestors.len()).rev().any(|end| matches_from(before, &ancestors[..end])),
+    }
+}
+
+impl Compound {
+    fn matches(&self, visit: &Visit) -> bool {
+        let element = visit.element;
+        self.tag.as_ref().is_none_or(|tag| *tag == element.tag)
+            && self.id.as_ref().is_none_or(|id| element.attribute("id") == Some(id.as_str()))
+            && self.classes.iter().all(|class| element.attribute("class").is_some_and(|classes| classes.split_whitespace().any(|c| c == class)))
+            && self.attributes.iter().all(|(name, test)| element.attribute(name).is_some_and(|value| test.accepts(value)))
+            && self.positions.iter().all(|position| match position {
+                Position::First => visit.position == 1,
+                Position::Last => visit.position == visit.siblings,
+                Position::Nth(n) => visit.position == *n,
+            })
+    }
+}
+
+impl AttributeTest {
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            AttributeTest::Present => true,
+            AttributeTest::Equals(expected) => value == expected,
+            AttributeTest::StartsWith(prefix) => value.starts_with(prefix.as_str()),
+            AttributeTest::EndsWith(suffix) => value.ends_with(suffix.as_str()),
+            AttributeTest::Contains(part) => value.contains(part.as_str()),
+            AttributeTest::HasWord(word) => value.split_whitespace().any(|w| w == word),
+        }
+    }
+}
+
+// Selectors like `ul.menu > li a[href^="https"]`, `#main p:first-child` or `h1, h2`
+fn parse_selectors(text: &str) -> Result<Vec<Selector>, String> {
+    let unsupported = |problem: &str| format!("Unsupported CSS selector '{}': {}.", text, problem);
+    let mut selectors = vec![];
+
+    for part in text.split(',') {
+        let mut compounds = vec![];
+        let mut combinator = Combinator::Descendant;
+        let mut chars = part.trim().chars().peekable();
+
+        while chars.peek().is_some() {
+            let mut compound = Compound::default();
+            let mut empty = true;
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '>' {
+                    break;
+                }
+                empty = false;
+                match c {
+                    '*' => {
+                        chars.next();
+                    }
+                    '#' | '.' => {
+                        chars.next();
+                        let name = take_name(&mut chars);
+                        if name.is_empty() {
+                            return Err(unsupported(&format!("expected a name after '{}'", c)));
+                        }
+                        if c == '#' { compound.id = Some(name) } else { compound.classes.push(name) }
+                    }
+                    '[' => {
+                        chars.next();
+                        let inside: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                        compound.attributes.push(parse_attribute_test(&inside).ok_or_else(|| unsupported(&format!("cannot read [{}]", inside)))?);
+                    }
+                    ':' => {
+                        chars.next();
+                        let name = take_name(&mut chars);
+                        let position = match name.as_str() {
+                            "first-child" => Position::First,
+                            "last-child" => Position::Last,
+                            "nth-child" if chars.next_if_eq(&'(').is_some() => {
+                                let n: String = chars.by_ref().take_while(|c| *c != ')').collect();
+                                Position::Nth(n.trim().parse().map_err(|_| unsupported("nth-child takes a number"))?)
+                            }
+                            _ => return Err(unsupported(&format!("':{}' is not supported; use :first-child, :last-child or :nth-child(n)", name))),
+                        };
+                        compound.positions.push(position);
+                    }
+                    c if c.is_alphanumeric() || c == '-' || c == '_' => compound.tag = Some(take_name(&mut chars).to_lowercase()),
+                    c => return Err(unsupported(&format!("unexpected '{}'; only descendant and '>' combinators are supported", c))),
+                }
+            }
+
+            if empty {
+                return Err(unsupported("expected an element after the combinator"));
+            }
+            compounds.push((combinator, compound));
+
+            // Spaces around `>` don't matter, spaces alone mean a descendant
+            combinator = Combinator::Descendant;
+            while let Some(&c) = chars.peek() {
+                if c == '>' {
+                    combinator = Combinator::Child;
+                } else if !c.is_whitespace() {
+                    break;
+                }
+                chars.next();
+            }
+        }
+
+        if compounds.is_empty() {
+            return Err(unsupported("expected a selector"));
+        }
+        selectors.push(Selector { compounds });
+    }
+    Ok(selectors)
+}
+
+fn take_name(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '-' || *c == '_') {
+        name.push(c);
+    }
+    name
+}
+
+// The inside of `[name]`, `[name=value]` or `[name^="value"]` and the like
+fn parse_attribute_test(inside: &str) -> Option<(String, AttributeTest)> {
+    let Some(equals) = inside.find('=') else {
+        let name = inside.trim();
+        return (!name.is_empty()).then(|| (name.to_lowercase(), AttributeTest::Present));
+    };
+    let (name, operator) = match inside[..equals].trim_end().char_indices().last() {
+        Some((i, operator @ ('^' | '$' | '*' | '~'))) => (&inside[..i], Some(operator)),
+        _ => (&inside[..equals], None),
+    };
+    let value = inside[equals + 1..].trim().trim_matches(['"', '\'']).to_string();
+    let test = match operator {
+        Some('^') => AttributeTest::StartsWith(value),
+        Some('$') => AttributeTest::EndsWith(value),
+        Some('*') => AttributeTest::Contains(value),
+        Some('~') => AttributeTest::HasWord(value),
+        _ => AttributeTest::Equals(value),
+    };
+    let name = name.trim();
+    (!name.is_empty()).then(|| (name.to_lowercase(), test))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(page: &Rc<Element>, selector: &str) -> Vec<String> {
+        let LiteralValue::Array(found) = page.call_method("select", vec![LiteralValue::StringValue(selector.to_string())]).unwrap() else {
+            panic!("Expected an array");
+        };
+        found.iter().map(|element| match element {
+            LiteralValue::HtmlElement(element) => element.text(),
+            other => panic!("Expected an element, got {}", other),
+        }).collect()
+    }
+
+    #[test]
+    fn selectors_find_elements_in_loosely_written_pages() {
+        let page = parse_document("<!DOCTYPE html><html><body>\n\
+            <h1 id=title>Books &amp; more</h1>\n\
+            <ul class=\"list main\">\n\
+                <li><a href=\"https://a.example\">First</a>\n\
+                <li class=sale><a href='/b'>Second</a> <b>new</b>\n\
+                <li><span><a href=\"https://c.example\" data-id=3>Third</a></span>\n\
+            </ul>\n\
+            <p>1 < 2<br>done</div></p>\n\
+            <script>if (a < b) { document.write('<li>no</li>'); }</script>\n\
+        </body></html>");
+
+        assert_eq!(select(&page, "h1#title"), ["Books & more"]);
+        assert_eq!(select(&page, "ul.main > li").len(), 3);
+        assert_eq!(select(&page, "li.sale"), ["Second new"]);
+        assert_eq!(select(&page, "ul > li > a"), ["First", "Second"]);
+        assert_eq!(select(&page, "ul a[href^=https]"), ["First", "Third"]);
+        assert_eq!(select(&page, "li:last-child a, h1"), ["Books & more", "Third"]);
+        assert_eq!(select(&page, "li:nth-child(2) b"), ["new"]);
+        assert_eq!(select(&page, "p"), ["1 < 2 done"]);
+        assert!(select(&page, "script li").is_empty());
+
+        let link = page.call_method("first", vec![LiteralValue::StringValue("[data-id]".to_string())]).unwrap();
+        let LiteralValue::HtmlElement(link) = link else { panic!("Expected an element") };
+        assert_eq!(link.call_method("attr", vec![LiteralValue::StringValue("href".to_string())]).unwrap(), LiteralValue::StringValue("https://c.example".to_string()));
+        assert_eq!(link.call_method("attr", vec![LiteralValue::StringValue("title".to_string())]).unwrap(), LiteralValue::Nil);
+        assert_eq!(link.to_string(), "<a href=\"https://c.example\" data-id=\"3\">");
+
+        let error = page.call_method("select", vec![LiteralValue::StringValue("li + li".to_string())]).unwrap_err();
+        assert!(error.starts_with("Unsupported CSS selector 'li + li'"), "{}", error);
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::expr::Expr;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::modules::rcn_compress::byte_array;
+use crate::modules::rcn_fs::native_path;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["encode_query", "parse_query", "parse_cookies", "multipart"];
+
+// Request building and response parsing helpers. There is no HTTP client yet; these produce
+// and read the strings one needs, so scripts can already talk to APIs through other tools.
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "encode_query" | "parse_query" | "parse_cookies" | "multipart" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            let arg = parser.expression()?; // Parse the map or string to convert
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(fn_http(&identifier, arg))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_http(name: &str, arg: Expr) -> Expr {
+    Expr::PreFunction {
+        module: "http".to_string(),
+        name: name.to_string(),
+        args: vec![arg],
+        span: Span::default(),
+    }
+}
+
+fn map_arg<'a>(args: &'a [LiteralValue], function: &str) -> Result<Vec<(&'a MapKey, &'a LiteralValue)>, String> {
+    match args.first() {
+        Some(LiteralValue::Map(entries)) => {
+            // Sorted so the output is the same on every run
+            let mut entries: Vec<_> = entries.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            Ok(entries)
+        }
+        _ => Err(format!("{} expects a map.", function)),
+    }
+}
+
+fn string_arg<'a>(args: &'a [LiteralValue], function: &str) -> Result<&'a str, String> {
+    match args.first() {
+        Some(LiteralValue::StringValue(text)) => Ok(text),
+        _ => Err(format!("{} expects a string.", function)),
+    }
+}
+
+// Map keys and values as plain text, without the quotes Display gives strings
+fn plain(value: &LiteralValue) -> String {
+    match value {
+        LiteralValue::StringValue(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+// `{ "q": "a b", "tag": ["x", "y"] }` becomes `q=a%20b&tag=x&tag=y`
+pub fn encode_query(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let mut pairs = vec![];
+    for (key, value) in map_arg(&args, "encode_query")? {
+        let key = percent_encode(&plain(&key.to_value()));
+        match value {
+            LiteralValue::Array(values) => pairs.extend(values.iter().map(|value| format!("{}={}", key, percent_encode(&plain(value))))),
+            LiteralValue::Nil => pairs.push(key),
+            value => pairs.push(format!("{}={}", key, percent_encode(&plain(value)))),
+        }
+    }
+    Ok(LiteralValue::StringValue(pairs.join("&")))
+}
+
+// The reverse of encode_query; a key given more than once maps to an array of its values
+pub fn parse_query(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let query = string_arg(&args, "parse_query")?;
+    let query = query.strip_prefix('?').unwrap_or(query);
+
+    let mut entries: HashMap<MapKey, LiteralValue> = HashMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let key = MapKey::from(percent_decode(key)?.as_str());
+        let value = LiteralValue::StringValue(percent_decode(value)?);
+
+        match entries.get_mut(&key) {
+            Some(LiteralValue::Array(values)) => values.push(value),
+            Some(existing) => *existing = LiteralValue::Array(vec![existing.clone(), value]),
+            None => {
+                entries.insert(key, value);
+            }
+        }
+    }
+    Ok(LiteralValue::Map(entries))
+}
+
+// A `Cookie` header like `id=42; theme=dark` as a map
+pub fn parse_cookies(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let header = string_arg(&args, "parse_cookies")?;
+
+    let mut cookies = HashMap::new();
+    for cookie in header.split(';') {
+        if let Some((name, value)) = cookie.split_once('=') {
+            let value = value.trim();
+            let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+            cookies.insert(MapKey::from(name.trim()), LiteralValue::StringValue(value.to_string()));
+        }
+    }
+    Ok(LiteralValue::Map(cookies))
+}
+
+// A multipart/form-data body for uploading a form. Each entry is a field; a map like
+// `{ "file": "report.txt" }` as the value uploads that file. Returns the body and the
+// Content-Type header to send it with. Files are sent byte for byte, so a body holding a
+// binary file comes back as an array of bytes instead of a string.
+pub fn multipart(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let fields = map_arg(&args, "multipart")?;
+    let boundary = format!("recolon-{:016x}", rand::random::<u64>());
+
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        let name = plain(&name.to_value());
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+
+        let upload = match value {
+            LiteralValue::Map(upload) => upload.get(&MapKey::from("file")),
+            _ => None,
+        };
+        match upload {
+            Some(LiteralValue::StringValue(path)) => {
+                let file = native_path(path);
+                let contents = fs::read(&file).map_err(|e| format!("Could not read '{}' for upload: {}", path, e))?;
+                let file_name = file.file_name().map_or(path.clone(), |name| name.to_string_lossy().into_owned());
+                body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n", name, file_name).as_bytes());
+                body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+                body.extend_from_slice(&contents);
+            }
+            Some(other) => return Err(format!("multipart file for '{}' must be a path, got {}.", name, other.to_type())),
+            None => {
+                body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes());
+                body.extend_from_slice(plain(value).as_bytes());
+            }
+        }
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    let body = match String::from_utf8(body) {
+        Ok(text) => LiteralValue::StringValue(text),
+        Err(e) => byte_array(e.into_bytes()),
+    };
+
+    Ok(LiteralValue::Map(HashMap::from([
+        (MapKey::from("content_type"), LiteralValue::StringValue(format!("multipart/form-data; boundary={}", boundary))),
+        (MapKey::from("body"), body),
+    ])))
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+fn percent_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = text.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(byte) => decoded.push(byte),
+                    None => return Err(format!("Invalid percent escape in '{}'.", text)),
+                }
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).map_err(|_| format!("'{}' does not decode to UTF-8 text.", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, LiteralValue)]) -> LiteralValue {
+        LiteralValue::Map(entries.iter().map(|(key, value)| (MapKey::from(*key), value.clone())).collect())
+    }
+
+    fn text(value: &str) -> LiteralValue {
+        LiteralValue::StringValue(value.to_string())
+    }
+
+    #[test]
+    fn queries_round_trip_through_encoding() {
+        let query = map(&[("q", text("a b&c")), ("tag", LiteralValue::Array(vec![text("x"), text("y")])), ("page", LiteralValue::Int(2))]);
+        let encoded = encode_query(vec![query.clone()]).unwrap();
+        assert_eq!(encoded, text("page=2&q=a%20b%26c&tag=x&tag=y"));
+
+        let parsed = parse_query(vec![encoded]).unwrap();
+        assert_eq!(parsed, map(&[("q", text("a b&c")), ("tag", LiteralValue::Array(vec![text("x"), text("y")])), ("page", text("2"))]));
+        assert!(parse_query(vec![text("a=%zz")]).is_err());
+    }
+
+    #[test]
+    fn cookies_and_forms_are_split_into_fields() {
+        let cookies = parse_cookies(vec![text("id=42; theme=\"dark\";flag")]).unwrap();
+        assert_eq!(cookies, map(&[("id", text("42")), ("theme", text("dark"))]));
+
+        let form = multipart(vec![map(&[("name", text("Ann"))])]).unwrap();
+        let LiteralValue::Map(form) = form else { panic!("Expected a map") };
+        let content_type = plain(&form[&MapKey::from("content_type")]);
+        let boundary = content_type.split("boundary=").nth(1).unwrap();
+        assert_eq!(plain(&form[&MapKey::from("body")]), format!("--{b}\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nAnn\r\n--{b}--\r\n", b = boundary));
+
+        // Binary files are uploaded as they are, not as lossy text
+        let path = std::env::temp_dir().join("recolon_upload.bin");
+        fs::write(&path, [0x89, b'P', 0xff, 0x00]).unwrap();
+        let upload = map(&[("file", text(&path.display().to_string()))]);
+        let LiteralValue::Map(form) = multipart(vec![map(&[("logo", upload)])]).unwrap() else { panic!("Expected a map") };
+        let LiteralValue::Array(body) = &form[&MapKey::from("body")] else { panic!("Expected the body as bytes") };
+        let bytes: Vec<u8> = body.iter().map(|byte| byte.as_int().unwrap() as u8).collect();
+        assert!(bytes.windows(6).any(|window| window == b"\r\n\x89P\xff\x00"));
+        let _ = fs::remove_file(path);
+    }
+}
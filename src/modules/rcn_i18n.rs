@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::expr::Expr;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::modules::interpreter_native;
+use crate::modules::rcn_data::parse_json;
+use crate::parser::Parser;
+use crate::scanner::TokenType;
+
+pub const FUNCTIONS: &[&str] = &["load"];
+
+// Loading fills the run's catalog, which `t` reads, so both are natives the interpreter defines
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "load" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'load'")?;
+            parser.finish_call(interpreter_native("i18n", "load"))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+const PLURAL_CATEGORIES: [&str; 6] = ["zero", "one", "two", "few", "many", "other"];
+
+enum Message {
+    Text(String),
+    Plural(String, HashMap<String, String>), // The file's locale and the forms by category, picked with the `count` argument
+}
+
+/// Messages loaded with `i18n.load` from JSON files like:
+///
+/// ```text
+/// {
+///     "@locale": "fr",
+///     "greeting": "Bonjour {name}",
+///     "files": { "one": "{count} fichier", "other": "{count} fichiers" },
+///     "menu": { "open": "Ouvrir" }
+/// }
+/// ```
+///
+/// Nested objects give dotted keys like `menu.open`. Without `@locale` the locale comes from the
+/// file name, `fr.json` or `messages.fr.json`. Loading another file adds to the catalog, so a
+/// fallback language can be loaded first. Plural messages keep the locale of their own file, so
+/// one left untranslated still picks its form by the fallback language's rules.
+#[derive(Default)]
+pub struct Catalog {
+    messages: HashMap<String, Message>,
+}
+
+impl Catalog {
+    pub fn load(&mut self, path: &str) -> Result<(), String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Failed to load messages '{}': {}", path, e))?;
+        let mut entries = match parse_json(&contents).map_err(|e| format!("Failed to parse '{}': {}", path, e))? {
+            LiteralValue::Map(entries) => entries,
+            other => return Err(format!("'{}' should hold an object of messages, got {}.", path, other.to_type())),
+        };
+
+        let locale = match entries.remove(&MapKey::from("@locale")) {
+            Some(LiteralValue::StringValue(locale)) => locale,
+            Some(other) => return Err(format!("'{}': '@locale' should be a string, got {}.", path, other.to_type())),
+            None => Path::new(path).file_stem().and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.rsplit('.').next())
+                .unwrap_or_default().to_string(),
+        };
+        self.add_messages(&locale, "", entries)
+    }
+
+    fn add_messages(&mut self, locale: &str, prefix: &str, entries: HashMap<MapKey, LiteralValue>) -> Result<(), String> {
+        for (key, value) in entries {
+            let key = match key {
+                MapKey::String(key) => format!("{}{}", prefix, key),
+                other => return Err(format!("Message keys should be strings, got {:?}.", other)),
+            };
+            match value {
+                LiteralValue::StringValue(text) => {
+                    self.messages.insert(key, Message::Text(text));
+                }
+                LiteralValue::Map(forms) if is_plural(&forms) => {
+                    let forms = forms.into_iter().filter_map(|(category, form)| match (category, form) {
+                        (MapKey::String(category), LiteralValue::StringValue(form)) => Some((category, form)),
+                        _ => None,
+                    }).collect();
+                    self.messages.insert(key, Message::Plural(locale.to_string(), forms));
+                }
+                LiteralValue::Map(nested) => self.add_messages(locale, &format!("{}.", key), nested)?,
+                other => return Err(format!("Message '{}' should be a string or an object, got {}.", key, other.to_type())),
+            }
+        }
+        Ok(())
+    }
+
+    /// The message for `key` with `{name}` replaced by the argument of that name. Plural messages
+    /// pick their form by the `count` argument. Unknown keys come back as they are, so a missing
+    /// translation shows up in the output instead of stopping the program.
+    pub fn translate(&self, key: &str, args: &LiteralValue) -> Result<String, String> {
+        let args = match args {
+            LiteralValue::Map(args) => Some(args),
+            LiteralValue::Nil => None,
+            other => return Err(format!("t expects a map of arguments or nil, got {}.", other.to_type())),
+        };
+        let arg = |name: &str| args.and_then(|args| args.get(&MapKey::from(name)));
+
+        let text = match self.messages.get(key) {
+            None => return Ok(key.to_string()),
+            Some(Message::Text(text)) => text,
+            Some(Message::Plural(locale, forms)) => {
+                let count = match arg("count") {
+                    Some(LiteralValue::Int(count)) => *count as f64,
+                    Some(LiteralValue::Float(count)) => *count,
+                    Some(other) => return Err(format!("Message '{}' needs a number for 'count', got {}.", key, other.to_type())),
+                    None => return Err(format!("Message '{}' has plural forms and needs a 'count' argument.", key)),
+                };
+                // An explicit zero form reads better than "0 files" in any language
+                let category = if count == 0.0 && forms.contains_key("zero") { "zero" } else { plural_category(locale, count) };
+                forms.get(category).or_else(|| forms.get("other"))
+                    .ok_or_else(|| format!("Message '{}' has no '{}' or 'other' form.", key, category))?
+            }
+        };
+
+        Ok(interpolate(text, arg))
+    }
+}
+
+// Objects whose keys are all plural categories are the forms of one message, not nested keys
+fn is_plural(forms: &HashMap<MapKey, LiteralValue>) -> bool {
+    !forms.is_empty() && forms.keys().all(|key| matches!(key, MapKey::String(key) if PLURAL_CATEGORIES.contains(&key.as_str())))
+}
+
+// The CLDR plural category of `count` for the languages whose rules differ from English
+fn plural_category(locale: &str, count: f64) -> &'static str {
+    let language = locale.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    // Fractions are "other" in the languages below, except French where 1.5 is still singular
+    let n = if count.fract() == 0.0 { Some(count.abs() as i64) } else { None };
+
+    match (language.as_str(), n) {
+        ("ja" | "zh" | "ko" | "vi" | "th" | "id", _) => "other",
+        ("fr" | "pt", _) if count.abs() < 2.0 => "one",
+        ("ru" | "uk" | "be" | "pl" | "cs" | "sk", None) => "other",
+        ("ru" | "uk" | "be", Some(n)) => match (n % 10, n % 100) {
+            (1, 11) => "many",
+            (1, _) => "one",
+            (2..=4, 12..=14) => "many",
+            (2..=4, _) => "few",
+            _ => "many",
+        },
+        ("pl", Some(n)) => match (n, n % 10, n % 100) {
+            (1, _, _) => "one",
+            (_, 2..=4, 12..=14) => "many",
+            (_, 2..=4, _) => "few",
+            _ => "many",
+        },
+        ("cs" | "sk", Some(n)) => match n {
+            1 => "one",
+            2..=4 => "few",
+            _ => "other",
+        },
+        (_, Some(1)) => "one",
+        _ => "other",
+    }
+}
+
+// Replaces `{name}` with the argument's value; placeholders without an argument are kept
+fn interpolate<'a>(text: &str, arg: impl Fn(&str) -> Option<&'a LiteralValue>) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after_open = &rest[start + 1..];
+        match after_open.find('}').and_then(|end| arg(&after_open[..end]).map(|value| (end, value))) {
+            Some((end, value)) => {
+                output.push_str(&value.to_string());
+                rest = &after_open[end + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = after_open;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plural_forms_follow_the_locale() {
+        let forms = |locale: &str| [0.0, 1.0, 2.0, 5.0, 21.0, 22.0, 1.5].map(|count| plural_category(locale, count));
+        assert_eq!(forms("en"), ["other", "one", "other", "other", "other", "other", "other"]);
+        assert_eq!(forms("fr-CA"), ["one", "one", "other", "other", "other", "other", "one"]);
+        assert_eq!(forms("ru"), ["many", "one", "few", "many", "one", "few", "other"]);
+        assert_eq!(forms("pl"), ["many", "one", "few", "many", "many", "few", "other"]);
+        assert_eq!(forms("ja"), ["other"; 7]);
+    }
+
+    #[test]
+    fn untranslated_messages_keep_the_plural_rules_of_their_language() {
+        let dir = std::env::temp_dir().join("recolon_i18n_layers");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("en.json"), r#"{ "files": { "one": "{count} file", "other": "{count} files" } }"#).unwrap();
+        fs::write(dir.join("ru.json"), r#"{ "dirs": { "one": "{count} папка", "few": "{count} папки", "many": "{count} папок" } }"#).unwrap();
+
+        let mut catalog = Catalog::default();
+        catalog.load(&dir.join("en.json").to_string_lossy()).unwrap();
+        catalog.load(&dir.join("ru.json").to_string_lossy()).unwrap();
+        let count = |n: i64| LiteralValue::Map(HashMap::from([(MapKey::from("count"), LiteralValue::Int(n))]));
+        assert_eq!(catalog.translate("files", &count(21)), Ok("21 files".to_string()));
+        assert_eq!(catalog.translate("dirs", &count(21)), Ok("21 папка".to_string()));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
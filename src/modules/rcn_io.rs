@@ -1,12 +1,22 @@
+use std::cell::RefCell;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
 
 use crate::expr::Expr;
 use crate::literal_value::LiteralValue;
 use crate::parser::Parser;
-use crate::scanner::TokenType;
+use crate::modules::interpreter_native;
+use crate::modules::rcn_fs::native_path;
+use crate::scanner::{Span, TokenType};
+use crate::types::rcn_generator::{Generator, YieldFn};
 
 
+pub const FUNCTIONS: &[&str] = &[
+    "read_input", "lines_stdin", "open_file", "open_handle", "write_file", "file_exists",
+    "delete_file", "confirm", "select", "password", "redirect_output", "capture",
+];
+
 pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
     match identifier.as_str() {
         "read_input" => {
@@ -15,12 +25,33 @@ pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, Strin
 
             Ok(fn_read_input())
         },
+        "lines_stdin" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'lines_stdin'")?;
+            parser.consume(TokenType::RightParen, "Expected ')' after '('")?;
+
+            Ok(fn_prompt("lines_stdin", vec![]))
+        },
         "open_file" => {
             parser.consume(TokenType::LeftParen, "Expected '(' after 'file_open'")?;
-            let arg = parser.expression()?; // Parse the argument expression
-            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+            let mut args = vec![parser.expression()?]; // Parse the path
+            if parser.check(TokenType::Comma) {
+                parser.consume(TokenType::Comma, "Expected ',' after path")?;
+                args.push(parser.expression()?); // Optional: true turns "\r\n" line endings into "\n"
+            }
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
 
-            Ok(fn_open_file(arg))
+            Ok(fn_open_file(args))
+        },
+        "open_handle" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'open_handle'")?;
+            let mut args = vec![parser.expression()?]; // Parse the path
+            if parser.check(TokenType::Comma) {
+                parser.consume(TokenType::Comma, "Expected ',' after path")?;
+                args.push(parser.expression()?); // Optional mode: "r", "w" or "a"
+            }
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(fn_open_handle(args))
         },
         "write_file" => {
             parser.consume(TokenType::LeftParen, "Expected '(' after 'file_write'")?;
@@ -45,23 +76,73 @@ pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, Strin
 
             Ok(fn_delete_file(arg))
         },
+        "confirm" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'confirm'")?;
+            let question = parser.expression()?; // Parse the question shown to the user
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(fn_prompt("confirm", vec![question]))
+        },
+        "select" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'select'")?;
+            let question = parser.expression()?; // Parse the question shown to the user
+            parser.consume(TokenType::Comma, "Expected ',' after question")?;
+            let options = parser.expression()?; // Parse the array of options
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(fn_prompt("select", vec![question, options]))
+        },
+        "password" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'password'")?;
+            let mut args = vec![];
+            if !parser.check(TokenType::RightParen) {
+                args.push(parser.expression()?); // Optional prompt
+            }
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(fn_prompt("password", args))
+        },
+        "redirect_output" | "capture" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            parser.finish_call(interpreter_native("io", &identifier))
+        },
         _ => Err(format!("Unknown identifier '{}'.", identifier)),
     }
 }
 
+pub(crate) fn fn_prompt(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::PreFunction {
+        module: "io".to_string(),
+        name: name.to_string(),
+        args,
+        span: Span::default(),
+    }
+}
+
 pub(crate) fn fn_read_input() -> Expr {
     Expr::PreFunction {
         module: "io".to_string(),
         name: "read_input".to_string(),
         args: Vec::new(),
+        span: Span::default(),
     }
 }
 
-pub(crate) fn fn_open_file(arg: Expr) -> Expr {
+pub(crate) fn fn_open_file(args: Vec<Expr>) -> Expr {
     Expr::PreFunction {
         module: "io".to_string(),
         name: "open_file".to_string(),
-        args: vec![arg],
+        args,
+        span: Span::default(),
+    }
+}
+
+pub(crate) fn fn_open_handle(args: Vec<Expr>) -> Expr {
+    Expr::PreFunction {
+        module: "io".to_string(),
+        name: "open_handle".to_string(),
+        args,
+        span: Span::default(),
     }
 }
 
@@ -70,6 +151,7 @@ pub(crate) fn fn_write_file(filename: Expr, content: Expr) -> Expr {
         module: "io".to_string(),
         name: "write_file".to_string(),
         args: vec![filename, content],
+        span: Span::default(),
     }
 }
 
@@ -78,6 +160,7 @@ pub(crate) fn fn_file_exists(arg: Expr) -> Expr {
         module: "io".to_string(),
         name: "file_exists".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
@@ -86,6 +169,7 @@ pub(crate) fn fn_delete_file(arg: Expr) -> Expr {
         module: "io".to_string(),
         name: "delete_file".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
@@ -99,22 +183,243 @@ pub fn read_input() -> Result<LiteralValue, String> {
     Ok(LiteralValue::StringValue(input))
 }
 
+// Shows `prompt` and reads one line, failing instead of looping forever once stdin is closed
+fn prompt_line(prompt: &str) -> Result<String, String> {
+    print!("{}", prompt);
+    io::stdout().flush().map_err(|e| e.to_string())?;
+
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(0) => Err("Input ended while waiting for an answer.".to_string()),
+        Ok(_) => Ok(input.trim().to_string()),
+        Err(e) => Err(format!("Failed to read input: {}", e)),
+    }
+}
+
+fn prompt_arg(args: &[LiteralValue], function: &str) -> Result<String, String> {
+    match args.first() {
+        Some(LiteralValue::StringValue(prompt)) => Ok(format!("{} ", prompt)),
+        None => Ok(String::new()),
+        Some(other) => Err(format!("{} prompt must be a string, got {}.", function, other.to_type())),
+    }
+}
+
+// Every line piped into the script, read as a for-in asks for it so input of any size can be filtered.
+// Standard input can't be rewound, so walking it again goes on where the last walk stopped
+pub fn lines_stdin() -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Generator(Rc::new(Generator {
+        name: "lines_stdin".to_string(),
+        run: Box::new(|yield_to| stream_lines(io::stdin().lock(), yield_to)),
+    })))
+}
+
+fn stream_lines(mut reader: impl BufRead, yield_to: YieldFn) -> Result<(), String> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(|e| format!("Error reading standard input: {}", e))? == 0 {
+            return Ok(());
+        }
+        let text = line.trim_end_matches(['\n', '\r']).to_string();
+        if !(yield_to.borrow_mut())(LiteralValue::StringValue(text))? {
+            return Ok(());
+        }
+    }
+}
+
+// Yes/no question, anything but y or yes counts as no
+pub fn confirm(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let answer = prompt_line(&prompt_arg(&args, "confirm")?)?;
+    Ok(LiteralValue::check_bool(is_yes(&answer)))
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.to_lowercase().as_str(), "y" | "yes")
+}
+
+// Numbered menu of `options`, asked again until the answer is one of the numbers
+pub fn select(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let question = prompt_arg(&args, "select")?;
+    let options = match args.get(1) {
+        Some(LiteralValue::Array(options)) if !options.is_empty() => options,
+        _ => return Err("select requires a question and a non-empty array of options.".to_string()),
+    };
+
+    println!("{}", question.trim_end());
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {}", i + 1, option);
+    }
+    loop {
+        let answer = prompt_line(&format!("[1-{}] ", options.len()))?;
+        if let Some(index) = option_index(&answer, options.len()) {
+            return Ok(options[index].clone());
+        }
+    }
+}
+
+fn option_index(answer: &str, count: usize) -> Option<usize> {
+    answer.parse::<usize>().ok().filter(|choice| (1..=count).contains(choice)).map(|choice| choice - 1)
+}
+
+// Reads a line without echoing it, through `stty` where the terminal supports it
+pub fn password(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let prompt = prompt_arg(&args, "password")?;
+    let hidden = set_echo(false);
+    let answer = prompt_line(&prompt);
+    if hidden {
+        set_echo(true);
+        // The Enter key wasn't echoed either
+        println!();
+    }
+    Ok(LiteralValue::StringValue(answer?))
+}
+
+fn set_echo(on: bool) -> bool {
+    std::process::Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
 pub fn open_file(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
-    if args.len() != 1 {
-        Err("You can only open one file at a time.".to_string())
+    let (filename, normalize) = match args.as_slice() {
+        [LiteralValue::StringValue(filename)] => (filename, false),
+        [LiteralValue::StringValue(filename), normalize] => (filename, normalize_flag(normalize, "open_file")?),
+        [_] | [_, _] => return Err("File path must be a string".to_string()),
+        _ => return Err("You can only open one file at a time.".to_string()),
+    };
+
+    match fs::read_to_string(native_path(filename)) {
+        Ok(contents) => Ok(LiteralValue::StringValue(normalize_line_endings(contents, normalize))),
+        Err(e) => Err(format!("Error reading file: {}", e)),
+    }
+}
+
+// The optional flag of `open_file` and `read`, which has to be a bool so a stray argument isn't ignored
+fn normalize_flag(flag: &LiteralValue, function: &str) -> Result<bool, String> {
+    match flag {
+        LiteralValue::True => Ok(true),
+        LiteralValue::False => Ok(false),
+        other => Err(format!("{} expects true or false to normalize line endings, got {}.", function, other.to_type())),
+    }
+}
+
+// Text with Windows "\r\n" line endings turned into "\n" when asked, so files read the same on every OS
+fn normalize_line_endings(text: String, normalize: bool) -> String {
+    if normalize && text.contains("\r\n") {
+        text.replace("\r\n", "\n")
     } else {
-        let filename = match &args[0] {
-            LiteralValue::StringValue(s) => s,
-            _ => return Err("File path must be a string".to_string()),
+        text
+    }
+}
+
+pub fn open_handle(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let (path, mode) = match args.as_slice() {
+        [LiteralValue::StringValue(path)] => (path, "r"),
+        [LiteralValue::StringValue(path), LiteralValue::StringValue(mode)] => (path, mode.as_str()),
+        _ => return Err("open_handle() takes a path and an optional mode string.".to_string()),
+    };
+
+    let mut options = fs::OpenOptions::new();
+    match mode {
+        "r" => options.read(true),
+        "w" => options.write(true).create(true).truncate(true),
+        "a" => options.append(true).create(true),
+        other => return Err(format!("open_handle() mode must be \"r\", \"w\" or \"a\", got \"{}\".", other)),
+    };
+    let file = options.open(native_path(path)).map_err(|e| format!("Error opening file '{}': {}", path, e))?;
+
+    Ok(LiteralValue::FileHandle(Rc::new(RefCell::new(FileHandle { path: path.clone(), file: Some(BufReader::new(file)) }))))
+}
+
+/// An open file from `io.open_handle`, read a line at a time or written in pieces instead of
+/// all at once, so files larger than memory can be processed. `seek(n)` moves to byte n and
+/// `lines()` walks the rest of the file in a for-in. The file stays open until `close()`,
+/// which `with` calls on leaving its block.
+#[derive(Debug)]
+pub struct FileHandle {
+    path: String,
+    file: Option<BufReader<fs::File>>, // None once closed
+}
+
+impl FileHandle {
+    pub fn call_method(&mut self, method_name: &str, args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+        if method_name == "close" {
+            // Closing twice is harmless, so an explicit close inside `with` is fine
+            self.file = None;
+            return Ok(LiteralValue::Nil);
+        }
+        let path = &self.path;
+        let Some(file) = self.file.as_mut() else {
+            return Err(format!("File '{}' is already closed.", path));
         };
 
-        match fs::read_to_string(filename) {
-            Ok(contents) => Ok(LiteralValue::StringValue(contents)),
-            Err(e) => Err(format!("Error reading file: {}", e)),
+        match (method_name, args.as_slice()) {
+            // The next line without its line ending, or nil at the end of the file
+            ("read_line", []) => {
+                let mut line = String::new();
+                if file.read_line(&mut line).map_err(|e| format!("Error reading '{}': {}", path, e))? == 0 {
+                    return Ok(LiteralValue::Nil);
+                }
+                let length = line.trim_end_matches(['\n', '\r']).len();
+                line.truncate(length);
+                Ok(LiteralValue::StringValue(line))
+            }
+            // The rest of the file; `read(true)` turns "\r\n" line endings into "\n"
+            ("read", [] | [_]) => {
+                let normalize = args.first().map_or(Ok(false), |flag| normalize_flag(flag, "read"))?;
+                let mut rest = String::new();
+                file.read_to_string(&mut rest).map_err(|e| format!("Error reading '{}': {}", path, e))?;
+                Ok(LiteralValue::StringValue(normalize_line_endings(rest, normalize)))
+            }
+            ("write", [LiteralValue::StringValue(text)]) => {
+                file.get_mut().write_all(text.as_bytes()).map_err(|e| format!("Error writing '{}': {}", path, e))?;
+                Ok(LiteralValue::Nil)
+            }
+            ("write", _) => Err("write method takes exactly one string.".to_string()),
+            // Moves to byte `offset` from the start, so a large file can be resumed where it was left
+            ("seek", [offset]) => {
+                let offset = offset.as_int().and_then(|offset| u64::try_from(offset).ok())
+                    .ok_or_else(|| format!("seek method takes a byte offset of at least 0, got {}.", offset))?;
+                file.seek(SeekFrom::Start(offset)).map_err(|e| format!("Error seeking in '{}': {}", path, e))?;
+                Ok(LiteralValue::Nil)
+            }
+            ("seek", _) => Err("seek method takes exactly one byte offset.".to_string()),
+            ("read_line", _) => Err("read_line method takes no arguments.".to_string()),
+            ("read", _) => Err("read method takes at most whether to normalize line endings.".to_string()),
+            _ => Err(format!("Unknown method '{}' for file handles", method_name)),
         }
     }
 }
 
+// `for (line in file.lines())` reads one line per iteration, so only that line is held in memory
+pub fn lines(handle: Rc<RefCell<FileHandle>>, args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    if !args.is_empty() {
+        return Err("lines method takes no arguments.".to_string());
+    }
+    let name = format!("lines of {}", handle.borrow().path);
+
+    Ok(LiteralValue::Generator(Rc::new(Generator {
+        name,
+        run: Box::new(move |yield_to: YieldFn| {
+            loop {
+                let line = handle.borrow_mut().call_method("read_line", vec![])?;
+                if line == LiteralValue::Nil || !(yield_to.borrow_mut())(line)? {
+                    return Ok(());
+                }
+            }
+        }),
+    })))
+}
+
+impl std::fmt::Display for FileHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<file {}{}>", self.path, if self.file.is_none() { " (closed)" } else { "" })
+    }
+}
+
 pub fn write_file(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 2 {
         return Err("file_write requires exactly 2 arguments: filename and content.".to_string());
@@ -130,7 +435,7 @@ pub fn write_file(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
         _ => return Err("File content must be a string".to_string()),
     };
 
-    match fs::write(filename, content) {
+    match fs::write(native_path(filename), content) {
         Ok(_) => Ok(LiteralValue::True),
         Err(e) => Err(format!("Error writing to file: {}", e)),
     }
@@ -146,7 +451,7 @@ pub fn file_exists(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
         _ => return Err("File path must be a string".to_string()),
     };
 
-    if fs::metadata(filename).is_ok() {
+    if fs::metadata(native_path(filename)).is_ok() {
         Ok(LiteralValue::True)
     } else {
         Ok(LiteralValue::False)
@@ -163,8 +468,94 @@ pub fn delete_file(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
         _ => return Err("File path must be a string".to_string()),
     };
 
-    match fs::remove_file(filename) {
+    match fs::remove_file(native_path(filename)) {
         Ok(_) => Ok(LiteralValue::True),
         Err(e) => Err(format!("Error deleting file: {}", e)),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_are_interpreted_leniently() {
+        assert!(is_yes("Y") && is_yes("yes"));
+        assert!(!is_yes("") && !is_yes("nope"));
+
+        assert_eq!(option_index("2", 3), Some(1));
+        assert_eq!(option_index("0", 3), None);
+        assert_eq!(option_index("4", 3), None);
+        assert_eq!(option_index("two", 3), None);
+    }
+
+    #[test]
+    fn piped_lines_stream_until_the_loop_stops() {
+        let seen = Rc::new(RefCell::new(vec![]));
+        let collect = seen.clone();
+        let input = io::Cursor::new("GET /\r\nPOST /login\n\nGET /admin\n");
+        stream_lines(input, Rc::new(RefCell::new(move |line| {
+            collect.borrow_mut().push(line);
+            Ok(collect.borrow().len() < 3)
+        }))).unwrap();
+
+        let text = |s: &str| LiteralValue::StringValue(s.to_string());
+        assert_eq!(*seen.borrow(), vec![text("GET /"), text("POST /login"), text("")]);
+    }
+
+    #[test]
+    fn handles_stream_lines_and_seek() {
+        let path = std::env::temp_dir().join("recolon_stream_handle.txt");
+        fs::write(&path, "alpha\nbeta\ngamma").unwrap();
+        let text = |s: &str| LiteralValue::StringValue(s.to_string());
+
+        let LiteralValue::FileHandle(handle) = open_handle(vec![text(&path.display().to_string())]).unwrap() else {
+            panic!("open_handle should return a file handle");
+        };
+        let call = |name: &str, args: Vec<LiteralValue>| handle.borrow_mut().call_method(name, args);
+        assert_eq!(call("read_line", vec![]), Ok(text("alpha")));
+        assert_eq!(call("seek", vec![LiteralValue::Int(6)]), Ok(LiteralValue::Nil));
+        assert_eq!(call("read", vec![]), Ok(text("beta\ngamma")));
+        assert_eq!(call("read_line", vec![]), Ok(LiteralValue::Nil));
+
+        // lines() starts wherever the handle is, and stops when the loop stops asking
+        call("seek", vec![LiteralValue::Int(0)]).unwrap();
+        let LiteralValue::Generator(generator) = lines(handle.clone(), vec![]).unwrap() else {
+            panic!("lines should return a generator");
+        };
+        let seen = Rc::new(RefCell::new(vec![]));
+        let collect = seen.clone();
+        (generator.run)(Rc::new(RefCell::new(move |line| {
+            collect.borrow_mut().push(line);
+            Ok(collect.borrow().len() < 2)
+        }))).unwrap();
+        assert_eq!(*seen.borrow(), vec![text("alpha"), text("beta")]);
+        assert_eq!(call("read_line", vec![]), Ok(text("gamma")));
+
+        call("close", vec![]).unwrap();
+        assert_eq!(call("seek", vec![LiteralValue::Int(0)]), Err(format!("File '{}' is already closed.", path.display())));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn crlf_files_read_the_same_as_lf_files() {
+        let dir = std::env::temp_dir().join("recolon_crlf");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.txt"), "one\r\ntwo\r\n").unwrap();
+        let text = |s: &str| LiteralValue::StringValue(s.to_string());
+
+        // Windows separators work everywhere
+        let windows_path = format!("{}\\notes.txt", dir.display());
+        assert_eq!(file_exists(vec![text(&windows_path)]), Ok(LiteralValue::True));
+        assert_eq!(open_file(vec![text(&windows_path)]), Ok(text("one\r\ntwo\r\n")));
+        assert_eq!(open_file(vec![text(&windows_path), LiteralValue::True]), Ok(text("one\ntwo\n")));
+        assert_eq!(open_file(vec![text(&windows_path), text("yes")]), Err("open_file expects true or false to normalize line endings, got String.".to_string()));
+
+        let LiteralValue::FileHandle(handle) = open_handle(vec![text(&windows_path)]).unwrap() else {
+            panic!("open_handle should return a file handle");
+        };
+        assert_eq!(handle.borrow_mut().call_method("read_line", vec![]), Ok(text("one")));
+        assert_eq!(handle.borrow_mut().call_method("read", vec![LiteralValue::True]), Ok(text("two\n")));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::expr::Expr;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub const FUNCTIONS: &[&str] = &["send"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "send" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'send'")?;
+            let server = parser.expression()?; // Parse the server settings
+            parser.consume(TokenType::Comma, "Expected ',' after server settings")?;
+            let message = parser.expression()?; // Parse the message
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(fn_send(server, message))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_send(server: Expr, message: Expr) -> Expr {
+    Expr::PreFunction {
+        module: "mail".to_string(),
+        name: "send".to_string(),
+        args: vec![server, message],
+        span: Span::default(),
+    }
+}
+
+// Fields of a map or struct instance, so a `struct Message { ... }` works as well as a map literal
+fn fields(value: Option<&LiteralValue>, what: &str) -> Result<HashMap<String, LiteralValue>, String> {
+    match value {
+        Some(LiteralValue::Map(entries)) => Ok(entries.iter().filter_map(|(key, value)| match key {
+            MapKey::String(name) => Some((name.clone(), value.clone())),
+            _ => None,
+        }).collect()),
+        Some(LiteralValue::StructInst(instance)) => Ok(instance.borrow().fields.clone()),
+        _ => Err(format!("mail.send expects the {} as a map or struct.", what)),
+    }
+}
+
+fn text_field(fields: &HashMap<String, LiteralValue>, name: &str, what: &str) -> Result<Option<String>, String> {
+    match fields.get(name) {
+        None | Some(LiteralValue::Nil) => Ok(None),
+        Some(LiteralValue::StringValue(text)) => Ok(Some(text.clone())),
+        Some(other) => Err(format!("The {} '{}' must be a string, got {}.", what, name, other.to_type())),
+    }
+}
+
+// Sends a plain-text message over SMTP. The server settings are `host`, optional `port` (25),
+// and `username`/`password` for AUTH PLAIN; the message has `from`, `to` (one address or an
+// array), `subject` and `body`. There is no TLS, so use a local or trusted relay.
+pub fn send(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let server = fields(args.first(), "server settings")?;
+    let message = fields(args.get(1), "message")?;
+
+    let host = text_field(&server, "host", "server setting")?.ok_or("mail.send needs a 'host' in the server settings.")?;
+    let port = match server.get("port") {
+        None | Some(LiteralValue::Nil) => 25,
+        Some(LiteralValue::Int(port)) if (1..=65535).contains(port) => *port as u16,
+        Some(other) => return Err(format!("The server setting 'port' must be a port number, got {}.", other)),
+    };
+    let from = text_field(&message, "from", "message field")?.ok_or("mail.send needs a 'from' address.")?;
+    let to: Vec<String> = match message.get("to") {
+        Some(LiteralValue::StringValue(address)) => vec![address.clone()],
+        Some(LiteralValue::Array(addresses)) if !addresses.is_empty() => addresses.iter().map(|address| match address {
+            LiteralValue::StringValue(address) => Ok(address.clone()),
+            other => Err(format!("Recipient addresses must be strings, got {}.", other.to_type())),
+        }).collect::<Result<_, _>>()?,
+        _ => return Err("mail.send needs a 'to' address or array of addresses.".to_string()),
+    };
+    let subject = text_field(&message, "subject", "message field")?.unwrap_or_default();
+    // Line breaks in an address or the subject would let it smuggle in extra commands or headers
+    if to.iter().chain([&from, &subject]).any(|text| text.contains(['\r', '\n'])) {
+        return Err("Addresses and the subject of a mail cannot contain line breaks.".to_string());
+    }
+    let body = text_field(&message, "body", "message field")?.unwrap_or_default();
+
+    let stream = TcpStream::connect((host.as_str(), port)).map_err(|e| format!("Could not connect to {}:{}: {}", host, port, e))?;
+    let _ = stream.set_read_timeout(Some(TIMEOUT));
+    let _ = stream.set_write_timeout(Some(TIMEOUT));
+    let mut smtp = Smtp { reader: BufReader::new(stream.try_clone().map_err(|e| e.to_string())?), writer: stream };
+
+    smtp.expect(220)?;
+    smtp.command("EHLO localhost", 250)?;
+    if let Some(username) = text_field(&server, "username", "server setting")? {
+        let password = text_field(&server, "password", "server setting")?.unwrap_or_default();
+        smtp.command(&format!("AUTH PLAIN {}", base64(format!("\0{}\0{}", username, password).as_bytes())), 235)?;
+    }
+    smtp.command(&format!("MAIL FROM:<{}>", from), 250)?;
+    for address in &to {
+        smtp.command(&format!("RCPT TO:<{}>", address), 250)?;
+    }
+    smtp.command("DATA", 354)?;
+
+    let mut data = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+        from, to.join(", "), subject
+    );
+    for line in body.lines() {
+        // A line starting with '.' is escaped so it can't end the message early
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push('.');
+    smtp.command(&data, 250)?;
+    let _ = smtp.command("QUIT", 221);
+
+    Ok(LiteralValue::True)
+}
+
+struct Smtp {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Smtp {
+    fn command(&mut self, line: &str, code: u16) -> Result<(), String> {
+        write!(self.writer, "{}\r\n", line).map_err(|e| format!("SMTP connection failed: {}", e))?;
+        self.expect(code)
+    }
+
+    // Reads a reply, which may span several `250-...` lines, and checks its status code
+    fn expect(&mut self, code: u16) -> Result<(), String> {
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return Err("SMTP server closed the connection.".to_string()),
+                Ok(_) => (),
+                Err(e) => return Err(format!("SMTP connection failed: {}", e)),
+            }
+            // `get` instead of slicing, as a reply can put any character where the code should be
+            let (Some(status), Some(separator)) = (line.get(..3), line.get(3..4)) else {
+                return Err(format!("Unexpected SMTP reply: {}", line.trim_end()));
+            };
+            if !status.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("Unexpected SMTP reply: {}", line.trim_end()));
+            }
+            if separator == "-" {
+                continue;
+            }
+            if status != code.to_string() {
+                return Err(format!("SMTP server refused: {}", line.trim_end()));
+            }
+            return Ok(());
+        }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn base64_pads_partial_chunks() {
+        assert_eq!(base64(b"\0user\0pass"), "AHVzZXIAcGFzcw==");
+        assert_eq!(base64(b"abc"), "YWJj");
+        assert_eq!(base64(b"ab"), "YWI=");
+    }
+
+    #[test]
+    fn send_speaks_smtp_to_the_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // A server that accepts everything and records what it was sent
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut received = vec![];
+            writer.write_all(b"220 ready\r\n").unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                received.push(line.trim_end().to_string());
+                let reply: &[u8] = match line.trim_end() {
+                    "EHLO localhost" => b"250-hello\r\n250 OK\r\n",
+                    "DATA" => b"354 go on\r\n",
+                    "QUIT" => b"221 bye\r\n",
+                    line if line.starts_with("AUTH") => b"235 OK\r\n",
+                    line if line.starts_with("MAIL") || line.starts_with("RCPT") || line == "." => b"250 OK\r\n",
+                    _ => continue,
+                };
+                writer.write_all(reply).unwrap();
+            }
+            received
+        });
+
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        let map = |entries: Vec<(&str, LiteralValue)>| LiteralValue::Map(entries.into_iter().map(|(key, value)| (MapKey::from(key), value)).collect());
+        let server_cfg = map(vec![("host", text("127.0.0.1")), ("port", LiteralValue::Int(port as i64)), ("username", text("user")), ("password", text("pass"))]);
+        let message = map(vec![("from", text("bot@example.com")), ("to", text("ops@example.com")), ("subject", text("Disk")), ("body", text("90% full\n.hidden"))]);
+
+        assert_eq!(send(vec![server_cfg, message]), Ok(LiteralValue::True));
+        let received = server.join().unwrap();
+        assert!(received.contains(&"AUTH PLAIN AHVzZXIAcGFzcw==".to_string()));
+        assert!(received.contains(&"RCPT TO:<ops@example.com>".to_string()));
+        assert!(received.contains(&"Subject: Disk".to_string()));
+        assert!(received.contains(&"..hidden".to_string()));
+        assert_eq!(received.last(), Some(&"QUIT".to_string()));
+    }
+
+    #[test]
+    fn garbled_replies_are_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all("22\u{e9} ready\r\n".as_bytes()).unwrap();
+        });
+
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        let map = |entries: Vec<(&str, LiteralValue)>| LiteralValue::Map(entries.into_iter().map(|(key, value)| (MapKey::from(key), value)).collect());
+        let server_cfg = map(vec![("host", text("127.0.0.1")), ("port", LiteralValue::Int(port as i64))]);
+        let message = map(vec![("from", text("bot@example.com")), ("to", text("ops@example.com"))]);
+
+        assert_eq!(send(vec![server_cfg, message]), Err("Unexpected SMTP reply: 22\u{e9} ready".to_string()));
+        server.join().unwrap();
+    }
+}
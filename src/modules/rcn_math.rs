@@ -1,24 +1,40 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use num_traits::Signed;
 use rand::Rng;
 
 use crate::expr::Expr;
 use crate::literal_value::LiteralValue;
-use crate::scanner::TokenType;
+use crate::scanner::{Span, TokenType};
 use crate::parser::Parser;
+use crate::types::rcn_struct::StructInstance;
+
+pub const FUNCTIONS: &[&str] = &[
+    "pi", "e", "tau", "nan", "floor", "ceil", "round", "sqrt", "abs", "max", "min", "random",
+    "pow", "lgm", "cos", "sin", "tan", "degrees", "radians", "vec2", "vec3", "dot", "cross",
+    "normalize", "matmul",
+];
 
 pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String>{
     match identifier.as_str() {
         // Constants
         "pi" => Ok(Expr::Literal {
-            value: LiteralValue::Number(get_pi()), // Call the function to get PI
+            value: LiteralValue::Float(get_pi()), // Call the function to get PI
+            span: Span::default(),
         }),
         "e" => Ok(Expr::Literal {
-            value: LiteralValue::Number(get_e()), // Call the function to get PI
+            value: LiteralValue::Float(get_e()), // Call the function to get PI
+            span: Span::default(),
         }),
         "tau" => Ok(Expr::Literal {
-            value: LiteralValue::Number(get_tau()), // Call the function to get PI
+            value: LiteralValue::Float(get_tau()), // Call the function to get PI
+            span: Span::default(),
         }),
         "nan" => Ok(Expr::Literal {
             value: LiteralValue::Nil, // Call the function to get PI
+            span: Span::default(),
         }),
 
         //Number representative
@@ -150,20 +166,41 @@ pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, Strin
 
             Ok(get_radians(arg))
         },
+
+        // Vectors and matrices
+        "vec2" | "vec3" | "dot" | "cross" | "normalize" | "matmul" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            let mut args = vec![];
+            if !parser.check(TokenType::RightParen) {
+                args.push(parser.expression()?);
+                while parser.check(TokenType::Comma) {
+                    parser.consume(TokenType::Comma, "Expected ',' between arguments")?;
+                    args.push(parser.expression()?);
+                }
+            }
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(Expr::PreFunction {
+                module: "math".to_string(),
+                name: identifier,
+                args,
+                span: Span::default(),
+            })
+        },
         _ => Err(format!("Unknown identifier '{}'.", identifier)),
     }
 }
 
-pub fn get_pi() -> f32 {
-    std::f32::consts::PI
+pub fn get_pi() -> f64 {
+    std::f64::consts::PI
 }
 
-pub fn get_e() -> f32 {
-    std::f32::consts::E
+pub fn get_e() -> f64 {
+    std::f64::consts::E
 }
 
-pub fn get_tau() -> f32 {
-    std::f32::consts::TAU
+pub fn get_tau() -> f64 {
+    std::f64::consts::TAU
 }
 
 pub(crate) fn get_floor(arg: Expr) -> Expr {
@@ -171,6 +208,7 @@ pub(crate) fn get_floor(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "floor".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
@@ -179,6 +217,7 @@ pub(crate) fn get_ceil(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "ceil".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
@@ -187,6 +226,7 @@ pub(crate) fn get_round(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "round".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
@@ -195,6 +235,7 @@ pub(crate) fn get_sqrt(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "sqrt".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
@@ -203,6 +244,7 @@ pub(crate) fn get_abs(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "abs".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
@@ -211,6 +253,7 @@ pub(crate) fn get_max(arg1: Expr, arg2: Expr) -> Expr {
         module: "math".to_string(),
         name: "max".to_string(),
         args: vec![arg1, arg2],
+        span: Span::default(),
     }
 }
 
@@ -219,6 +262,7 @@ pub(crate) fn get_min(arg1: Expr, arg2: Expr) -> Expr {
         module: "math".to_string(),
         name: "min".to_string(),
         args: vec![arg1, arg2],
+        span: Span::default(),
     }
 }
 
@@ -227,6 +271,7 @@ pub(crate) fn get_rand(arg1: Expr, arg2: Expr) -> Expr {
         module: "math".to_string(),
         name: "random".to_string(),
         args: vec![arg1, arg2],
+        span: Span::default(),
     }
 }
 
@@ -235,6 +280,7 @@ pub(crate) fn get_pow(arg1: Expr, arg2: Expr) -> Expr {
         module: "math".to_string(),
         name: "pow".to_string(),
         args: vec![arg1, arg2],
+        span: Span::default(),
     }
 }
 
@@ -248,6 +294,7 @@ pub(crate) fn get_log(arg: Expr, base: Option<Expr>) -> Expr {
         module: "math".to_string(),
         name: "lgm".to_string(),
         args,
+        span: Span::default(),
     }
 }
 
@@ -256,6 +303,7 @@ pub(crate) fn get_cos(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "cos".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 pub(crate) fn get_sin(arg: Expr) -> Expr {
@@ -263,6 +311,7 @@ pub(crate) fn get_sin(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "sin".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 pub(crate) fn get_tan(arg: Expr) -> Expr {
@@ -270,6 +319,7 @@ pub(crate) fn get_tan(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "tan".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
@@ -278,6 +328,7 @@ pub(crate) fn get_degrees(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "degrees".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
@@ -286,16 +337,27 @@ pub(crate) fn get_radians(arg: Expr) -> Expr {
         module: "math".to_string(),
         name: "radians".to_string(),
         args: vec![arg],
+        span: Span::default(),
     }
 }
 
+// Largest result of an integer pow(), about 300,000 digits
+const MAX_POW_BITS: f64 = 1_000_000.0;
+
+// A rounded float as an Int, so it can index arrays; infinities and NaN stay Floats
+fn whole(x: f64) -> LiteralValue {
+    let value = LiteralValue::Float(x);
+    value.as_int().map_or(value, LiteralValue::Int)
+}
+
 // Define the functions within the module
 pub fn floor(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("floor() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.floor())),
+    match &args[0] {
+        integer @ (LiteralValue::Int(_) | LiteralValue::BigInt(_)) => Ok(integer.clone()),
+        LiteralValue::Float(x) => Ok(whole(x.floor())),
         _ => Err("floor() requires a numeric argument.".to_string()),
     }
 }
@@ -304,8 +366,9 @@ pub fn ceil(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("ceil() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.ceil())),
+    match &args[0] {
+        integer @ (LiteralValue::Int(_) | LiteralValue::BigInt(_)) => Ok(integer.clone()),
+        LiteralValue::Float(x) => Ok(whole(x.ceil())),
         _ => Err("ceil() requires a numeric argument.".to_string()),
     }
 }
@@ -314,8 +377,9 @@ pub fn round(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("round() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.round())),
+    match &args[0] {
+        integer @ (LiteralValue::Int(_) | LiteralValue::BigInt(_)) => Ok(integer.clone()),
+        LiteralValue::Float(x) => Ok(whole(x.round())),
         _ => Err("round() requires a numeric argument.".to_string()),
     }
 }
@@ -324,8 +388,8 @@ pub fn sqrt(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("sqrt() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.sqrt())),
+    match args[0].as_f64() {
+        Some(x) => Ok(LiteralValue::Float(x.sqrt())),
         _ => Err("sqrt() requires a numeric argument.".to_string()),
     }
 }
@@ -334,8 +398,9 @@ pub fn abs(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("abs() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.abs())),
+    match &args[0] {
+        LiteralValue::Int(_) | LiteralValue::BigInt(_) => Ok(LiteralValue::from_bigint(args[0].as_bigint().unwrap_or_default().abs())),
+        LiteralValue::Float(x) => Ok(LiteralValue::Float(x.abs())),
         _ => Err("abs() requires a numeric argument.".to_string()),
     }
 }
@@ -344,12 +409,12 @@ pub fn max(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 2 {
         return Err("max() requires two arguments.".to_string());
     }
-    match (&args[0], &args[1])  {
-        (LiteralValue::Number(a) , LiteralValue::Number(b)) => {
+    match (args[0].as_f64(), args[1].as_f64())  {
+        (Some(a) , Some(b)) => {
             if a >= b {
-                Ok(LiteralValue::Number(*a))
+                Ok(args[0].clone())
             } else {
-                Ok(LiteralValue::Number(*b))
+                Ok(args[1].clone())
             }
         }
         _ => Err("max() requires two numeric arguments.".to_string()),
@@ -360,12 +425,12 @@ pub fn min(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 2 {
         return Err("min() requires two arguments.".to_string());
     }
-    match (&args[0], &args[1]) {
-        (LiteralValue::Number(a), LiteralValue::Number(b)) => {
+    match (args[0].as_f64(), args[1].as_f64()) {
+        (Some(a), Some(b)) => {
             if a <= b {
-                Ok(LiteralValue::Number(*a))
+                Ok(args[0].clone())
             } else {
-                Ok(LiteralValue::Number(*b))
+                Ok(args[1].clone())
             }
         }
         _ => Err("min() requires two numeric arguments.".to_string()),
@@ -377,10 +442,12 @@ pub fn random(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
         return Err("random() requires two arguments.".to_string());
     }
     match (&args[0], &args[1]) {
-        (LiteralValue::Number(a), LiteralValue::Number(b)) => {
+        (LiteralValue::Int(a), LiteralValue::Int(b)) if a <= b => Ok(LiteralValue::Int(rand::thread_rng().gen_range(*a..=*b))),
+        (a, b) if a.as_f64().is_some() && b.as_f64().is_some() => {
+            let (a, b) = (a.as_f64().unwrap_or_default(), b.as_f64().unwrap_or_default());
             if a <= b {
-                let num = rand::thread_rng().gen_range(*a..*b);
-                Ok(LiteralValue::Number(num.round()))
+                let num = rand::thread_rng().gen_range(a..=b);
+                Ok(whole(num.round()))
             } else {
                Err("First argument should be lower than the second argument.".to_string())
             }
@@ -394,10 +461,18 @@ pub fn pow(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
         return Err("pow() requires two arguments.".to_string());
     }
     match (&args[0], &args[1]) {
-        (LiteralValue::Number(a), LiteralValue::Number(b)) => {
-            let result = a.powf(*b);
+        // Whole powers of integers stay exact, growing into a BigInt when needed
+        (a @ (LiteralValue::Int(_) | LiteralValue::BigInt(_)), LiteralValue::Int(b)) if *b >= 0 => {
+            let a = a.as_bigint().unwrap_or_default();
+            if a.bits() as f64 * *b as f64 > MAX_POW_BITS {
+                return Err(format!("pow({}, {}) has too many digits.", a, b));
+            }
+            Ok(LiteralValue::from_bigint(a.pow(*b as u32)))
+        }
+        (a, b) if a.as_f64().is_some() && b.as_f64().is_some() => {
+            let result = a.as_f64().unwrap_or_default().powf(b.as_f64().unwrap_or_default());
             if result.is_finite() {
-                Ok(LiteralValue::Number(result))
+                Ok(LiteralValue::Float(result))
             } else {
                 Err("Result is not a finite number.".to_string())
             }
@@ -409,10 +484,10 @@ pub fn pow(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
 pub fn lgm(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     match args.len() {
         1 => {
-            if let LiteralValue::Number(x) = args[0] {
+            if let Some(x) = args[0].as_f64() {
                 if x > 0.0 {
                     let result = x.ln(); // Natural logarithm
-                    Ok(LiteralValue::Number(result))
+                    Ok(LiteralValue::Float(result))
                 } else {
                     Err("Logarithm undefined for non-positive values.".to_string())
                 }
@@ -421,10 +496,10 @@ pub fn lgm(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
             }
         }
         2 => {
-            if let (LiteralValue::Number(x), LiteralValue::Number(base)) = (&args[0], &args[1]) {
-                if *x > 0.0 && *base > 0.0 && *base != 1.0 {
-                    let result = x.log(*base); // Logarithm with specified base
-                    Ok(LiteralValue::Number(result))
+            if let (Some(x), Some(base)) = (args[0].as_f64(), args[1].as_f64()) {
+                if x > 0.0 && base > 0.0 && base != 1.0 {
+                    let result = x.log(base); // Logarithm with specified base
+                    Ok(LiteralValue::Float(result))
                 } else {
                     Err("Logarithm requires positive x and base != 1.".to_string())
                 }
@@ -440,8 +515,8 @@ pub fn cos(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("ceil() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.cos())),
+    match args[0].as_f64() {
+        Some(x) => Ok(LiteralValue::Float(x.cos())),
         _ => Err("ceil() requires a numeric argument.".to_string()),
     }
 }
@@ -450,8 +525,8 @@ pub fn sin(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("ceil() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.sin())),
+    match args[0].as_f64() {
+        Some(x) => Ok(LiteralValue::Float(x.sin())),
         _ => Err("ceil() requires a numeric argument.".to_string()),
     }
 }
@@ -460,8 +535,8 @@ pub fn tan(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("ceil() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.tan())),
+    match args[0].as_f64() {
+        Some(x) => Ok(LiteralValue::Float(x.tan())),
         _ => Err("ceil() requires a numeric argument.".to_string()),
     }
 }
@@ -470,8 +545,8 @@ pub fn degrees(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("ceil() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.to_degrees())),
+    match args[0].as_f64() {
+        Some(x) => Ok(LiteralValue::Float(x.to_degrees())),
         _ => Err("ceil() requires a numeric argument.".to_string()),
     }
 }
@@ -480,8 +555,157 @@ pub fn radians(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
     if args.len() != 1 {
         return Err("ceil() requires exactly one argument.".to_string());
     }
-    match args[0] {
-        LiteralValue::Number(x) => Ok(LiteralValue::Number(x.to_radians())),
+    match args[0].as_f64() {
+        Some(x) => Ok(LiteralValue::Float(x.to_radians())),
         _ => Err("ceil() requires a numeric argument.".to_string()),
     }
-}
\ No newline at end of file
+}
+
+const AXES: [&str; 3] = ["x", "y", "z"];
+
+// A Vec2 or Vec3 struct with Float fields x, y and z
+fn vector(components: &[f64]) -> LiteralValue {
+    let fields: HashMap<String, LiteralValue> = AXES.iter()
+        .zip(components)
+        .map(|(axis, component)| (axis.to_string(), LiteralValue::Float(*component)))
+        .collect();
+
+    LiteralValue::StructInst(Rc::new(RefCell::new(StructInstance {
+        name: format!("Vec{}", components.len()),
+        fields,
+        frozen: false,
+    })))
+}
+
+// The components of a Vec2 or Vec3, or of any struct with numeric x, y and optionally z fields
+fn components(value: &LiteralValue, function: &str) -> Result<Vec<f64>, String> {
+    let LiteralValue::StructInst(instance) = value else {
+        return Err(format!("{}() expects vectors from math.vec2 or math.vec3, got {}.", function, value.to_type()));
+    };
+    let instance = instance.borrow();
+    let dimensions = if instance.fields.contains_key("z") { 3 } else { 2 };
+
+    AXES[..dimensions].iter()
+        .map(|axis| instance.get_field(axis).and_then(LiteralValue::as_f64)
+            .ok_or_else(|| format!("{}() expects a vector, but {} has no numeric '{}' field.", function, instance.name, axis)))
+        .collect()
+}
+
+fn arg_count(args: &[LiteralValue], count: usize, function: &str) -> Result<(), String> {
+    if args.len() != count {
+        return Err(format!("{}() requires exactly {} argument{}.", function, count, if count == 1 { "" } else { "s" }));
+    }
+    Ok(())
+}
+
+pub fn vec2(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    new_vector(args, 2, "vec2")
+}
+
+pub fn vec3(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    new_vector(args, 3, "vec3")
+}
+
+fn new_vector(args: Vec<LiteralValue>, dimensions: usize, function: &str) -> Result<LiteralValue, String> {
+    arg_count(&args, dimensions, function)?;
+    let components = args.iter()
+        .map(|arg| arg.as_f64().ok_or_else(|| format!("{}() requires numeric arguments.", function)))
+        .collect::<Result<Vec<f64>, String>>()?;
+    Ok(vector(&components))
+}
+
+pub fn dot(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    arg_count(&args, 2, "dot")?;
+    let (a, b) = (components(&args[0], "dot")?, components(&args[1], "dot")?);
+    if a.len() != b.len() {
+        return Err(format!("dot() needs vectors of the same size, got Vec{} and Vec{}.", a.len(), b.len()));
+    }
+    Ok(LiteralValue::Float(a.iter().zip(&b).map(|(a, b)| a * b).sum()))
+}
+
+pub fn cross(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    arg_count(&args, 2, "cross")?;
+    let (a, b) = (components(&args[0], "cross")?, components(&args[1], "cross")?);
+    if a.len() != 3 || b.len() != 3 {
+        return Err("cross() is only defined for Vec3.".to_string());
+    }
+    Ok(vector(&[
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]))
+}
+
+pub fn normalize(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    arg_count(&args, 1, "normalize")?;
+    let v = components(&args[0], "normalize")?;
+    let length = v.iter().map(|c| c * c).sum::<f64>().sqrt();
+    if length == 0.0 {
+        return Err("Cannot normalize a zero-length vector.".to_string());
+    }
+    Ok(vector(&v.iter().map(|c| c / length).collect::<Vec<f64>>()))
+}
+
+// The rows of a matrix written as an array of equally long arrays of numbers
+fn matrix(value: &LiteralValue) -> Result<Vec<Vec<f64>>, String> {
+    let invalid = || "matmul() expects a matrix as an array of rows, like [[1, 0], [0, 1]].".to_string();
+    let LiteralValue::Array(rows) = value else { return Err(invalid()) };
+
+    let rows = rows.iter()
+        .map(|row| match row {
+            LiteralValue::Array(cells) => cells.iter().map(|cell| cell.as_f64().ok_or_else(invalid)).collect(),
+            _ => Err(invalid()),
+        })
+        .collect::<Result<Vec<Vec<f64>>, String>>()?;
+    if rows.is_empty() || rows.iter().any(|row| row.is_empty() || row.len() != rows[0].len()) {
+        return Err(invalid());
+    }
+    Ok(rows)
+}
+
+// Matrix times matrix, or matrix times vector to transform a Vec2 or Vec3
+pub fn matmul(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    arg_count(&args, 2, "matmul")?;
+    let a = matrix(&args[0])?;
+
+    if let LiteralValue::StructInst(_) = &args[1] {
+        let v = components(&args[1], "matmul")?;
+        if a[0].len() != v.len() || a.len() != v.len() {
+            return Err(format!("matmul() needs a {0}x{0} matrix to transform a Vec{0}, got {1}x{2}.", v.len(), a.len(), a[0].len()));
+        }
+        return Ok(vector(&a.iter().map(|row| row.iter().zip(&v).map(|(m, c)| m * c).sum()).collect::<Vec<f64>>()));
+    }
+
+    let b = matrix(&args[1])?;
+    if a[0].len() != b.len() {
+        return Err(format!("matmul() cannot multiply a {}x{} matrix by a {}x{} matrix.", a.len(), a[0].len(), b.len(), b[0].len()));
+    }
+    Ok(LiteralValue::Array(a.iter()
+        .map(|row| LiteralValue::Array((0..b[0].len())
+            .map(|column| LiteralValue::Float(row.iter().zip(&b).map(|(m, b_row)| m * b_row[column]).sum()))
+            .collect()))
+        .collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vectors_and_matrices() {
+        let unit = |x: f64, y: f64, z: f64| vec3(vec![LiteralValue::Float(x), LiteralValue::Float(y), LiteralValue::Float(z)]).unwrap();
+
+        assert_eq!(dot(vec![unit(1.0, 2.0, 3.0), unit(4.0, 5.0, 6.0)]), Ok(LiteralValue::Float(32.0)));
+        assert_eq!(components(&cross(vec![unit(1.0, 0.0, 0.0), unit(0.0, 1.0, 0.0)]).unwrap(), "cross"), Ok(vec![0.0, 0.0, 1.0]));
+        assert_eq!(components(&normalize(vec![vec2(vec![LiteralValue::Int(3), LiteralValue::Int(4)]).unwrap()]).unwrap(), "normalize"), Ok(vec![0.6, 0.8]));
+        assert_eq!(normalize(vec![unit(0.0, 0.0, 0.0)]), Err("Cannot normalize a zero-length vector.".to_string()));
+
+        let matrix = |rows: &[&[i64]]| LiteralValue::Array(rows.iter()
+            .map(|row| LiteralValue::Array(row.iter().map(|cell| LiteralValue::Int(*cell)).collect()))
+            .collect());
+        let rotate = matrix(&[&[0, -1, 0], &[1, 0, 0], &[0, 0, 1]]);
+        assert_eq!(components(&matmul(vec![rotate.clone(), unit(1.0, 0.0, 0.0)]).unwrap(), "matmul"), Ok(vec![0.0, 1.0, 0.0]));
+        assert_eq!(matmul(vec![matrix(&[&[1, 2], &[3, 4]]), matrix(&[&[5], &[6]])]), Ok(matrix(&[&[17], &[39]])));
+        assert_eq!(matmul(vec![rotate, matrix(&[&[1, 2]])]), Err("matmul() cannot multiply a 3x3 matrix by a 1x2 matrix.".to_string()));
+    }
+}
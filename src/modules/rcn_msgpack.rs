@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+use crate::expr::Expr;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::modules::rcn_compress::{byte_array, bytes_arg};
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["encode", "decode"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "encode" | "decode" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            let value = parser.expression()?;
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(Expr::PreFunction {
+                module: "msgpack".to_string(),
+                name: identifier,
+                args: vec![value],
+                span: Span::default(),
+            })
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+// MessagePack for exchanging data with other runtimes, as an array of bytes. Maps are written
+// with sorted keys so equal values give equal bytes. Like JSON, struct instances become maps
+// of their fields, and decimals become strings as MessagePack has no exact decimal type.
+pub fn encode(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let mut output = Vec::new();
+    write_value(&args[0], &mut output, &mut vec![])?;
+    Ok(byte_array(output))
+}
+
+// Binary data comes back as an array of bytes; unsigned integers above the int range as big ints
+pub fn decode(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let data = bytes_arg(&args, "msgpack.decode")?;
+    let mut reader = Reader { data: &data, position: 0 };
+    let value = reader.value()?;
+    if reader.position != data.len() {
+        return Err(format!("msgpack.decode found {} bytes after the value.", data.len() - reader.position));
+    }
+    Ok(value)
+}
+
+fn write_value(value: &LiteralValue, output: &mut Vec<u8>, seen: &mut Vec<usize>) -> Result<(), String> {
+    match value {
+        LiteralValue::Nil => output.push(0xc0),
+        LiteralValue::False => output.push(0xc2),
+        LiteralValue::True => output.push(0xc3),
+        LiteralValue::Int(x) => write_int(*x, output),
+        LiteralValue::BigInt(x) => match x.to_u64() {
+            Some(x) => {
+                output.push(0xcf);
+                output.extend(x.to_be_bytes());
+            }
+            None => return Err(format!("msgpack.encode can only write integers from -2^63 to 2^64 - 1, got {}.", x)),
+        },
+        LiteralValue::Float(x) => {
+            output.push(0xcb);
+            output.extend(x.to_be_bytes());
+        }
+        LiteralValue::Decimal(x) => write_str(&x.to_string(), output),
+        LiteralValue::StringValue(text) => write_str(text, output),
+        LiteralValue::Array(elements) | LiteralValue::Tuple(elements) => {
+            write_header(elements.len(), [0x90, 0xdc, 0xdd], output)?;
+            for element in elements {
+                write_value(element, output, seen)?;
+            }
+        }
+        LiteralValue::Map(entries) => {
+            let mut entries: Vec<_> = entries.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            let entries: Vec<_> = entries.into_iter().map(|(key, value)| (key.to_value(), value)).collect();
+            write_map(entries.iter().map(|(key, value)| (key, *value)), entries.len(), output, seen)?;
+        }
+        LiteralValue::StructInst(instance) => {
+            let pointer = Rc::as_ptr(instance) as usize;
+            if seen.contains(&pointer) {
+                return Err("msgpack.encode cannot write a cyclic value.".to_string());
+            }
+            seen.push(pointer);
+            let instance = instance.borrow();
+            let mut fields: Vec<_> = instance.fields.iter().collect();
+            fields.sort_by_key(|(name, _)| *name);
+            let fields: Vec<_> = fields.into_iter().map(|(name, value)| (LiteralValue::StringValue(name.clone()), value)).collect();
+            write_map(fields.iter().map(|(name, value)| (name, *value)), fields.len(), output, seen)?;
+            seen.pop();
+        }
+        other => return Err(format!("msgpack.encode cannot write {}.", other.to_type())),
+    }
+    Ok(())
+}
+
+fn write_map<'a>(entries: impl Iterator<Item = (&'a LiteralValue, &'a LiteralValue)>, length: usize, output: &mut Vec<u8>, seen: &mut Vec<usize>) -> Result<(), String> {
+    write_header(length, [0x80, 0xde, 0xdf], output)?;
+    for (key, value) in entries {
+        write_value(key, output, seen)?;
+        write_value(value, output, seen)?;
+    }
+    Ok(())
+}
+
+// The smallest form that holds the integer
+fn write_int(x: i64, output: &mut Vec<u8>) {
+    match x {
+        0..=0x7f => output.push(x as u8),
+        -32..=-1 => output.push(x as i8 as u8),
+        0x80..=0xff => output.extend([0xcc, x as u8]),
+        0x100..=0xffff => {
+            output.push(0xcd);
+            output.extend((x as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            output.push(0xce);
+            output.extend((x as u32).to_be_bytes());
+        }
+        -0x80..=-33 => output.extend([0xd0, x as i8 as u8]),
+        -0x8000..=-0x81 => {
+            output.push(0xd1);
+            output.extend((x as i16).to_be_bytes());
+        }
+        -0x8000_0000..=-0x8001 => {
+            output.push(0xd2);
+            output.extend((x as i32).to_be_bytes());
+        }
+        _ if x > 0 => {
+            output.push(0xcf);
+            output.extend((x as u64).to_be_bytes());
+        }
+        _ => {
+            output.push(0xd3);
+            output.extend(x.to_be_bytes());
+        }
+    }
+}
+
+fn write_str(text: &str, output: &mut Vec<u8>) {
+    let length = text.len();
+    match length {
+        0..=31 => output.push(0xa0 | length as u8),
+        0x20..=0xff => output.extend([0xd9, length as u8]),
+        0x100..=0xffff => {
+            output.push(0xda);
+            output.extend((length as u16).to_be_bytes());
+        }
+        _ => {
+            output.push(0xdb);
+            output.extend((length as u32).to_be_bytes());
+        }
+    }
+    output.extend(text.as_bytes());
+}
+
+// The header of an array or map: its fix form when short, then the 16 and 32 bit forms
+fn write_header(length: usize, [fix, long, longer]: [u8; 3], output: &mut Vec<u8>) -> Result<(), String> {
+    if length <= 15 {
+        output.push(fix | length as u8);
+    } else if let Ok(length) = u16::try_from(length) {
+        output.push(long);
+        output.extend(length.to_be_bytes());
+    } else if let Ok(length) = u32::try_from(length) {
+        output.push(longer);
+        output.extend(length.to_be_bytes());
+    } else {
+        return Err("msgpack.encode cannot write more than 2^32 - 1 elements.".to_string());
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], String> {
+        let bytes = self.position.checked_add(count).and_then(|end| self.data.get(self.position..end))
+            .ok_or_else(|| format!("msgpack.decode ran out of bytes at byte {}.", self.position))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    // A big-endian unsigned number of `count` bytes, for lengths and integers
+    fn uint(&mut self, count: usize) -> Result<u64, String> {
+        Ok(self.take(count)?.iter().fold(0, |number, byte| number << 8 | *byte as u64))
+    }
+
+    fn value(&mut self) -> Result<LiteralValue, String> {
+        let start = self.position;
+        let marker = self.byte()?;
+        match marker {
+            0x00..=0x7f => Ok(LiteralValue::Int(marker as i64)),
+            0xe0..=0xff => Ok(LiteralValue::Int(marker as i8 as i64)),
+            0xc0 => Ok(LiteralValue::Nil),
+            0xc2 => Ok(LiteralValue::False),
+            0xc3 => Ok(LiteralValue::True),
+            0xcc..=0xcf => {
+                let x = self.uint(1 << (marker - 0xcc))?;
+                Ok(i64::try_from(x).map_or_else(|_| LiteralValue::BigInt(BigInt::from(x)), LiteralValue::Int))
+            }
+            0xd0..=0xd3 => {
+                let bytes = 1 << (marker - 0xd0);
+                let x = self.uint(bytes)?;
+                // Sign-extend from the integer's width
+                let shift = 64 - bytes * 8;
+                Ok(LiteralValue::Int(((x << shift) as i64) >> shift))
+            }
+            0xca => Ok(LiteralValue::Float(f32::from_bits(self.uint(4)? as u32) as f64)),
+            0xcb => Ok(LiteralValue::Float(f64::from_bits(self.uint(8)?))),
+            0xa0..=0xbf => self.str((marker & 0x1f) as usize),
+            0xd9..=0xdb => {
+                let length = self.uint(1 << (marker - 0xd9))? as usize;
+                self.str(length)
+            }
+            0xc4..=0xc6 => {
+                let length = self.uint(1 << (marker - 0xc4))? as usize;
+                Ok(byte_array(self.take(length)?.to_vec()))
+            }
+            0x90..=0x9f => self.array((marker & 0x0f) as usize),
+            0xdc | 0xdd => {
+                let length = self.uint(if marker == 0xdc { 2 } else { 4 })? as usize;
+                self.array(length)
+            }
+            0x80..=0x8f => self.map((marker & 0x0f) as usize),
+            0xde | 0xdf => {
+                let length = self.uint(if marker == 0xde { 2 } else { 4 })? as usize;
+                self.map(length)
+            }
+            _ => Err(format!("msgpack.decode cannot read the type 0x{:02x} at byte {}.", marker, start)),
+        }
+    }
+
+    fn str(&mut self, length: usize) -> Result<LiteralValue, String> {
+        let start = self.position;
+        let text = std::str::from_utf8(self.take(length)?)
+            .map_err(|_| format!("msgpack.decode found a string that is not UTF-8 at byte {}.", start))?;
+        Ok(LiteralValue::StringValue(text.to_string()))
+    }
+
+    fn array(&mut self, length: usize) -> Result<LiteralValue, String> {
+        (0..length).map(|_| self.value()).collect::<Result<_, _>>().map(LiteralValue::Array)
+    }
+
+    fn map(&mut self, length: usize) -> Result<LiteralValue, String> {
+        let mut entries = HashMap::new();
+        for _ in 0..length {
+            let key = MapKey::from_value(&self.value()?)?;
+            entries.insert(key, self.value()?);
+        }
+        Ok(LiteralValue::Map(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(value: LiteralValue) -> Vec<i64> {
+        match encode(vec![value]).unwrap() {
+            LiteralValue::Array(bytes) => bytes.iter().map(|byte| byte.as_int().unwrap()).collect(),
+            other => panic!("Expected bytes, got {}", other),
+        }
+    }
+
+    #[test]
+    fn values_encode_to_the_smallest_forms_and_decode_back() {
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        assert_eq!(bytes(LiteralValue::Int(5)), [0x05]);
+        assert_eq!(bytes(LiteralValue::Int(-1)), [0xff]);
+        assert_eq!(bytes(LiteralValue::Int(200)), [0xcc, 200]);
+        assert_eq!(bytes(LiteralValue::Int(-200)), [0xd1, 0xff, 0x38]);
+        assert_eq!(bytes(text("hi")), [0xa2, b'h' as i64, b'i' as i64]);
+        assert_eq!(bytes(LiteralValue::Map(HashMap::from([(MapKey::from("b"), LiteralValue::Nil), (MapKey::from("a"), LiteralValue::True)]))),
+            [0x82, 0xa1, b'a' as i64, 0xc3, 0xa1, b'b' as i64, 0xc0]);
+
+        let value = LiteralValue::Array(vec![
+            LiteralValue::Int(i64::MIN), LiteralValue::Int(70_000), LiteralValue::Float(-0.5), text(&"x".repeat(40)),
+            LiteralValue::BigInt(BigInt::from(u64::MAX)), LiteralValue::Map(HashMap::from([(MapKey::Int(1), LiteralValue::Array(vec![]))])),
+        ]);
+        assert_eq!(decode(vec![encode(vec![value.clone()]).unwrap()]).unwrap(), value);
+
+        // bin data from other runtimes, and a float32
+        assert_eq!(decode(vec![byte_array(vec![0xc4, 2, 0, 255])]).unwrap(), byte_array(vec![0, 255]));
+        assert_eq!(decode(vec![byte_array(vec![0xca, 0x3f, 0xc0, 0, 0])]).unwrap(), LiteralValue::Float(1.5));
+        assert!(decode(vec![byte_array(vec![0x92, 0x01])]).unwrap_err().contains("ran out of bytes"));
+        assert!(decode(vec![byte_array(vec![0xc1])]).unwrap_err().contains("0xc1"));
+    }
+}
@@ -0,0 +1,114 @@
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::expr::Expr;
+use crate::literal_value::LiteralValue;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+// Port tried by `ping` when the host doesn't name one
+const PING_PORT: u16 = 80;
+
+pub const FUNCTIONS: &[&str] = &["resolve", "ping"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "resolve" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'resolve'")?;
+            let host = parser.expression()?; // Parse the host name
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(fn_net("resolve", vec![host]))
+        },
+        "ping" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'ping'")?;
+            let host = parser.expression()?; // Parse the host, optionally with a port
+            parser.consume(TokenType::Comma, "Expected ',' after host")?;
+            let timeout = parser.expression()?; // Parse the timeout in milliseconds
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(fn_net("ping", vec![host, timeout]))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_net(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::PreFunction {
+        module: "net".to_string(),
+        name: name.to_string(),
+        args,
+        span: Span::default(),
+    }
+}
+
+fn host_arg<'a>(args: &'a [LiteralValue], function: &str) -> Result<&'a str, String> {
+    match args.first() {
+        Some(LiteralValue::StringValue(host)) => Ok(host),
+        _ => Err(format!("{} expects a host name.", function)),
+    }
+}
+
+// `host` or `host:port`, with the port defaulting to `default_port`
+fn addresses(host: &str, default_port: u16) -> std::io::Result<Vec<SocketAddr>> {
+    match host.to_socket_addrs() {
+        Ok(addresses) => Ok(addresses.collect()),
+        Err(_) => (host.trim_start_matches('[').trim_end_matches(']'), default_port).to_socket_addrs().map(|addresses| addresses.collect()),
+    }
+}
+
+// The IP addresses of a host name, without duplicates
+pub fn resolve(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let host = host_arg(&args, "resolve")?;
+    let addresses = addresses(host, 0).map_err(|e| format!("Could not resolve '{}': {}", host, e))?;
+
+    let mut ips: Vec<String> = vec![];
+    for address in addresses {
+        let ip = address.ip().to_string();
+        if !ips.contains(&ip) {
+            ips.push(ip);
+        }
+    }
+    Ok(LiteralValue::Array(ips.into_iter().map(LiteralValue::StringValue).collect()))
+}
+
+// Milliseconds to open a TCP connection, or nil when the host can't be reached in time.
+// ICMP needs elevated privileges, so this measures a connect to port 80 or the given port.
+pub fn ping(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let host = host_arg(&args, "ping")?;
+    let timeout = match args.get(1).and_then(LiteralValue::as_f64) {
+        Some(ms) if ms > 0.0 => Duration::from_secs_f64(ms / 1000.0),
+        _ => return Err("ping timeout must be a positive number of milliseconds.".to_string()),
+    };
+
+    let Ok(addresses) = addresses(host, PING_PORT) else {
+        return Ok(LiteralValue::Nil);
+    };
+    for address in addresses {
+        let start = Instant::now();
+        if TcpStream::connect_timeout(&address, timeout).is_ok() {
+            return Ok(LiteralValue::Float(start.elapsed().as_secs_f64() * 1000.0));
+        }
+    }
+    Ok(LiteralValue::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn ping_measures_a_connect_and_resolve_lists_ips() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("127.0.0.1:{}", listener.local_addr().unwrap().port());
+
+        let latency = ping(vec![LiteralValue::StringValue(host), LiteralValue::Int(1000)]).unwrap();
+        assert!(matches!(latency, LiteralValue::Float(ms) if ms >= 0.0));
+
+        drop(listener);
+        let resolved = resolve(vec![LiteralValue::StringValue("127.0.0.1".to_string())]).unwrap();
+        assert_eq!(resolved, LiteralValue::Array(vec![LiteralValue::StringValue("127.0.0.1".to_string())]));
+        assert!(resolve(vec![LiteralValue::StringValue("no such host.invalid".to_string())]).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use crate::expr::Expr;
+use crate::literal_value::LiteralValue;
+use crate::modules::rcn_data;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub const FUNCTIONS: &[&str] = &["webhook"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "webhook" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'webhook'")?;
+            let url = parser.expression()?; // Parse the endpoint
+            parser.consume(TokenType::Comma, "Expected ',' after url")?;
+            let payload = parser.expression()?; // Parse the payload to send as JSON
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(fn_webhook(url, payload))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_webhook(url: Expr, payload: Expr) -> Expr {
+    Expr::PreFunction {
+        module: "notify".to_string(),
+        name: "webhook".to_string(),
+        args: vec![url, payload],
+        span: Span::default(),
+    }
+}
+
+// POSTs the payload as JSON, the way Slack, Discord and most chat webhooks expect it, and
+// returns the response status. A 4xx or 5xx reply is an error carrying the response body.
+pub fn webhook(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let url = match args.first() {
+        Some(LiteralValue::StringValue(url)) => url,
+        _ => return Err("notify.webhook expects a url.".to_string()),
+    };
+    let payload = match args.get(1) {
+        Some(payload @ (LiteralValue::Map(_) | LiteralValue::StructInst(_))) => rcn_data::to_json(payload)?,
+        Some(other) => return Err(format!("notify.webhook expects the payload as a map, got {}.", other.to_type())),
+        None => return Err("notify.webhook requires a payload.".to_string()),
+    };
+
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let response = agent.post(url).set("Content-Type", "application/json").send_string(&payload);
+    match response {
+        Ok(response) => Ok(LiteralValue::Int(response.status() as i64)),
+        Err(ureq::Error::Status(status, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            Err(format!("Webhook '{}' answered {}: {}", url, status, body.trim()))
+        }
+        Err(e) => Err(format!("Could not reach webhook '{}': {}", url, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::literal_value::MapKey;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    // A server that answers one request with `status` and returns the request body
+    fn serve_once(status: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim_end().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(&stream, "HTTP/1.1 {}\r\nContent-Length: 7\r\nConnection: close\r\n\r\nnot ok!", status).unwrap();
+            String::from_utf8(body).unwrap()
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn webhook_posts_the_payload_as_json() {
+        let payload = LiteralValue::Map([(MapKey::from("text"), LiteralValue::StringValue("deploy done".to_string()))].into_iter().collect());
+
+        let (url, server) = serve_once("200 OK");
+        let status = webhook(vec![LiteralValue::StringValue(url), payload.clone()]);
+        assert_eq!(status, Ok(LiteralValue::Int(200)));
+        assert_eq!(server.join().unwrap(), "{\n  \"text\": \"deploy done\"\n}");
+
+        let (url, server) = serve_once("403 Forbidden");
+        let error = webhook(vec![LiteralValue::StringValue(url), payload]).unwrap_err();
+        assert!(error.ends_with("answered 403: not ok!"), "{}", error);
+        server.join().unwrap();
+    }
+}
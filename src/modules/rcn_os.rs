@@ -0,0 +1,18 @@
+use crate::expr::Expr;
+use crate::modules::interpreter_native;
+use crate::parser::Parser;
+use crate::scanner::TokenType;
+
+pub const FUNCTIONS: &[&str] = &["on_exit", "exit", "args"];
+
+// These change how the run ends or read how it started, so they are natives the interpreter
+// defines with the run's state
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "on_exit" | "exit" | "args" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            parser.finish_call(interpreter_native("os", &identifier))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
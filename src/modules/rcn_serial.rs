@@ -0,0 +1,282 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
+use crate::environment::Environment;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::types::rcn_struct::StructInstance;
+
+// Bumped whenever the format changes, so an older reader rejects text it would misread
+const VERSION: &str = "rcn1";
+
+/// `serialize(value)`: the value as text another recolon process can read back with `deserialize`.
+///
+/// The text is the version followed by the value, where a value is one of
+///
+/// ```text
+/// n  t  f                     nil, true, false
+/// i42  b98765432109876543210  int, big int
+/// f0.1  d19.99                float (round-trips exactly), decimal
+/// s5:hello                    string, its length in bytes first so it needs no escaping
+/// [v v]  (v v)                array, tuple
+/// {k v k v}                   map, sorted by key
+/// <s5:Point s1:x i1 s1:y i2>  struct instance: its name, then fields sorted by name
+/// ```
+///
+/// Items are separated by one space, so equal values always give the same text.
+pub(crate) fn serialize(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let mut output = String::from(VERSION);
+    output.push(' ');
+    write_value(&args[0], &mut output, &mut vec![])?;
+    Ok(LiteralValue::StringValue(output))
+}
+
+/// `deserialize(text)`: the value `serialize` wrote.
+pub(crate) fn deserialize(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let LiteralValue::StringValue(text) = &args[0] else {
+        return Err(format!("deserialize expects a string, got {}.", args[0].to_type()));
+    };
+
+    let version = text.split(' ').next().unwrap_or_default();
+    if version != VERSION {
+        return Err(format!("deserialize expects text from serialize starting with '{}', got '{}'.", VERSION, version));
+    }
+    let mut reader = Reader { text, position: (version.len() + 1).min(text.len()) };
+    let value = reader.value()?;
+    if reader.position != text.len() {
+        return Err(reader.error("unexpected text after the value"));
+    }
+    Ok(value)
+}
+
+fn write_value(value: &LiteralValue, output: &mut String, seen: &mut Vec<usize>) -> Result<(), String> {
+    match value {
+        LiteralValue::Nil => output.push('n'),
+        LiteralValue::True => output.push('t'),
+        LiteralValue::False => output.push('f'),
+        LiteralValue::Int(x) => output.push_str(&format!("i{}", x)),
+        LiteralValue::BigInt(x) => output.push_str(&format!("b{}", x)),
+        LiteralValue::Float(x) => output.push_str(&format!("f{:?}", x)),
+        LiteralValue::Decimal(x) => output.push_str(&format!("d{}", x)),
+        LiteralValue::StringValue(text) => write_string(text, output),
+        LiteralValue::Array(elements) => write_sequence('[', elements.iter(), ']', output, seen)?,
+        LiteralValue::Tuple(elements) => write_sequence('(', elements.iter(), ')', output, seen)?,
+        LiteralValue::Map(entries) => {
+            let mut entries: Vec<_> = entries.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            output.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    output.push(' ');
+                }
+                write_value(&key.to_value(), output, seen)?;
+                output.push(' ');
+                write_value(value, output, seen)?;
+            }
+            output.push('}');
+        }
+        LiteralValue::StructInst(instance) => {
+            let pointer = Rc::as_ptr(instance) as usize;
+            if seen.contains(&pointer) {
+                return Err("Cannot serialize a cyclic value.".to_string());
+            }
+            seen.push(pointer);
+            let instance = instance.borrow();
+            let mut fields: Vec<_> = instance.fields.iter().collect();
+            fields.sort_by_key(|(name, _)| *name);
+            output.push('<');
+            write_string(&instance.name, output);
+            for (name, value) in fields {
+                output.push(' ');
+                write_string(name, output);
+                output.push(' ');
+                write_value(value, output, seen)?;
+            }
+            output.push('>');
+            seen.pop();
+        }
+        other => return Err(format!("Cannot serialize {}; only numbers, strings, bools, nil, arrays, tuples, maps and struct instances can be.", other.to_type())),
+    }
+    Ok(())
+}
+
+fn write_sequence<'a>(open: char, elements: impl Iterator<Item = &'a LiteralValue>, close: char, output: &mut String, seen: &mut Vec<usize>) -> Result<(), String> {
+    output.push(open);
+    for (i, element) in elements.enumerate() {
+        if i > 0 {
+            output.push(' ');
+        }
+        write_value(element, output, seen)?;
+    }
+    output.push(close);
+    Ok(())
+}
+
+fn write_string(text: &str, output: &mut String) {
+    output.push_str(&format!("s{}:{}", text.len(), text));
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    position: usize, // In bytes, which is what errors report
+}
+
+impl Reader<'_> {
+    fn error(&self, problem: &str) -> String {
+        self.error_at(self.position, problem)
+    }
+
+    fn error_at(&self, position: usize, problem: &str) -> String {
+        format!("Invalid serialized value at byte {}: {}.", position, problem)
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.text.as_bytes().get(self.position).copied();
+        self.position += usize::from(byte.is_some());
+        byte
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.position).copied()
+    }
+
+    fn value(&mut self) -> Result<LiteralValue, String> {
+        let start = self.position;
+        match self.next() {
+            Some(b'n') => Ok(LiteralValue::Nil),
+            Some(b't') => Ok(LiteralValue::True),
+            Some(b'f') => {
+                // A bare `f` is false, `f` with digits a float
+                if matches!(self.peek(), None | Some(b' ' | b']' | b')' | b'}' | b'>')) {
+                    return Ok(LiteralValue::False);
+                }
+                self.number().parse().map(LiteralValue::Float).map_err(|_| self.error_at(start, "invalid float"))
+            }
+            Some(b'i') => self.number().parse().map(LiteralValue::Int).map_err(|_| self.error_at(start, "invalid int")),
+            Some(b'b') => self.number().parse::<BigInt>().map(LiteralValue::BigInt).map_err(|_| self.error_at(start, "invalid big int")),
+            Some(b'd') => self.number().parse::<Decimal>().map(LiteralValue::Decimal).map_err(|_| self.error_at(start, "invalid decimal")),
+            Some(b's') => self.string().map(|text| LiteralValue::StringValue(text.to_string())),
+            Some(b'[') => Ok(LiteralValue::Array(self.sequence(b']')?)),
+            Some(b'(') => Ok(LiteralValue::Tuple(self.sequence(b')')?)),
+            Some(b'{') => {
+                let items = self.sequence(b'}')?;
+                if items.len() % 2 != 0 {
+                    return Err(self.error("a map key without a value"));
+                }
+                let mut entries = HashMap::new();
+                let mut items = items.into_iter();
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    entries.insert(MapKey::from_value(&key)?, value);
+                }
+                Ok(LiteralValue::Map(entries))
+            }
+            Some(b'<') => {
+                if self.next() != Some(b's') {
+                    return Err(self.error("a struct instance should start with its name"));
+                }
+                let name = self.string()?.to_string();
+                let mut fields = HashMap::new();
+                while self.peek() == Some(b' ') {
+                    self.position += 1;
+                    if self.next() != Some(b's') {
+                        return Err(self.error("a field name should be a string"));
+                    }
+                    let field = self.string()?.to_string();
+                    self.expect(b' ')?;
+                    fields.insert(field, self.value()?);
+                }
+                self.expect(b'>')?;
+                Ok(LiteralValue::StructInst(Rc::new(RefCell::new(StructInstance { name, fields, frozen: false }))))
+            }
+            Some(other) => Err(self.error_at(start, &format!("unknown value '{}'", other as char))),
+            None => Err(self.error("the text ended before the value")),
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        match self.next() {
+            Some(found) if found == byte => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", byte as char))),
+        }
+    }
+
+    // The text of a number, up to the next separator
+    fn number(&mut self) -> &str {
+        let start = self.position;
+        while !matches!(self.peek(), None | Some(b' ' | b']' | b')' | b'}' | b'>')) {
+            self.position += 1;
+        }
+        &self.text[start..self.position]
+    }
+
+    fn string(&mut self) -> Result<&str, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let length: usize = self.text[start..self.position].parse().map_err(|_| self.error("expected a string length"))?;
+        self.expect(b':')?;
+        let text = self.position.checked_add(length)
+            .and_then(|end| self.text.get(self.position..end))
+            .ok_or_else(|| self.error("the string is cut off"))?;
+        self.position += length;
+        Ok(text)
+    }
+
+    fn sequence(&mut self, close: u8) -> Result<Vec<LiteralValue>, String> {
+        let mut items = Vec::new();
+        if self.peek() == Some(close) {
+            self.position += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(self.value()?);
+            match self.next() {
+                Some(b' ') => continue,
+                Some(found) if found == close => return Ok(items),
+                _ => return Err(self.error(&format!("expected ' ' or '{}'", close as char))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(value: LiteralValue) -> (String, LiteralValue) {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let text = serialize(env.clone(), &[value]).unwrap();
+        let back = deserialize(env, std::slice::from_ref(&text)).unwrap();
+        (text.to_string(), back)
+    }
+
+    #[test]
+    fn values_round_trip_through_canonical_text() {
+        let text = |value: &str| LiteralValue::StringValue(value.to_string());
+        let point = LiteralValue::StructInst(Rc::new(RefCell::new(StructInstance {
+            name: "Point".to_string(),
+            fields: HashMap::from([("y".to_string(), LiteralValue::Float(0.1)), ("x".to_string(), text("a b]"))]),
+            frozen: true,
+        })));
+        let map = LiteralValue::Map(HashMap::from([
+            (MapKey::from("b"), LiteralValue::Tuple(vec![LiteralValue::Nil, LiteralValue::False])),
+            (MapKey::Int(1), LiteralValue::Decimal(Decimal::new(1999, 2))),
+        ]));
+        let value = LiteralValue::Array(vec![LiteralValue::Int(-3), text("héllo"), map, point, LiteralValue::Array(vec![])]);
+
+        let (serialized, back) = round_trip(value.clone());
+        assert_eq!(serialized, "rcn1 [i-3 s6:héllo {i1 d19.99 s1:b (n f)} <s5:Point s1:x s4:a b] s1:y f0.1> []]");
+        assert_eq!(back, value);
+        assert_eq!(round_trip(back).0, serialized);
+
+        let env = Rc::new(RefCell::new(Environment::new()));
+        assert!(deserialize(env.clone(), &[text("rcn2 n")]).unwrap_err().contains("starting with 'rcn1'"));
+        assert_eq!(deserialize(env.clone(), &[text("rcn1 [i1 s9:ab]")]).unwrap_err(), "Invalid serialized value at byte 12: the string is cut off.");
+        assert_eq!(deserialize(env.clone(), &[text("rcn1 s18446744073709551615:x")]).unwrap_err(), "Invalid serialized value at byte 27: the string is cut off.");
+        assert!(serialize(env, &[LiteralValue::StringBuilder(Rc::default())]).unwrap_err().starts_with("Cannot serialize"));
+    }
+}
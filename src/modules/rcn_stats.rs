@@ -0,0 +1,145 @@
+use crate::expr::Expr;
+use crate::literal_value::LiteralValue;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["mean", "stddev", "percentile", "correlation"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "mean" | "stddev" => {
+            parser.consume(TokenType::LeftParen, &format!("Expected '(' after '{}'", identifier))?;
+            let values = parser.expression()?; // Parse the array of numbers
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(fn_stats(&identifier, vec![values]))
+        },
+        "percentile" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'percentile'")?;
+            let values = parser.expression()?; // Parse the array of numbers
+            parser.consume(TokenType::Comma, "Expected ',' after values")?;
+            let rank = parser.expression()?; // Parse the percentile from 0 to 100
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(fn_stats("percentile", vec![values, rank]))
+        },
+        "correlation" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'correlation'")?;
+            let xs = parser.expression()?; // Parse the first array of numbers
+            parser.consume(TokenType::Comma, "Expected ',' after first argument")?;
+            let ys = parser.expression()?; // Parse the second array of numbers
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(fn_stats("correlation", vec![xs, ys]))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_stats(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::PreFunction {
+        module: "stats".to_string(),
+        name: name.to_string(),
+        args,
+        span: Span::default(),
+    }
+}
+
+// The numbers of a non-empty array
+fn samples(value: &LiteralValue, function: &str) -> Result<Vec<f64>, String> {
+    let LiteralValue::Array(elements) = value else {
+        return Err(format!("{}() expects an array of numbers, got {}.", function, value.to_type()));
+    };
+    if elements.is_empty() {
+        return Err(format!("{}() needs at least one value.", function));
+    }
+
+    elements.iter()
+        .map(|element| element.as_f64().ok_or_else(|| format!("{}() expects only numbers, got {} {}.", function, element.to_type(), element)))
+        .collect()
+}
+
+// Mean and sum of squared deviations in one pass with Welford's method,
+// which stays accurate where summing x and x² separately cancels out
+fn mean_and_deviation(values: &[f64]) -> (f64, f64) {
+    let (mut mean, mut squares) = (0.0, 0.0);
+    for (count, x) in values.iter().enumerate() {
+        let delta = x - mean;
+        mean += delta / (count + 1) as f64;
+        squares += delta * (x - mean);
+    }
+    (mean, squares)
+}
+
+pub fn mean(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let values = samples(&args[0], "mean")?;
+    Ok(LiteralValue::Float(mean_and_deviation(&values).0))
+}
+
+// Sample standard deviation, dividing by n - 1
+pub fn stddev(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let values = samples(&args[0], "stddev")?;
+    if values.len() < 2 {
+        return Err("stddev() needs at least two values.".to_string());
+    }
+    let (_, squares) = mean_and_deviation(&values);
+    Ok(LiteralValue::Float((squares / (values.len() - 1) as f64).sqrt()))
+}
+
+// The value below which `rank` percent of the values fall, interpolating between the closest two
+pub fn percentile(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let mut values = samples(&args[0], "percentile")?;
+    let rank = match args[1].as_f64() {
+        Some(rank) if (0.0..=100.0).contains(&rank) => rank,
+        _ => return Err(format!("percentile() takes a percentile from 0 to 100, got {}.", args[1])),
+    };
+    values.sort_by(f64::total_cmp);
+
+    let position = rank / 100.0 * (values.len() - 1) as f64;
+    let (below, above) = (position.floor() as usize, position.ceil() as usize);
+    let fraction = position - below as f64;
+    Ok(LiteralValue::Float(values[below] + (values[above] - values[below]) * fraction))
+}
+
+// Pearson correlation from -1 to 1, with the means taken out first to keep the sums small
+pub fn correlation(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let (xs, ys) = (samples(&args[0], "correlation")?, samples(&args[1], "correlation")?);
+    if xs.len() != ys.len() {
+        return Err(format!("correlation() needs arrays of the same length, got {} and {}.", xs.len(), ys.len()));
+    }
+
+    let (mean_x, spread_x) = mean_and_deviation(&xs);
+    let (mean_y, spread_y) = mean_and_deviation(&ys);
+    if spread_x == 0.0 || spread_y == 0.0 {
+        return Err("correlation() is undefined when all values of an array are equal.".to_string());
+    }
+    let covariance: f64 = xs.iter().zip(&ys).map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    Ok(LiteralValue::Float(covariance / (spread_x * spread_y).sqrt()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(values: &[f64]) -> LiteralValue {
+        LiteralValue::Array(values.iter().map(|x| LiteralValue::Float(*x)).collect())
+    }
+
+    #[test]
+    fn summaries_stay_accurate_for_large_offsets() {
+        // Classic case where the naive sum-of-squares formula loses all precision
+        let shifted = numbers(&[1e9 + 4.0, 1e9 + 7.0, 1e9 + 13.0, 1e9 + 16.0]);
+        assert_eq!(mean(vec![shifted.clone()]), Ok(LiteralValue::Float(1e9 + 10.0)));
+        assert_eq!(stddev(vec![shifted]), Ok(LiteralValue::Float(30.0f64.sqrt())));
+
+        let values = numbers(&[15.0, 20.0, 35.0, 40.0, 50.0]);
+        assert_eq!(percentile(vec![values.clone(), LiteralValue::Int(50)]), Ok(LiteralValue::Float(35.0)));
+        assert_eq!(percentile(vec![values.clone(), LiteralValue::Int(40)]), Ok(LiteralValue::Float(29.0)));
+        assert_eq!(percentile(vec![values.clone(), LiteralValue::Int(100)]), Ok(LiteralValue::Float(50.0)));
+
+        let doubled = numbers(&[30.0, 40.0, 70.0, 80.0, 100.0]);
+        assert_eq!(correlation(vec![values.clone(), doubled]), Ok(LiteralValue::Float(1.0)));
+        assert_eq!(mean(vec![numbers(&[])]), Err("mean() needs at least one value.".to_string()));
+        assert_eq!(percentile(vec![values, LiteralValue::Int(101)]), Err("percentile() takes a percentile from 0 to 100, got 101.".to_string()));
+    }
+}
@@ -1,56 +1,269 @@
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::thread::sleep;
 use std::time::Duration;
 
 use colored::Colorize;
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
 
 use crate::environment::Environment;
-use crate::literal_value::LiteralValue;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::types::rcn_struct::StructInstance;
 
 
-pub(crate) fn clock_impl(_env: Rc<RefCell<Environment>>, _args: &Vec<LiteralValue>) -> LiteralValue {
+pub(crate) fn clock_impl(_env: Rc<RefCell<Environment>>, _args: &[LiteralValue]) -> Result<LiteralValue, String> {
     let now = std::time::SystemTime::now()
         .duration_since(std::time::SystemTime::UNIX_EPOCH)
         .expect("Could not get system time")
         .as_millis();
 
-    LiteralValue::Number(now as f32 / 1000.0)
+    Ok(LiteralValue::Float(now as f64 / 1000.0))
 }
 
-pub(crate) fn wait_ms(_env: Rc<RefCell<Environment>>, args: &Vec<LiteralValue>) -> LiteralValue {
+// Names a module defines, sorted, leaving out `__file__` and the other metadata
+pub(crate) fn members(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let LiteralValue::Namespace(namespace) = &args[0] else {
+        return Err(format!("members expects an imported module, got {}.", args[0].to_type()));
+    };
+
+    let namespace = namespace.borrow();
+    let mut names: Vec<&String> = namespace.values.keys().filter(|name| !name.starts_with("__")).collect();
+    names.sort();
+    Ok(LiteralValue::Array(names.into_iter().map(|name| LiteralValue::StringValue(name.clone())).collect()))
+}
+
+// Results for fns that can fail, which `?` unwraps or returns from the calling fn
+pub(crate) fn ok(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(result("Ok", "value", args[0].clone()))
+}
+
+pub(crate) fn err(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(result("Err", "error", args[0].clone()))
+}
+
+fn result(name: &str, field: &str, value: LiteralValue) -> LiteralValue {
+    LiteralValue::StructInst(Rc::new(RefCell::new(StructInstance {
+        name: name.to_string(),
+        fields: HashMap::from([(field.to_string(), value)]),
+        frozen: false,
+    })))
+}
+
+pub(crate) fn wait_ms(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
     if args.len() != 1 {
-        return LiteralValue::StringValue("sleep function requires exactly one argument.".to_string());
+        return Err("sleep function requires exactly one argument.".to_string());
     }
 
-    match &args[0] {
-        LiteralValue::Number(ms) => {
-            let duration = Duration::from_millis(*ms as u64);
+    match args[0].as_f64() {
+        Some(ms) => {
+            let duration = Duration::from_millis(ms as u64);
             sleep(duration);
-            LiteralValue::Nil
+            Ok(LiteralValue::Nil)
+        },
+        _ => Err("sleep function requires a number as the argument.".to_string()),
+    }
+}
+
+// Explicit conversions for mixing strings and numbers outside of `+`
+pub(crate) fn str(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let value = &args[0];
+    let text = if value.is_compound() { value.to_pretty_string(true) } else { value.to_string() };
+    Ok(LiteralValue::StringValue(text))
+}
+
+pub(crate) fn num(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[0] {
+        number @ (LiteralValue::Int(_) | LiteralValue::BigInt(_) | LiteralValue::Float(_) | LiteralValue::Decimal(_)) => Ok(number.clone()),
+        LiteralValue::StringValue(text) => LiteralValue::parse_number(text.trim())
+            .ok_or_else(|| format!("Cannot convert \"{}\" to a number.", text)),
+        LiteralValue::True => Ok(LiteralValue::Int(1)),
+        LiteralValue::False => Ok(LiteralValue::Int(0)),
+        other => Err(format!("Cannot convert {} to a number.", other.to_type())),
+    }
+}
+
+// An integer of any size from a string of digits like "123456789012345678901234567890"
+pub(crate) fn bigint(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[0] {
+        integer @ (LiteralValue::Int(_) | LiteralValue::BigInt(_)) => Ok(integer.clone()),
+        LiteralValue::StringValue(text) => text.trim().replace('_', "").parse::<BigInt>()
+            .map(LiteralValue::from_bigint)
+            .map_err(|_| format!("Cannot convert \"{}\" to an integer.", text)),
+        other => match other.as_int() {
+            Some(x) => Ok(LiteralValue::Int(x)),
+            None => Err(format!("Cannot convert {} {} to an integer.", other.to_type(), other)),
+        },
+    }
+}
+
+// An exact Decimal from a string like "19.99", or from an Int or Float
+pub(crate) fn decimal(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let text = match &args[0] {
+        LiteralValue::Decimal(x) => return Ok(LiteralValue::Decimal(*x)),
+        LiteralValue::Int(x) => return Ok(LiteralValue::Decimal(Decimal::from(*x))),
+        // The shortest text that reads back as the Float, so decimal(0.1) is 0.1 rather than 0.1000000000000000055...
+        LiteralValue::Float(x) => x.to_string(),
+        LiteralValue::BigInt(x) => x.to_string(),
+        LiteralValue::StringValue(text) => text.trim().to_string(),
+        other => return Err(format!("Cannot convert {} to a decimal.", other.to_type())),
+    };
+    Decimal::from_str_exact(&text)
+        .or_else(|_| Decimal::from_scientific(&text))
+        .map(LiteralValue::Decimal)
+        .map_err(|_| format!("Cannot convert \"{}\" to a decimal.", text))
+}
+
+// An empty StringBuilder for building large strings piece by piece
+pub(crate) fn string_builder(_env: Rc<RefCell<Environment>>, _args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::StringBuilder(Rc::new(RefCell::new(String::new()))))
+}
+
+pub(crate) fn freeze(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[0] {
+        LiteralValue::StructInst(instance) => {
+            instance.borrow_mut().freeze();
+            Ok(LiteralValue::StructInst(instance.clone()))
+        },
+        _ => Err("freeze function requires a struct instance as the argument.".to_string()),
+    }
+}
+
+pub(crate) fn satisfies(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[1] {
+        LiteralValue::Interface(interface) => Ok(LiteralValue::check_bool(interface.is_satisfied_by(&args[0]))),
+        _ => Err("satisfies function requires an interface as the second argument.".to_string()),
+    }
+}
+
+pub(crate) fn fields(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[0] {
+        LiteralValue::StructInst(instance) => {
+            let mut names: Vec<String> = instance.borrow().fields.keys().cloned().collect();
+            names.sort();
+            Ok(LiteralValue::Array(names.into_iter().map(LiteralValue::StringValue).collect()))
         },
-        _ => LiteralValue::StringValue("sleep function requires a number as the argument.".to_string()),
+        _ => Err("fields function requires a struct instance as the argument.".to_string()),
     }
 }
 
-pub fn color_console(_env: Rc<RefCell<Environment>>, args: &Vec<LiteralValue>) -> LiteralValue {
+pub(crate) fn get_field(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match (&args[0], &args[1]) {
+        (LiteralValue::StructInst(instance), LiteralValue::StringValue(name)) => {
+            let instance = instance.borrow();
+            instance.get_field(name).cloned()
+                .ok_or_else(|| format!("Field '{}' not found in struct '{}'.", name, instance.name))
+        },
+        _ => Err("get_field function requires a struct instance and a field name.".to_string()),
+    }
+}
+
+pub(crate) fn set_field(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    match &args[1] {
+        LiteralValue::StringValue(name) if matches!(args[0], LiteralValue::StructInst(_)) => {
+            args[0].update_struct_field(name.clone(), args[2].clone())?;
+            Ok(LiteralValue::Nil)
+        },
+        _ => Err("set_field function requires a struct instance, a field name and a value.".to_string()),
+    }
+}
+
+// Elements paired with the key they sort by, which orders bools before numbers before strings like map keys
+fn sort_keys<'a>(args: &'a [LiteralValue], function: &str) -> Result<Vec<(MapKey, &'a LiteralValue)>, String> {
+    match &args[0] {
+        LiteralValue::Array(elements) => elements.iter()
+            .map(|element| MapKey::from_value(element)
+                .map(|key| (key, element))
+                .map_err(|_| format!("{} function takes an array of numbers, strings or bools, got {} {}.", function, element.to_type(), element)))
+            .collect(),
+        other => Err(format!("{} function requires an array, got {}.", function, other.to_type())),
+    }
+}
+
+// A sorted copy of the array, keeping equal elements in their original order
+pub(crate) fn sorted(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let mut keyed = sort_keys(args, "sorted")?;
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(LiteralValue::Array(keyed.into_iter().map(|(_, element)| element.clone()).collect()))
+}
+
+// The elements without repeats, each kept where it first appears
+pub(crate) fn unique(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let mut seen = HashSet::new();
+    Ok(LiteralValue::Array(sort_keys(args, "unique")?.into_iter()
+        .filter(|(key, _)| seen.insert(key.clone()))
+        .map(|(_, element)| element.clone())
+        .collect()))
+}
+
+// The index of `x` in an array sorted like `sorted` sorts it, or nil when it is missing
+pub(crate) fn binary_search(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let LiteralValue::Array(elements) = &args[0] else {
+        return Err(format!("binary_search function requires a sorted array, got {}.", args[0].to_type()));
+    };
+    let target = MapKey::from_value(&args[1]).map_err(|_| format!("binary_search function looks for a number, string or bool, got {}.", args[1].to_type()))?;
+
+    // Only the elements the search visits are converted
+    let mut invalid = None;
+    let found = elements.binary_search_by(|element| match MapKey::from_value(element) {
+        Ok(key) => key.cmp(&target),
+        Err(_) => {
+            invalid.get_or_insert_with(|| format!("binary_search function takes an array of numbers, strings or bools, got {} {}.", element.to_type(), element));
+            std::cmp::Ordering::Less
+        }
+    });
+    if let Some(message) = invalid {
+        return Err(message);
+    }
+    Ok(found.map_or(LiteralValue::Nil, |index| LiteralValue::Int(index as i64)))
+}
+
+// Wraps a function so each result is computed once per distinct arguments.
+// Reassigning a recursive fn, as in `fib = memo(fib);`, memoizes its recursive calls as well.
+pub(crate) fn memo(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
+    let LiteralValue::Callable { name, arity, fun } = &args[0] else {
+        return Err(format!("memo function requires a function as the argument, got {}.", args[0].to_type()));
+    };
+
+    let cache: RefCell<HashMap<Vec<MapKey>, LiteralValue>> = RefCell::new(HashMap::new());
+    let (inner, memo_name) = (fun.clone(), name.clone());
+    Ok(LiteralValue::Callable {
+        name: name.clone(),
+        arity: *arity,
+        fun: Rc::new(move |call_env, args: &[LiteralValue]| {
+            let key = args.iter().map(MapKey::from_value).collect::<Result<Vec<_>, _>>()
+                .map_err(|msg| format!("Memoized {} needs hashable arguments: {}", memo_name, msg))?;
+            if let Some(result) = cache.borrow().get(&key) {
+                return Ok(result.clone());
+            }
+
+            // The cache is not borrowed during the call, which may recurse into this function
+            let result = inner(call_env, args)?;
+            cache.borrow_mut().insert(key, result.clone());
+            Ok(result)
+        }),
+    })
+}
+
+pub fn color_console(_env: Rc<RefCell<Environment>>, args: &[LiteralValue]) -> Result<LiteralValue, String> {
     if args.len() < 3 {
-        return LiteralValue::StringValue("color_console function takes three arguments.".to_string());
+        return Err("color_console function takes three arguments.".to_string());
     }
 
     let color = match &args[0] {
         LiteralValue::StringValue(s) => s.clone(),
-        _ => return LiteralValue::StringValue("First argument must be a text color as a string.".to_string()),
+        _ => return Err("First argument must be a text color as a string.".to_string()),
     };
 
     let bg_color = match &args[1] {
         LiteralValue::StringValue(s) => s.clone(),
-        _ => return LiteralValue::StringValue("Second argument must be a background color as a string.".to_string()),
+        _ => return Err("Second argument must be a background color as a string.".to_string()),
     };
 
     let text = match &args[2] {
         LiteralValue::StringValue(s) => s.clone(),
-        _ => return LiteralValue::StringValue("Third argument must be the text as a string.".to_string()),
+        _ => return Err("Third argument must be the text as a string.".to_string()),
     };
 
     let colored_text = match color.as_str() {
@@ -62,7 +275,7 @@ pub fn color_console(_env: Rc<RefCell<Environment>>, args: &Vec<LiteralValue>) -
         "cyan" => text.cyan(),
         "" => text.white(),
         "black" => text.black(),
-        _ => return LiteralValue::StringValue("Unsupported text color.".to_string()),
+        _ => return Err("Unsupported text color.".to_string()),
     };
 
     let colored_text_with_bg = match bg_color.as_str() {
@@ -74,8 +287,8 @@ pub fn color_console(_env: Rc<RefCell<Environment>>, args: &Vec<LiteralValue>) -
         "cyan" => colored_text.on_cyan().to_string(),
         "white" => colored_text.on_white().to_string(),
         "" => colored_text.on_black().to_string(),
-        _ => return LiteralValue::StringValue("Unsupported background color.".to_string()),
+        _ => return Err("Unsupported background color.".to_string()),
     };
 
-    LiteralValue::StringValue(colored_text_with_bg)
+    Ok(LiteralValue::StringValue(colored_text_with_bg))
 }
@@ -0,0 +1,166 @@
+use crate::expr::Expr;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+pub const FUNCTIONS: &[&str] = &["render"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "render" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'render'")?;
+            let template = parser.expression()?; // Parse the template string
+            parser.consume(TokenType::Comma, "Expected ',' after template")?;
+            let data = parser.expression()?; // Parse the data argument
+            parser.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+
+            Ok(fn_render(template, data))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_render(template: Expr, data: Expr) -> Expr {
+    Expr::PreFunction {
+        module: "template".to_string(),
+        name: "render".to_string(),
+        args: vec![template, data],
+        span: Span::default(),
+    }
+}
+
+pub fn render(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    if args.len() != 2 {
+        return Err("render() requires exactly 2 arguments: template and data.".to_string());
+    }
+
+    let template = match &args[0] {
+        LiteralValue::StringValue(s) => s,
+        _ => return Err("Template must be a string".to_string()),
+    };
+
+    let mut output = String::new();
+    render_section(template, &mut vec![args[1].clone()], &mut output)?;
+
+    Ok(LiteralValue::StringValue(output))
+}
+
+// Render `template` into `output`, resolving names against the innermost scope first
+fn render_section(template: &str, scopes: &mut Vec<LiteralValue>, output: &mut String) -> Result<(), String> {
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        let end = after_open.find("}}").ok_or("Unclosed '{{' in template")?;
+        let tag = after_open[..end].trim();
+        rest = &after_open[end + 2..];
+
+        if let Some(name) = tag.strip_prefix("#each ") {
+            let (body, remainder) = split_block(rest, "each")?;
+            match lookup(scopes, name.trim())? {
+                LiteralValue::Array(items) => {
+                    for item in items {
+                        scopes.push(item);
+                        render_section(body, scopes, output)?;
+                        scopes.pop();
+                    }
+                }
+                other => return Err(format!("'{}' must be an array to use #each, got {}", name.trim(), other.to_type())),
+            }
+            rest = remainder;
+        } else if let Some(name) = tag.strip_prefix("#if ") {
+            let (body, remainder) = split_block(rest, "if")?;
+            let (then, els) = match body.find("{{else}}") {
+                Some(pos) => (&body[..pos], &body[pos + "{{else}}".len()..]),
+                None => (body, ""),
+            };
+
+            if lookup(scopes, name.trim())?.is_truthy() == LiteralValue::True {
+                render_section(then, scopes, output)?;
+            } else {
+                render_section(els, scopes, output)?;
+            }
+            rest = remainder;
+        } else {
+            output.push_str(&lookup(scopes, tag)?.to_string());
+        }
+    }
+
+    output.push_str(rest);
+    Ok(())
+}
+
+// Split off the body of a block up to its matching {{/kind}}, allowing nested blocks of the same kind
+fn split_block<'a>(template: &'a str, kind: &str) -> Result<(&'a str, &'a str), String> {
+    let open = format!("{{{{#{} ", kind);
+    let close = format!("{{{{/{}}}}}", kind);
+    let mut depth = 0;
+    let mut index = 0;
+
+    while index < template.len() {
+        let rest = &template[index..];
+        if rest.starts_with(&open) {
+            depth += 1;
+            index += open.len();
+        } else if rest.starts_with(&close) {
+            if depth == 0 {
+                return Ok((&template[..index], &template[index + close.len()..]));
+            }
+            depth -= 1;
+            index += close.len();
+        } else {
+            index += rest.chars().next().map_or(1, |c| c.len_utf8());
+        }
+    }
+
+    Err(format!("Missing '{}' in template", close))
+}
+
+// Resolve a dotted name such as `user.name`; `this` refers to the current #each item
+fn lookup(scopes: &[LiteralValue], path: &str) -> Result<LiteralValue, String> {
+    let mut parts = path.split('.');
+    let first = parts.next().unwrap_or_default();
+
+    let mut value = if first == "this" {
+        scopes.last().cloned()
+    } else {
+        scopes.iter().rev().find_map(|scope| field(scope, first))
+    }.ok_or_else(|| format!("Template key '{}' not found", path))?;
+
+    for part in parts {
+        value = field(&value, part).ok_or_else(|| format!("Template key '{}' not found", path))?;
+    }
+
+    Ok(value)
+}
+
+fn field(value: &LiteralValue, name: &str) -> Option<LiteralValue> {
+    match value {
+        LiteralValue::StructInst(instance) => instance.borrow().get_field(name).cloned(),
+        LiteralValue::Namespace(env) => env.borrow().get(name),
+        LiteralValue::Map(entries) => entries.get(&MapKey::from(name)).cloned(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> LiteralValue {
+        LiteralValue::StringValue(value.to_string())
+    }
+
+    fn map(entries: Vec<(&str, LiteralValue)>) -> LiteralValue {
+        LiteralValue::Map(entries.into_iter().map(|(key, value)| (MapKey::from(key), value)).collect())
+    }
+
+    #[test]
+    fn names_are_looked_up_in_maps() {
+        let user = map(vec![("name", text("Ann")), ("tags", LiteralValue::Array(vec![text("a"), text("b")]))]);
+        let data = map(vec![("user", user)]);
+        assert_eq!(render(vec![text("Hi {{ user.name }}:{{#each user.tags}} {{this}}{{/each}}"), data.clone()]), Ok(text("Hi Ann: a b")));
+        assert_eq!(render(vec![text("{{ user.age }}"), data]), Err("Template key 'user.age' not found".to_string()));
+    }
+}
@@ -0,0 +1,143 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::expr::Expr;
+use crate::literal_value::LiteralValue;
+use crate::parser::Parser;
+use crate::scanner::{Span, TokenType};
+
+const BAR_WIDTH: usize = 30;
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+pub const FUNCTIONS: &[&str] = &["progress", "spinner"];
+
+pub fn check_type(parser: &mut Parser, identifier: String) -> Result<Expr, String> {
+    match identifier.as_str() {
+        "progress" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'progress'")?;
+            let total = parser.expression()?; // Parse the number of steps
+            parser.consume(TokenType::RightParen, "Expected ')' after argument")?;
+
+            Ok(fn_progress(vec![total]))
+        },
+        "spinner" => {
+            parser.consume(TokenType::LeftParen, "Expected '(' after 'spinner'")?;
+            parser.consume(TokenType::RightParen, "Expected ')' after '('")?;
+
+            Ok(fn_progress(vec![]))
+        },
+        _ => Err(format!("Unknown identifier '{}'.", identifier)),
+    }
+}
+
+pub(crate) fn fn_progress(args: Vec<Expr>) -> Expr {
+    Expr::PreFunction {
+        module: "term".to_string(),
+        name: if args.is_empty() { "spinner" } else { "progress" }.to_string(),
+        args,
+        span: Span::default(),
+    }
+}
+
+pub fn progress(args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let total = match args.first().and_then(LiteralValue::as_f64) {
+        Some(total) if total > 0.0 => total,
+        _ => return Err("progress() requires a positive number of steps.".to_string()),
+    };
+    Ok(LiteralValue::Progress(Rc::new(RefCell::new(Progress::new(Some(total))))))
+}
+
+pub fn spinner() -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Progress(Rc::new(RefCell::new(Progress::new(None)))))
+}
+
+/// A progress bar, or a spinner when the total is unknown, redrawn in place on stderr
+/// so it stays out of the script's regular output.
+#[derive(Debug)]
+pub struct Progress {
+    total: Option<f64>,
+    done: f64,
+    finished: bool,
+}
+
+impl Progress {
+    fn new(total: Option<f64>) -> Self {
+        Self { total, done: 0.0, finished: false }
+    }
+
+    pub fn call_method(&mut self, method_name: &str, args: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+        match (method_name, args.as_slice()) {
+            // `tick()` advances by one step, `tick(n)` by n
+            ("tick", []) => self.advance(1.0),
+            ("tick", [steps]) if steps.as_f64().is_some() => self.advance(steps.as_f64().unwrap_or_default()),
+            ("tick", _) => return Err("tick method takes an optional number of steps.".to_string()),
+            ("finish", []) => {
+                if !self.finished {
+                    self.finished = true;
+                    if let Some(total) = self.total {
+                        self.done = total;
+                    }
+                    eprintln!("\r{}", self.render());
+                }
+            }
+            ("finish", _) => return Err("finish method takes no arguments.".to_string()),
+            _ => return Err(format!("Unknown method '{}' for progress bars", method_name)),
+        }
+        Ok(LiteralValue::Nil)
+    }
+
+    fn advance(&mut self, steps: f64) {
+        if self.finished {
+            return;
+        }
+        self.done += steps;
+        if let Some(total) = self.total {
+            self.done = self.done.min(total);
+        }
+        eprint!("\r{}", self.render());
+        let _ = io::stderr().flush();
+    }
+
+    fn render(&self) -> String {
+        match self.total {
+            Some(total) => {
+                let filled = ((self.done / total) * BAR_WIDTH as f64) as usize;
+                format!("[{}{}] {}/{} {:>3}%", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), self.done, total, (self.done / total * 100.0) as usize)
+            }
+            None if self.finished => format!("done ({})", self.done),
+            None => format!("{} {}", SPINNER_FRAMES[self.done as usize % SPINNER_FRAMES.len()], self.done),
+        }
+    }
+}
+
+impl std::fmt::Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.total {
+            Some(total) => write!(f, "Progress {}/{}", self.done, total),
+            None => write!(f, "Spinner {}", self.done),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bar_fills_with_ticks_and_stops_at_total() {
+        let mut bar = Progress::new(Some(4.0));
+        bar.call_method("tick", vec![]).unwrap();
+        assert_eq!(bar.render(), format!("[{}{}] 1/4  25%", "#".repeat(7), "-".repeat(23)));
+
+        bar.call_method("tick", vec![LiteralValue::Int(10)]).unwrap();
+        assert_eq!(bar.render(), format!("[{}] 4/4 100%", "#".repeat(30)));
+
+        let mut spinner = Progress::new(None);
+        spinner.call_method("tick", vec![]).unwrap();
+        assert_eq!(spinner.render(), "/ 1");
+        spinner.call_method("finish", vec![]).unwrap();
+        assert_eq!(spinner.render(), "done (1)");
+        assert!(spinner.call_method("reset", vec![]).is_err());
+    }
+}
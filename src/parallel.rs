@@ -0,0 +1,129 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::thread;
+
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
+use crate::interpreter::Interpreter;
+use crate::literal_value::{LiteralValue, MapKey};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::types::rcn_struct::StructInstance;
+
+/// A deep copy of a value that can move between threads. Values themselves are `Rc`-based,
+/// so `par_map` copies the elements into this form, and the results back out of it.
+pub(crate) enum SharedValue {
+    Int(i64),
+    BigInt(BigInt),
+    Float(f64),
+    Decimal(Decimal),
+    String(String),
+    Bool(bool),
+    Nil,
+    Array(Vec<SharedValue>),
+    Tuple(Vec<SharedValue>),
+    Map(Vec<(MapKey, SharedValue)>),
+    Struct { name: String, fields: Vec<(String, SharedValue)> },
+}
+
+impl SharedValue {
+    pub(crate) fn from_value(value: &LiteralValue) -> Result<SharedValue, String> {
+        Ok(match value {
+            LiteralValue::Int(x) => SharedValue::Int(*x),
+            LiteralValue::BigInt(x) => SharedValue::BigInt(x.clone()),
+            LiteralValue::Float(x) => SharedValue::Float(*x),
+            LiteralValue::Decimal(x) => SharedValue::Decimal(*x),
+            LiteralValue::StringValue(text) => SharedValue::String(text.clone()),
+            LiteralValue::True => SharedValue::Bool(true),
+            LiteralValue::False => SharedValue::Bool(false),
+            LiteralValue::Nil => SharedValue::Nil,
+            LiteralValue::Array(elements) => SharedValue::Array(elements.iter().map(SharedValue::from_value).collect::<Result<_, _>>()?),
+            LiteralValue::Tuple(elements) => SharedValue::Tuple(elements.iter().map(SharedValue::from_value).collect::<Result<_, _>>()?),
+            LiteralValue::Map(entries) => SharedValue::Map(entries.iter()
+                .map(|(key, value)| Ok((key.clone(), SharedValue::from_value(value)?)))
+                .collect::<Result<_, String>>()?),
+            LiteralValue::StructInst(instance) => {
+                let instance = instance.borrow();
+                SharedValue::Struct {
+                    name: instance.name.clone(),
+                    fields: instance.fields.iter()
+                        .map(|(name, value)| Ok((name.clone(), SharedValue::from_value(value)?)))
+                        .collect::<Result<_, String>>()?,
+                }
+            }
+            other => return Err(format!("par_map can only pass numbers, strings, bools, nil, arrays, tuples, maps and structs between threads, got {}.", other.to_type())),
+        })
+    }
+
+    pub(crate) fn into_value(self) -> LiteralValue {
+        match self {
+            SharedValue::Int(x) => LiteralValue::Int(x),
+            SharedValue::BigInt(x) => LiteralValue::BigInt(x),
+            SharedValue::Float(x) => LiteralValue::Float(x),
+            SharedValue::Decimal(x) => LiteralValue::Decimal(x),
+            SharedValue::String(text) => LiteralValue::StringValue(text),
+            SharedValue::Bool(b) => LiteralValue::check_bool(b),
+            SharedValue::Nil => LiteralValue::Nil,
+            SharedValue::Array(elements) => LiteralValue::Array(elements.into_iter().map(SharedValue::into_value).collect()),
+            SharedValue::Tuple(elements) => LiteralValue::Tuple(elements.into_iter().map(SharedValue::into_value).collect()),
+            SharedValue::Map(entries) => LiteralValue::Map(entries.into_iter().map(|(key, value)| (key, value.into_value())).collect()),
+            SharedValue::Struct { name, fields } => LiteralValue::StructInst(Rc::new(RefCell::new(StructInstance {
+                name,
+                fields: fields.into_iter().map(|(name, value)| (name, value.into_value())).collect::<HashMap<_, _>>(),
+                frozen: false,
+            }))),
+        }
+    }
+}
+
+/// Calls the fn `name` on every element, spread over one thread per core. Each thread runs a
+/// fresh interpreter that only declares `functions`, the sources of the script's top-level fns,
+/// so the fn sees its argument and other top-level fns but not the script's variables.
+/// Results keep the order of the elements; the first failing element's error is returned.
+/// The threads are sandboxed when the script calling par_map is.
+pub(crate) fn par_map(functions: &[String], name: &str, mut elements: Vec<SharedValue>, sandboxed: bool) -> Result<Vec<SharedValue>, String> {
+    let workers = thread::available_parallelism().map_or(1, |cores| cores.get()).min(elements.len()).max(1);
+    let chunk_size = elements.len().div_ceil(workers).max(1);
+
+    let mut chunks = vec![];
+    while !elements.is_empty() {
+        let rest = elements.split_off(chunk_size.min(elements.len()));
+        chunks.push(std::mem::replace(&mut elements, rest));
+    }
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = chunks.into_iter()
+            .map(|chunk| scope.spawn(move || map_chunk(functions, name, chunk, sandboxed)))
+            .collect();
+
+        let mut results = vec![];
+        for handle in handles {
+            let chunk = handle.join().map_err(|_| "par_map worker thread panicked.".to_string())??;
+            results.extend(chunk);
+        }
+        Ok(results)
+    })
+}
+
+fn map_chunk(functions: &[String], name: &str, chunk: Vec<SharedValue>, sandboxed: bool) -> Result<Vec<SharedValue>, String> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_sandbox(sandboxed);
+    for source in functions {
+        let stmts = Parser::new(Scanner::new(source).scan_tokens()?).parse()?;
+        interpreter.interpret(stmts)?;
+    }
+
+    let function = interpreter.environment().borrow().get(name);
+    let Some(LiteralValue::Callable { fun, .. }) = function else {
+        return Err(format!("par_map could not declare fn '{}' on a worker thread.", name));
+    };
+
+    chunk.into_iter()
+        .map(|element| {
+            let result = fun(interpreter.environment().clone(), &[element.into_value()])?;
+            SharedValue::from_value(&result)
+        })
+        .collect()
+}
@@ -1,37 +1,122 @@
 use std::collections::HashMap;
 use std::string::String;
 
-use crate::scanner::{Token, TokenType, TokenType::*};
+use crate::scanner::{self, Scanner, Token, TokenType, TokenType::*};
 use crate::expr::{Expr::*, Expr};
 use crate::literal_value::LiteralValue;
-use crate::stmt::Stmt;
+use crate::stmt::{LogLevel, Pattern, Stmt};
+use crate::suggest::did_you_mean;
 
-use crate::modules::{rcn_io, rcn_math};
+use crate::modules::{module_functions, RESERVED_MODULES, rcn_archive, rcn_clipboard, rcn_compress, rcn_debug, rcn_fs, rcn_html, rcn_http, rcn_i18n, rcn_io, rcn_mail, rcn_math, rcn_msgpack, rcn_net, rcn_notify, rcn_os, rcn_stats, rcn_template, rcn_term};
 
 /// Represents the parser structure that processes tokens.
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    loop_labels: Vec<Option<String>>, // Loops enclosing the current statement, innermost last
+    yields: Option<bool>, // Whether the fn being parsed has a `yield` so far, None outside of fns
+    source: Option<Box<dyn Iterator<Item = Result<Token, String>>>>, // Refills `tokens` on demand when streaming
+    scan_errors: Vec<String>,
 }
 
-#[derive(Clone, Debug)]
-pub struct StructDefinition {
-    pub name: String,
-    pub fields: HashMap<String, Expr>, // Fields as expressions during parsing
-}
+// The source of `tokens` with each token back at its line and column, so errors point at the same place.
+// Comments and other text between the tokens become blanks.
+fn source_text(tokens: &[Token]) -> String {
+    let (mut source, mut line, mut column) = (String::new(), 1, 1);
+    for token in tokens {
+        while line < token.line_number {
+            source.push('\n');
+            (line, column) = (line + 1, 1);
+        }
+        while column < token.column {
+            source.push(' ');
+            column += 1;
+        }
 
-#[derive(Clone, Debug)]
-pub struct StructInstance {
-    pub name: String,
-    pub fields: HashMap<String, LiteralValue>, // Fields as evaluated values during runtime
+        source.push_str(&token.lexeme);
+        match token.lexeme.rfind('\n') {
+            Some(last_break) => {
+                line += token.lexeme.matches('\n').count();
+                column = token.lexeme.len() - last_break;
+            }
+            None => column += token.lexeme.len(),
+        }
+    }
+    source
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
+        // Doc comments are only kept in front of the declarations they can document
+        let mut kept = Vec::with_capacity(tokens.len());
+        for (i, token) in tokens.iter().enumerate() {
+            if token.token_type == DocComment {
+                let documented = tokens[i..].iter().find(|next| next.token_type != DocComment);
+                if !documented.is_some_and(|next| matches!(next.token_type, Function | Struct | Const)) {
+                    continue;
+                }
+            }
+            kept.push(token.clone());
+        }
+
+        Self {
+            tokens: kept,
+            current: 0,
+            loop_labels: vec![],
+            yields: None,
+            source: None,
+            scan_errors: vec![],
+        }
+    }
+
+    /// Parses from a token stream, such as a `Scanner` used as an iterator, pulling
+    /// tokens only as far as the current statement needs. Iterate the parser to get
+    /// one top-level statement at a time.
+    pub fn streaming(tokens: impl Iterator<Item = Result<Token, String>> + 'static) -> Self {
         Self {
-            tokens,
+            tokens: vec![],
             current: 0,
+            loop_labels: vec![],
+            yields: None,
+            source: Some(Box::new(tokens)),
+            scan_errors: vec![],
+        }
+    }
+
+    // Pulls tokens from the stream until index `upto` is buffered or the stream ends
+    fn fill(&mut self, upto: usize) {
+        while self.tokens.len() <= upto {
+            let Some(token) = self.pull() else { return };
+            if token.token_type != DocComment {
+                self.tokens.push(token);
+                continue;
+            }
+
+            // Same rule as `new`: doc comments only survive in front of a declaration
+            let mut docs = vec![token];
+            let documented = loop {
+                match self.pull() {
+                    Some(next) if next.token_type == DocComment => docs.push(next),
+                    next => break next,
+                }
+            };
+            if documented.as_ref().is_some_and(|next| matches!(next.token_type, Function | Struct | Const)) {
+                self.tokens.append(&mut docs);
+            }
+            self.tokens.extend(documented);
+        }
+    }
+
+    fn pull(&mut self) -> Option<Token> {
+        let source = self.source.as_mut()?;
+        for next in source.by_ref() {
+            match next {
+                Ok(token) => return Some(token),
+                Err(msg) => self.scan_errors.push(msg),
+            }
         }
+        self.source = None;
+        None
     }
 
     pub fn parse(&mut self) -> Result<Vec<Stmt>, String> {
@@ -49,7 +134,7 @@ impl Parser {
             }
         }
 
-        if errs.len() == 0 {
+        if errs.is_empty() {
             Ok(stmts)
         } else {
             Err(errs.join("\n"))
@@ -57,6 +142,9 @@ impl Parser {
     }
 
     fn declaration(&mut self) -> Result<Stmt, String> {
+        // Skipped here and read back by the documented declaration
+        while self.match_token(DocComment) {}
+
         if self.match_token(Var) {
             match self.var_declaration() {
                 Ok(stmt) => Ok(stmt),
@@ -77,96 +165,149 @@ impl Parser {
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, String> {
-        let token = self.consume(Identifier, "Expected variable name")?;
-
-        let initializer;
-        if self.match_token(Equal) {
-            if self.match_token(LeftBracket) {
-                // Parse the array literal
-                let mut elements = Vec::new();
-
-                if !self.check(RightBracket) { // Handle empty array case
-                    loop {
-                        let expr = self.expression()?; // Parse each element
-                        elements.push(expr);
-
-                        if !self.match_token(Comma) {
-                            break;
-                        }
-                    }
-                }
+        let span = self.previous().span();
+
+        // `var (a, b) = pair;` and `var [x, y] = arr;` bind by position, `var { x, y } = point;` by field name
+        let closing = match self.peek().token_type {
+            LeftParen => Some(RightParen),
+            LeftBracket => Some(RightBracket),
+            LeftBrace => Some(RightBrace),
+            _ => None,
+        };
+        if let Some(closing) = closing {
+            self.advance();
+            let mut names = vec![self.consume(Identifier, "Expected variable name")?];
+            while self.match_token(Comma) {
+                names.push(self.consume(Identifier, "Expected variable name after ','")?);
+            }
+            self.consume(closing, "Expected the names to unpack to be closed.")?;
+            return self.unpack(names, closing == RightBrace, span);
+        }
 
-                self.consume(RightBracket, "Expected ']' after array elements")?;
+        let token = self.consume(Identifier, "Expected variable name")?;
 
-                initializer = Array {
-                    elements,
-                };
-            } else {
-                initializer = self.expression()?;
+        // `var a, b = f();` unpacks the values of a tuple or array
+        if self.check(Comma) {
+            let mut names = vec![token];
+            while self.match_token(Comma) {
+                names.push(self.consume(Identifier, "Expected variable name after ','")?);
             }
+            return self.unpack(names, false, span);
+        }
+
+        let initializer = if self.match_token(Equal) {
+            self.expression()?
         } else {
-            initializer = Literal {
+            Literal {
                 value: LiteralValue::Nil,
-            };
-        }
+                span: token.span(),
+            }
+        };
 
         self.consume(Semicolon, "Expected ';' after variable declaration.")?;
 
         Ok(Stmt::Var {
             name: token,
             initializer,
+            span,
         })
     }
 
+    fn unpack(&mut self, names: Vec<Token>, by_field: bool, span: scanner::Span) -> Result<Stmt, String> {
+        self.consume(Equal, "Expected '=' after the names to unpack.")?;
+        let initializer = self.expression()?;
+        self.consume(Semicolon, "Expected ';' after variable declaration.")?;
+        Ok(Stmt::Unpack { names, by_field, initializer, span })
+    }
+
     fn const_declaration(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        let doc = self.doc_comment();
         let token = self.consume(Identifier, "Expected constant name")?;
 
-        let initializer;
-        if self.match_token(Equal) {
-            initializer = self.expression()?;
+        let initializer = if self.match_token(Equal) {
+            self.expression()?
         } else {
             return Err("Expected '=' after constant name".to_string());
-        }
+        };
 
         self.consume(Semicolon, "Expected ';' after constant declaration.")?;
 
         Ok(Stmt::Const {
             name: token,
             initializer,
+            doc,
+            span,
         })
     }
 
     fn statement(&mut self) -> Result<Stmt, String> {
-        if self.match_token(Log) {
-            self.log_statement()
+        if self.check(Identifier) && self.check_next(Colon) {
+            self.labeled_loop()
+        } else if self.match_token(Log) {
+            if self.match_token(Dot) {
+                let level_name = self.consume(Identifier, "Expected log level after 'log.'")?;
+                let level = LogLevel::from_name(&level_name.lexeme)
+                    .ok_or(format!("Line {}: Unknown log level '{}'.", level_name.line_number, level_name.lexeme))?;
+                self.log_statement(false, Some(level))
+            } else {
+                self.log_statement(false, None)
+            }
+        } else if self.match_token(LogCompact) {
+            self.log_statement(true, None)
         } else if self.match_token(Error) {
-            self.log_err_statement()
+            self.log_err_statement(false)
+        } else if self.match_token(Fatal) {
+            self.log_err_statement(true)
         } else if self.match_token(Print) {
             self.print_statement()
         } else if self.match_token(LeftBrace) {
             self.block_statement()
         } else if self.match_token(If) {
             self.if_statement()
+        } else if self.match_token(Match) {
+            self.match_statement()
         } else if self.match_token(While) {
-            self.while_statement()
+            self.while_statement(None)
         } else if self.match_token(For) {
-            self.for_statement()
+            self.for_statement(None)
         } else if self.match_token(Return) {
             self.return_statement()
+        } else if self.match_token(Yield) {
+            self.yield_statement()
+        } else if self.match_token(With) {
+            self.with_statement()
+        } else if self.match_token(Try) {
+            self.try_statement()
+        } else if self.match_token(Throw) {
+            self.throw_statement()
         } else if self.match_token(Loop) {
-            self.loop_statement()
+            self.loop_statement(None)
+        } else if self.match_token(Break) {
+            self.loop_jump(true)
+        } else if self.match_token(Continue) {
+            self.loop_jump(false)
         } else if self.match_token(Function) {
             self.function_statement()
         } else if self.match_token(Struct) {
             self.struct_statement()
+        } else if self.match_token(Interface) {
+            self.interface_statement()
         } else if self.match_token(Import) {
             self.import_statement()
-        }else {
+        } else if self.check(Identifier) && self.peek().lexeme == "module" && self.check_next(TokenType::String) {
+            // Only a keyword before the module's name, so scripts can still use `module` as a name
+            self.advance();
+            self.module_statement()
+        } else {
             self.expression_statement()
         }
     }
 
     fn function_statement(&mut self) -> Result<Stmt, String> {
+        let first = self.current - 1;
+        let span = self.previous().span();
+        let doc = self.doc_comment();
         let name = self.consume(Identifier, "Expected function name")?.lexeme.clone();
 
         self.consume(LeftParen, "Expected '(' after function name")?;
@@ -184,43 +325,117 @@ impl Parser {
 
         self.consume(RightParen, "Expected ')' after parameters")?;
         self.consume(LeftBrace, "Expected '{' before function body")?;
-        let body = vec![Box::new(self.block_statement()?)]; // Parse the function body as a block
+        // Loops around the declaration can't be broken out of from inside the function
+        let outer_loops = std::mem::take(&mut self.loop_labels);
+        let outer_yields = self.yields.replace(false);
+        let body = self.block_statement();
+        self.loop_labels = outer_loops;
+        let generator = std::mem::replace(&mut self.yields, outer_yields) == Some(true);
+        let body = vec![body?]; // Parse the function body as a block
+
+        let source = source_text(&self.tokens[first..self.current]);
 
-        // println!("body is: {:?}", body);
-        // println!("Defining function '{}' in the module environment.", name);
+        Ok(Stmt::FuncStmt { name, parameters, body, doc, source, generator, span })
+    }
+    // `yield value;` makes the enclosing fn a generator
+    fn yield_statement(&mut self) -> Result<Stmt, String> {
+        let keyword = self.previous();
+        if self.yields.is_none() {
+            return Err(format!("Line {}: 'yield' can only be used inside a fn.", keyword.line_number));
+        }
+        self.yields = Some(true);
 
-        Ok(Stmt::FuncStmt { name, parameters, body })
+        let value = self.expression()?;
+        self.consume(Semicolon, "Expected ';' after yield value.")?;
+        Ok(Stmt::Yield { value, span: keyword.span() })
     }
+
     fn return_statement(&mut self) -> Result<Stmt, String> {
         let keyword = self.previous(); // 'return' token
         let value = if !self.check(Semicolon) {
-            Some(self.expression()?)
+            let first = self.expression()?;
+            // `return a, b;` returns both as one tuple
+            if self.check(Comma) {
+                let span = first.span();
+                let mut elements = vec![first];
+                while self.match_token(Comma) {
+                    elements.push(self.expression()?);
+                }
+                Some(Expr::Tuple { elements, span })
+            } else {
+                Some(first)
+            }
         } else {
             None
         };
 
         self.consume(Semicolon, "Expected ';' after return value.")?;
-        Ok(Stmt::ReturnStmt { keyword, value })
+        let span = keyword.span();
+        Ok(Stmt::ReturnStmt { keyword, value, span })
+    }
+
+    fn module_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        let name = self.consume(TokenType::String, "Expected module name as a string")?;
+        self.consume(LeftBrace, "Expected '{' after module name")?;
+
+        let mut body = vec![];
+        while !self.check(RightBrace) && !self.is_at_end() {
+            body.push(self.declaration()?);
+        }
+        self.consume(RightBrace, "Expected '}' after the module.")?;
+
+        Ok(Stmt::Module { name: name.lexeme.trim_matches('"').to_string(), body, span })
     }
 
     fn import_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
         let module_name_token = self.consume(TokenType::String, "Expected module name as a string")?;
         self.consume(TokenType::As, "Expected 'as' keyword after module name")?;
         let alias_name_token = self.consume(TokenType::Identifier, "Expected alias name after 'as'")?;
+        // `else value` is bound to the alias instead when the module isn't there
+        let fallback = if self.match_token(Else) { Some(self.expression()?) } else { None };
         self.consume(TokenType::Semicolon, "Expected ';' after alias name")?;
 
         Ok(Stmt::Import {
             module_name: module_name_token.lexeme.clone(),
             alias_name: alias_name_token.lexeme.clone(),
+            fallback,
+            span,
         })
     }
 
     fn struct_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        let doc = self.doc_comment();
         let name = self.consume(Identifier, "Expected struct name")?.lexeme.clone();
+
+        let parent = if self.match_token(Colon) {
+            Some(self.consume(Identifier, "Expected parent struct name after ':'")?.lexeme.clone())
+        } else {
+            None
+        };
+
+        let mut interfaces = vec![];
+        if self.match_token(Implements) {
+            loop {
+                interfaces.push(self.consume(Identifier, "Expected interface name after 'implements'")?.lexeme.clone());
+                if !self.match_token(Comma) {
+                    break;
+                }
+            }
+        }
+
         self.consume(LeftBrace, "Expected '{' after struct name")?;
 
         let mut fields = HashMap::new();
-        while !self.check(RightBrace) {
+        let mut functions = vec![];
+        while !self.check(RightBrace) && !self.is_at_end() {
+            if self.match_token(Function) {
+                functions.push(self.function_statement()?);
+                continue;
+            }
+
             let field_name = self.consume(Identifier, "Expected field name")?.lexeme.clone();
             self.consume(Colon, "Expected ':' after field name")?;
             let field_value = self.expression()?;
@@ -233,18 +448,99 @@ impl Parser {
 
         self.consume(RightBrace, "Expected '}' after struct fields")?;
 
-        Ok(Stmt::StructStmt { name, params: fields })
+        Ok(Stmt::StructStmt { name, parent, interfaces, params: fields, functions, doc, span })
     }
 
-    fn loop_statement(&mut self) -> Result<Stmt, String> {
+    fn interface_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        let name = self.consume(Identifier, "Expected interface name")?.lexeme.clone();
+        self.consume(LeftBrace, "Expected '{' after interface name")?;
+
+        let mut methods = vec![];
+        while !self.check(RightBrace) && !self.is_at_end() {
+            self.consume(Function, "Expected 'fn' in interface body")?;
+            let method = self.consume(Identifier, "Expected method name")?.lexeme.clone();
+            self.consume(LeftParen, "Expected '(' after method name")?;
+
+            let mut arity = 0;
+            if !self.check(RightParen) {
+                loop {
+                    self.consume(Identifier, "Expected parameter name")?;
+                    arity += 1;
+                    if !self.match_token(Comma) {
+                        break;
+                    }
+                }
+            }
+
+            self.consume(RightParen, "Expected ')' after parameters")?;
+            self.consume(Semicolon, "Expected ';' after interface method")?;
+            methods.push((method, arity));
+        }
+
+        self.consume(RightBrace, "Expected '}' after interface body")?;
+
+        Ok(Stmt::InterfaceStmt { name, methods, span })
+    }
+
+    // `name: while/for/compose ...`, the target of `break name;` and `continue name;`
+    fn labeled_loop(&mut self) -> Result<Stmt, String> {
+        let label = self.advance();
+        self.consume(Colon, "Expected ':' after loop label.")?;
+
+        if self.match_token(While) {
+            self.while_statement(Some(label.lexeme))
+        } else if self.match_token(For) {
+            self.for_statement(Some(label.lexeme))
+        } else if self.match_token(Loop) {
+            self.loop_statement(Some(label.lexeme))
+        } else {
+            Err(format!("Line {}: Only loops can be labeled, but '{}' labels something else.", label.line_number, label.lexeme))
+        }
+    }
+
+    // Parse a loop body with `label` in scope for break and continue
+    fn loop_body(&mut self, label: &Option<String>) -> Result<Stmt, String> {
+        self.loop_labels.push(label.clone());
+        let body = self.statement();
+        self.loop_labels.pop();
+        body
+    }
+
+    fn loop_jump(&mut self, is_break: bool) -> Result<Stmt, String> {
+        let keyword = self.previous();
+        let label = if self.check(Identifier) { Some(self.advance().lexeme) } else { None };
+        self.consume(Semicolon, &format!("Expected ';' after '{}'.", keyword.lexeme))?;
+
+        if self.loop_labels.is_empty() {
+            return Err(format!("Line {}: '{}' outside of a loop.", keyword.line_number, keyword.lexeme));
+        }
+        if let Some(label) = &label {
+            if !self.loop_labels.iter().any(|enclosing| enclosing.as_ref() == Some(label)) {
+                return Err(format!("Line {}: Unknown loop label '{}'.", keyword.line_number, label));
+            }
+        }
+
+        let span = keyword.span();
+        if is_break {
+            Ok(Stmt::Break { label, span })
+        } else {
+            Ok(Stmt::Continue { label, span })
+        }
+    }
+
+    fn loop_statement(&mut self, label: Option<String>) -> Result<Stmt, String> {
+        let span = self.previous().span();
         self.consume(LeftParen, "Expected '(' after 'compose'.")?;
-        self.consume(RightParen, "Expected ')' after '('. ")?;
-        let body = Box::new(self.statement()?);
+        let count = if self.check(RightParen) { None } else { Some(self.expression()?) };
+        self.consume(RightParen, "Expected ')' after 'compose' count.")?;
+        let body = Box::new(self.loop_body(&label)?);
 
-        Ok(Stmt::LoopStmt { body })
+        Ok(Stmt::LoopStmt { body, count, label, span })
     }
 
     fn if_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
         self.consume(LeftParen, "Expected '(' after 'if'.")?;
         let predicate = self.expression()?;
         self.consume(RightParen, "Expected ')' after condition.")?;
@@ -273,21 +569,168 @@ impl Parser {
             then,
             elifs,
             els,
+            span,
         })
     }
 
-    fn while_statement(&mut self) -> Result<Stmt, String> {
+    // `match (value) { 1, 2 => ...; "x" => { ... } else => ...; }` runs the first arm with an equal value
+    fn match_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        self.consume(LeftParen, "Expected '(' after 'match'.")?;
+        let subject = self.expression()?;
+        self.consume(RightParen, "Expected ')' after match value.")?;
+        self.consume(LeftBrace, "Expected '{' before match arms.")?;
+
+        let mut arms = Vec::new();
+        let mut default = None;
+        while !self.check(RightBrace) && !self.is_at_end() {
+            if default.is_some() {
+                return Err(format!("Line {}: The 'else' arm must be the last arm of a match.", self.peek().line_number));
+            }
+
+            if self.match_token(Else) {
+                self.consume(FatArrow, "Expected '=>' after 'else'.")?;
+                default = Some(Box::new(self.statement()?));
+                continue;
+            }
+
+            let mut patterns = vec![self.pattern()?];
+            while self.match_token(Comma) {
+                patterns.push(self.pattern()?);
+            }
+            self.consume(FatArrow, "Expected '=>' after match values.")?;
+            arms.push((patterns, Box::new(self.statement()?)));
+        }
+        self.consume(RightBrace, "Expected '}' after match arms.")?;
+
+        Ok(Stmt::Match { subject, arms, default, span })
+    }
+
+    // `with (var name = resource) { ... }` calls `name.close()` however the body is left
+    fn with_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        self.consume(LeftParen, "Expected '(' after 'with'.")?;
+        self.consume(Var, "Expected 'var' after 'with ('.")?;
+        let name = self.consume(Identifier, "Expected variable name")?;
+        self.consume(Equal, "Expected '=' after the variable name.")?;
+        let initializer = self.expression()?;
+        self.consume(RightParen, "Expected ')' after the resource.")?;
+        let body = self.statement()?;
+
+        Ok(Stmt::With { name, initializer, body: Box::new(body), span })
+    }
+
+    // `try { ... } catch (e) { ... }`; the `(e)` can be left out when the error itself doesn't matter
+    fn try_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        let body = self.statement()?;
+        self.consume(Catch, "Expected 'catch' after the 'try' body.")?;
+        let name = if self.match_token(LeftParen) {
+            let name = self.consume(Identifier, "Expected a name for the error after 'catch ('.")?;
+            self.consume(RightParen, "Expected ')' after the error name.")?;
+            Some(name)
+        } else {
+            None
+        };
+        let handler = self.statement()?;
+
+        Ok(Stmt::Try { body: Box::new(body), name, handler: Box::new(handler), span })
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        let value = self.expression()?;
+        self.consume(Semicolon, "Expected ';' after the thrown value.")?;
+
+        Ok(Stmt::Throw { value, span })
+    }
+
+    // A match arm pattern: a literal, a name to bind (`_` for any value), `[a, ..rest]`, `(a, b)` or `Point { x, y: 0 }`
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        if self.match_token(LeftBracket) {
+            let mut elements = vec![];
+            let mut rest = None;
+            while !self.check(RightBracket) && !self.is_at_end() {
+                if self.match_token(DotDot) {
+                    let name = if self.check(Identifier) { self.advance().lexeme } else { "_".to_string() };
+                    rest = Some(name);
+                    break;
+                }
+                elements.push(self.pattern()?);
+                if !self.match_token(Comma) {
+                    break;
+                }
+            }
+            self.consume(RightBracket, "Expected ']' after array pattern.")?;
+            return Ok(Pattern::Array { elements, rest });
+        }
+
+        if self.match_token(LeftParen) {
+            let mut elements = vec![self.pattern()?];
+            while self.match_token(Comma) {
+                elements.push(self.pattern()?);
+            }
+            self.consume(RightParen, "Expected ')' after tuple pattern.")?;
+            return Ok(Pattern::Tuple(elements));
+        }
+
+        if self.check(Identifier) {
+            let name = self.advance().lexeme;
+            if !self.match_token(LeftBrace) {
+                return Ok(Pattern::Bind(name));
+            }
+
+            let mut fields = vec![];
+            while !self.check(RightBrace) && !self.is_at_end() {
+                let field = self.consume(Identifier, "Expected field name in struct pattern.")?.lexeme;
+                let pattern = if self.match_token(Colon) { self.pattern()? } else { Pattern::Bind(field.clone()) };
+                fields.push((field, pattern));
+                if !self.match_token(Comma) {
+                    break;
+                }
+            }
+            self.consume(RightBrace, "Expected '}' after struct pattern.")?;
+            return Ok(Pattern::Struct { name, fields });
+        }
+
+        let line = self.peek().line_number;
+        let value = self.unary()?;
+        let is_literal = match &value {
+            Expr::Literal { .. } => true,
+            Expr::Unary { operator, right, .. } => operator.token_type == Minus && matches!(**right, Expr::Literal { .. }),
+            _ => false,
+        };
+        if !is_literal {
+            return Err(format!("Line {}: Match arms take patterns like 1, \"text\", name, [a, ..rest] or Point {{ x }}.", line));
+        }
+        Ok(Pattern::Literal(value))
+    }
+
+    fn while_statement(&mut self, label: Option<String>) -> Result<Stmt, String> {
+        let span = self.previous().span();
         self.consume(LeftParen, "Expected '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(RightParen, "Expected ')' after condition.")?;
-        let body = self.statement()?;
+        let body = self.loop_body(&label)?;
 
-        Ok(Stmt::WhileStmt { condition, body: Box::new(body) })
+        Ok(Stmt::WhileStmt { condition, body: Box::new(body), increment: None, label, span })
     }
 
-    fn for_statement(&mut self) -> Result<Stmt, String> {
+    fn for_statement(&mut self, label: Option<String>) -> Result<Stmt, String> {
+        let span = self.previous().span();
         self.consume(LeftParen, "Expected '(' after 'for'.")?;
 
+        // `for (item in items)`, optionally written `for (var item in items)`
+        if self.check(Identifier) && self.check_next(In) || self.check(Var) && self.check_next(Identifier) && self.check_after_next(In) {
+            self.match_token(Var);
+            let name = self.advance();
+            self.advance(); // The 'in'
+            let iterable = self.expression()?;
+            self.consume(RightParen, "Expected ')' after for-in clause.")?;
+            let body = self.loop_body(&label)?;
+            return Ok(Stmt::ForIn { name, iterable, body: Box::new(body), label, span });
+        }
+
         // Initialization statement
         let initializer = if self.match_token(Semicolon) {
             None // No initialization
@@ -301,7 +744,7 @@ impl Parser {
 
         // Condition expression
         let condition = if self.check(Semicolon) {
-            Literal { value: LiteralValue::True } // Default condition is true
+            Literal { value: LiteralValue::True, span: self.peek().span() } // Default condition is true
         } else {
             self.expression()?
         };
@@ -316,23 +759,15 @@ impl Parser {
         self.consume(RightParen, "Expected ')' after for clauses.")?;
 
         // Loop body
-        let body = self.statement()?;
-
-        // Desugaring the for-loop into a while-loop
-        let mut loop_body = vec![body];
-        if let Some(increment) = increment {
-            loop_body.push(Stmt::Expression {
-                expression: increment
-            });
-        }
-
-        let loop_body_stmt = Stmt::Block {
-            statements: loop_body
-        };
+        let body = self.loop_body(&label)?;
 
+        // Desugaring the for-loop into a while-loop; the increment also runs after `continue`
         let while_stmt = Stmt::WhileStmt {
             condition,
-            body: Box::new(loop_body_stmt)
+            body: Box::new(body),
+            increment,
+            label,
+            span,
         };
 
         let mut block_statements = Vec::new();
@@ -343,11 +778,13 @@ impl Parser {
         block_statements.push(while_stmt);
 
         Ok(Stmt::Block {
-            statements: block_statements
+            statements: block_statements,
+            span,
         })
     }
 
     fn block_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
         let mut statements = vec![];
         while !self.check(RightBrace) && !self.is_at_end() {
             let decl = self.declaration()?;
@@ -355,44 +792,61 @@ impl Parser {
         }
 
         self.consume(RightBrace, "Expected '}' after a block.")?;
-        Ok(Stmt::Block { statements })
+        Ok(Stmt::Block { statements, span })
     }
 
-    fn log_statement(&mut self) -> Result<Stmt, String> {
+    fn log_statement(&mut self, compact: bool, level: Option<LogLevel>) -> Result<Stmt, String> {
+        let span = self.previous().span();
         self.consume(LeftParen, "Expected '(' before value.")?;
-        let value = self.expression()?;
+        let mut values = vec![self.expression()?];
+        while self.match_token(Comma) {
+            values.push(self.expression()?);
+        }
         self.consume(RightParen, "Expected ')' after value.")?;
         self.consume(Semicolon, "Expected ';'.")?;
         Ok(Stmt::Log {
-            expression: value
+            expressions: values,
+            compact,
+            level,
+            span,
         })
     }
 
-    fn log_err_statement(&mut self) -> Result<Stmt, String> {
+    fn log_err_statement(&mut self, fatal: bool) -> Result<Stmt, String> {
+        let span = self.previous().span();
         self.consume(LeftParen, "Expected '(' before value.")?;
         let value = self.expression()?;
         self.consume(RightParen, "Expected ')' after value.")?;
         self.consume(Semicolon, "Expected ';'.")?;
         Ok(Stmt::Err {
-            expression: value
+            expression: value,
+            fatal,
+            span,
         })
     }
 
     fn print_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
         self.consume(LeftParen, "Expected '(' before value.")?;
-        let value = self.expression()?;
+        let mut values = vec![self.expression()?];
+        while self.match_token(Comma) {
+            values.push(self.expression()?);
+        }
         self.consume(RightParen, "Expected ')' after value.")?;
         self.consume(Semicolon, "Expected ';'.")?;
         Ok(Stmt::Print {
-            expression: value
+            expressions: values,
+            span,
         })
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.peek().span();
         let expr = self.expression()?;
         self.consume(Semicolon, "Expected ';' after value.")?;
         Ok(Stmt::Expression {
-            expression: expr
+            expression: expr,
+            span,
         })
     }
 
@@ -406,17 +860,24 @@ impl Parser {
         if self.match_token(Equal) {
             let value = self.assignment()?;
 
-            match expr {
-                Expr::Variable { name } => {
-                    Ok(Expr::Assign { name, value: Box::from(value) })
+            match variable_target(expr) {
+                Expr::Variable { name, span } => {
+                    Ok(Expr::Assign { name, value: Box::from(value), span })
                 },
-                Expr::FieldAccess { object, field } => {
+                Expr::FieldAccess { object, field, span } => {
                     Ok(Expr::FieldAssign {
                         object,
                         field,
                         value: Box::new(value),
+                        span,
                     })
                 },
+                Expr::Index { index, .. } if matches!(*index, Expr::Range { .. }) => Err("Cannot assign to a slice.".to_string()),
+                // `grid[i][j] = v` writes through every level, so the chain has to start at a variable or field
+                Expr::Index { array, .. } if !is_place(&array) => Err("Only elements of a variable or field can be assigned.".to_string()),
+                Expr::Index { array, index, span } => {
+                    Ok(Expr::IndexAssign { object: array, index, value: Box::new(value), span })
+                },
                 _ => Err("Invalid assignment target.".to_string())
             }
         } else {
@@ -431,7 +892,8 @@ impl Parser {
             let operator = self.previous();
             let right = self.and()?;
 
-            expr = Logical { left: Box::new(expr), operator, right: Box::new(right), };
+            let span = operator.span();
+            expr = Logical { left: Box::new(expr), operator, right: Box::new(right), span };
         }
 
         Ok(expr)
@@ -443,10 +905,12 @@ impl Parser {
         while self.match_token(And) {
             let operator = self.previous();
             let right = self.equality()?;
+            let span = operator.span();
             expr = Logical {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
+                span,
             };
         }
 
@@ -459,10 +923,12 @@ impl Parser {
         while self.match_tokens(&[BangEqual, EqualEqual]) {
             let operator = self.previous();
             let rhs = self.comparison()?;
+            let span = operator.span();
             expr = Binary {
                 left: Box::new(expr),
-                operator, 
+                operator,
                 right: Box::new(rhs),
+                span,
             };
         }
 
@@ -470,13 +936,14 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> Result<Expr, String> {
-        let mut expr = self.term()?;
+        let mut expr = self.range()?;
 
-        while self.match_tokens(&[Greater, GreaterEqual, Less, LessEqual]) {
+        while self.match_tokens(&[Greater, GreaterEqual, Less, LessEqual, In]) {
             let op = self.previous();
-            let rhs = self.term()?;
+            let rhs = self.range()?;
             expr = Binary {
                 left: Box::from(expr),
+                span: op.span(),
                 operator: op,
                 right: Box::from(rhs),
             };
@@ -485,6 +952,24 @@ impl Parser {
         Ok(expr)
     }
 
+    // `start..end` excludes the end and `start..=end` includes it; ranges do not chain
+    fn range(&mut self) -> Result<Expr, String> {
+        let expr = self.term()?;
+
+        if self.match_tokens(&[DotDot, DotDotEqual]) {
+            let op = self.previous();
+            let end = self.term()?;
+            return Ok(Expr::Range {
+                start: Box::new(expr),
+                end: Box::new(end),
+                inclusive: op.token_type == DotDotEqual,
+                span: op.span(),
+            });
+        }
+
+        Ok(expr)
+    }
+
     fn term(&mut self) -> Result<Expr, String> {
         let mut expr = self.factor()?;
 
@@ -493,6 +978,7 @@ impl Parser {
             let rhs = self.factor()?;
             expr = Binary {
                 left: Box::from(expr),
+                span: op.span(),
                 operator: op,
                 right: Box::from(rhs),
             };
@@ -509,6 +995,7 @@ impl Parser {
             let rhs = self.unary()?;
             expr = Binary {
                 left: Box::from(expr),
+                span: op.span(),
                 operator: op,
                 right: Box::from(rhs),
             };
@@ -522,6 +1009,7 @@ impl Parser {
             let op = self.previous();
             let rhs = self.unary()?;
             Ok(Unary {
+                span: op.span(),
                 operator: op,
                 right: Box::from(rhs),
             })
@@ -533,9 +1021,26 @@ impl Parser {
     fn call(&mut self) -> Result<Expr, String> {
         let mut expr = self.primary()?;
 
-        while true {
+        // Calls, field access, method calls and indexing compose in any order, e.g. `make().items[0].name`
+        loop {
             if self.match_token(LeftParen) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_token(Dot) {
+                expr = self.member(expr)?;
+            } else if self.match_token(Question) {
+                let question = self.previous();
+                if self.yields.is_none() {
+                    return Err(format!("Line {}: '?' can only be used inside a fn, which it returns the Err from.", question.line_number));
+                }
+                expr = Propagate { result: Box::new(expr), span: question.span() };
+            } else if self.match_token(LeftBracket) {
+                let index = self.expression()?;
+                self.consume(RightBracket, "Expected ']' after index")?;
+                expr = Index {
+                    span: expr.span(),
+                    array: Box::new(expr),
+                    index: Box::new(index),
+                };
             } else {
                 break;
             }
@@ -544,7 +1049,103 @@ impl Parser {
         Ok(expr)
     }
 
-    fn finish_call(&mut self, callee: Expr) -> Result<Expr, String> {
+    // `module.name(...)` after the module's name, with the '.' already consumed
+    fn module_function(&mut self, name: &str, token: &Token) -> Result<Expr, String> {
+        let identifier = self.consume(TokenType::Identifier, "Expected identifier after '.'")?;
+        let field_name = identifier.lexeme.clone();
+
+        let called = self.check(TokenType::LeftParen);
+        let function = if name == "math" {
+            rcn_math::check_type(self, field_name)
+        } else if name == "io" {
+            rcn_io::check_type(self, field_name)
+        } else if name == "template" {
+            rcn_template::check_type(self, field_name)
+        } else if name == "term" {
+            rcn_term::check_type(self, field_name)
+        } else if name == "clipboard" {
+            rcn_clipboard::check_type(self, field_name)
+        } else if name == "fs" {
+            rcn_fs::check_type(self, field_name)
+        } else if name == "archive" {
+            rcn_archive::check_type(self, field_name)
+        } else if name == "compress" {
+            rcn_compress::check_type(self, field_name)
+        } else if name == "http" {
+            rcn_http::check_type(self, field_name)
+        } else if name == "mail" {
+            rcn_mail::check_type(self, field_name)
+        } else if name == "net" {
+            rcn_net::check_type(self, field_name)
+        } else if name == "notify" {
+            rcn_notify::check_type(self, field_name)
+        } else if name == "stats" {
+            rcn_stats::check_type(self, field_name)
+        } else if name == "os" {
+            rcn_os::check_type(self, field_name)
+        } else if name == "i18n" {
+            rcn_i18n::check_type(self, field_name)
+        } else if name == "html" {
+            rcn_html::check_type(self, field_name)
+        } else if name == "msgpack" {
+            rcn_msgpack::check_type(self, field_name)
+        } else {
+            rcn_debug::check_type(self, field_name)
+        };
+
+        // Module functions are expanded at the call site, so unlike fn declarations they are not values
+        function.map(|function| function.with_span(token.span())).map_err(|msg| match msg {
+            msg if !called && msg.starts_with("Expected '('") => format!(
+                "Line {}: {}.{} is a module function and must be called; wrap it in a fn to pass it as a value.",
+                identifier.line_number, name, identifier.lexeme,
+            ),
+            msg if msg.starts_with("Unknown identifier") => format!(
+                "Line {}: {} has no function '{}'.{}",
+                identifier.line_number, name, identifier.lexeme, did_you_mean(&identifier.lexeme, module_functions(name).iter().copied()),
+            ),
+            msg => msg,
+        })
+    }
+
+    // `stats.mean(xs)` names the stats module unless the script has a variable called `stats`, so
+    // both readings are parsed and the interpreter picks one when it gets there
+    fn module_or_member(&mut self, name: String, token: Token) -> Result<Expr, String> {
+        let start = self.current;
+        self.advance(); // Consume '.'
+        let function = self.module_function(&name, &token);
+        let end = self.current;
+
+        self.current = start + 1;
+        let variable = Expr::Variable { name: token.clone(), span: token.span() };
+        match (function, self.member(variable)) {
+            (Ok(function), Ok(member)) if self.current == end => {
+                Ok(Expr::Module { name, function: Ok(Box::new(function)), member: Box::new(member), span: token.span() })
+            },
+            // A name the module doesn't have may still be a field or method of the variable
+            (Err(msg), Ok(member)) => Ok(Expr::Module { name, function: Err(msg), member: Box::new(member), span: token.span() }),
+            (function, _) => {
+                self.current = end;
+                function
+            },
+        }
+    }
+
+    // `.name` after `object`: a field access, or a method call when arguments follow
+    fn member(&mut self, object: Expr) -> Result<Expr, String> {
+        let field = self.consume(Identifier, "Expected field or method name after '.'")?;
+        if self.check(LeftParen) {
+            // Method call on a value, or a function exported by a module namespace
+            self.method_call(field.lexeme.clone(), object)
+        } else {
+            Ok(FieldAccess {
+                span: field.span(),
+                object: Box::new(object),
+                field,
+            })
+        }
+    }
+
+    pub(crate) fn finish_call(&mut self, callee: Expr) -> Result<Expr, String> {
         let mut arguments = vec![];
 
         if !self.check(RightParen) {
@@ -562,11 +1163,11 @@ impl Parser {
                 }
             }
         }
-        let paren = self.consume(RightParen, "Expected ')' after arguments.")?;
+        self.consume(RightParen, "Expected ')' after arguments.")?;
 
         Ok(Call {
+            span: callee.span(),
             callee: Box::new(callee),
-            paren,
             arguments,
         })
     }
@@ -587,6 +1188,7 @@ impl Parser {
         self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
 
         Ok(Expr::MethodCall {
+            span: object.span(),
             object: Box::new(object),
             method_name: name,
             arguments,
@@ -603,79 +1205,72 @@ impl Parser {
                 self.consume(TokenType::RightParen, "Expected ')' after expression")?;
                 Ok(Expr::Grouping {
                     expression: Box::new(expr),
+                    span: token.span(),
                 })
             }
             TokenType::False | TokenType::True | TokenType::Nil | TokenType::Number | TokenType::String => {
                 self.advance(); // Consume the literal token
                 Ok(Expr::Literal {
                     value: LiteralValue::from_token(token.clone()),
+                    span: token.span(),
                 })
             }
-            TokenType::Identifier => {
-                self.advance(); // Consume the first identifier
-                let name = self.previous().lexeme.clone(); // Capture the identifier name (could be a variable, struct, or module)
+            TokenType::LeftBracket => {
+                self.advance(); // Consume '['
+                let mut elements = Vec::new();
 
-                if self.match_token(TokenType::Dot) {
-                    let identifier = self.consume(TokenType::Identifier, "Expected identifier after '.'")?;
-                    let field_name = identifier.lexeme.clone();
+                if !self.check(TokenType::RightBracket) { // Handle empty array case
+                    loop {
+                        elements.push(self.expression()?);
+                        if !self.match_token(TokenType::Comma) {
+                            break;
+                        }
+                    }
+                }
 
-                    if name == "math" {
-                        Ok(rcn_math::check_type(self, field_name).expect("TODO: panic message"))
-                    } else if name == "io" {
-                        Ok(rcn_io::check_type(self, field_name).expect("TODO: panic message"))
-                    } else {
-                        if self.check(TokenType::LeftParen) {
-                            // Parse arguments for the function call
-                            self.advance(); // Consume '('
-                            let mut arguments = Vec::new();
-                            if !self.check(TokenType::RightParen) {
-                                loop {
-                                    arguments.push(self.expression()?);
-                                    if !self.match_token(TokenType::Comma) {
-                                        break;
-                                    }
-                                }
-                            }
-                            self.consume(RightParen, "Expected ')' after arguments")?;
-
-                            return Ok(Call {
-                                callee: Box::new(FieldAccess {
-                                    object: Box::new(Variable {
-                                        name: Token {
-                                            token_type: Identifier,
-                                            lexeme: name.clone(),
-                                            literal: None,
-                                            line_number: token.line_number,
-                                        },
-                                    }),
-                                    field: identifier,
-                                }),
-                                paren: token.clone(),
-                                arguments,
-                            });
-                        } else {
-                            return Ok(FieldAccess {
-                                object: Box::new(Variable {
-                                    name: Token {
-                                        token_type: Identifier,
-                                        lexeme: name.clone(),
-                                        literal: None,
-                                        line_number: token.line_number,
-                                    },
-                                }),
-                                field: identifier,
-                            });
+                self.consume(TokenType::RightBracket, "Expected ']' after array elements")?;
+                Ok(Expr::Array {
+                    elements,
+                    span: token.span(),
+                })
+            }
+            TokenType::LeftBrace => {
+                self.advance(); // Consume '{'
+                let mut entries = Vec::new();
+
+                if !self.check(TokenType::RightBrace) { // Handle empty map case
+                    loop {
+                        let key = self.expression()?;
+                        self.consume(TokenType::Colon, "Expected ':' after map key")?;
+                        entries.push((key, self.expression()?));
+                        if !self.match_token(TokenType::Comma) {
+                            break;
                         }
                     }
+                }
 
-                } else if self.match_token(TokenType::LeftBracket) {
-                    let index = self.expression()?;
-                    self.consume(TokenType::RightBracket, "Expected ']' after index")?;
+                self.consume(TokenType::RightBrace, "Expected '}' after map entries")?;
+                Ok(Expr::Map {
+                    entries,
+                    span: token.span(),
+                })
+            }
+            TokenType::InterpolatedString => {
+                self.advance(); // Consume the heredoc token
+                self.interpolated_string(&token)
+            }
+            TokenType::Identifier => {
+                self.advance(); // Consume the first identifier
+                let name = self.previous().lexeme.clone(); // Capture the identifier name (could be a variable, struct, or module)
 
-                    Ok(Expr::Index {
-                        array: Box::new(Expr::Variable { name: token.clone() }),
-                        index: Box::new(index),
-                    })
+                if !module_functions(&name).is_empty() && self.check(TokenType::Dot) {
+                    // Other modules give way to a variable of the same name, which is only known once the script runs
+                    if RESERVED_MODULES.contains(&name.as_str()) {
+                        self.advance(); // Consume '.'
+                        self.module_function(&name, &token)
+                    } else {
+                        self.module_or_member(name, token)
+                    }
                 } else if self.match_token(TokenType::LeftBrace) {
                     // Struct instantiation syntax
                     let mut fields = HashMap::new();
@@ -696,6 +1291,7 @@ impl Parser {
                     Ok(Expr::StructInst {
                         name,
                         fields,
+                        span: token.span(),
                     })
                 } else if self.match_token(TokenType::Const) {
                     // Handle constant definitions
@@ -706,10 +1302,12 @@ impl Parser {
                     Ok(Expr::Const {
                         name,
                         value: Box::new(initializer),
+                        span: token.span(),
                     })
                 } else {
                     Ok(Expr::Variable {
                         name: token.clone(), // Use the original token as variable name
+                        span: token.span(),
                     })
                 }
             }
@@ -717,6 +1315,59 @@ impl Parser {
         }
     }
 
+    /// Splits a `$"""` heredoc into literal text and `{expression}` parts.
+    fn interpolated_string(&mut self, token: &Token) -> Result<Expr, String> {
+        let text = match &token.literal {
+            Some(scanner::LiteralValue::StringValue(text)) => dedent(text),
+            _ => return Err(format!("Line {}: Invalid interpolated string", token.line_number)),
+        };
+
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut source = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => source.push(c),
+                            None => return Err(format!("Line {}: Unclosed '{{' in interpolated string", token.line_number)),
+                        }
+                    }
+
+                    if !literal.is_empty() {
+                        parts.push(Literal { value: LiteralValue::StringValue(std::mem::take(&mut literal)), span: token.span() });
+                    }
+
+                    let tokens = Scanner::new(&source).scan_tokens()?;
+                    let mut parser = Parser::new(tokens);
+                    parts.push(parser.expression()?);
+                    if !parser.is_at_end() {
+                        return Err(format!("Line {}: Unexpected tokens in interpolation '{{{}}}'", token.line_number, source));
+                    }
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Literal { value: LiteralValue::StringValue(literal), span: token.span() });
+        }
+
+        Ok(Interpolate { parts, span: token.span() })
+    }
+
     pub fn consume(&mut self, token_type: TokenType, msg: &str) -> Result<Token, String>{
         let token = self.peek();
         if token.token_type == token_type {
@@ -732,6 +1383,16 @@ impl Parser {
         self.peek().token_type == typ
     }
 
+    fn check_next(&mut self, typ: TokenType) -> bool {
+        self.fill(self.current + 1);
+        self.tokens.get(self.current + 1).is_some_and(|token| token.token_type == typ)
+    }
+
+    fn check_after_next(&mut self, typ: TokenType) -> bool {
+        self.fill(self.current + 2);
+        self.tokens.get(self.current + 2).is_some_and(|token| token.token_type == typ)
+    }
+
     fn match_token(&mut self, typ: TokenType) -> bool {
         if self.is_at_end() {
             false
@@ -764,6 +1425,7 @@ impl Parser {
 
     /// Returns the current token without advancing.
     pub(crate) fn peek(&mut self) -> Token {
+        self.fill(self.current);
         self.tokens[self.current].clone()
     }
 
@@ -777,6 +1439,21 @@ impl Parser {
         self.peek().token_type == TokenType::Eof
     }
 
+    // Joins the `##` lines directly in front of the keyword that was just matched
+    fn doc_comment(&self) -> Option<String> {
+        let keyword = self.current - 1;
+        let first = self.tokens[..keyword].iter().rposition(|token| token.token_type != DocComment).map_or(0, |i| i + 1);
+        if first == keyword {
+            return None;
+        }
+
+        let lines: Vec<String> = self.tokens[first..keyword].iter().map(|token| match &token.literal {
+            Some(scanner::LiteralValue::StringValue(text)) => text.clone(),
+            _ => String::new(),
+        }).collect();
+        Some(lines.join("\n"))
+    }
+
     fn sync(&mut self) {
         self.advance();
 
@@ -786,7 +1463,7 @@ impl Parser {
             }
 
             match self.peek().token_type {
-                Class | Function | Var | For | If | While | Log | Error | Return => return,
+                Class | Function | Struct | Interface | Var | For | If | While | Log | LogCompact | Error | Fatal | Return | Yield | With | Try | Throw => return,
                 _ => (),
             }
 
@@ -795,10 +1472,78 @@ impl Parser {
     }
 }
 
+// Modules cannot be assigned to, so a target that starts with a module's name means the variable
+fn variable_target(expr: Expr) -> Expr {
+    match expr {
+        Expr::Module { member, .. } => *member,
+        Expr::FieldAccess { object, field, span } => Expr::FieldAccess { object: Box::new(variable_target(*object)), field, span },
+        Expr::Index { array, index, span } => Expr::Index { array: Box::new(variable_target(*array)), index, span },
+        expr => expr,
+    }
+}
+
+// Whether `expr` names a storage location that an assignment can write back to
+fn is_place(expr: &Expr) -> bool {
+    match expr {
+        Expr::Variable { .. } | Expr::FieldAccess { .. } => true,
+        Expr::Index { array, index, .. } => !matches!(**index, Expr::Range { .. }) && is_place(array),
+        _ => false,
+    }
+}
+
+/// Yields the top-level statements one by one. A statement that fails to parse is
+/// yielded as an error and parsing resumes at the next one, like `parse` does.
+impl Iterator for Parser {
+    type Item = Result<Stmt, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_at_end() && self.scan_errors.is_empty() {
+            return None;
+        }
+
+        // Tokens before the previous one are never looked at again
+        if self.current > 1 {
+            self.tokens.drain(..self.current - 1);
+            self.current = 1;
+        }
+
+        let stmt = if self.is_at_end() { None } else { Some(self.declaration()) };
+        if stmt.as_ref().is_some_and(|stmt| stmt.is_err()) {
+            self.sync();
+        }
+
+        // A statement parsed from a source with holes in it is reported by the scan errors behind them
+        if !self.scan_errors.is_empty() {
+            return Some(Err(self.scan_errors.drain(..).collect::<Vec<_>>().join("\n")));
+        }
+        stmt
+    }
+}
+
+/// Drops the newline after the opening quotes, a blank closing line, and the indentation shared by all lines.
+fn dedent(text: &str) -> String {
+    let text = text.strip_prefix("\r\n").or_else(|| text.strip_prefix('\n')).unwrap_or(text);
+    let mut lines: Vec<&str> = text.lines().collect();
+    if lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    let indent = lines.iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines.iter()
+        .map(|line| if line.len() >= indent { &line[indent..] } else { line.trim_start() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::scanner::{Scanner, LiteralValue::*};
+    use crate::scanner::LiteralValue::*;
 
     #[test]
     fn test_addition() {
@@ -806,27 +1551,27 @@ mod tests {
             token_type: Number, 
             lexeme: "4".to_string(), 
             literal: Some(IntValue(4)), 
-            line_number: 0 };
+            line_number: 0, column: 0 };
         let plus = Token { 
             token_type: Plus, 
             lexeme: "+".to_string(), 
             literal: None, 
-            line_number: 0 };
+            line_number: 0, column: 0 };
         let three = Token { 
             token_type: Number, 
             lexeme: "3".to_string(), 
             literal: Some(IntValue(3)), 
-            line_number: 0 };
+            line_number: 0, column: 0 };
         let semicolon = Token { 
             token_type: Semicolon, 
             lexeme: ";".to_string(), 
             literal: None, 
-            line_number: 0 };
+            line_number: 0, column: 0 };
         let eof = Token {
             token_type: Eof,
             lexeme: "".to_string(),
             literal: None,
-            line_number: 0 };
+            line_number: 0, column: 0 };
 
         // Vector of tokens to be parsed
         let tokens = vec![four, plus, three, semicolon, eof];
@@ -862,4 +1607,178 @@ mod tests {
 
         assert_eq!(string_expr, "(== 1 (group (+ 3 5)))");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_struct_parent() {
+        let source = "struct Dog : Animal { breed: \"lab\" }";
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed = parser.parse().unwrap();
+
+        match &parsed[0] {
+            Stmt::StructStmt { name, parent, params, .. } => {
+                assert_eq!(name, "Dog");
+                assert_eq!(parent.as_deref(), Some("Animal"));
+                assert!(params.contains_key("breed"));
+            }
+            _ => panic!("Expected a struct statement"),
+        }
+    }
+
+    #[test]
+    fn test_interface_methods() {
+        let source = "interface Drawable { fn draw(); fn scale(factor); }";
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed = parser.parse().unwrap();
+
+        match &parsed[0] {
+            Stmt::InterfaceStmt { name, methods, .. } => {
+                assert_eq!(name, "Drawable");
+                assert_eq!(methods, &vec![("draw".to_string(), 0), ("scale".to_string(), 1)]);
+            }
+            _ => panic!("Expected an interface statement"),
+        }
+    }
+
+    #[test]
+    fn test_interpolated_heredoc() {
+        let source = "$\"\"\"\n    Total: {a + 1}\n      {{done}}\n    \"\"\";";
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed_expr = parser.parse().unwrap();
+        let string_expr = parsed_expr[0].to_string();
+
+        assert_eq!(string_expr, "(interpolate [Total: , (+ (var a) 1), \n  {done}])");
+    }
+
+    #[test]
+    fn test_variadic_log() {
+        let source = "log(\"x =\", x, \"y =\", 2);";
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed_expr = parser.parse().unwrap();
+        let string_expr = parsed_expr[0].to_string();
+
+        assert_eq!(string_expr, "(log x = (var x) y = 2)");
+    }
+
+    #[test]
+    fn test_log_level() {
+        let source = "log.warn(\"low disk\", free);";
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed_expr = parser.parse().unwrap();
+        let string_expr = parsed_expr[0].to_string();
+
+        assert_eq!(string_expr, "(log.warn low disk (var free))");
+    }
+
+    #[test]
+    fn test_fatal() {
+        let source = "fatal(\"missing config\");";
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed_expr = parser.parse().unwrap();
+        let string_expr = parsed_expr[0].to_string();
+
+        assert_eq!(string_expr, "(fatal missing config)");
+    }
+
+    #[test]
+    fn test_loop_jumps_need_an_enclosing_loop() {
+        let parse = |source: &str| Parser::new(Scanner::new(source).scan_tokens().unwrap()).parse();
+
+        assert!(parse("outer: compose(3) { while (true) { break outer; } continue; }").is_ok());
+        assert_eq!(parse("break;").unwrap_err(), "Line 1: 'break' outside of a loop.");
+        assert_eq!(parse("compose() { continue inner; }").unwrap_err(), "Line 1: Unknown loop label 'inner'.");
+        assert_eq!(parse("compose() { fn f() { break; } }").unwrap_err(), "Line 1: 'break' outside of a loop.");
+    }
+
+    #[test]
+    fn test_print() {
+        let source = "print(\"total:\", 3);";
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed_expr = parser.parse().unwrap();
+        let string_expr = parsed_expr[0].to_string();
+
+        assert_eq!(string_expr, "(print total: 3)");
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let source = "## Not attached to a declaration.\nlog(1);\n## Says hello.\n## Twice.\nfn greet() {}";
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let parsed = parser.parse().unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        match &parsed[1] {
+            Stmt::FuncStmt { doc, .. } => assert_eq!(doc.as_deref(), Some("Says hello.\nTwice.")),
+            _ => panic!("Expected a function statement"),
+        }
+    }
+
+    #[test]
+    fn streaming_yields_statements_as_they_are_parsed() {
+        let source = "## Says hello.\nfn greet() {}\nlog(1 +);\nprint(2);";
+        let mut parser = Parser::streaming(Scanner::new(source));
+
+        match parser.next() {
+            Some(Ok(Stmt::FuncStmt { doc, .. })) => assert_eq!(doc.as_deref(), Some("Says hello.")),
+            _ => panic!("Expected a function statement"),
+        }
+        assert!(parser.next().unwrap().is_err());
+        assert_eq!(parser.next().unwrap().unwrap().to_string(), "(print 2)");
+        assert!(parser.next().is_none());
+
+        let mut parser = Parser::streaming(Scanner::new("print(1);\nprint(\"open);"));
+        assert!(parser.next().unwrap().is_ok());
+        assert_eq!(parser.next().unwrap().unwrap_err(), "String not closed.");
+    }
+
+    #[test]
+    fn module_is_only_a_keyword_before_a_name() {
+        let source = "var module = 1;\nmodule \"util\" { fn show(n) { print(n); } }\nprint(module + 1);";
+        let parsed = Parser::new(Scanner::new(source).scan_tokens().unwrap()).parse().unwrap();
+        let printed: Vec<String> = parsed.iter().map(|stmt| stmt.to_string()).collect();
+        assert_eq!(printed, ["(var module)", "(module util (fn show(n) (block (print (var n)))))", "(print (+ (var module) 1))"]);
+    }
+
+    #[test]
+    fn statements_print_as_trees() {
+        let source = "if (a) print(1); elif (b) print(2); else print(3);\n\
+                      match (x) { 1, 2 => print(x); [first, ..rest] => print(first); else => print(0); }\n\
+                      rows: for (item in items) { continue rows; }\n\
+                      while (n > 0) print(n);\n\
+                      compose(3) print(1);\n\
+                      try { throw 1; } catch (e) { print(e); }\n\
+                      import \"lib\" as lib else nil;\n\
+                      const LIMIT = 3;\n\
+                      struct Point { x: 0, y: 0 }\n\
+                      interface Shape { fn area(self); }";
+        let parsed = Parser::new(Scanner::new(source).scan_tokens().unwrap()).parse().unwrap();
+        let printed: Vec<String> = parsed.iter().map(|stmt| stmt.to_string()).collect();
+        assert_eq!(printed, [
+            "(if (var a) (print 1) (elif (var b) (print 2)) (else (print 3)))",
+            "(match (var x) (1, 2 (print (var x))) ([first, ..rest] (print (var first))) (else (print 0)))",
+            "(for rows: item in (var items) (block (continue rows)))",
+            "(while (> (var n) 0) (print (var n)))",
+            "(compose 3 (print 1))",
+            "(try (block (throw 1)) catch e (block (print (var e))))",
+            "(import \"lib\" as lib else nil)",
+            "(const LIMIT)",
+            "(struct Point x y)",
+            "(interface Shape area/1)",
+        ]);
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::interpreter::Interpreter;
+use crate::literal_value::LiteralValue;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+
+/// File name of a project manifest.
+pub const MANIFEST: &str = "project.rcn";
+
+/// A project declared by a `project.rcn`, a script that sets `entry` and optionally
+/// `roots`, `name` and `version`:
+///
+/// ```text
+/// var name = "game";
+/// var version = "0.3.0";
+/// var entry = "src/main.rcn";
+/// var roots = ["src", "lib"];
+/// ```
+///
+/// Paths are relative to the manifest, so the project runs the same from any directory.
+pub struct Project {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub entry: PathBuf,
+    pub roots: Vec<PathBuf>, // The project directory itself comes first
+}
+
+/// The closest `project.rcn` in `dir` or one of its parents.
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|dir| dir.join(MANIFEST)).find(|path| path.is_file())
+}
+
+/// Runs the manifest at `path` and reads the project settings from its variables.
+pub fn load(path: &Path) -> Result<Project, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let tokens = Scanner::new(&contents).scan_tokens()?;
+    let stmts = Parser::new(tokens).parse()?;
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_path(&path.display().to_string());
+    interpreter.interpret(stmts).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let environment = interpreter.environment().borrow();
+    let text = |name: &str| match environment.get(name) {
+        None | Some(LiteralValue::Nil) => Ok(None),
+        Some(LiteralValue::StringValue(value)) => Ok(Some(value)),
+        Some(other) => Err(format!("{}: '{}' should be a string, got {}.", path.display(), name, other.to_type())),
+    };
+
+    let entry = text("entry")?.ok_or_else(|| format!("{}: 'entry' is not set.", path.display()))?;
+    let mut roots = vec![dir.clone()];
+    match environment.get("roots") {
+        None | Some(LiteralValue::Nil) => (),
+        Some(LiteralValue::Array(values)) => for value in values {
+            match value {
+                LiteralValue::StringValue(root) => roots.push(dir.join(root)),
+                other => return Err(format!("{}: 'roots' should hold paths, got {}.", path.display(), other.to_type())),
+            }
+        },
+        Some(other) => return Err(format!("{}: 'roots' should be an array, got {}.", path.display(), other.to_type())),
+    }
+
+    Ok(Project {
+        name: text("name")?,
+        version: text("version")?,
+        entry: dir.join(entry),
+        roots,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifests_are_found_upwards_and_resolve_paths_from_their_directory() {
+        let dir = std::env::temp_dir().join("recolon_project");
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::write(dir.join(MANIFEST), "var name = \"game\";\nvar entry = \"src/main.rcn\";\nvar roots = [\"lib\"];").unwrap();
+
+        let manifest = find(&dir.join("src/nested")).unwrap();
+        assert_eq!(manifest, dir.join(MANIFEST));
+
+        let project = load(&manifest).unwrap();
+        assert_eq!(project.name.as_deref(), Some("game"));
+        assert_eq!(project.version, None);
+        assert_eq!(project.entry, dir.join("src/main.rcn"));
+        assert_eq!(project.roots, vec![dir.clone(), dir.join("lib")]);
+
+        fs::write(dir.join(MANIFEST), "var roots = [\"lib\"];").unwrap();
+        assert!(load(&manifest).err().unwrap().ends_with("'entry' is not set."));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,5 +1,9 @@
 use std::string::String;
 use std::collections::HashMap;
+use std::io::BufRead;
+
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
 
 use TokenType::*;
 use LiteralValue::*;
@@ -10,7 +14,13 @@ pub struct Scanner {
     start: usize,
     current: usize,
     line: usize,
+    line_start: usize, // Index where the current line begins
+    start_line: usize,
+    start_column: usize,
     keywords: HashMap<&'static str, TokenType>,
+    finished: bool, // Set once the iterator has yielded Eof
+    input: Option<Box<dyn BufRead>>, // Where the rest of `source` comes from, line by line, when streaming
+    read_error: Option<String>, // Why reading `input` stopped early, reported once by the iterator
 }
 
 impl Scanner {
@@ -21,8 +31,47 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
             keywords: get_keyword_hashmap(),
+            finished: false,
+            input: None,
+            read_error: None,
+        }
+    }
+
+    /// A scanner that reads `input` a line at a time as tokens are asked for, so a large or
+    /// growing file can be run statement by statement. Lines before the current one are dropped.
+    pub fn from_reader(input: impl BufRead + 'static) -> Self {
+        Self { input: Some(Box::new(input)), ..Self::new("") }
+    }
+
+    // Reads lines from the input until index `upto` is in the source; false when the source ends first
+    fn fill(&mut self, upto: usize) -> bool {
+        while self.source.len() <= upto {
+            let Some(input) = self.input.as_mut() else { return false };
+            match input.read_line(&mut self.source) {
+                Ok(0) => self.input = None,
+                Ok(_) => (),
+                Err(e) => {
+                    self.read_error = Some(format!("Error reading the source at line {}: {}", self.line, e));
+                    self.input = None;
+                }
+            }
+        }
+        true
+    }
+
+    // Forgets the finished lines of a streamed source, keeping indexes relative to what is left
+    fn drop_finished_lines(&mut self) {
+        if self.input.is_none() || self.line_start == 0 {
+            return;
         }
+        let cut = self.source.char_indices().nth(self.line_start).map_or(self.source.len(), |(i, _)| i);
+        self.source.drain(..cut);
+        self.current -= self.line_start;
+        self.line_start = 0;
     }
 
     pub fn scan_tokens(&mut self) -> Result<Vec<Token>, String> {
@@ -30,19 +79,12 @@ impl Scanner {
         let mut errors = vec![];
     
         while !self.is_at_end() {
-            self.start = self.current;
-            match self.scan_token() {
-                Ok(_) => (),
-                Err(msg) => errors.push(msg),
+            if let Err(msg) = self.scan_next() {
+                errors.push(msg);
             }
         }
 
-        self.tokens.push(Token {
-            token_type: Eof,
-            lexeme: "".to_string(),
-            literal: None,
-            line_number: self.line,
-        });
+        self.tokens.push(self.eof());
 
         if !errors.is_empty() {
             // Join all error messages into a single string, separated by newlines
@@ -54,8 +96,35 @@ impl Scanner {
         Ok(self.tokens.clone())
     }
 
-    fn is_at_end(&self) -> bool {
-        self.current >= self.source.len()
+    fn scan_next(&mut self) -> Result<(), String> {
+        self.drop_finished_lines();
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.current - self.line_start + 1;
+        self.scan_token()
+    }
+
+    fn eof(&self) -> Token {
+        Token {
+            token_type: Eof,
+            lexeme: "".to_string(),
+            literal: None,
+            line_number: self.line,
+            column: self.current - self.line_start + 1,
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    fn previous_char(&self) -> char {
+        self.source.chars().nth(self.current - 1).unwrap()
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        !self.fill(self.current)
     }
 
     fn scan_token(&mut self) -> Result<(), String> {
@@ -69,7 +138,18 @@ impl Scanner {
             '[' => self.add_token(LeftBracket),
             ']' => self.add_token(RightBracket),
             ',' => self.add_token(Comma),
-            '.' => self.add_token(Dot),
+            '?' => self.add_token(Question),
+            '.' => {
+                // `..` and `..=` build ranges like `1..10`
+                let token = if !self.char_match('.') {
+                    Dot
+                } else if self.char_match('=') {
+                    DotDotEqual
+                } else {
+                    DotDot
+                };
+                self.add_token(token);
+            },
             ';' => self.add_token(Semicolon),
             ':' => self.add_token(Colon),
             '+' => self.add_token(Plus),
@@ -77,9 +157,16 @@ impl Scanner {
             '/' => self.add_token(Slash),
             '*' => self.add_token(Star),
             '#' => {
+                let is_doc = self.char_match('#');
                 while self.peek() != '\n' && !self.is_at_end() {
                     self.advance(); // Skip the rest of the line
                 }
+
+                // `##` comments document the declaration that follows them
+                if is_doc {
+                    let text = self.source[self.start + 2..self.current].trim().to_string();
+                    self.add_token_lit(DocComment, Some(StringValue(text)));
+                }
             },
             '!' => {
                 let token = if self.char_match('=') {
@@ -92,6 +179,8 @@ impl Scanner {
             '=' => {
                 let token = if self.char_match('=') {
                     TokenType::EqualEqual
+                } else if self.char_match('>') {
+                    TokenType::FatArrow
                 } else {
                     TokenType::Equal
                 };
@@ -114,8 +203,22 @@ impl Scanner {
                 self.add_token(token);
             },
             ' ' | '\r' | '\t' => {},
-            '\n' => self.line += 1,
-            '"' => self.string()?,
+            '\n' => self.new_line(),
+            '"' => {
+                if self.peek() == '"' && self.peek_next() == '"' {
+                    self.raw_string(String)?
+                } else {
+                    self.string()?
+                }
+            },
+            '$' => {
+                if self.fill(self.current + 2) && self.source[self.current..].starts_with("\"\"\"") {
+                    self.advance();
+                    self.raw_string(InterpolatedString)?
+                } else {
+                    return Err(format!("Expected '\"\"\"' after '$' at line {}", self.line));
+                }
+            },
             c => {
                 if is_digit(c) {
                     let _ = self.number();
@@ -139,7 +242,7 @@ impl Scanner {
     }
 
     fn peek_next(&mut self) -> char {
-        if self.current + 1 >= self.source.len() {
+        if !self.fill(self.current + 1) {
             return '\0'
         }
 
@@ -152,19 +255,19 @@ impl Scanner {
         }
 
         if self.source.chars().nth(self.current).unwrap() != _ch {
-            return false;
+            false
         } else {
             self.current += 1;
-            return true;
+            true
         }
     }
 
     fn string(&mut self) -> Result<(), String> {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-            }
             self.advance();
+            if self.previous_char() == '\n' {
+                self.new_line();
+            }
         }
 
         if self.is_at_end() {
@@ -180,6 +283,35 @@ impl Scanner {
         Ok(())
     }
 
+    // Scan a triple-quoted string, kept verbatim including newlines
+    fn raw_string(&mut self, token_type: TokenType) -> Result<(), String> {
+        self.advance();
+        self.advance();
+        let content_start = self.current;
+
+        while !self.is_at_end() {
+            if self.fill(self.current + 2) && self.source[self.current..].starts_with("\"\"\"") {
+                break;
+            }
+            self.advance();
+            if self.previous_char() == '\n' {
+                self.new_line();
+            }
+        }
+
+        if self.is_at_end() {
+            return Err("Raw string not closed.".to_string())
+        }
+
+        self.current += 3;
+
+        let value = &self.source[content_start..self.current - 3];
+
+        self.add_token_lit(token_type, Some(StringValue(value.to_string())));
+
+        Ok(())
+    }
+
     fn number(&mut self) -> Result<(), String> {
         while is_digit(self.peek()) {
             self.advance();
@@ -193,12 +325,33 @@ impl Scanner {
             }
         }
 
+        // A `d` suffix, as in `19.99d`, makes an exact Decimal
+        if self.peek() == 'd' && !is_alpha_numeric(self.peek_next()) {
+            let digits = &self.source[self.start..self.current];
+            let value = Decimal::from_str_exact(digits).map_err(|_| format!("Could not parse decimal: {}d", digits))?;
+            self.advance();
+            self.add_token_lit(Number, Some(DecimalValue(value)));
+            return Ok(());
+        }
+
         let substring = &self.source[self.start..self.current];
+        if let Ok(value) = substring.parse::<i64>() {
+            self.add_token_lit(Number, Some(IntValue(value)));
+            return Ok(());
+        }
+        // Whole numbers past the i64 range stay exact as a BigInt
+        if !substring.contains('.') {
+            if let Ok(value) = substring.parse::<BigInt>() {
+                self.add_token_lit(Number, Some(BigIntValue(value)));
+                return Ok(());
+            }
+        }
+
         let value = substring.parse::<f64>();
         match value {
             Ok(value) => self.add_token_lit(Number, Some(FloatValue(value))),
             Err(_) => return Err(format!("Could not parse number: {}", substring))
-        } 
+        }
 
         Ok(())
     }
@@ -224,13 +377,7 @@ impl Scanner {
     }
 
     fn add_token(&mut self, token_type: TokenType) {
-        let text = self.source[self.start..self.current].to_string();
-        self.tokens.push(Token {
-            token_type,
-            lexeme: text,
-            literal: None,
-            line_number: self.line,
-        });
+        self.add_token_lit(token_type, None);
     }
 
     fn add_token_lit(
@@ -243,11 +390,38 @@ impl Scanner {
             token_type,
             lexeme: text,
             literal,
-            line_number: self.line,
+            line_number: self.start_line,
+            column: self.start_column,
         });
     }
 }
 
+/// Scans lazily, one token per call, so a consumer can stop at the first error
+/// without the rest of the source being tokenized. Ends with Eof like `scan_tokens`.
+impl Iterator for Scanner {
+    type Item = Result<Token, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // `tokens` only holds what the last scan step produced and has not been yielded yet
+        while self.tokens.is_empty() {
+            if self.is_at_end() {
+                if let Some(msg) = self.read_error.take() {
+                    return Some(Err(msg));
+                }
+                if self.finished {
+                    return None;
+                }
+                self.finished = true;
+                return Some(Ok(self.eof()));
+            }
+            if let Err(msg) = self.scan_next() {
+                return Some(Err(msg));
+            }
+        }
+        Some(Ok(self.tokens.remove(0)))
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TokenType {
     LeftParen,
@@ -258,8 +432,11 @@ pub enum TokenType {
     RightBracket,
     Comma,
     Dot,
+    DotDot,
+    DotDotEqual,
     Semicolon,
     Colon,
+    Question,
     Plus,
     Minus,
     Slash,
@@ -269,6 +446,7 @@ pub enum TokenType {
     BangEqual,
     Equal,
     EqualEqual,
+    FatArrow,
     Greater,
     GreaterEqual,
     Less,
@@ -276,12 +454,15 @@ pub enum TokenType {
 
     Identifier,
     String,
+    InterpolatedString,
+    DocComment,
     Number,
     Var,
     Const,
 
     If,
     Elif,
+    Match,
     Else,
     For,
     In,
@@ -295,11 +476,22 @@ pub enum TokenType {
     Class,
     Function,
     Struct,
+    Interface,
+    Implements,
     Log,
+    LogCompact,
     Error,
+    Fatal,
     Print,
     Return,
+    Yield,
+    With,
+    Try,
+    Catch,
+    Throw,
     Loop,
+    Break,
+    Continue,
 
     Import,
     As,
@@ -314,11 +506,13 @@ impl std::fmt::Display for TokenType {
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum LiteralValue {
     IntValue(i64),
+    BigIntValue(BigInt),
     FloatValue(f64),
+    DecimalValue(Decimal),
     StringValue(String),
-    IdentifierValue(String),
 }
 
 #[derive(Debug, Clone)]
@@ -327,37 +521,46 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<LiteralValue>,
     pub line_number: usize,
+    pub column: usize,
 }
 
 impl Token {
-    pub fn new(
-        token_type: TokenType,
-        lexeme: String,
-        literal: Option<LiteralValue>,
-        line_number: usize,
-    ) -> Self {
-        Self {
-            token_type,
-            lexeme,
-            literal,
-            line_number,
+    pub fn span(&self) -> Span {
+        Span { line: self.line_number, column: self.column }
+    }
+}
+
+/// Position of a token or syntax node in the source, both counted from 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    // Prefixes a runtime error with this position, unless a nested node already did
+    pub fn locate(&self, msg: String) -> String {
+        if msg.starts_with("Line ") || *self == Span::default() {
+            msg
+        } else {
+            format!("Line {}:{}: {}", self.line, self.column, msg)
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!("{} {} {:?}", self.token_type, self.lexeme, self.literal)
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} {:?}", self.token_type, self.lexeme, self.literal)
     }
 }
 
 //Helper Functions
 fn is_digit(ch: char) -> bool {
-    ch as u8 >= '0' as u8 && ch as u8 <= '9' as u8
+    ch.is_ascii_digit()
 }
 
 fn is_alpha(ch: char) -> bool {
-    (ch as u8 >= 'a' as u8 && ch as u8 <= 'z' as u8) || 
-    (ch as u8 >= 'A' as u8 && ch as u8 <= 'Z' as u8) ||
-    (ch == '_')
+    ch.is_ascii_alphabetic() || ch == '_'
 }
 
 fn is_alpha_numeric(ch: char) -> bool {
@@ -368,6 +571,7 @@ fn get_keyword_hashmap() -> HashMap<&'static str, TokenType> {
     HashMap::from([
         ("if", If), // Works
         ("elif", Elif), // Works
+        ("match", Match),
         ("else", Else), // Works
         ("for", For), // Works
         ("in", In), // Works
@@ -381,12 +585,23 @@ fn get_keyword_hashmap() -> HashMap<&'static str, TokenType> {
         ("class", Class),
         ("fn", Function), // Works
         ("struct", Struct), // Works
+        ("interface", Interface),
+        ("implements", Implements),
         ("return", Return), // Works
+        ("yield", Yield),
+        ("with", With),
+        ("try", Try),
+        ("catch", Catch),
+        ("throw", Throw),
         ("compose", Loop), // Works
+        ("break", Break),
+        ("continue", Continue),
         ("var", Var), // Works
         ("const", Const),
         ("log", Log), // Works
+        ("log_compact", LogCompact),
         ("err", Error), // Works
+        ("fatal", Fatal),
         ("print", Print), // Works
         ("import", Import), // Works
         ("as", As), // Works
@@ -397,6 +612,40 @@ fn get_keyword_hashmap() -> HashMap<&'static str, TokenType> {
 mod tests {
     use super::*;
 
+    // Hands out one line per read and counts them, like a file that is still being written
+    struct LineByLine {
+        lines: Vec<&'static str>,
+        read: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl std::io::Read for LineByLine {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some(line) = self.lines.get(self.read.get()) else { return Ok(0) };
+            buf[..line.len()].copy_from_slice(line.as_bytes());
+            self.read.set(self.read.get() + 1);
+            Ok(line.len())
+        }
+    }
+
+    #[test]
+    fn streamed_sources_are_read_as_tokens_are_needed() {
+        let lines = vec!["var a = 1;\n", "# comment\n", "print(\"two\n", "lines\", a);\n", "var \"\"\"raw\"\"\" = 2.5;"];
+        let read = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut scanner = Scanner::from_reader(std::io::BufReader::new(LineByLine { lines: lines.clone(), read: read.clone() }));
+
+        let first: Vec<Token> = scanner.by_ref().take(5).map(Result::unwrap).collect();
+        assert_eq!(first.last().unwrap().token_type, Semicolon);
+        assert_eq!(read.get(), 1);
+
+        // The rest, including a string across lines, comes out as if the whole source was scanned at once
+        let mut streamed = first;
+        streamed.extend(scanner.map(Result::unwrap));
+        let whole = Scanner::new(&lines.concat()).scan_tokens().unwrap();
+        let describe = |tokens: &[Token]| tokens.iter().map(|token| (token.lexeme.clone(), token.line_number, token.column)).collect::<Vec<_>>();
+        assert_eq!(describe(&streamed), describe(&whole));
+        assert_eq!(read.get(), lines.len());
+    }
+
     #[test]
     fn handle_one_char_tokens() {
         let source = "(( ))";
@@ -466,6 +715,21 @@ mod tests {
         assert_eq!(scanner.tokens[1].token_type, Eof); // Second token should be Eof
     }
 
+    #[test]
+    fn handle_raw_string_lit() {
+        let source = "\"\"\"SELECT *\nFROM \"users\";\"\"\"";
+        let mut scanner = Scanner::new(source);
+        let _ = scanner.scan_tokens().expect("Failed to scan tokens");
+
+        assert_eq!(scanner.tokens.len(), 2);
+        assert_eq!(scanner.tokens[0].token_type, String);
+        match scanner.tokens[0].literal.as_ref().unwrap() {
+            StringValue(val) => assert_eq!(val, "SELECT *\nFROM \"users\";"),
+            _ => panic!("Incorrect literal type"),
+        }
+        assert_eq!(scanner.line, 2);
+    }
+
     #[test]
     fn number_literals() {
         let source = "123.123\n321.0\n5"; 
@@ -531,4 +795,21 @@ mod tests {
         assert_eq!(tokens[5].token_type, Eof);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn iterating_yields_the_same_tokens_lazily() {
+        let source = "var x = 1; @ log x;";
+        let expected: Vec<TokenType> = vec![Var, Identifier, Equal, Number, Semicolon, Log, Identifier, Semicolon, Eof];
+
+        let mut scanned = vec![];
+        let mut errors = vec![];
+        for token in Scanner::new(source) {
+            match token {
+                Ok(token) => scanned.push(token.token_type),
+                Err(msg) => errors.push(msg),
+            }
+        }
+
+        assert_eq!(scanned, expected);
+        assert_eq!(errors, vec!["Unrecognized token '@' at line 1".to_string()]);
+    }
+}
@@ -1,71 +1,307 @@
 use std::collections::HashMap;
 use crate::expr::{Expr};
-use crate::scanner::Token;
+use crate::scanner::{Span, Token};
+
+/// Severity of a `log.debug/info/warn` statement, ordered from most to least verbose.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+}
+
+impl LogLevel {
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name.to_lowercase().as_str() {
+            "debug" => Some(LogLevel::Debug),
+            "info" => Some(LogLevel::Info),
+            "warn" => Some(LogLevel::Warn),
+            _ => None,
+        }
+    }
+
+    // Threshold from the RCN_LOG environment variable, defaulting to info
+    pub fn from_env() -> LogLevel {
+        std::env::var("RCN_LOG").ok()
+            .and_then(|name| LogLevel::from_name(&name))
+            .unwrap_or(LogLevel::Info)
+    }
+}
+
+/// What a `match` arm compares its value with. Names in the pattern are bound in the arm's body.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Literal(Expr), // 1, "text", -2, true or nil, compared with ==
+    Bind(String), // Any value, bound to the name; `_` binds nothing
+    Array { elements: Vec<Pattern>, rest: Option<String> }, // `[a, b]`, or `[first, ..rest]` for at least that many elements
+    Tuple(Vec<Pattern>), // `(a, b)`
+    Struct { name: String, fields: Vec<(String, Pattern)> }, // `Point { x: 0, y }`, the `y` being short for `y: y`
+}
 
 #[derive(Clone, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Stmt {
-    Expression { expression: Expr },
-    Log { expression: Expr },
-    Err { expression: Expr },
-    Print { expression: Expr },
-    Var { name: Token, initializer: Expr },
-    Const { name: Token, initializer: Expr },
-    Block { statements: Vec<Stmt>},
+    Expression { expression: Expr, span: Span },
+    Log { expressions: Vec<Expr>, compact: bool, level: Option<LogLevel>, span: Span },
+    Err { expression: Expr, fatal: bool, span: Span },
+    Print { expressions: Vec<Expr>, span: Span },
+    Var { name: Token, initializer: Expr, span: Span },
+    Unpack { names: Vec<Token>, by_field: bool, initializer: Expr, span: Span }, // `var (a, b) = pair;`, or `var { x, y } = point;` by field
+    Const { name: Token, initializer: Expr, doc: Option<String>, span: Span },
+    Block { statements: Vec<Stmt>, span: Span },
     IfStmt {
         predicate: Expr,
         then: Box<Stmt>,
         elifs: Vec<(Expr, Box<Stmt>)>,
         els: Option<Box<Stmt>>,
+        span: Span,
+    },
+    Match {
+        subject: Expr,
+        arms: Vec<(Vec<Pattern>, Box<Stmt>)>, // Patterns, any of which selects the body
+        default: Option<Box<Stmt>>, // The `else` arm
+        span: Span,
     },
     Import {
         module_name: String,
-        alias_name: String
+        alias_name: String,
+        fallback: Option<Expr>, // From `else value`, used when the module file doesn't exist
+        span: Span,
+    },
+    // `module "name" { ... }` from `recolon bundle`, imported by that name instead of a file
+    Module {
+        name: String,
+        body: Vec<Stmt>,
+        span: Span,
     },
     WhileStmt {
         condition: Expr,
         body: Box<Stmt>,
+        increment: Option<Expr>, // Runs after every iteration, even one ended by `continue`
+        label: Option<String>,
+        span: Span,
     },
     ReturnStmt {
         keyword: Token,
-        value: Option<Expr>
+        value: Option<Expr>,
+        span: Span,
+    },
+    Yield {
+        value: Expr,
+        span: Span,
+    },
+    With {
+        name: Token, // Bound to the resource inside the body
+        initializer: Expr,
+        body: Box<Stmt>,
+        span: Span,
+    },
+    Try {
+        body: Box<Stmt>,
+        name: Option<Token>, // From `catch (e)`, bound to the error inside the handler
+        handler: Box<Stmt>,
+        span: Span,
+    },
+    Throw {
+        value: Expr,
+        span: Span,
+    },
+    ForIn {
+        name: Token, // Bound to each element in a fresh scope per iteration
+        iterable: Expr,
+        body: Box<Stmt>,
+        label: Option<String>,
+        span: Span,
     },
     LoopStmt {
-        body: Box<Stmt>
+        body: Box<Stmt>,
+        count: Option<Expr>, // `compose(n)` runs the body n times, `compose()` until a break
+        label: Option<String>,
+        span: Span,
+    },
+    Break {
+        label: Option<String>,
+        span: Span,
+    },
+    Continue {
+        label: Option<String>,
+        span: Span,
     },
     FuncStmt {
         name: String,
         parameters: Vec<Token>,
-        body: Vec<Box<Stmt>>,
+        body: Vec<Stmt>,
+        doc: Option<String>,
+        source: String, // The declaration's tokens, to declare the fn again on a par_map thread
+        generator: bool, // Whether the body yields, which makes calls return a Generator
+        span: Span,
     },
     StructStmt {
         name: String,
-        params: HashMap<String, Expr>
+        parent: Option<String>,
+        interfaces: Vec<String>,
+        params: HashMap<String, Expr>,
+        functions: Vec<Stmt>, // `fn` declarations in the body, called on the struct like `Point.new(1, 2)`
+        doc: Option<String>,
+        span: Span,
+    },
+    InterfaceStmt {
+        name: String,
+        methods: Vec<(String, usize)>,
+        span: Span,
     }
 }
 
 impl Stmt {
-    pub fn to_string(&self) -> String {
-        use Stmt::*;
+    /// Where the statement starts in the source.
+    pub fn span(&self) -> Span {
         match self {
-            Expression { expression } => expression.to_string(),
-            Log { expression } => format!("(log {})", expression.to_string()),
-            Err { expression } => format!("(err {})", expression.to_string()),
-            Print { expression } => format!("(log {})", expression.to_string()),
-            Var { name, initializer: _ } => format!("(var {})", name.lexeme),
-            Block { statements } => format!(
-                "(block {}",
-                statements.into_iter().map(|stmt| stmt.to_string())
-                    .collect::<String>()
-            ),
-            ReturnStmt { keyword: _, value } => {
+            Stmt::Expression { span, .. }
+            | Stmt::Log { span, .. }
+            | Stmt::Err { span, .. }
+            | Stmt::Print { span, .. }
+            | Stmt::Var { span, .. }
+            | Stmt::Unpack { span, .. }
+            | Stmt::Const { span, .. }
+            | Stmt::Block { span, .. }
+            | Stmt::IfStmt { span, .. }
+            | Stmt::Match { span, .. }
+            | Stmt::Import { span, .. }
+            | Stmt::Module { span, .. }
+            | Stmt::WhileStmt { span, .. }
+            | Stmt::ForIn { span, .. }
+            | Stmt::With { span, .. }
+            | Stmt::Try { span, .. }
+            | Stmt::Throw { span, .. }
+            | Stmt::ReturnStmt { span, .. }
+            | Stmt::Yield { span, .. }
+            | Stmt::LoopStmt { span, .. }
+            | Stmt::Break { span, .. }
+            | Stmt::Continue { span, .. }
+            | Stmt::FuncStmt { span, .. }
+            | Stmt::StructStmt { span, .. }
+            | Stmt::InterfaceStmt { span, .. } => *span,
+        }
+    }
+}
+
+impl std::fmt::Display for Stmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Stmt::*;
+        let text = match self {
+            Expression { expression, .. } => expression.to_string(),
+            Log { expressions, level, .. } => format!("(log{} {})", level.map_or(String::new(), |level| format!(".{:?}", level).to_lowercase()), expressions.iter().map(|expr| expr.to_string()).collect::<Vec<_>>().join(" ")),
+            Err { expression, fatal, .. } => format!("({} {})", if *fatal { "fatal" } else { "err" }, expression),
+            Print { expressions, .. } => format!("(print {})", expressions.iter().map(|expr| expr.to_string()).collect::<Vec<_>>().join(" ")),
+            Var { name, .. } => format!("(var {})", name.lexeme),
+            Unpack { names, .. } => format!("(var {})", names.iter().map(|name| name.lexeme.as_str()).collect::<Vec<_>>().join(", ")),
+            Block { statements, .. } => format!("(block {})", join(statements, " ")),
+            ReturnStmt { keyword, value, .. } => {
                 let value_str = match value {
                     Some(expr) => expr.to_string(),
                     None => "None".to_string(),
                 };
-                format!("(return ReturnStmt with value: {}", value_str)
+                format!("({} ReturnStmt with value: {}", keyword.lexeme, value_str)
+            }
+            Yield { value, .. } => format!("(yield {})", value),
+            With { name, initializer, body, .. } => format!("(with {} {} {})", name.lexeme, initializer, body),
+            Throw { value, .. } => format!("(throw {})", value),
+            Break { label, .. } => format!("(break{})", label.as_ref().map_or(String::new(), |label| format!(" {}", label))),
+            Continue { label, .. } => format!("(continue{})", label.as_ref().map_or(String::new(), |label| format!(" {}", label))),
+            Const { name, .. } => format!("(const {})", name.lexeme),
+            IfStmt { predicate, then, elifs, els, .. } => format!(
+                "(if {} {}{}{})",
+                predicate,
+                then,
+                elifs.iter().map(|(predicate, body)| format!(" (elif {} {})", predicate, body)).collect::<String>(),
+                els.as_ref().map_or(String::new(), |els| format!(" (else {})", els)),
+            ),
+            Match { subject, arms, default, .. } => format!(
+                "(match {}{}{})",
+                subject,
+                arms.iter().map(|(patterns, body)| format!(" ({} {})", join(patterns, ", "), body)).collect::<String>(),
+                default.as_ref().map_or(String::new(), |default| format!(" (else {})", default)),
+            ),
+            Import { module_name, alias_name, fallback, .. } => format!(
+                "(import {} as {}{})",
+                module_name,
+                alias_name,
+                fallback.as_ref().map_or(String::new(), |fallback| format!(" else {}", fallback)),
+            ),
+            Module { name, body, .. } => format!("(module {} {})", name, join(body, " ")),
+            WhileStmt { condition, body, increment, label, .. } => format!(
+                "(while{} {} {}{})",
+                label.as_ref().map_or(String::new(), |label| format!(" {}:", label)),
+                condition,
+                body,
+                increment.as_ref().map_or(String::new(), |increment| format!(" {}", increment)),
+            ),
+            ForIn { name, iterable, body, label, .. } => format!(
+                "(for{} {} in {} {})",
+                label.as_ref().map_or(String::new(), |label| format!(" {}:", label)),
+                name.lexeme,
+                iterable,
+                body,
+            ),
+            LoopStmt { body, count, label, .. } => format!(
+                "(compose{} {} {})",
+                label.as_ref().map_or(String::new(), |label| format!(" {}:", label)),
+                count.as_ref().map_or(String::new(), |count| count.to_string()),
+                body,
+            ),
+            Try { body, name, handler, .. } => format!(
+                "(try {} catch{} {})",
+                body,
+                name.as_ref().map_or(String::new(), |name| format!(" {}", name.lexeme)),
+                handler,
+            ),
+            FuncStmt { name, parameters, body, .. } => format!(
+                "(fn {}({}) {})",
+                name,
+                parameters.iter().map(|parameter| parameter.lexeme.as_str()).collect::<Vec<_>>().join(", "),
+                join(body, " "),
+            ),
+            StructStmt { name, parent, params, functions, .. } => {
+                let mut fields: Vec<&String> = params.keys().collect();
+                fields.sort();
+                format!(
+                    "(struct {}{} {}{})",
+                    name,
+                    parent.as_ref().map_or(String::new(), |parent| format!(": {}", parent)),
+                    fields.iter().map(|field| field.as_str()).collect::<Vec<_>>().join(" "),
+                    functions.iter().map(|function| format!(" {}", function)).collect::<String>(),
+                )
+            }
+            InterfaceStmt { name, methods, .. } => format!(
+                "(interface {} {})",
+                name,
+                methods.iter().map(|(method, arity)| format!("{}/{}", method, arity)).collect::<Vec<_>>().join(" "),
+            ),
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Literal(value) => write!(f, "{}", value),
+            Pattern::Bind(name) => write!(f, "{}", name),
+            Pattern::Array { elements, rest } => {
+                let rest = rest.as_ref().map(|rest| format!("..{}", rest));
+                let parts: Vec<String> = elements.iter().map(|element| element.to_string()).chain(rest).collect();
+                write!(f, "[{}]", parts.join(", "))
+            }
+            Pattern::Tuple(elements) => write!(f, "({})", join(elements, ", ")),
+            Pattern::Struct { name, fields } => {
+                let fields: Vec<String> = fields.iter().map(|(field, pattern)| format!("{}: {}", field, pattern)).collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
             }
-            _ => todo!(),
         }
     }
 }
 
+fn join<T: std::fmt::Display>(items: &[T], separator: &str) -> String {
+    items.iter().map(|item| item.to_string()).collect::<Vec<_>>().join(separator)
+}
+
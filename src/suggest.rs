@@ -0,0 +1,51 @@
+/// ` Did you mean 'name'?` for the candidate closest to a name that wasn't found, to append to
+/// the error, or nothing when no candidate is close enough to be a likely typo.
+pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    // Roughly one typo per three characters, so short names don't match everything
+    let limit = (name.chars().count() / 3).max(1);
+    let closest = candidates.into_iter()
+        .filter(|candidate| *candidate != name && !candidate.starts_with("__") && !candidate.contains('.'))
+        .map(|candidate| (edit_distance(&name.to_lowercase(), &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min();
+
+    match closest {
+        Some((_, candidate)) => format!(" Did you mean '{}'?", candidate),
+        None => String::new(),
+    }
+}
+
+// Levenshtein distance, with swapping two neighbouring characters counted as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            rows[i][j] = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                rows[i][j] = rows[i][j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_the_closest_name_within_a_few_edits() {
+        assert_eq!(edit_distance("lenght", "length"), 1);
+        assert_eq!(did_you_mean("conut", ["count", "amount", "counter"]), " Did you mean 'count'?");
+        assert_eq!(did_you_mean("Print", ["print"]), " Did you mean 'print'?");
+        assert_eq!(did_you_mean("x", ["y", "xs"]), " Did you mean 'xs'?");
+        assert_eq!(did_you_mean("total", ["width", "__file__", "io.capture"]), "");
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::interpreter::{InterruptHandle, Interpreter};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+
+type Reply = Result<(), String>;
+
+/// A `Send + Sync` handle to an interpreter that lives on its own thread.
+///
+/// Values and environments are `Rc`-based and never leave that thread; scripts are
+/// sent to it as source and only their outcome comes back, so the handle can be
+/// shared between worker threads or held across `.await` points.
+pub struct ThreadedInterpreter {
+    jobs: Option<Sender<(String, Sender<Reply>)>>,
+    worker: Option<JoinHandle<()>>,
+    interrupt: InterruptHandle,
+}
+
+impl ThreadedInterpreter {
+    pub fn spawn() -> Self {
+        Self::spawn_with(|_| ())
+    }
+
+    // `configure` runs on the interpreter's thread before the first script, e.g. to set the log level
+    pub fn spawn_with(configure: impl FnOnce(&mut Interpreter) + Send + 'static) -> Self {
+        let (jobs, incoming) = mpsc::channel::<(String, Sender<Reply>)>();
+        let (handle_sender, handle) = mpsc::channel();
+
+        let worker = thread::spawn(move || {
+            let mut interpreter = Interpreter::new();
+            configure(&mut interpreter);
+            let _ = handle_sender.send(interpreter.interrupt_handle());
+
+            for (source, reply) in incoming {
+                // The caller may have stopped waiting, which is fine
+                let _ = reply.send(run_source(&mut interpreter, &source));
+            }
+        });
+
+        Self {
+            jobs: Some(jobs),
+            worker: Some(worker),
+            interrupt: handle.recv().expect("Interpreter thread failed to start."),
+        }
+    }
+
+    // Cancels the script that is currently running; queued ones still run
+    pub fn interrupt(&self) {
+        self.interrupt.interrupt();
+    }
+
+    // Queue `source` behind earlier scripts and return where its outcome will arrive
+    pub fn submit(&self, source: &str) -> Receiver<Reply> {
+        let (reply, outcome) = mpsc::channel();
+        if let Some(jobs) = &self.jobs {
+            if let Err(mpsc::SendError((_, reply))) = jobs.send((source.to_string(), reply)) {
+                let _ = reply.send(Err("Interpreter thread has stopped.".to_string()));
+            }
+        }
+        outcome
+    }
+
+    // Run `source` and wait for it to finish. Globals persist between runs, as in the REPL.
+    pub fn run(&self, source: &str) -> Result<(), String> {
+        self.submit(source).recv().unwrap_or_else(|_| Err("Interpreter thread has stopped.".to_string()))
+    }
+}
+
+impl Drop for ThreadedInterpreter {
+    fn drop(&mut self) {
+        // Closing the channel ends the worker's loop once queued scripts are done
+        self.jobs = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_source(interpreter: &mut Interpreter, source: &str) -> Result<(), String> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+    let stmts = Parser::new(tokens).parse()?;
+    interpreter.check_redefinitions(&stmts)?;
+    interpreter.interpret(stmts).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn handle_is_shared_between_threads() {
+        let interpreter = Arc::new(ThreadedInterpreter::spawn());
+        interpreter.run("var total = 0; fn add(n) { total = total + n; }").unwrap();
+
+        let workers: Vec<_> = (0..4).map(|_| {
+            let interpreter = interpreter.clone();
+            thread::spawn(move || interpreter.run("add(1);"))
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+
+        assert!(interpreter.run("if (total != 4) { fatal(\"wrong total\"); }").is_ok());
+        assert!(interpreter.run("undefined_call();").is_err());
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::environment::Environment;
+use crate::expr::Expr;
+use crate::literal_value::LiteralValue;
+use crate::scanner::TokenType;
+use crate::stmt::Stmt;
+
+/// Type of an expression as far as it can be known without running it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Int,
+    BigInt,
+    Float,
+    Decimal,
+    String,
+    Bool,
+    Nil,
+    Array(Box<Type>), // Element type, Unknown when mixed
+    Tuple(Vec<Type>),
+    Map,
+    Function { arity: Option<usize>, returns: Box<Type> },
+    Struct(String), // Instance of the named struct
+    StructDef(String),
+    Interface(String),
+    Namespace,
+    StringBuilder,
+    Progress,
+    FileHandle,
+    Generator,
+    HtmlElement,
+    Unknown,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "Int"),
+            Type::BigInt => write!(f, "BigInt"),
+            Type::Float => write!(f, "Float"),
+            Type::Decimal => write!(f, "Decimal"),
+            Type::String => write!(f, "String"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Nil => write!(f, "nil"),
+            Type::Array(element) if **element == Type::Unknown => write!(f, "Array"),
+            Type::Array(element) => write!(f, "Array<{}>", element),
+            Type::Tuple(elements) => write!(f, "({})", elements.iter().map(|element| element.to_string()).collect::<Vec<_>>().join(", ")),
+            Type::Map => write!(f, "Map"),
+            Type::Function { arity, returns } => {
+                match arity {
+                    Some(arity) => write!(f, "Function/{}", arity)?,
+                    None => write!(f, "Function")?,
+                }
+                if **returns != Type::Unknown {
+                    write!(f, " -> {}", returns)?;
+                }
+                Ok(())
+            }
+            Type::Struct(name) => write!(f, "{}", name),
+            Type::StructDef(name) => write!(f, "Struct {}", name),
+            Type::Interface(name) => write!(f, "interface {}", name),
+            Type::Namespace => write!(f, "Namespace"),
+            Type::StringBuilder => write!(f, "StringBuilder"),
+            Type::Progress => write!(f, "Progress"),
+            Type::FileHandle => write!(f, "FileHandle"),
+            Type::Generator => write!(f, "Generator"),
+            Type::HtmlElement => write!(f, "HtmlElement"),
+            Type::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+impl Type {
+    pub fn of_value(value: &LiteralValue) -> Type {
+        match value {
+            LiteralValue::Int(_) => Type::Int,
+            LiteralValue::BigInt(_) => Type::BigInt,
+            LiteralValue::Float(_) => Type::Float,
+            LiteralValue::Decimal(_) => Type::Decimal,
+            LiteralValue::StringValue(_) => Type::String,
+            LiteralValue::True | LiteralValue::False => Type::Bool,
+            LiteralValue::Nil => Type::Nil,
+            LiteralValue::Array(elements) => Type::Array(Box::new(common_type(elements.iter().map(Type::of_value)))),
+            LiteralValue::Tuple(elements) => Type::Tuple(elements.iter().map(Type::of_value).collect()),
+            LiteralValue::Map(_) => Type::Map,
+            LiteralValue::Callable { arity, .. } => Type::Function { arity: Some(*arity as usize), returns: Box::new(Type::Unknown) },
+            LiteralValue::StructDef(definition) => Type::StructDef(definition.name.clone()),
+            LiteralValue::StructInst(instance) => Type::Struct(instance.borrow().name.clone()),
+            LiteralValue::Interface(interface) => Type::Interface(interface.name.clone()),
+            LiteralValue::Namespace(_) => Type::Namespace,
+            LiteralValue::StringBuilder(_) => Type::StringBuilder,
+            LiteralValue::Progress(_) => Type::Progress,
+            LiteralValue::FileHandle(_) => Type::FileHandle,
+            LiteralValue::Generator(_) => Type::Generator,
+            LiteralValue::HtmlElement(_) => Type::HtmlElement,
+        }
+    }
+}
+
+// The shared type of all `types`, or Unknown when they disagree
+fn common_type(types: impl Iterator<Item = Type>) -> Type {
+    let mut common = None;
+    for current in types {
+        match &common {
+            None => common = Some(current),
+            Some(previous) if *previous == current => (),
+            Some(_) => return Type::Unknown,
+        }
+    }
+    common.unwrap_or(Type::Unknown)
+}
+
+// Result of `-` and `*` (and of `+` on numbers): Int for two Ints, Decimal for Decimals with
+// Ints, and Float once a Float is involved
+fn numeric_type(left: Type, right: Type) -> Type {
+    match (left, right) {
+        (Type::Int, Type::Int) => Type::Int,
+        (Type::BigInt, Type::Int | Type::BigInt) | (Type::Int, Type::BigInt) => Type::BigInt,
+        (Type::Decimal, Type::Int | Type::Decimal) | (Type::Int, Type::Decimal) => Type::Decimal,
+        (Type::Float, _) | (_, Type::Float) => Type::Float,
+        _ => Type::Unknown,
+    }
+}
+
+/// Types of the names in scope, plus the field types of known structs.
+#[derive(Clone, Default)]
+pub struct TypeEnv {
+    bindings: HashMap<String, Type>,
+    structs: HashMap<String, HashMap<String, Type>>,
+}
+
+impl TypeEnv {
+    /// Seeds the bindings from the current values of a runtime environment and its enclosing scopes.
+    pub fn from_environment(environment: &Environment) -> Self {
+        let mut type_env = match &environment.enclosing {
+            Some(enclosing) => TypeEnv::from_environment(&enclosing.borrow()),
+            None => TypeEnv::default(),
+        };
+
+        for (name, value) in &environment.values {
+            match value {
+                LiteralValue::StructDef(definition) => {
+                    let fields = definition.fields.iter().map(|(field, default)| (field.clone(), type_env.infer(default))).collect();
+                    type_env.structs.insert(definition.name.clone(), fields);
+                }
+                LiteralValue::StructInst(instance) => {
+                    let instance = instance.borrow();
+                    type_env.structs.entry(instance.name.clone()).or_insert_with(|| {
+                        instance.fields.iter().map(|(field, value)| (field.clone(), Type::of_value(value))).collect()
+                    });
+                }
+                _ => (),
+            }
+            type_env.bindings.insert(name.clone(), Type::of_value(value));
+        }
+
+        type_env
+    }
+
+    /// Sets the type of a name, like Unknown for a variable that is assigned other values later.
+    pub(crate) fn bind(&mut self, name: &str, binding: Type) {
+        self.bindings.insert(name.to_string(), binding);
+    }
+
+    /// Whether a name is in scope, which decides between a module and a variable of the same name.
+    pub(crate) fn binds(&self, name: &str) -> bool {
+        self.bindings.contains_key(name)
+    }
+
+    /// The field types of a struct declared so far.
+    pub(crate) fn fields(&self, struct_name: &str) -> Option<&HashMap<String, Type>> {
+        self.structs.get(struct_name)
+    }
+
+    pub fn infer(&self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Literal { value, .. } => Type::of_value(value),
+            Expr::Array { elements, .. } => Type::Array(Box::new(common_type(elements.iter().map(|element| self.infer(element))))),
+            Expr::Grouping { expression, .. } => self.infer(expression),
+            Expr::Interpolate { .. } => Type::String,
+            Expr::Variable { name, .. } => self.bindings.get(&name.lexeme).cloned().unwrap_or(Type::Unknown),
+            Expr::Assign { value, .. } | Expr::FieldAssign { value, .. } => self.infer(value),
+            Expr::Const { value, .. } => self.infer(value),
+            Expr::StructInst { name, .. } => Type::Struct(name.clone()),
+            Expr::Unary { operator, right, .. } => match operator.token_type {
+                TokenType::Bang => Type::Bool,
+                _ => match self.infer(right) {
+                    number @ (Type::Int | Type::BigInt | Type::Float | Type::Decimal) => number,
+                    _ => Type::Unknown,
+                },
+            },
+            Expr::Binary { left, operator, right, .. } => match operator.token_type {
+                TokenType::Slash => match (self.infer(left), self.infer(right)) {
+                    (Type::Decimal, _) | (_, Type::Decimal) => Type::Decimal,
+                    _ => Type::Float,
+                },
+                TokenType::Minus => numeric_type(self.infer(left), self.infer(right)),
+                TokenType::Star => match (self.infer(left), self.infer(right)) {
+                    (Type::String, _) | (_, Type::String) => Type::String,
+                    (left, right) => numeric_type(left, right),
+                },
+                TokenType::Plus => match (self.infer(left), self.infer(right)) {
+                    (Type::String, _) | (_, Type::String) => Type::String,
+                    (Type::Array(x), Type::Array(y)) => Type::Array(Box::new(common_type([*x, *y].into_iter()))),
+                    (left, right) => numeric_type(left, right),
+                },
+                _ => Type::Bool,
+            },
+            Expr::Logical { left, right, .. } => common_type([self.infer(left), self.infer(right)].into_iter()),
+            Expr::Range { .. } => Type::Array(Box::new(Type::Int)),
+            Expr::Map { .. } => Type::Map,
+            Expr::IndexAssign { value, .. } => self.infer(value),
+            Expr::Tuple { elements, .. } => Type::Tuple(elements.iter().map(|element| self.infer(element)).collect()),
+            Expr::Index { array, index, .. } if matches!(**index, Expr::Range { .. }) => match self.infer(array) {
+                sliced @ (Type::Array(_) | Type::String) => sliced,
+                _ => Type::Unknown,
+            },
+            Expr::Index { array, index, .. } => match self.infer(array) {
+                Type::Array(element) => *element,
+                // Only a literal position tells which element of a tuple is meant
+                Type::Tuple(elements) => match &**index {
+                    Expr::Literal { value: LiteralValue::Int(position), .. } => usize::try_from(*position).ok()
+                        .and_then(|position| elements.get(position).cloned())
+                        .unwrap_or(Type::Unknown),
+                    _ => Type::Unknown,
+                },
+                _ => Type::Unknown,
+            },
+            Expr::FieldAccess { object, field, .. } => match self.infer(object) {
+                Type::Struct(name) => self.structs.get(&name)
+                    .and_then(|fields| fields.get(&field.lexeme).cloned())
+                    .unwrap_or(Type::Unknown),
+                _ => Type::Unknown,
+            },
+            Expr::Call { callee, .. } => match self.infer(callee) {
+                Type::Function { returns, .. } => *returns,
+                _ => Type::Unknown,
+            },
+            Expr::MethodCall { object, method_name, .. } => match (self.infer(object), method_name.as_str()) {
+                (Type::Array(_) | Type::Tuple(_) | Type::String | Type::Map, "length") => Type::Int,
+                (Type::String, "upper" | "lower" | "trim" | "clone") => Type::String,
+                (Type::String, "lines") => Type::Array(Box::new(Type::String)),
+                (Type::Map, "keys") => Type::Array(Box::new(Type::Unknown)),
+                (Type::Map, "has") => Type::Bool,
+                (Type::Map, "clone") => Type::Map,
+                (Type::StringBuilder, "append" | "clone") => Type::StringBuilder,
+                (Type::StringBuilder, "build") => Type::String,
+                (Type::StringBuilder, "length") => Type::Int,
+                (Type::Progress, "tick" | "finish") => Type::Nil,
+                (Type::FileHandle, "read" | "read_line") => Type::String,
+                (Type::FileHandle, "write" | "seek" | "close") => Type::Nil,
+                (Type::FileHandle, "lines") => Type::Generator,
+                (Type::HtmlElement, "select") => Type::Array(Box::new(Type::HtmlElement)),
+                (Type::HtmlElement, "text" | "tag") => Type::String,
+                (Type::HtmlElement, "attrs") => Type::Map,
+                (Type::Decimal, "round") => Type::Decimal,
+                (Type::Decimal, "format") => Type::String,
+                (Type::Array(_), "push") => Type::Nil,
+                (Type::Array(element), "pop") => *element,
+                (Type::Array(_), "par_map") => Type::Array(Box::new(Type::Unknown)),
+                (object_type @ (Type::Array(_) | Type::Tuple(_) | Type::Struct(_)), "clone") => object_type,
+                _ => Type::Unknown,
+            },
+            Expr::Propagate { .. } => Type::Unknown,
+            Expr::Module { name, function: Ok(function), .. } if !self.binds(name) => self.infer(function),
+            Expr::Module { member, .. } => self.infer(member),
+            Expr::PreFunction { module, name, .. } => match (module.as_str(), name.as_str()) {
+                ("math", "floor" | "ceil" | "round" | "random") => Type::Int,
+                ("math", "vec2") => Type::Struct("Vec2".to_string()),
+                ("math", "vec3" | "cross") => Type::Struct("Vec3".to_string()),
+                ("math", "normalize" | "matmul") => Type::Unknown,
+                ("math", _) => Type::Float,
+                ("io", "read_input") | ("io", "open_file") | ("io", "password") | ("template", "render") => Type::String,
+                ("io", "select") => Type::Unknown,
+                ("io", "open_handle") => Type::FileHandle,
+                ("io", "lines_stdin") => Type::Generator,
+                ("io", _) => Type::Bool,
+                ("debug", _) => Type::Map,
+                ("term", _) => Type::Progress,
+                ("fs", "glob") | ("archive", _) => Type::Array(Box::new(Type::String)),
+                ("compress", "gzip") | ("msgpack", "encode") => Type::Array(Box::new(Type::Int)),
+                ("html", "parse") => Type::HtmlElement,
+                ("http", "encode_query") => Type::String,
+                ("http", _) => Type::Map,
+                ("mail", "send") => Type::Bool,
+                ("net", "resolve") => Type::Array(Box::new(Type::String)),
+                ("notify", "webhook") => Type::Int,
+                ("stats", _) => Type::Float,
+                ("clipboard", "get") => Type::String,
+                ("clipboard", "set") => Type::Nil,
+                _ => Type::Unknown,
+            },
+        }
+    }
+
+    /// Records the names a statement declares and describes them, or the statement's own expression.
+    pub fn declare(&mut self, stmt: &Stmt) -> Option<String> {
+        match stmt {
+            Stmt::Var { name, initializer, .. } => {
+                let var_type = self.infer(initializer);
+                self.bindings.insert(name.lexeme.clone(), var_type.clone());
+                Some(format!("var {} : {}", name.lexeme, var_type))
+            }
+            Stmt::Unpack { names, by_field, initializer, .. } => {
+                let types = match self.infer(initializer) {
+                    Type::Struct(struct_name) if *by_field => names.iter()
+                        .map(|name| self.structs.get(&struct_name).and_then(|fields| fields.get(&name.lexeme).cloned()).unwrap_or(Type::Unknown))
+                        .collect(),
+                    Type::Tuple(types) if !by_field && types.len() == names.len() => types,
+                    Type::Array(element) if !by_field => vec![*element; names.len()],
+                    _ => vec![Type::Unknown; names.len()],
+                };
+                for (name, var_type) in names.iter().zip(&types) {
+                    self.bindings.insert(name.lexeme.clone(), var_type.clone());
+                }
+                let names = names.iter().map(|name| name.lexeme.as_str()).collect::<Vec<_>>().join(", ");
+                Some(format!("var {} : {}", names, Type::Tuple(types)))
+            }
+            Stmt::Const { name, initializer, .. } => {
+                let const_type = self.infer(initializer);
+                self.bindings.insert(name.lexeme.clone(), const_type.clone());
+                Some(format!("const {} : {}", name.lexeme, const_type))
+            }
+            Stmt::FuncStmt { name, parameters, body, generator, .. } => {
+                // Bind the function before looking at its body so recursive calls resolve
+                let arity = Some(parameters.len());
+                self.bindings.insert(name.clone(), Type::Function { arity, returns: Box::new(Type::Unknown) });
+
+                let mut body_env = self.clone();
+                for param in parameters {
+                    body_env.bindings.insert(param.lexeme.clone(), Type::Unknown);
+                }
+                let mut returns = vec![];
+                body_env.collect_returns(body, &mut returns);
+                let returns = if *generator {
+                    Type::Generator
+                } else if returns.is_empty() {
+                    Type::Nil
+                } else {
+                    common_type(returns.into_iter())
+                };
+
+                let fun_type = Type::Function { arity, returns: Box::new(returns) };
+                self.bindings.insert(name.clone(), fun_type.clone());
+                Some(format!("fn {} : {}", name, fun_type))
+            }
+            Stmt::StructStmt { name, parent, params, .. } => {
+                let mut fields = parent.as_ref().and_then(|parent| self.structs.get(parent).cloned()).unwrap_or_default();
+                for (field, default) in params {
+                    fields.insert(field.clone(), self.infer(default));
+                }
+
+                let mut names: Vec<&String> = fields.keys().collect();
+                names.sort();
+                let description = names.iter().map(|field| format!("{}: {}", field, fields[*field])).collect::<Vec<_>>().join(", ");
+
+                self.structs.insert(name.clone(), fields.clone());
+                self.bindings.insert(name.clone(), Type::StructDef(name.clone()));
+                Some(format!("struct {} {{ {} }}", name, description))
+            }
+            Stmt::InterfaceStmt { name, .. } => {
+                self.bindings.insert(name.clone(), Type::Interface(name.clone()));
+                Some(format!("interface {}", name))
+            }
+            Stmt::Import { alias_name, .. } => {
+                self.bindings.insert(alias_name.clone(), Type::Unknown);
+                None
+            }
+            Stmt::Expression { expression, .. } => Some(format!("{} : {}", expression, self.infer(expression))),
+            _ => None,
+        }
+    }
+
+    // Types of every `return` reachable in `stmts`, declaring locals along the way
+    fn collect_returns(&mut self, stmts: &[Stmt], returns: &mut Vec<Type>) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::ReturnStmt { value, .. } => {
+                    returns.push(value.as_ref().map_or(Type::Nil, |value| self.infer(value)));
+                }
+                Stmt::Block { statements, .. } => self.collect_returns(statements, returns),
+                Stmt::IfStmt { then, elifs, els, .. } => {
+                    self.collect_returns(std::slice::from_ref(then), returns);
+                    for (_, body) in elifs {
+                        self.collect_returns(std::slice::from_ref(body), returns);
+                    }
+                    if let Some(els) = els {
+                        self.collect_returns(std::slice::from_ref(els), returns);
+                    }
+                }
+                Stmt::Match { arms, default, .. } => {
+                    for (_, body) in arms {
+                        self.collect_returns(std::slice::from_ref(body), returns);
+                    }
+                    if let Some(default) = default {
+                        self.collect_returns(std::slice::from_ref(default), returns);
+                    }
+                }
+                Stmt::WhileStmt { body, .. } | Stmt::ForIn { body, .. } | Stmt::LoopStmt { body, .. } => {
+                    self.collect_returns(std::slice::from_ref(body), returns);
+                }
+                Stmt::Try { body, handler, .. } => {
+                    self.collect_returns(std::slice::from_ref(body), returns);
+                    self.collect_returns(std::slice::from_ref(handler), returns);
+                }
+                other => {
+                    self.declare(other);
+                }
+            }
+        }
+    }
+}
+
+/// One line per top-level declaration or expression, as printed by `--explain`.
+pub fn explain(stmts: &[Stmt], environment: &Environment) -> Vec<String> {
+    let mut type_env = TypeEnv::from_environment(environment);
+    stmts.iter().filter_map(|stmt| type_env.declare(stmt)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn explain_source(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        explain(&stmts, &Environment::new())
+    }
+
+    #[test]
+    fn infers_declarations_without_running() {
+        let lines = explain_source(
+            "struct Point { x: 0, label: \"\" }\n\
+             fn double(n) { return n * 2.0; }\n\
+             var p = Point { x: 1, label: \"a\" };\n\
+             var total = double(p.x) + 1;\n\
+             var names = [\"a\", \"b\"];\n\
+             p.label + 1;"
+        );
+
+        assert_eq!(lines, vec![
+            "struct Point { label: String, x: Int }",
+            "fn double : Function/1 -> Float",
+            "var p : Point",
+            "var total : Float",
+            "var names : Array<String>",
+            "(+ (. (var p) label) 1) : String",
+        ]);
+    }
+}
@@ -1 +1,3 @@
-pub mod rcn_struct;
\ No newline at end of file
+pub mod rcn_struct;
+pub mod rcn_interface;
+pub mod rcn_generator;
@@ -0,0 +1,15 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::literal_value::LiteralValue;
+
+/// Receives each value a generator yields and answers whether the generator should go on.
+pub type YieldFn = Rc<RefCell<dyn FnMut(LiteralValue) -> Result<bool, String>>>;
+
+/// What calling a fn that contains `yield` returns. Nothing runs until a `for ... in` walks it;
+/// the body then runs from the start and hands every yielded value to the loop as it comes,
+/// so no array of all values is built. Walking the same generator again starts it over.
+pub struct Generator {
+    pub name: String,
+    pub run: Box<dyn Fn(YieldFn) -> Result<(), String>>,
+}
@@ -0,0 +1,35 @@
+use crate::literal_value::LiteralValue;
+
+#[derive(Clone, Debug)]
+pub struct InterfaceDefinition {
+    pub name: String,
+    pub methods: Vec<(String, usize)>, // Required method names and their arity
+}
+
+impl InterfaceDefinition {
+    // Find the first required method that `lookup` cannot provide as a callable with the right arity
+    pub fn missing_method(&self, lookup: impl Fn(&str) -> Option<LiteralValue>) -> Option<String> {
+        for (method, arity) in &self.methods {
+            match lookup(method) {
+                Some(LiteralValue::Callable { arity: found, .. }) if found as usize == *arity => (),
+                _ => return Some(format!("{}/{}", method, arity)),
+            }
+        }
+
+        None
+    }
+
+    // Check whether a runtime value provides every method of the interface
+    pub fn is_satisfied_by(&self, value: &LiteralValue) -> bool {
+        match value {
+            LiteralValue::StructInst(instance) => {
+                let instance = instance.borrow();
+                self.missing_method(|name| instance.get_field(name).cloned()).is_none()
+            }
+            LiteralValue::Namespace(env) => {
+                self.missing_method(|name| env.borrow().get(name)).is_none()
+            }
+            _ => false,
+        }
+    }
+}
@@ -1,18 +1,24 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::rc::Rc;
 use crate::expr::Expr;
 use crate::literal_value::LiteralValue;
+use crate::suggest::did_you_mean;
 
 #[derive(Clone, Debug)]
 pub struct StructDefinition {
     pub name: String,
-    pub fields: HashMap<String, Expr>, // Fields as expressions during parsing
+    pub parent: Option<String>, // Struct this definition extends, if any
+    pub interfaces: Vec<String>, // Interfaces checked when the struct was defined
+    pub fields: HashMap<String, Expr>, // Fields as expressions during parsing, including inherited ones
+    pub functions: Rc<HashMap<String, LiteralValue>>, // Associated fns like `Point.new`, including inherited ones
 }
 
 #[derive(Clone, Debug)]
 pub struct StructInstance {
     pub name: String,
     pub fields: HashMap<String, LiteralValue>, // Fields as evaluated values during runtime
+    pub frozen: bool, // Frozen instances reject field assignment
 }
 
 // Implement Display for StructInstance to format the output as desired
@@ -41,4 +47,14 @@ impl StructInstance {
     pub fn get_field(&self, field_name: &str) -> Option<&LiteralValue> {
         self.fields.get(field_name)
     }
+
+    // The error for a field the instance doesn't have, suggesting the closest one
+    pub fn missing_field(&self, field_name: &str) -> String {
+        format!("Field '{}' not found in struct '{}'.{}", field_name, self.name, did_you_mean(field_name, self.fields.keys().map(String::as_str)))
+    }
+
+    // Mark the instance so its fields can no longer be assigned
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
 }
\ No newline at end of file